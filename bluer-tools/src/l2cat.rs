@@ -2,13 +2,13 @@
 
 use bluer::{
     adv::{Advertisement, AdvertisementHandle},
-    l2cap::{Socket, SocketAddr, Stream, StreamListener},
+    l2cap::{ChannelStats, Pinger, Socket, SocketAddr, Stream, StreamListener},
     Address, AddressType, Uuid,
 };
 use bytes::BytesMut;
 use clap::Parser;
 use crossterm::{terminal, tty::IsTty};
-use futures::{future, pin_mut};
+use futures::{future, pin_mut, StreamExt};
 use libc::{STDIN_FILENO, STDOUT_FILENO};
 use rand::prelude::*;
 use std::{
@@ -54,6 +54,10 @@ enum Cmd {
     SpeedClient(SpeedClientOpts),
     /// Speed test server.
     SpeedServer(SpeedServerOpts),
+    /// Measure round-trip time using echo requests over the signalling channel.
+    ///
+    /// Only classic Bluetooth (BR/EDR) is supported.
+    Ping(PingOpts),
 }
 
 #[derive(Parser)]
@@ -429,7 +433,7 @@ impl SpeedClientOpts {
         let opts = conn.as_ref().l2cap_opts();
         let conn_info = conn.as_ref().conn_info()?;
         let phy = conn.as_ref().phy()?;
-        println!("Connected with {:?} and {:?} and PHYs {:#016b}", &opts, &conn_info, phy);
+        println!("Connected with {:?} and {:?} and PHYs {:?}", &opts, &conn_info, phy);
 
         let recv_mtu = conn.as_ref().recv_mtu()?;
         println!("Receive MTU is {} bytes", recv_mtu);
@@ -442,13 +446,17 @@ impl SpeedClientOpts {
         };
         pin_mut!(done);
 
+        let (mut rh, wh) = conn.split();
+        let stats = wh.as_ref().as_ref().stats_stream(STATS_PERIOD);
+        pin_mut!(stats);
+
         let start = Instant::now();
         let mut total = 0;
         let mut received = VecDeque::new();
         let mut buf = vec![0; 4096];
         loop {
             tokio::select! {
-                res = conn.read(&mut buf) => {
+                res = rh.read(&mut buf) => {
                     match res? {
                         0 => break,
                         n => {
@@ -458,6 +466,7 @@ impl SpeedClientOpts {
                     }
                 }
                 () = &mut done => break,
+                Some(res) = stats.next() => print_stats(&res?),
             }
 
             loop {
@@ -487,6 +496,23 @@ impl SpeedClientOpts {
     }
 }
 
+/// Period for printing channel statistics during speed tests.
+const STATS_PERIOD: Duration = Duration::from_secs(5);
+
+fn print_stats(stats: &ChannelStats) {
+    let mtu = |mtu: Option<u16>| mtu.map(|mtu| mtu.to_string()).unwrap_or_else(|| "?".to_string());
+    println!(
+        "Send MTU {}, receive MTU {}, flow control {:?}, send queue {}/{} bytes, receive queue {}/{} bytes",
+        mtu(stats.send_mtu),
+        mtu(stats.recv_mtu),
+        stats.flow_control,
+        stats.send_queue,
+        stats.send_buffer,
+        stats.recv_queue,
+        stats.recv_buffer
+    );
+}
+
 #[derive(Parser)]
 struct SpeedServerOpts {
     /// Address of local Bluetooth adapter to use.
@@ -526,21 +552,42 @@ impl SpeedServerOpts {
                     let conn_info = conn.as_ref().conn_info()?;
                     let phy = conn.as_ref().phy()?;
                     println!(
-                        "Connection from {} with {:?} and {:?} and PHYs {:#016b}",
+                        "Connection from {} with {:?} and {:?} and PHYs {:?}",
                         peer_sa.addr, &opts, &conn_info, phy
                     );
 
                     let send_mtu = conn.as_ref().send_mtu()?;
                     println!("Send MTU is {} bytes", send_mtu);
 
+                    let (rh, mut wh) = conn.split();
+                    let stats = rh.as_ref().as_ref().stats_stream(STATS_PERIOD);
+                    pin_mut!(stats);
+
+                    // Write within select! using write, which is cancel-safe, and track
+                    // how much of the buffer has been sent.
+                    let mut buf = vec![0; 4096];
+                    let mut pos = buf.len();
                     loop {
-                        let mut rng = rand::thread_rng();
-                        let mut buf = vec![0; 4096];
-                        rng.fill_bytes(&mut buf);
+                        if pos == buf.len() {
+                            rand::thread_rng().fill_bytes(&mut buf);
+                            pos = 0;
+                        }
 
-                        if let Err(err) = conn.write_all(&buf).await {
-                            println!("Disconnected: {}", err);
-                            break;
+                        tokio::select! {
+                            res = wh.write(&buf[pos..]) => {
+                                match res {
+                                    Ok(0) => {
+                                        println!("Disconnected");
+                                        break;
+                                    }
+                                    Ok(n) => pos += n,
+                                    Err(err) => {
+                                        println!("Disconnected: {}", err);
+                                        break;
+                                    }
+                                }
+                            }
+                            Some(res) = stats.next() => print_stats(&res?),
                         }
                     }
                 }
@@ -556,6 +603,101 @@ impl SpeedServerOpts {
     }
 }
 
+#[derive(Parser)]
+struct PingOpts {
+    /// Address of local Bluetooth adapter to use.
+    #[clap(long, short)]
+    bind: Option<Address>,
+    /// Size of echo data in bytes.
+    #[clap(long, short, default_value = "44")]
+    size: u16,
+    /// Number of echo requests to send.
+    ///
+    /// If unspecified requests are sent until interrupted.
+    #[clap(long, short)]
+    count: Option<usize>,
+    /// Interval between echo requests in seconds.
+    #[clap(long, short, default_value = "1")]
+    interval: f64,
+    /// Time to wait for an echo response in seconds.
+    #[clap(long, short, default_value = "10")]
+    timeout: f64,
+    /// Public Bluetooth address of target device.
+    address: Address,
+}
+
+impl PingOpts {
+    pub async fn perform(self) -> Result<()> {
+        let socket = Socket::<Pinger>::new_pinger()?;
+        socket.bind(SocketAddr::new(self.bind.unwrap_or_else(Address::any), AddressType::BrEdr, 0))?;
+        let mut pinger = socket.connect(SocketAddr::new(self.address, AddressType::BrEdr, 0)).await?;
+
+        let local_sa = pinger.as_ref().local_addr()?;
+        println!("Ping {} from {} with {} bytes of data", self.address, local_sa.addr, self.size);
+
+        let data: Vec<u8> = (0..self.size).map(|i| (i % 40) as u8 + b'A').collect();
+        let interval = Duration::from_secs_f64(self.interval);
+        let timeout = Duration::from_secs_f64(self.timeout);
+
+        let mut rtts = Vec::new();
+        let pings = async {
+            let mut seq = 0;
+            while self.count.map(|count| seq < count).unwrap_or(true) {
+                let start = Instant::now();
+                match pinger.echo(&data, timeout).await? {
+                    Some(rtt) => {
+                        println!(
+                            "{} bytes from {} seq={} time={:.2} ms",
+                            self.size,
+                            self.address,
+                            seq,
+                            rtt.as_secs_f64() * 1000.0
+                        );
+                        rtts.push(Some(rtt));
+                    }
+                    None => {
+                        println!("no response from {} seq={}", self.address, seq);
+                        rtts.push(None);
+                    }
+                }
+                seq += 1;
+                if self.count.map(|count| seq < count).unwrap_or(true) {
+                    sleep(interval.saturating_sub(start.elapsed())).await;
+                }
+            }
+            Result::Ok(())
+        };
+
+        let result = select! {
+            res = pings => res,
+            res = tokio::signal::ctrl_c() => res.map_err(|err| err.into()),
+        };
+
+        let sent = rtts.len();
+        let received: Vec<f64> = rtts.iter().flatten().map(|rtt| rtt.as_secs_f64() * 1000.0).collect();
+        println!();
+        println!(
+            "{} sent, {} received, {:.1}% loss",
+            sent,
+            received.len(),
+            if sent > 0 { (sent - received.len()) as f64 * 100.0 / sent as f64 } else { 0.0 }
+        );
+        if !received.is_empty() {
+            let min = received.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = received.iter().copied().fold(0.0, f64::max);
+            let avg = received.iter().sum::<f64>() / received.len() as f64;
+            let jitter = if received.len() > 1 {
+                received.windows(2).map(|w| (w[1] - w[0]).abs()).sum::<f64>() / (received.len() - 1) as f64
+            } else {
+                0.0
+            };
+            println!("rtt min/avg/max/jitter = {:.2}/{:.2}/{:.2}/{:.2} ms", min, avg, max, jitter);
+        }
+
+        result
+    }
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     use tokio_compat_02::FutureExt;
@@ -568,6 +710,7 @@ async fn main() -> Result<()> {
         Cmd::Serve(s) => s.perform().compat().await,
         Cmd::SpeedClient(sc) => sc.perform().await,
         Cmd::SpeedServer(ss) => ss.perform().await,
+        Cmd::Ping(p) => p.perform().await,
     };
 
     match result {
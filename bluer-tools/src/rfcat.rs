@@ -3,8 +3,9 @@
 use bluer::{
     agent::Agent,
     id::ServiceClass,
+    l2cap,
     rfcomm::{Listener, Profile, ReqError, Role, Socket, SocketAddr, Stream},
-    AdapterEvent, Address, Uuid,
+    sdp, AdapterEvent, Address, AddressType, Uuid,
 };
 use bytes::BytesMut;
 use clap::Parser;
@@ -75,11 +76,37 @@ struct ConnectOpts {
     /// Target RFCOMM profile.
     #[clap(long, short)]
     profile: Option<Uuid>,
+    /// Look up the RFCOMM channel of the target profile using SDP and
+    /// connect directly without registering a profile with the Bluetooth daemon.
+    #[clap(long, short)]
+    sdp: bool,
 }
 
 impl ConnectOpts {
+    async fn find_channel(&self) -> Result<u8> {
+        let uuid = self.profile.unwrap_or_else(|| ServiceClass::SerialPort.into());
+
+        let socket = l2cap::Socket::new_stream()?;
+        socket.bind(l2cap::SocketAddr::new(self.bind.unwrap_or_else(Address::any), AddressType::BrEdr, 0))?;
+        let stream = socket.connect(l2cap::SocketAddr::new(self.address, AddressType::BrEdr, sdp::PSM)).await?;
+
+        match sdp::Client::new(stream).find_rfcomm_channel(uuid).await? {
+            Some(channel) => {
+                eprintln!("Profile {} uses channel {}", uuid, channel);
+                Ok(channel)
+            }
+            None => Err(format!("profile {} not found using SDP", uuid).into()),
+        }
+    }
+
     pub async fn perform(self) -> Result<()> {
-        let stream = match (self.channel, self.profile) {
+        let channel = match self.channel {
+            None if self.sdp => Some(self.find_channel().await?),
+            channel => channel,
+        };
+        let profile = if self.sdp { None } else { self.profile };
+
+        let stream = match (channel, profile) {
             (Some(channel), None) => {
                 let socket = Socket::new()?;
                 let local_sa = match self.bind {
@@ -142,7 +169,7 @@ impl ConnectOpts {
                             eprintln!("Connect request from {}", req.device());
                             if req.device() == self.address {
                                 eprintln!("Accepting request...");
-                                break req.accept()?;
+                                break Stream::try_from(req.accept()?)?;
                             } else {
                                 req.reject(ReqError::Rejected);
                             }
@@ -250,7 +277,7 @@ impl ListenOpts {
 
                 let req = hndl.next().await.expect("received no connect request");
                 eprintln!("Connect from {}", req.device());
-                req.accept()?
+                Stream::try_from(req.accept()?)?
             }
             _ => {
                 eprintln!("either channel or profile must be specified");
@@ -366,7 +393,7 @@ impl ServeOpts {
                 (None, Some(hndl)) => {
                     let req = hndl.next().await.expect("received no connect request");
                     eprintln!("Connect from {}", req.device());
-                    req.accept()?
+                    Stream::try_from(req.accept()?)?
                 }
                 _ => unreachable!(),
             };
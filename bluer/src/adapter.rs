@@ -16,16 +16,20 @@ use std::{
     u32,
 };
 use strum::{Display, EnumString};
-use tokio::sync::mpsc;
+use tokio::{sync::mpsc, task};
 use tokio_stream::wrappers::ReceiverStream;
 use uuid::Uuid;
 
 use crate::{
     adv,
     adv::{Advertisement, AdvertisementHandle, Capabilities, Feature, PlatformFeature, SecondaryChannel},
-    all_dbus_objects, device,
+    all_dbus_objects,
+    bond::{self, Bonds},
+    device,
     device::Device,
-    gatt, Address, AddressType, Error, ErrorKind, Event, InternalErrorKind, Modalias, Result, SessionInner,
+    gatt, mgmt,
+    oob::{LeOobRecord, OobData},
+    Address, AddressType, Error, ErrorKind, Event, InternalErrorKind, Modalias, Result, SessionInner,
     SingleSessionToken, SERVICE_NAME, TIMEOUT,
 };
 
@@ -350,6 +354,130 @@ impl Adapter {
 
         self.device(address)
     }
+
+    // ===========================================================================================
+    // Management interface
+    // ===========================================================================================
+
+    /// Index of the adapter used by the kernel management interface.
+    ///
+    /// For example, this is 0 for the adapter `hci0`.
+    pub fn index(&self) -> Result<u16> {
+        self.name
+            .strip_prefix("hci")
+            .and_then(|idx| idx.parse().ok())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidName((*self.name).clone())))
+    }
+
+    /// Accesses the adapter through the kernel management interface.
+    ///
+    /// This opens a new [management client](mgmt::Client).
+    /// Most commands require the `CAP_NET_ADMIN` capability.
+    pub fn mgmt_controller(&self) -> Result<mgmt::Controller> {
+        Ok(mgmt::Client::new()?.controller(self.index()?))
+    }
+
+    /// Reads the local out-of-band pairing data of the adapter.
+    ///
+    /// The P-256 values are only available if Secure Connections
+    /// are enabled on the adapter.
+    /// New values are generated each time this method is called and
+    /// only the most recently generated values are valid for pairing.
+    ///
+    /// This uses the kernel management interface and requires the
+    /// `CAP_NET_ADMIN` capability.
+    pub async fn local_oob_data(&self) -> Result<OobData> {
+        let rsp = mgmt::command(self.index()?, mgmt::OP_READ_LOCAL_OOB_DATA, &[]).await?;
+        OobData::from_mgmt(&rsp).ok_or_else(|| Error::new(ErrorKind::Internal(InternalErrorKind::InvalidValue)))
+    }
+
+    /// Reads the local Bluetooth LE out-of-band record of the adapter.
+    ///
+    /// The record contains the LE address, role and flags of the adapter and,
+    /// if Secure Connections are enabled, the confirmation and random values.
+    /// New values are generated each time this method is called and
+    /// only the most recently generated values are valid for pairing.
+    ///
+    /// This uses the kernel management interface and requires the
+    /// `CAP_NET_ADMIN` capability.
+    pub async fn local_le_oob_record(&self) -> Result<LeOobRecord> {
+        let rsp =
+            mgmt::command(self.index()?, mgmt::OP_READ_LOCAL_OOB_EXT_DATA, &[LeOobRecord::MGMT_ADDRESS_TYPES])
+                .await?;
+        LeOobRecord::from_mgmt(&rsp)
+            .ok_or_else(|| Error::new(ErrorKind::Internal(InternalErrorKind::InvalidValue)))
+    }
+
+    /// Provides out-of-band pairing data of a remote device.
+    ///
+    /// This must be called before pairing with the device using [Device::pair].
+    /// For Bluetooth LE devices the P-192 values are ignored.
+    ///
+    /// This uses the kernel management interface and requires the
+    /// `CAP_NET_ADMIN` capability.
+    pub async fn add_remote_oob_data(
+        &self, address: Address, address_type: AddressType, data: &OobData,
+    ) -> Result<()> {
+        let mut params = Vec::new();
+        mgmt::encode_addr_info(&mut params, address, address_type);
+        match address_type {
+            AddressType::BrEdr => data.to_mgmt(&mut params),
+            AddressType::LePublic | AddressType::LeRandom => {
+                OobData { hash_192: None, randomizer_192: None, ..data.clone() }.to_mgmt(&mut params)
+            }
+        }
+        mgmt::command(self.index()?, mgmt::OP_ADD_REMOTE_OOB_DATA, &params).await?;
+        Ok(())
+    }
+
+    /// Exports all bonded devices of the adapter including their keys.
+    ///
+    /// The bonds are read from the storage directory of the Bluetooth daemon,
+    /// which requires root privileges.
+    pub async fn export_bonds(&self) -> Result<Bonds> {
+        let address = self.address().await?;
+        let bonds = task::spawn_blocking(move || Bonds::read_storage(bond::STORAGE_DIR, address)).await??;
+        Ok(bonds)
+    }
+
+    /// Imports bonded devices including their keys, which were possibly
+    /// exported from another adapter.
+    ///
+    /// The bonds are written to the storage directory of the Bluetooth daemon
+    /// for this adapter and the keys and connection parameters of all bonded devices
+    /// of this adapter are loaded into the kernel using the management interface.
+    /// This requires root privileges.
+    ///
+    /// The Bluetooth daemon must be restarted for the imported devices to appear.
+    /// Remote devices will only accept the imported keys if this adapter uses
+    /// the same identity address as the adapter the bonds were exported from.
+    pub async fn import_bonds(&self, bonds: &Bonds) -> Result<()> {
+        let address = self.address().await?;
+        let bonds = bonds.clone();
+        let all_bonds = task::spawn_blocking(move || {
+            bonds.write_storage(bond::STORAGE_DIR, address)?;
+            Bonds::read_storage(bond::STORAGE_DIR, address)
+        })
+        .await??;
+
+        let index = self.index()?;
+        mgmt::command(index, mgmt::OP_LOAD_LINK_KEYS, &all_bonds.to_mgmt_link_keys()?).await?;
+        mgmt::command(index, mgmt::OP_LOAD_LONG_TERM_KEYS, &all_bonds.to_mgmt_long_term_keys()?).await?;
+        mgmt::command(index, mgmt::OP_LOAD_IRKS, &all_bonds.to_mgmt_irks()?).await?;
+        self.mgmt_controller()?.load_connection_parameters(&all_bonds.connection_parameters()).await?;
+        Ok(())
+    }
+
+    /// Removes previously provided out-of-band pairing data of a remote device.
+    ///
+    /// This uses the kernel management interface and requires the
+    /// `CAP_NET_ADMIN` capability.
+    pub async fn remove_remote_oob_data(&self, address: Address, address_type: AddressType) -> Result<()> {
+        let mut params = Vec::new();
+        mgmt::encode_addr_info(&mut params, address, address_type);
+        mgmt::command(self.index()?, mgmt::OP_REMOVE_REMOTE_OOB_DATA, &params).await?;
+        Ok(())
+    }
 }
 
 define_properties!(
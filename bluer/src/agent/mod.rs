@@ -2,22 +2,65 @@
 
 use dbus::nonblock::Proxy;
 use dbus_crossroads::{Crossroads, IfaceBuilder, IfaceToken};
-use futures::{pin_mut, Future};
-use std::{fmt, pin::Pin, sync::Arc};
+use futures::{pin_mut, Future, Stream};
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
 use strum::IntoStaticStr;
 use tokio::{
     select,
-    sync::{oneshot, Mutex},
+    sync::{mpsc, oneshot},
+    time::sleep_until,
 };
 use uuid::Uuid;
 
 use crate::{method_call, Address, Device, Result, SessionInner, ERR_PREFIX, SERVICE_NAME, TIMEOUT};
 
+mod policy;
+pub use policy::{ConfirmPolicy, DevicePattern, PairingButton, PolicyAgent};
+
+/// Input and output capability of an agent.
+///
+/// The Bluetooth daemon selects the pairing model based on it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, IntoStaticStr)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Capability {
+    /// Can display a passkey or pin code only.
+    DisplayOnly,
+    /// Can display a passkey and answer yes/no questions.
+    DisplayYesNo,
+    /// Can enter a passkey or pin code only.
+    KeyboardOnly,
+    /// Neither input nor output is available.
+    NoInputNoOutput,
+    /// Can display and enter a passkey or pin code.
+    KeyboardDisplay,
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", <&'static str>::from(self))
+    }
+}
+
 pub(crate) const INTERFACE: &str = "org.bluez.Agent1";
 pub(crate) const MANAGER_INTERFACE: &str = "org.bluez.AgentManager1";
 pub(crate) const MANAGER_PATH: &str = "/org/bluez";
 pub(crate) const AGENT_PREFIX: &str = publish_path!("agent/");
 
+/// Time after which the Bluetooth daemon gives up waiting for a reply to a request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Number of lifecycle events queued for an agent handle before further events are dropped.
+const EVENT_QUEUE_LEN: usize = 32;
+
 /// Error response from us to a Bluetooth agent request.
 #[derive(Clone, Copy, Debug, displaydoc::Display, Eq, PartialEq, Ord, PartialOrd, Hash, IntoStaticStr)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -48,13 +91,18 @@ impl From<ReqError> for dbus::MethodErr {
 pub type ReqResult<T> = std::result::Result<T, ReqError>;
 
 /// Arguments for a pin code request.
-#[derive(Debug)]
+#[derive(custom_debug::Debug)]
 #[non_exhaustive]
 pub struct RequestPinCode {
     /// Adapter making the request.
     pub adapter: String,
     /// Address of device making the request.
     pub device: Address,
+    /// Resolves once the request has been canceled.
+    #[debug(skip)]
+    pub cancel: oneshot::Receiver<()>,
+    /// Deadline after which the request is canceled.
+    pub deadline: Instant,
 }
 
 /// Function handling a pin code request.
@@ -74,6 +122,8 @@ pub struct DisplayPinCode {
     /// Resolves once the pin code should not be displayed anymore.
     #[debug(skip)]
     pub cancel: oneshot::Receiver<()>,
+    /// Deadline after which the request is canceled.
+    pub deadline: Instant,
 }
 
 /// Function handling a display pin code request.
@@ -81,13 +131,18 @@ pub type DisplayPinCodeFn =
     Box<dyn (Fn(DisplayPinCode) -> Pin<Box<dyn Future<Output = ReqResult<()>> + Send>>) + Send + Sync>;
 
 /// Argument for a passkey request.
-#[derive(Debug)]
+#[derive(custom_debug::Debug)]
 #[non_exhaustive]
 pub struct RequestPasskey {
     /// Adapter making the request.
     pub adapter: String,
     /// Address of device making the request.
     pub device: Address,
+    /// Resolves once the request has been canceled.
+    #[debug(skip)]
+    pub cancel: oneshot::Receiver<()>,
+    /// Deadline after which the request is canceled.
+    pub deadline: Instant,
 }
 
 /// Function handling a passkey request.
@@ -109,6 +164,8 @@ pub struct DisplayPasskey {
     /// Resolves once the passkey should not be displayed anymore.
     #[debug(skip)]
     pub cancel: oneshot::Receiver<()>,
+    /// Deadline after which the request is canceled.
+    pub deadline: Instant,
 }
 
 /// Function handling a display passkey request.
@@ -116,7 +173,7 @@ pub type DisplayPasskeyFn =
     Box<dyn (Fn(DisplayPasskey) -> Pin<Box<dyn Future<Output = ReqResult<()>> + Send>>) + Send + Sync>;
 
 /// Arguments for a confirmation request.
-#[derive(Debug)]
+#[derive(custom_debug::Debug)]
 #[non_exhaustive]
 pub struct RequestConfirmation {
     /// Adapter making the request.
@@ -125,6 +182,11 @@ pub struct RequestConfirmation {
     pub device: Address,
     /// Passkey.
     pub passkey: u32,
+    /// Resolves once the request has been canceled.
+    #[debug(skip)]
+    pub cancel: oneshot::Receiver<()>,
+    /// Deadline after which the request is canceled.
+    pub deadline: Instant,
 }
 
 /// Function handling a confirmation request.
@@ -132,13 +194,18 @@ pub type RequestConfirmationFn =
     Box<dyn (Fn(RequestConfirmation) -> Pin<Box<dyn Future<Output = ReqResult<()>> + Send>>) + Send + Sync>;
 
 /// Arguments for an authorization request.
-#[derive(Debug)]
+#[derive(custom_debug::Debug)]
 #[non_exhaustive]
 pub struct RequestAuthorization {
     /// Adapter making the request.
     pub adapter: String,
     /// Address of device making the request.
     pub device: Address,
+    /// Resolves once the request has been canceled.
+    #[debug(skip)]
+    pub cancel: oneshot::Receiver<()>,
+    /// Deadline after which the request is canceled.
+    pub deadline: Instant,
 }
 
 /// Function handling an authorization request.
@@ -146,7 +213,7 @@ pub type RequestAuthorizationFn =
     Box<dyn (Fn(RequestAuthorization) -> Pin<Box<dyn Future<Output = ReqResult<()>> + Send>>) + Send + Sync>;
 
 /// Arguments for an authorize service request.
-#[derive(Debug)]
+#[derive(custom_debug::Debug)]
 #[non_exhaustive]
 pub struct AuthorizeService {
     /// Adapter making the request.
@@ -155,6 +222,11 @@ pub struct AuthorizeService {
     pub device: Address,
     /// Service UUID.
     pub service: Uuid,
+    /// Resolves once the request has been canceled.
+    #[debug(skip)]
+    pub cancel: oneshot::Receiver<()>,
+    /// Deadline after which the request is canceled.
+    pub deadline: Instant,
 }
 
 /// Function handling an authorize service request.
@@ -168,9 +240,14 @@ pub type AuthorizeServiceFn =
 /// Setting all handlers to [None] (the default) will result in a `NoInputNoOutput` handler
 /// that accepts all requests.
 ///
-/// The future of a particular request is dropped when BlueZ cancels that request.
+/// The future of a particular request is dropped when BlueZ cancels that request
+/// or its deadline passes.
+/// Each request also carries a `cancel` receiver that resolves upon cancellation;
+/// use it to tear down prompts presented to the user.
 ///
 /// Use [Session::register_agent](crate::session::Session::register_agent) to register the handler.
+///
+/// For headless devices a [PolicyAgent] can be used instead of implementing the handlers.
 #[derive(Default)]
 pub struct Agent {
     /// This requests is to make the application agent
@@ -250,13 +327,21 @@ pub struct Agent {
     /// This method gets called when the service daemon
     /// needs to authorize a connection/service request.
     pub authorize_service: Option<AuthorizeServiceFn>,
+    /// Capability published to the service daemon.
+    ///
+    /// If [None] the capability is derived from the handlers that are set.
+    pub capability: Option<Capability>,
     #[doc(hidden)]
     pub _non_exhaustive: (),
 }
 
 impl Agent {
     /// BlueZ capability parameter.
-    pub(crate) fn capability(&self) -> &'static str {
+    pub(crate) fn capability(&self) -> Capability {
+        if let Some(capability) = self.capability {
+            return capability;
+        }
+
         let keyboard = self.request_passkey.is_some() || self.request_pin_code.is_some();
         let display_only = self.display_passkey.is_some() || self.display_pin_code.is_some();
         let yes_no = self.request_confirmation.is_some()
@@ -264,62 +349,162 @@ impl Agent {
             || self.authorize_service.is_some();
 
         match (keyboard, display_only, yes_no) {
-            (true, false, false) => "KeyboardOnly",
-            (false, true, false) => "DisplayOnly",
-            (false, _, true) => "DisplayYesNo",
-            (true, true, _) | (true, _, true) => "KeyboardDisplay",
-            (false, false, false) => "NoInputNoOutput",
+            (true, false, false) => Capability::KeyboardOnly,
+            (false, true, false) => Capability::DisplayOnly,
+            (false, _, true) => Capability::DisplayYesNo,
+            (true, true, _) | (true, _, true) => Capability::KeyboardDisplay,
+            (false, false, false) => Capability::NoInputNoOutput,
         }
     }
 }
 
+/// Lifecycle event of a registered agent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AgentEvent {
+    /// The agent has been registered with the Bluetooth daemon.
+    Registered,
+    /// The agent has become the default agent.
+    MadeDefault,
+    /// The Bluetooth daemon has unregistered the agent.
+    ///
+    /// No further requests will be received.
+    Released,
+    /// A request has been canceled by the Bluetooth daemon or
+    /// because its deadline has passed.
+    RequestCanceled {
+        /// Adapter that made the request.
+        adapter: String,
+        /// Address of device the request was about.
+        device: Address,
+    },
+}
+
 pub(crate) struct RegisteredAgent {
     a: Agent,
-    cancel: Mutex<Option<oneshot::Sender<()>>>,
+    next_id: AtomicU64,
+    cancel: std::sync::Mutex<Vec<(u64, oneshot::Sender<()>)>>,
+    request: std::sync::Mutex<Option<(u64, String, Address)>>,
+    event_tx: mpsc::Sender<AgentEvent>,
 }
 
-impl RegisteredAgent {
-    pub(crate) fn new(agent: Agent) -> Self {
-        Self { a: agent, cancel: Mutex::new(None) }
-    }
+/// Request in progress.
+///
+/// Forgets the request when dropped.
+struct ActiveRequest<'a> {
+    reg: &'a RegisteredAgent,
+    id: u64,
+    adapter: String,
+    device: Address,
+    deadline: Instant,
+}
 
-    async fn get_cancel(&self) -> oneshot::Receiver<()> {
+impl<'a> ActiveRequest<'a> {
+    /// Receiver that resolves once this request has been canceled.
+    fn cancel_rx(&self) -> oneshot::Receiver<()> {
         let (cancel_tx, cancel_rx) = oneshot::channel();
-        *self.cancel.lock().await = Some(cancel_tx);
+        let mut cancel = self.reg.cancel.lock().unwrap();
+        cancel.retain(|(_, tx)| !tx.is_closed());
+        cancel.push((self.id, cancel_tx));
         cancel_rx
     }
+}
 
-    async fn call<A, F, R>(&self, f: &Option<impl Fn(A) -> F>, arg: A) -> ReqResult<R>
-    where
-        F: Future<Output = ReqResult<R>> + Send + 'static,
-    {
-        match f {
-            Some(f) => f(arg).await,
-            None => Err(ReqError::Rejected),
+impl<'a> Drop for ActiveRequest<'a> {
+    fn drop(&mut self) {
+        self.reg.cancel.lock().unwrap().retain(|(id, _)| *id != self.id);
+        let mut request = self.reg.request.lock().unwrap();
+        if matches!(&*request, Some((id, _, _)) if *id == self.id) {
+            *request = None;
         }
     }
+}
 
-    async fn call_with_cancel<A, F, R>(&self, f: &Option<impl Fn(A) -> F>, arg: A) -> ReqResult<R>
+impl RegisteredAgent {
+    pub(crate) fn new(agent: Agent) -> (Self, mpsc::Receiver<AgentEvent>) {
+        let (event_tx, event_rx) = mpsc::channel(EVENT_QUEUE_LEN);
+        let reg = Self {
+            a: agent,
+            next_id: AtomicU64::new(0),
+            cancel: Default::default(),
+            request: Default::default(),
+            event_tx,
+        };
+        (reg, event_rx)
+    }
+
+    /// Sends an event, dropping it if the queue is full.
+    fn send_event(&self, event: AgentEvent) {
+        if let Err(mpsc::error::TrySendError::Full(event)) = self.event_tx.try_send(event) {
+            log::debug!("Dropping agent event {:?} because queue is full", &event);
+        }
+    }
+
+    /// Cancels all requests or, if `id` is specified, only that request.
+    fn cancel(&self, id: Option<u64>) {
+        let matches = |req_id: u64| id.map(|id| id == req_id).unwrap_or(true);
+
+        let canceled: Vec<_> = {
+            let mut cancel = self.cancel.lock().unwrap();
+            let (canceled, kept) = cancel.drain(..).partition(|(req_id, _)| matches(*req_id));
+            *cancel = kept;
+            canceled
+        };
+        for (_, cancel_tx) in canceled {
+            let _ = cancel_tx.send(());
+        }
+
+        let request = {
+            let mut request = self.request.lock().unwrap();
+            match &*request {
+                Some((req_id, _, _)) if matches(*req_id) => request.take(),
+                _ => None,
+            }
+        };
+        if let Some((_, adapter, device)) = request {
+            log::trace!("Agent request from {} on {} canceled", &device, &adapter);
+            self.send_event(AgentEvent::RequestCanceled { adapter, device });
+        }
+    }
+
+    async fn call_with_cancel<A, F, R>(
+        &self, req: &ActiveRequest<'_>, f: &Option<impl Fn(A) -> F>, arg: A,
+    ) -> ReqResult<R>
     where
         F: Future<Output = ReqResult<R>> + Send + 'static,
     {
-        let cancel_rx = self.get_cancel().await;
+        let cancel_rx = req.cancel_rx();
         match f {
             Some(f) => {
                 let fut = f(arg);
                 pin_mut!(fut);
                 select! {
                     result = fut => result,
-                    _ = cancel_rx => Err(ReqError::Canceled)
+                    _ = cancel_rx => Err(ReqError::Canceled),
+                    () = sleep_until(req.deadline.into()) => {
+                        self.cancel(Some(req.id));
+                        Err(ReqError::Canceled)
+                    }
                 }
             }
             None => Err(ReqError::Rejected),
         }
     }
 
-    fn parse_device_path(device: &dbus::Path<'static>) -> ReqResult<(String, Address)> {
+    /// Parses the device path of a request and records it as the current request.
+    fn begin_request(&self, device: &dbus::Path<'static>) -> ReqResult<ActiveRequest<'_>> {
         match Device::parse_dbus_path(device) {
-            Some((adapter, addr)) => Ok((adapter.to_string(), addr)),
+            Some((adapter, addr)) => {
+                let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                *self.request.lock().unwrap() = Some((id, adapter.to_string(), addr));
+                Ok(ActiveRequest {
+                    reg: self,
+                    id,
+                    adapter: adapter.to_string(),
+                    device: addr,
+                    deadline: Instant::now() + REQUEST_TIMEOUT,
+                })
+            }
             None => {
                 log::error!("Cannot parse device path {}", &device);
                 Err(ReqError::Rejected)
@@ -329,11 +514,16 @@ impl RegisteredAgent {
 
     pub(crate) fn register_interface(cr: &mut Crossroads) -> IfaceToken<Arc<Self>> {
         cr.register(INTERFACE, |ib: &mut IfaceBuilder<Arc<Self>>| {
+            ib.method_with_cr_async("Release", (), (), |ctx, cr, ()| {
+                method_call(ctx, cr, move |reg: Arc<Self>| async move {
+                    log::trace!("Agent released");
+                    reg.send_event(AgentEvent::Released);
+                    Ok(())
+                })
+            });
             ib.method_with_cr_async("Cancel", (), (), |ctx, cr, ()| {
                 method_call(ctx, cr, move |reg: Arc<Self>| async move {
-                    if let Some(cancel_tx) = reg.cancel.lock().await.take() {
-                        let _ = cancel_tx.send(());
-                    }
+                    reg.cancel(None);
                     Ok(())
                 })
             });
@@ -343,9 +533,19 @@ impl RegisteredAgent {
                 ("value",),
                 |ctx, cr, (device,): (dbus::Path<'static>,)| {
                     method_call(ctx, cr, |reg: Arc<Self>| async move {
-                        let (adapter, device) = Self::parse_device_path(&device)?;
+                        let req = reg.begin_request(&device)?;
+                        let cancel = req.cancel_rx();
                         Ok((reg
-                            .call_with_cancel(&reg.a.request_pin_code, RequestPinCode { adapter, device })
+                            .call_with_cancel(
+                                &req,
+                                &reg.a.request_pin_code,
+                                RequestPinCode {
+                                    adapter: req.adapter.clone(),
+                                    device: req.device,
+                                    cancel,
+                                    deadline: req.deadline,
+                                },
+                            )
                             .await?,))
                     })
                 },
@@ -356,10 +556,18 @@ impl RegisteredAgent {
                 (),
                 |ctx, cr, (device, pincode): (dbus::Path<'static>, String)| {
                     method_call(ctx, cr, |reg: Arc<Self>| async move {
-                        let (adapter, device) = Self::parse_device_path(&device)?;
-                        reg.call(
+                        let req = reg.begin_request(&device)?;
+                        let cancel = req.cancel_rx();
+                        reg.call_with_cancel(
+                            &req,
                             &reg.a.display_pin_code,
-                            DisplayPinCode { adapter, device, pincode, cancel: reg.get_cancel().await },
+                            DisplayPinCode {
+                                adapter: req.adapter.clone(),
+                                device: req.device,
+                                pincode,
+                                cancel,
+                                deadline: req.deadline,
+                            },
                         )
                         .await?;
                         Ok(())
@@ -372,9 +580,19 @@ impl RegisteredAgent {
                 ("value",),
                 |ctx, cr, (device,): (dbus::Path<'static>,)| {
                     method_call(ctx, cr, |reg: Arc<Self>| async move {
-                        let (adapter, device) = Self::parse_device_path(&device)?;
+                        let req = reg.begin_request(&device)?;
+                        let cancel = req.cancel_rx();
                         Ok((reg
-                            .call_with_cancel(&reg.a.request_passkey, RequestPasskey { adapter, device })
+                            .call_with_cancel(
+                                &req,
+                                &reg.a.request_passkey,
+                                RequestPasskey {
+                                    adapter: req.adapter.clone(),
+                                    device: req.device,
+                                    cancel,
+                                    deadline: req.deadline,
+                                },
+                            )
                             .await?,))
                     })
                 },
@@ -385,10 +603,19 @@ impl RegisteredAgent {
                 (),
                 |ctx, cr, (device, passkey, entered): (dbus::Path<'static>, u32, u16)| {
                     method_call(ctx, cr, move |reg: Arc<Self>| async move {
-                        let (adapter, device) = Self::parse_device_path(&device)?;
-                        reg.call(
+                        let req = reg.begin_request(&device)?;
+                        let cancel = req.cancel_rx();
+                        reg.call_with_cancel(
+                            &req,
                             &reg.a.display_passkey,
-                            DisplayPasskey { adapter, device, passkey, entered, cancel: reg.get_cancel().await },
+                            DisplayPasskey {
+                                adapter: req.adapter.clone(),
+                                device: req.device,
+                                passkey,
+                                entered,
+                                cancel,
+                                deadline: req.deadline,
+                            },
                         )
                         .await?;
                         Ok(())
@@ -401,10 +628,18 @@ impl RegisteredAgent {
                 (),
                 |ctx, cr, (device, passkey): (dbus::Path<'static>, u32)| {
                     method_call(ctx, cr, move |reg: Arc<Self>| async move {
-                        let (adapter, device) = Self::parse_device_path(&device)?;
+                        let req = reg.begin_request(&device)?;
+                        let cancel = req.cancel_rx();
                         reg.call_with_cancel(
+                            &req,
                             &reg.a.request_confirmation,
-                            RequestConfirmation { adapter, device, passkey },
+                            RequestConfirmation {
+                                adapter: req.adapter.clone(),
+                                device: req.device,
+                                passkey,
+                                cancel,
+                                deadline: req.deadline,
+                            },
                         )
                         .await?;
                         Ok(())
@@ -417,10 +652,17 @@ impl RegisteredAgent {
                 (),
                 |ctx, cr, (device,): (dbus::Path<'static>,)| {
                     method_call(ctx, cr, move |reg: Arc<Self>| async move {
-                        let (adapter, device) = Self::parse_device_path(&device)?;
+                        let req = reg.begin_request(&device)?;
+                        let cancel = req.cancel_rx();
                         reg.call_with_cancel(
+                            &req,
                             &reg.a.request_authorization,
-                            RequestAuthorization { adapter, device },
+                            RequestAuthorization {
+                                adapter: req.adapter.clone(),
+                                device: req.device,
+                                cancel,
+                                deadline: req.deadline,
+                            },
                         )
                         .await?;
                         Ok(())
@@ -428,12 +670,12 @@ impl RegisteredAgent {
                 },
             );
             ib.method_with_cr_async(
-                "RequestConfirmation",
+                "AuthorizeService",
                 ("device", "uuid"),
                 (),
                 |ctx, cr, (device, uuid): (dbus::Path<'static>, String)| {
                     method_call(ctx, cr, move |reg: Arc<Self>| async move {
-                        let (adapter, device) = Self::parse_device_path(&device)?;
+                        let req = reg.begin_request(&device)?;
                         let service: Uuid = match uuid.parse() {
                             Ok(service) => service,
                             Err(_) => {
@@ -441,9 +683,17 @@ impl RegisteredAgent {
                                 return Err(ReqError::Rejected.into());
                             }
                         };
+                        let cancel = req.cancel_rx();
                         reg.call_with_cancel(
+                            &req,
                             &reg.a.authorize_service,
-                            AuthorizeService { adapter, device, service },
+                            AuthorizeService {
+                                adapter: req.adapter.clone(),
+                                device: req.device,
+                                service,
+                                cancel,
+                                deadline: req.deadline,
+                            },
                         )
                         .await?;
                         Ok(())
@@ -453,10 +703,13 @@ impl RegisteredAgent {
         })
     }
 
-    pub(crate) async fn register(self, inner: Arc<SessionInner>) -> Result<AgentHandle> {
+    pub(crate) async fn register(
+        self, event_rx: mpsc::Receiver<AgentEvent>, inner: Arc<SessionInner>,
+    ) -> Result<AgentHandle> {
         let name = dbus::Path::new(format!("{}{}", AGENT_PREFIX, Uuid::new_v4().as_simple())).unwrap();
-        let capability = self.a.capability();
+        let capability: &str = self.a.capability().into();
         let request_default = self.a.request_default;
+        let event_tx = self.event_tx.clone();
         log::trace!("Publishing agent at {} with capability {}", &name, &capability);
 
         {
@@ -468,6 +721,7 @@ impl RegisteredAgent {
         let proxy = Proxy::new(SERVICE_NAME, MANAGER_PATH, TIMEOUT, inner.connection.clone());
         proxy.method_call(MANAGER_INTERFACE, "RegisterAgent", (name.clone(), capability)).await?;
         let connection = inner.connection.clone();
+        let _ = event_tx.try_send(AgentEvent::Registered);
 
         let (drop_tx, drop_rx) = oneshot::channel();
         let unreg_name = name.clone();
@@ -487,17 +741,23 @@ impl RegisteredAgent {
             log::trace!("Requesting default agent for {}", &name);
             let proxy = Proxy::new(SERVICE_NAME, MANAGER_PATH, TIMEOUT, connection);
             proxy.method_call(MANAGER_INTERFACE, "RequestDefaultAgent", (name.clone(),)).await?;
+            let _ = event_tx.try_send(AgentEvent::MadeDefault);
         }
 
-        Ok(AgentHandle { name, _drop_tx: drop_tx })
+        Ok(AgentHandle { name, event_rx, _drop_tx: drop_tx })
     }
 }
 
 /// Handle to registered agent.
 ///
 /// Drop to unregister agent.
+///
+/// This provides a stream of [agent lifecycle events](AgentEvent).
+/// Consuming the stream is optional.
+/// Events that are not consumed in time are dropped.
 pub struct AgentHandle {
     name: dbus::Path<'static>,
+    event_rx: mpsc::Receiver<AgentEvent>,
     _drop_tx: oneshot::Sender<()>,
 }
 
@@ -512,3 +772,11 @@ impl fmt::Debug for AgentHandle {
         write!(f, "AgentHandle {{ {} }}", &self.name)
     }
 }
+
+impl Stream for AgentHandle {
+    type Item = AgentEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.event_rx.poll_recv(cx)
+    }
+}
@@ -0,0 +1,464 @@
+//! Policy-based Bluetooth authorization agent.
+
+use futures::FutureExt;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+use super::{
+    Agent, AuthorizeService, Capability, DisplayPasskey, DisplayPinCode, ReqError, ReqResult,
+    RequestAuthorization, RequestConfirmation, RequestPasskey, RequestPinCode,
+};
+use crate::{Address, InvalidAddress};
+
+/// Pattern matching the address of a remote device.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DevicePattern {
+    /// Matches exactly the specified device address.
+    Address(Address),
+    /// Matches all devices whose address starts with the specified
+    /// organizationally unique identifier (OUI).
+    Oui([u8; 3]),
+}
+
+impl DevicePattern {
+    /// Returns whether the specified device address matches this pattern.
+    pub fn matches(&self, addr: Address) -> bool {
+        match self {
+            Self::Address(a) => *a == addr,
+            Self::Oui(oui) => addr[..3] == oui[..],
+        }
+    }
+}
+
+impl fmt::Display for DevicePattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Address(addr) => write!(f, "{}", addr),
+            Self::Oui(oui) => write!(f, "{:02X}:{:02X}:{:02X}", oui[0], oui[1], oui[2]),
+        }
+    }
+}
+
+impl From<Address> for DevicePattern {
+    fn from(addr: Address) -> Self {
+        Self::Address(addr)
+    }
+}
+
+impl FromStr for DevicePattern {
+    type Err = InvalidAddress;
+
+    /// Parses either a full device address (`AA:BB:CC:DD:EE:FF`) or
+    /// an OUI prefix (`AA:BB:CC`).
+    fn from_str(s: &str) -> std::result::Result<Self, InvalidAddress> {
+        let fields = s
+            .split(':')
+            .map(|f| u8::from_str_radix(f, 16).map_err(|_| InvalidAddress(s.to_string())))
+            .collect::<std::result::Result<Vec<_>, InvalidAddress>>()?;
+        match fields.len() {
+            3 => Ok(Self::Oui([fields[0], fields[1], fields[2]])),
+            6 => Ok(Self::Address(Address::new(fields.try_into().unwrap()))),
+            _ => Err(InvalidAddress(s.to_string())),
+        }
+    }
+}
+
+/// Pairing button for confirming pairing requests on a headless device.
+///
+/// After [press](Self::press) has been called, pairing requests are
+/// confirmed for the duration of the time window.
+/// Clones of a pairing button share their state, thus a clone can be
+/// stored in the [PolicyAgent] while another one is pressed from the
+/// user interface.
+#[derive(Clone)]
+pub struct PairingButton {
+    window: Duration,
+    pressed: Arc<Mutex<Option<Instant>>>,
+}
+
+impl PairingButton {
+    /// Creates a new pairing button that confirms pairing requests for
+    /// the specified time window after it has been pressed.
+    pub fn new(window: Duration) -> Self {
+        Self { window, pressed: Arc::new(Mutex::new(None)) }
+    }
+
+    /// Presses the button, opening the time window.
+    pub fn press(&self) {
+        log::info!("Pairing button pressed, confirming pairing requests for {:?}", self.window);
+        *self.pressed.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Closes the time window, if it is open.
+    pub fn release(&self) {
+        *self.pressed.lock().unwrap() = None;
+    }
+
+    /// Returns whether the time window is currently open.
+    pub fn is_pressed(&self) -> bool {
+        match *self.pressed.lock().unwrap() {
+            Some(pressed) => pressed.elapsed() <= self.window,
+            None => false,
+        }
+    }
+
+    /// Time window after a button press.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+}
+
+impl fmt::Debug for PairingButton {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PairingButton")
+            .field("window", &self.window)
+            .field("pressed", &self.is_pressed())
+            .finish()
+    }
+}
+
+/// Policy for requests that only require a yes/no answer.
+#[derive(Clone, Debug)]
+pub enum ConfirmPolicy {
+    /// Reject all requests.
+    Reject,
+    /// Accept all requests.
+    Accept,
+    /// Accept requests only while the pairing button is pressed.
+    Button(PairingButton),
+}
+
+impl Default for ConfirmPolicy {
+    fn default() -> Self {
+        Self::Reject
+    }
+}
+
+impl ConfirmPolicy {
+    fn decide(&self) -> (bool, &'static str) {
+        match self {
+            Self::Reject => (false, "rejecting by policy"),
+            Self::Accept => (true, "accepting by policy"),
+            Self::Button(button) if button.is_pressed() => (true, "pairing button is pressed"),
+            Self::Button(_) => (false, "pairing button is not pressed"),
+        }
+    }
+}
+
+/// Bluetooth authorization agent built from declarative rules.
+///
+/// This is intended for headless devices where no user is available to
+/// answer pairing requests.
+/// All requests from devices not matching [allowed_devices](Self::allowed_devices)
+/// are rejected.
+/// Every decision made by the agent is logged.
+///
+/// Convert into an [Agent] and use [Session::register_agent](crate::session::Session::register_agent)
+/// to register the agent.
+/// Unless [capability](Self::capability) is specified, the agent is published
+/// as `KeyboardOnly` if a pin code or passkey is configured and as `NoInputNoOutput` otherwise.
+#[derive(Clone, Debug, Default)]
+pub struct PolicyAgent {
+    /// This requests is to make the application agent
+    /// the default agent.
+    pub request_default: bool,
+    /// Devices allowed to pair and connect.
+    ///
+    /// If [None] all devices are allowed.
+    pub allowed_devices: Option<Vec<DevicePattern>>,
+    /// Pin code used for devices not present in [pin_codes](Self::pin_codes).
+    pub pin_code: Option<String>,
+    /// Pin codes of particular devices.
+    pub pin_codes: HashMap<Address, String>,
+    /// Passkey used for devices not present in [passkeys](Self::passkeys).
+    pub passkey: Option<u32>,
+    /// Passkeys of particular devices.
+    ///
+    /// These are also used to answer confirmation requests:
+    /// a confirmation request is accepted if and only if its
+    /// passkey matches.
+    pub passkeys: HashMap<Address, u32>,
+    /// Policy for confirmation requests with a passkey not
+    /// known by [passkeys](Self::passkeys) and for authorization
+    /// requests that would trigger the just-works pairing model.
+    pub confirm: ConfirmPolicy,
+    /// Services that connected devices are authorized to use.
+    ///
+    /// If [None] all services are authorized.
+    pub allowed_services: Option<HashSet<Uuid>>,
+    /// Capability published to the Bluetooth daemon.
+    ///
+    /// Requests to display a passkey or pin code are decided by the
+    /// [confirm](Self::confirm) policy, since no display is assumed to be available.
+    pub capability: Option<Capability>,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl PolicyAgent {
+    /// Returns whether the specified device is allowed by the policy.
+    pub fn is_device_allowed(&self, device: Address) -> bool {
+        match &self.allowed_devices {
+            Some(allowed) => allowed.iter().any(|p| p.matches(device)),
+            None => true,
+        }
+    }
+
+    fn check_device(&self, request: &str, adapter: &str, device: Address) -> ReqResult<()> {
+        if self.is_device_allowed(device) {
+            Ok(())
+        } else {
+            log::info!("Rejecting {} from {} on {}: device is not allowed", request, device, adapter);
+            Err(ReqError::Rejected)
+        }
+    }
+
+    fn request_pin_code(&self, req: RequestPinCode) -> ReqResult<String> {
+        self.check_device("pin code request", &req.adapter, req.device)?;
+        match self.pin_codes.get(&req.device).or(self.pin_code.as_ref()) {
+            Some(pin_code) => {
+                log::info!("Providing pin code to {} on {}", req.device, req.adapter);
+                Ok(pin_code.clone())
+            }
+            None => {
+                log::info!("Rejecting pin code request from {} on {}: no pin code", req.device, req.adapter);
+                Err(ReqError::Rejected)
+            }
+        }
+    }
+
+    fn request_passkey(&self, req: RequestPasskey) -> ReqResult<u32> {
+        self.check_device("passkey request", &req.adapter, req.device)?;
+        match self.passkeys.get(&req.device).or(self.passkey.as_ref()) {
+            Some(passkey) => {
+                log::info!("Providing passkey to {} on {}", req.device, req.adapter);
+                Ok(*passkey)
+            }
+            None => {
+                log::info!("Rejecting passkey request from {} on {}: no passkey", req.device, req.adapter);
+                Err(ReqError::Rejected)
+            }
+        }
+    }
+
+    fn display_pin_code(&self, req: DisplayPinCode) -> ReqResult<()> {
+        self.check_device("display pin code request", &req.adapter, req.device)?;
+        let (accept, reason) = self.confirm.decide();
+        self.log_decision("display pin code request", &req.adapter, req.device, accept, reason)
+    }
+
+    fn display_passkey(&self, req: DisplayPasskey) -> ReqResult<()> {
+        self.check_device("display passkey request", &req.adapter, req.device)?;
+        let (accept, reason) = self.confirm.decide();
+        self.log_decision("display passkey request", &req.adapter, req.device, accept, reason)
+    }
+
+    fn request_confirmation(&self, req: RequestConfirmation) -> ReqResult<()> {
+        self.check_device("confirmation request", &req.adapter, req.device)?;
+        let (accept, reason) = match self.passkeys.get(&req.device).or(self.passkey.as_ref()) {
+            Some(passkey) if *passkey == req.passkey => (true, "passkey matches"),
+            Some(_) => (false, "passkey does not match"),
+            None => self.confirm.decide(),
+        };
+        self.log_decision("confirmation request", &req.adapter, req.device, accept, reason)
+    }
+
+    fn request_authorization(&self, req: RequestAuthorization) -> ReqResult<()> {
+        self.check_device("authorization request", &req.adapter, req.device)?;
+        let (accept, reason) = self.confirm.decide();
+        self.log_decision("authorization request", &req.adapter, req.device, accept, reason)
+    }
+
+    fn authorize_service(&self, req: AuthorizeService) -> ReqResult<()> {
+        self.check_device("service authorization", &req.adapter, req.device)?;
+        let (accept, reason) = match &self.allowed_services {
+            Some(allowed) if allowed.contains(&req.service) => (true, "service is allowed"),
+            Some(_) => (false, "service is not allowed"),
+            None => (true, "all services are allowed"),
+        };
+        self.log_decision(
+            &format!("service authorization for {}", req.service),
+            &req.adapter,
+            req.device,
+            accept,
+            reason,
+        )
+    }
+
+    fn log_decision(
+        &self, request: &str, adapter: &str, device: Address, accept: bool, reason: &str,
+    ) -> ReqResult<()> {
+        if accept {
+            log::info!("Accepting {} from {} on {}: {}", request, device, adapter, reason);
+            Ok(())
+        } else {
+            log::info!("Rejecting {} from {} on {}: {}", request, device, adapter, reason);
+            Err(ReqError::Rejected)
+        }
+    }
+}
+
+impl From<PolicyAgent> for Agent {
+    fn from(policy: PolicyAgent) -> Self {
+        let policy = Arc::new(policy);
+        let has_pin_code = policy.pin_code.is_some() || !policy.pin_codes.is_empty();
+        let has_passkey = policy.passkey.is_some() || !policy.passkeys.is_empty();
+        let capability = policy.capability.unwrap_or(if has_pin_code || has_passkey {
+            Capability::KeyboardOnly
+        } else {
+            Capability::NoInputNoOutput
+        });
+
+        Agent {
+            request_default: policy.request_default,
+            capability: Some(capability),
+            request_pin_code: has_pin_code.then(|| {
+                let policy = policy.clone();
+                Box::new(move |req| {
+                    let res = policy.request_pin_code(req);
+                    async move { res }.boxed()
+                }) as _
+            }),
+            request_passkey: has_passkey.then(|| {
+                let policy = policy.clone();
+                Box::new(move |req| {
+                    let res = policy.request_passkey(req);
+                    async move { res }.boxed()
+                }) as _
+            }),
+            display_pin_code: Some({
+                let policy = policy.clone();
+                Box::new(move |req| {
+                    let res = policy.display_pin_code(req);
+                    async move { res }.boxed()
+                })
+            }),
+            display_passkey: Some({
+                let policy = policy.clone();
+                Box::new(move |req| {
+                    let res = policy.display_passkey(req);
+                    async move { res }.boxed()
+                })
+            }),
+            request_confirmation: Some({
+                let policy = policy.clone();
+                Box::new(move |req| {
+                    let res = policy.request_confirmation(req);
+                    async move { res }.boxed()
+                })
+            }),
+            request_authorization: Some({
+                let policy = policy.clone();
+                Box::new(move |req| {
+                    let res = policy.request_authorization(req);
+                    async move { res }.boxed()
+                })
+            }),
+            authorize_service: Some(Box::new(move |req| {
+                let res = policy.authorize_service(req);
+                async move { res }.boxed()
+            })),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::oneshot;
+
+    const ALLOWED: Address = Address([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+    const OTHER: Address = Address([0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb]);
+
+    fn policy() -> PolicyAgent {
+        PolicyAgent {
+            allowed_devices: Some(vec![DevicePattern::Oui([0x00, 0x11, 0x22])]),
+            passkeys: [(ALLOWED, 123456)].into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    fn confirmation(device: Address, passkey: u32) -> RequestConfirmation {
+        let (_, cancel) = oneshot::channel();
+        RequestConfirmation { adapter: "hci0".to_string(), device, passkey, cancel, deadline: Instant::now() }
+    }
+
+    fn authorization(device: Address) -> RequestAuthorization {
+        let (_, cancel) = oneshot::channel();
+        RequestAuthorization { adapter: "hci0".to_string(), device, cancel, deadline: Instant::now() }
+    }
+
+    fn display_passkey(device: Address) -> DisplayPasskey {
+        let (_, cancel) = oneshot::channel();
+        DisplayPasskey {
+            adapter: "hci0".to_string(),
+            device,
+            passkey: 123456,
+            entered: 0,
+            cancel,
+            deadline: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn device_pattern() {
+        assert_eq!("00:11:22".parse::<DevicePattern>().unwrap(), DevicePattern::Oui([0x00, 0x11, 0x22]));
+        assert_eq!("00:11:22:33:44:55".parse::<DevicePattern>().unwrap(), DevicePattern::Address(ALLOWED));
+        assert!("00:11".parse::<DevicePattern>().is_err());
+        assert!(DevicePattern::Oui([0x00, 0x11, 0x22]).matches(ALLOWED));
+        assert!(!DevicePattern::Oui([0x00, 0x11, 0x22]).matches(OTHER));
+    }
+
+    #[test]
+    fn accept() {
+        let policy = policy();
+        assert_eq!(policy.request_confirmation(confirmation(ALLOWED, 123456)), Ok(()));
+
+        let policy = PolicyAgent { confirm: ConfirmPolicy::Accept, ..policy };
+        assert_eq!(policy.request_authorization(authorization(ALLOWED)), Ok(()));
+        assert_eq!(policy.display_passkey(display_passkey(ALLOWED)), Ok(()));
+    }
+
+    #[test]
+    fn reject() {
+        let policy = PolicyAgent { confirm: ConfirmPolicy::Accept, ..policy() };
+        assert_eq!(policy.request_confirmation(confirmation(ALLOWED, 654321)), Err(ReqError::Rejected));
+        assert_eq!(policy.request_confirmation(confirmation(OTHER, 123456)), Err(ReqError::Rejected));
+        assert_eq!(policy.request_authorization(authorization(OTHER)), Err(ReqError::Rejected));
+        assert_eq!(policy.display_passkey(display_passkey(OTHER)), Err(ReqError::Rejected));
+
+        let policy = PolicyAgent { confirm: ConfirmPolicy::Reject, ..policy };
+        assert_eq!(policy.display_passkey(display_passkey(ALLOWED)), Err(ReqError::Rejected));
+    }
+
+    #[test]
+    fn fallback() {
+        let button = PairingButton::new(Duration::from_secs(60));
+        let policy =
+            PolicyAgent { passkeys: HashMap::new(), confirm: ConfirmPolicy::Button(button.clone()), ..policy() };
+        assert_eq!(policy.request_confirmation(confirmation(ALLOWED, 123456)), Err(ReqError::Rejected));
+
+        button.press();
+        assert_eq!(policy.request_confirmation(confirmation(ALLOWED, 123456)), Ok(()));
+        assert_eq!(policy.request_authorization(authorization(ALLOWED)), Ok(()));
+
+        button.release();
+        assert_eq!(policy.request_authorization(authorization(ALLOWED)), Err(ReqError::Rejected));
+    }
+
+    #[test]
+    fn capability() {
+        assert_eq!(Agent::from(PolicyAgent::default()).capability(), Capability::NoInputNoOutput);
+        assert_eq!(Agent::from(policy()).capability(), Capability::KeyboardOnly);
+        let policy = PolicyAgent { capability: Some(Capability::DisplayYesNo), ..policy() };
+        assert_eq!(Agent::from(policy).capability(), Capability::DisplayYesNo);
+    }
+}
@@ -0,0 +1,701 @@
+//! Bonded devices and their keys.
+//!
+//! The bonds of an adapter are read from and written to the storage
+//! directory of the Bluetooth daemon, which is usually `/var/lib/bluetooth`.
+//! Use [Adapter::export_bonds](crate::Adapter::export_bonds) and
+//! [Adapter::import_bonds](crate::Adapter::import_bonds) to move the bonds
+//! of an adapter to another adapter, possibly on another host.
+
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::{Address, AddressType, ConnectionParameters};
+
+/// Default storage directory of the Bluetooth daemon.
+pub const STORAGE_DIR: &str = "/var/lib/bluetooth";
+
+/// Classic Bluetooth (BR/EDR) link key.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkKey {
+    /// Key value.
+    pub key: [u8; 16],
+    /// Key type as defined by the HCI specification.
+    pub key_type: u8,
+    /// Length of PIN code used for legacy pairing.
+    pub pin_length: u8,
+}
+
+/// Bluetooth LE long term key (LTK).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LongTermKey {
+    /// Key value.
+    pub key: [u8; 16],
+    /// Key type as defined by the kernel management interface.
+    ///
+    /// This specifies whether the key is authenticated and whether
+    /// it was generated using LE Secure Connections.
+    pub key_type: u8,
+    /// Whether the key is used when the local adapter is central of the connection.
+    pub central: bool,
+    /// Encryption key size.
+    pub enc_size: u8,
+    /// Encrypted diversifier.
+    pub ediv: u16,
+    /// Random number.
+    pub rand: u64,
+}
+
+/// Bluetooth LE connection signature resolving key (CSRK).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignatureKey {
+    /// Key value.
+    pub key: [u8; 16],
+    /// Sign counter.
+    pub counter: u32,
+    /// Whether the key is authenticated.
+    pub authenticated: bool,
+}
+
+/// Bonded remote device.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct BondedDevice {
+    /// Address of device.
+    ///
+    /// For Bluetooth LE devices using privacy this is the identity address.
+    pub address: Address,
+    /// Type of address.
+    pub address_type: AddressType,
+    /// Name of device.
+    pub name: Option<String>,
+    /// Whether the device is trusted.
+    pub trusted: bool,
+    /// Classic Bluetooth (BR/EDR) link key.
+    pub link_key: Option<LinkKey>,
+    /// Bluetooth LE long term keys.
+    pub long_term_keys: Vec<LongTermKey>,
+    /// Bluetooth LE identity resolving key (IRK) of device.
+    pub irk: Option<[u8; 16]>,
+    /// Bluetooth LE signature key used for signing data sent to the device.
+    pub local_csrk: Option<SignatureKey>,
+    /// Bluetooth LE signature key used for verifying data received from the device.
+    pub remote_csrk: Option<SignatureKey>,
+    /// Bluetooth LE connection parameters requested by the device.
+    pub connection_parameters: Option<ConnectionParameters>,
+}
+
+/// Bonds of an adapter.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Bonds {
+    /// Address of the adapter the bonds were exported from.
+    pub adapter: Address,
+    /// Bluetooth LE identity resolving key (IRK) of the adapter.
+    pub local_irk: Option<[u8; 16]>,
+    /// Bonded devices.
+    pub devices: Vec<BondedDevice>,
+}
+
+/// Minimal parser and writer for the key files used by the Bluetooth daemon.
+///
+/// The order of groups and entries as well as comments are preserved when
+/// a key file is modified and written back.
+/// Comments preceding a group header belong to that group and are removed
+/// together with it.
+#[derive(Default)]
+struct KeyFile {
+    groups: Vec<KeyFileGroup>,
+}
+
+/// Group of a key file.
+///
+/// Entries before the first group header belong to a group with an empty name.
+#[derive(Default)]
+struct KeyFileGroup {
+    comments: Vec<String>,
+    name: String,
+    lines: Vec<KeyFileLine>,
+}
+
+/// Line within a group of a key file.
+enum KeyFileLine {
+    Comment(String),
+    Entry(String, String),
+}
+
+impl KeyFile {
+    fn parse(data: &str) -> Self {
+        let mut kf = Self::default();
+        let mut group = String::new();
+        let mut comments = Vec::new();
+        for line in data.lines().map(|l| l.trim()) {
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('#') {
+                comments.push(line.to_string());
+            } else if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                group = name.to_string();
+                kf.group_mut(&group).comments.append(&mut comments);
+            } else if let Some((key, value)) = line.split_once('=') {
+                let lines = &mut kf.group_mut(&group).lines;
+                lines.extend(comments.drain(..).map(KeyFileLine::Comment));
+                lines.push(KeyFileLine::Entry(key.trim().to_string(), value.trim().to_string()));
+            }
+        }
+        if !comments.is_empty() {
+            kf.group_mut(&group).lines.extend(comments.into_iter().map(KeyFileLine::Comment));
+        }
+        kf
+    }
+
+    fn group_mut(&mut self, group: &str) -> &mut KeyFileGroup {
+        let idx = match self.groups.iter().position(|g| g.name == group) {
+            Some(idx) => idx,
+            None => {
+                self.groups.push(KeyFileGroup { name: group.to_string(), ..Default::default() });
+                self.groups.len() - 1
+            }
+        };
+        &mut self.groups[idx]
+    }
+
+    fn get(&self, group: &str, key: &str) -> Option<&str> {
+        let group = self.groups.iter().find(|g| g.name == group)?;
+        group.lines.iter().find_map(|line| match line {
+            KeyFileLine::Entry(k, v) if k == key => Some(v.as_str()),
+            _ => None,
+        })
+    }
+
+    fn get_parsed<T: FromStr>(&self, group: &str, key: &str) -> Result<Option<T>> {
+        match self.get(group, key) {
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| Error::new(ErrorKind::InvalidData, format!("invalid value of {}.{}", group, key))),
+            None => Ok(None),
+        }
+    }
+
+    fn get_key(&self, group: &str) -> Result<Option<[u8; 16]>> {
+        match self.get(group, "Key") {
+            Some(value) => Ok(Some(parse_key(value)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn set(&mut self, group: &str, key: &str, value: impl ToString) {
+        let group = self.group_mut(group);
+        let value = value.to_string();
+        for line in &mut group.lines {
+            if let KeyFileLine::Entry(k, v) = line {
+                if k == key {
+                    *v = value;
+                    return;
+                }
+            }
+        }
+        group.lines.push(KeyFileLine::Entry(key.to_string(), value));
+    }
+
+    fn remove_group(&mut self, group: &str) {
+        self.groups.retain(|g| g.name != group);
+    }
+
+    fn to_data(&self) -> String {
+        let mut out = String::new();
+        for group in &self.groups {
+            if group.name.is_empty() && group.comments.is_empty() && group.lines.is_empty() {
+                continue;
+            }
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            for comment in &group.comments {
+                let _ = writeln!(out, "{}", comment);
+            }
+            if !group.name.is_empty() {
+                let _ = writeln!(out, "[{}]", group.name);
+            }
+            for line in &group.lines {
+                match line {
+                    KeyFileLine::Comment(comment) => {
+                        let _ = writeln!(out, "{}", comment);
+                    }
+                    KeyFileLine::Entry(key, value) => {
+                        let _ = writeln!(out, "{}={}", key, value);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn read(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(data) => Ok(Self::parse(&data)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn parse_key(value: &str) -> Result<[u8; 16]> {
+    let mut key = [0; 16];
+    hex::decode_to_slice(value, &mut key).map_err(|_| Error::new(ErrorKind::InvalidData, "invalid key"))?;
+    Ok(key)
+}
+
+fn format_key(key: &[u8; 16]) -> String {
+    hex::encode_upper(key)
+}
+
+const GENERAL: &str = "General";
+const LINK_KEY: &str = "LinkKey";
+const LONG_TERM_KEY: &str = "LongTermKey";
+const PERIPHERAL_LONG_TERM_KEY: &str = "PeripheralLongTermKey";
+const SLAVE_LONG_TERM_KEY: &str = "SlaveLongTermKey";
+const IDENTITY_RESOLVING_KEY: &str = "IdentityResolvingKey";
+const LOCAL_SIGNATURE_KEY: &str = "LocalSignatureKey";
+const REMOTE_SIGNATURE_KEY: &str = "RemoteSignatureKey";
+const CONNECTION_PARAMETERS: &str = "ConnectionParameters";
+
+impl BondedDevice {
+    fn from_key_file(address: Address, kf: &KeyFile) -> Result<Self> {
+        let le = kf.get(GENERAL, "SupportedTechnologies").map(|t| t.contains("LE")).unwrap_or_default();
+        let bredr = kf.get(GENERAL, "SupportedTechnologies").map(|t| t.contains("BR/EDR")).unwrap_or_default();
+        let address_type = match kf.get(GENERAL, "AddressType") {
+            Some("static") => AddressType::LeRandom,
+            _ if le && !bredr => AddressType::LePublic,
+            _ => AddressType::BrEdr,
+        };
+
+        let link_key = match kf.get_key(LINK_KEY)? {
+            Some(key) => Some(LinkKey {
+                key,
+                key_type: kf.get_parsed(LINK_KEY, "Type")?.unwrap_or_default(),
+                pin_length: kf.get_parsed(LINK_KEY, "PINLength")?.unwrap_or_default(),
+            }),
+            None => None,
+        };
+
+        let mut long_term_keys = Vec::new();
+        for (group, central) in
+            [(LONG_TERM_KEY, true), (PERIPHERAL_LONG_TERM_KEY, false), (SLAVE_LONG_TERM_KEY, false)]
+        {
+            if let Some(key) = kf.get_key(group)? {
+                if !central && long_term_keys.iter().any(|ltk: &LongTermKey| !ltk.central) {
+                    continue;
+                }
+                long_term_keys.push(LongTermKey {
+                    key,
+                    key_type: kf.get_parsed(group, "Authenticated")?.unwrap_or_default(),
+                    central,
+                    enc_size: kf.get_parsed(group, "EncSize")?.unwrap_or(16),
+                    ediv: kf.get_parsed(group, "EDiv")?.unwrap_or_default(),
+                    rand: kf.get_parsed(group, "Rand")?.unwrap_or_default(),
+                });
+            }
+        }
+
+        let csrk = |group: &str| -> Result<Option<SignatureKey>> {
+            match kf.get_key(group)? {
+                Some(key) => Ok(Some(SignatureKey {
+                    key,
+                    counter: kf.get_parsed(group, "Counter")?.unwrap_or_default(),
+                    authenticated: kf.get_parsed(group, "Authenticated")?.unwrap_or_default(),
+                })),
+                None => Ok(None),
+            }
+        };
+
+        let connection_parameters = match (
+            kf.get_parsed(CONNECTION_PARAMETERS, "MinInterval")?,
+            kf.get_parsed(CONNECTION_PARAMETERS, "MaxInterval")?,
+            kf.get_parsed(CONNECTION_PARAMETERS, "Latency")?,
+            kf.get_parsed(CONNECTION_PARAMETERS, "Timeout")?,
+        ) {
+            (Some(min_interval), Some(max_interval), Some(latency), Some(supervision_timeout)) => {
+                Some(ConnectionParameters { min_interval, max_interval, latency, supervision_timeout })
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            address,
+            address_type,
+            name: kf.get(GENERAL, "Name").map(|n| n.to_string()),
+            trusted: kf.get_parsed(GENERAL, "Trusted")?.unwrap_or_default(),
+            link_key,
+            long_term_keys,
+            irk: kf.get_key(IDENTITY_RESOLVING_KEY)?,
+            local_csrk: csrk(LOCAL_SIGNATURE_KEY)?,
+            remote_csrk: csrk(REMOTE_SIGNATURE_KEY)?,
+            connection_parameters,
+        })
+    }
+
+    /// Updates the key file of the device, preserving entries not related to bonding.
+    fn update_key_file(&self, kf: &mut KeyFile) {
+        if let Some(name) = &self.name {
+            kf.set(GENERAL, "Name", name);
+        }
+        match self.address_type {
+            AddressType::BrEdr => {
+                if kf.get(GENERAL, "SupportedTechnologies").is_none() {
+                    kf.set(GENERAL, "SupportedTechnologies", "BR/EDR;");
+                }
+            }
+            AddressType::LePublic | AddressType::LeRandom => {
+                if kf.get(GENERAL, "SupportedTechnologies").is_none() {
+                    kf.set(GENERAL, "SupportedTechnologies", "LE;");
+                }
+                let ty = if self.address_type == AddressType::LePublic { "public" } else { "static" };
+                kf.set(GENERAL, "AddressType", ty);
+            }
+        }
+        kf.set(GENERAL, "Trusted", self.trusted);
+
+        kf.remove_group(LINK_KEY);
+        if let Some(lk) = &self.link_key {
+            kf.set(LINK_KEY, "Key", format_key(&lk.key));
+            kf.set(LINK_KEY, "Type", lk.key_type);
+            kf.set(LINK_KEY, "PINLength", lk.pin_length);
+        }
+
+        for group in [LONG_TERM_KEY, PERIPHERAL_LONG_TERM_KEY, SLAVE_LONG_TERM_KEY] {
+            kf.remove_group(group);
+        }
+        for ltk in &self.long_term_keys {
+            let group = if ltk.central { LONG_TERM_KEY } else { PERIPHERAL_LONG_TERM_KEY };
+            kf.set(group, "Key", format_key(&ltk.key));
+            kf.set(group, "Authenticated", ltk.key_type);
+            kf.set(group, "EncSize", ltk.enc_size);
+            kf.set(group, "EDiv", ltk.ediv);
+            kf.set(group, "Rand", ltk.rand);
+        }
+
+        kf.remove_group(IDENTITY_RESOLVING_KEY);
+        if let Some(irk) = &self.irk {
+            kf.set(IDENTITY_RESOLVING_KEY, "Key", format_key(irk));
+        }
+
+        for (group, csrk) in [(LOCAL_SIGNATURE_KEY, &self.local_csrk), (REMOTE_SIGNATURE_KEY, &self.remote_csrk)]
+        {
+            kf.remove_group(group);
+            if let Some(csrk) = csrk {
+                kf.set(group, "Key", format_key(&csrk.key));
+                kf.set(group, "Counter", csrk.counter);
+                kf.set(group, "Authenticated", csrk.authenticated);
+            }
+        }
+
+        kf.remove_group(CONNECTION_PARAMETERS);
+        if let Some(cp) = &self.connection_parameters {
+            kf.set(CONNECTION_PARAMETERS, "MinInterval", cp.min_interval);
+            kf.set(CONNECTION_PARAMETERS, "MaxInterval", cp.max_interval);
+            kf.set(CONNECTION_PARAMETERS, "Latency", cp.latency);
+            kf.set(CONNECTION_PARAMETERS, "Timeout", cp.supervision_timeout);
+        }
+    }
+
+    /// Reads the information about the device with the specified address
+    /// stored by the Bluetooth daemon for the adapter with the specified address.
+    ///
+    /// `storage_dir` is usually [STORAGE_DIR].
+    /// Returns `None` if no information about the device is stored.
+    pub fn read_storage(
+        storage_dir: impl AsRef<Path>, adapter: Address, address: Address,
+    ) -> Result<Option<Self>> {
+        let info = storage_dir.as_ref().join(adapter.to_string()).join(address.to_string()).join("info");
+        if !info.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(Self::from_key_file(address, &KeyFile::read(&info)?)?))
+    }
+
+    /// Address type used for Bluetooth LE keys.
+    ///
+    /// Devices supporting both classic Bluetooth and Bluetooth LE
+    /// use their public address for Bluetooth LE.
+    fn le_address_type(&self) -> AddressType {
+        match self.address_type {
+            AddressType::LeRandom => AddressType::LeRandom,
+            _ => AddressType::LePublic,
+        }
+    }
+
+    /// Whether the device has any keys.
+    pub fn is_bonded(&self) -> bool {
+        self.link_key.is_some()
+            || !self.long_term_keys.is_empty()
+            || self.irk.is_some()
+            || self.local_csrk.is_some()
+            || self.remote_csrk.is_some()
+    }
+}
+
+impl Bonds {
+    /// Reads the bonds of the adapter with the specified address from the
+    /// storage directory of the Bluetooth daemon.
+    ///
+    /// `storage_dir` is usually [STORAGE_DIR].
+    /// Devices without any keys are omitted.
+    pub fn read_storage(storage_dir: impl AsRef<Path>, adapter: Address) -> Result<Self> {
+        let adapter_dir = storage_dir.as_ref().join(adapter.to_string());
+
+        let identity = KeyFile::read(&adapter_dir.join("identity"))?;
+        let local_irk = match identity.get(GENERAL, "IdentityResolvingKey") {
+            Some(irk) => Some(parse_key(irk)?),
+            None => None,
+        };
+
+        let mut devices = Vec::new();
+        for entry in fs::read_dir(&adapter_dir)? {
+            let entry = entry?;
+            let address: Address = match entry.file_name().to_str().and_then(|name| name.parse().ok()) {
+                Some(address) => address,
+                None => continue,
+            };
+            let info = entry.path().join("info");
+            if !info.is_file() {
+                continue;
+            }
+
+            let device = BondedDevice::from_key_file(address, &KeyFile::read(&info)?)?;
+            if device.is_bonded() {
+                devices.push(device);
+            }
+        }
+        devices.sort_by_key(|dev| dev.address);
+
+        Ok(Self { adapter, local_irk, devices })
+    }
+
+    /// Writes the bonds into the storage directory of the Bluetooth daemon
+    /// for the adapter with the specified address.
+    ///
+    /// The target adapter address may differ from the [adapter address](Self::adapter)
+    /// the bonds were exported from.
+    /// Existing information about the devices not related to bonding, including comments,
+    /// is preserved.
+    ///
+    /// `storage_dir` is usually [STORAGE_DIR].
+    pub fn write_storage(&self, storage_dir: impl AsRef<Path>, adapter: Address) -> Result<()> {
+        let adapter_dir = storage_dir.as_ref().join(adapter.to_string());
+        fs::create_dir_all(&adapter_dir)?;
+
+        if let Some(irk) = &self.local_irk {
+            let path = adapter_dir.join("identity");
+            let mut identity = KeyFile::read(&path)?;
+            identity.set(GENERAL, "IdentityResolvingKey", format_key(irk));
+            fs::write(&path, identity.to_data())?;
+        }
+
+        for device in &self.devices {
+            let device_dir = adapter_dir.join(device.address.to_string());
+            fs::create_dir_all(&device_dir)?;
+            let path = device_dir.join("info");
+            let mut info = KeyFile::read(&path)?;
+            device.update_key_file(&mut info);
+            fs::write(&path, info.to_data())?;
+        }
+
+        Ok(())
+    }
+
+    /// Encodes the parameters of the management command Load Link Keys.
+    pub(crate) fn to_mgmt_link_keys(&self) -> Result<Vec<u8>> {
+        let keys: Vec<_> =
+            self.devices.iter().filter_map(|dev| dev.link_key.as_ref().map(|lk| (dev, lk))).collect();
+        let mut params = vec![0];
+        encode_key_count(&mut params, keys.len())?;
+        for (dev, lk) in keys {
+            crate::mgmt::encode_addr_info(&mut params, dev.address, AddressType::BrEdr);
+            params.push(lk.key_type);
+            params.extend_from_slice(&lk.key);
+            params.push(lk.pin_length);
+        }
+        Ok(params)
+    }
+
+    /// Encodes the parameters of the management command Load Long Term Keys.
+    pub(crate) fn to_mgmt_long_term_keys(&self) -> Result<Vec<u8>> {
+        let keys: Vec<_> =
+            self.devices.iter().flat_map(|dev| dev.long_term_keys.iter().map(move |ltk| (dev, ltk))).collect();
+        let mut params = Vec::new();
+        encode_key_count(&mut params, keys.len())?;
+        for (dev, ltk) in keys {
+            crate::mgmt::encode_addr_info(&mut params, dev.address, dev.le_address_type());
+            params.push(ltk.key_type);
+            params.push(ltk.central as u8);
+            params.push(ltk.enc_size);
+            params.extend_from_slice(&ltk.ediv.to_le_bytes());
+            params.extend_from_slice(&ltk.rand.to_le_bytes());
+            params.extend_from_slice(&ltk.key);
+        }
+        Ok(params)
+    }
+
+    /// Encodes the parameters of the management command Load Identity Resolving Keys.
+    pub(crate) fn to_mgmt_irks(&self) -> Result<Vec<u8>> {
+        let keys: Vec<_> = self.devices.iter().filter_map(|dev| dev.irk.as_ref().map(|irk| (dev, irk))).collect();
+        let mut params = Vec::new();
+        encode_key_count(&mut params, keys.len())?;
+        for (dev, irk) in keys {
+            crate::mgmt::encode_addr_info(&mut params, dev.address, dev.le_address_type());
+            params.extend_from_slice(irk);
+        }
+        Ok(params)
+    }
+
+    /// Valid connection parameters of Bluetooth LE devices.
+    pub(crate) fn connection_parameters(&self) -> Vec<(Address, AddressType, ConnectionParameters)> {
+        self.devices
+            .iter()
+            .filter_map(|dev| dev.connection_parameters.map(|cp| (dev.address, dev.le_address_type(), cp)))
+            .filter(|(_, _, cp)| cp.is_valid())
+            .collect()
+    }
+}
+
+/// Encodes the number of keys of a management command.
+fn encode_key_count(params: &mut Vec<u8>, count: usize) -> Result<()> {
+    let count = u16::try_from(count).map_err(|_| Error::new(ErrorKind::InvalidInput, "too many keys"))?;
+    params.extend_from_slice(&count.to_le_bytes());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const ADAPTER: Address = Address::new([0x00, 0x1a, 0x7d, 0xda, 0x71, 0x13]);
+    const HEADSET: Address = Address::new([0x60, 0xab, 0xd2, 0x11, 0x22, 0x33]);
+    const SENSOR: Address = Address::new([0xc4, 0x7c, 0x8d, 0x6a, 0x01, 0x02]);
+
+    fn fixture_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/bonds")
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bluer-bond-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn key_file_round_trip() {
+        let data = "# header\n[General]\nName=Test\nTrusted=true\n\n# keys\n[LinkKey]\nKey=00\n";
+        let mut kf = KeyFile::parse(data);
+        assert_eq!(kf.to_data(), data);
+
+        kf.set("General", "Trusted", false);
+        kf.set("General", "Alias", "Alias");
+        kf.remove_group("LinkKey");
+        assert_eq!(kf.to_data(), "# header\n[General]\nName=Test\nTrusted=false\nAlias=Alias\n");
+    }
+
+    #[test]
+    fn read_fixture() {
+        let bonds = Bonds::read_storage(fixture_dir(), ADAPTER).unwrap();
+        assert_eq!(bonds.adapter, ADAPTER);
+        assert_eq!(bonds.local_irk, Some(parse_key("5D2A0E1F8B3C4D6E7F8091A2B3C4D5E6").unwrap()));
+        assert_eq!(bonds.devices.len(), 2);
+
+        let headset = &bonds.devices[0];
+        assert_eq!(headset.address, HEADSET);
+        assert_eq!(headset.address_type, AddressType::BrEdr);
+        assert_eq!(headset.name.as_deref(), Some("Headset"));
+        assert!(headset.trusted);
+        let lk = headset.link_key.as_ref().unwrap();
+        assert_eq!(lk.key, parse_key("7F8E3A1C5B2D4E6F708192A3B4C5D6E7").unwrap());
+        assert_eq!(lk.key_type, 4);
+        assert!(headset.long_term_keys.is_empty());
+
+        let sensor = &bonds.devices[1];
+        assert_eq!(sensor.address, SENSOR);
+        assert_eq!(sensor.address_type, AddressType::LeRandom);
+        assert!(sensor.link_key.is_none());
+        assert_eq!(sensor.long_term_keys.len(), 1);
+        let ltk = &sensor.long_term_keys[0];
+        assert!(ltk.central);
+        assert_eq!(ltk.key_type, 2);
+        assert_eq!(ltk.enc_size, 16);
+        assert_eq!(sensor.irk, Some(parse_key("00112233445566778899AABBCCDDEEFF").unwrap()));
+        let csrk = sensor.local_csrk.as_ref().unwrap();
+        assert_eq!(csrk.counter, 3);
+        assert!(!csrk.authenticated);
+        assert!(sensor.remote_csrk.is_none());
+        assert_eq!(
+            sensor.connection_parameters,
+            Some(ConnectionParameters {
+                min_interval: 6,
+                max_interval: 12,
+                latency: 0,
+                supervision_timeout: 500
+            })
+        );
+
+        let sensor_only = BondedDevice::read_storage(fixture_dir(), ADAPTER, SENSOR).unwrap();
+        assert_eq!(sensor_only.as_ref(), Some(sensor));
+    }
+
+    #[test]
+    fn write_read_round_trip() {
+        let bonds = Bonds::read_storage(fixture_dir(), ADAPTER).unwrap();
+        let target = Address::new([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        let dir = temp_dir("round-trip");
+
+        bonds.write_storage(&dir, target).unwrap();
+        let read = Bonds::read_storage(&dir, target).unwrap();
+        assert_eq!(read.adapter, target);
+        assert_eq!(read.local_irk, bonds.local_irk);
+        assert_eq!(read.devices, bonds.devices);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_preserves_other_entries() {
+        let dir = temp_dir("preserve");
+        let src = fixture_dir().join(ADAPTER.to_string()).join(HEADSET.to_string()).join("info");
+        let device_dir = dir.join(ADAPTER.to_string()).join(HEADSET.to_string());
+        fs::create_dir_all(&device_dir).unwrap();
+        fs::copy(&src, device_dir.join("info")).unwrap();
+
+        let mut bonds = Bonds::read_storage(fixture_dir(), ADAPTER).unwrap();
+        bonds.devices.retain(|dev| dev.address == HEADSET);
+        bonds.devices[0].link_key.as_mut().unwrap().key = [0xaa; 16];
+        bonds.write_storage(&dir, ADAPTER).unwrap();
+
+        let data = fs::read_to_string(device_dir.join("info")).unwrap();
+        assert!(data.starts_with("# Headset paired for testing\n[General]\nName=Headset\nClass=0x240404\n"));
+        assert!(data.contains("Services=0000110b-0000-1000-8000-00805f9b34fb;"));
+        assert!(data.contains(&format!("[LinkKey]\nKey={}\n", "AA".repeat(16))));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn key_count() {
+        let mut params = Vec::new();
+        encode_key_count(&mut params, 0x0102).unwrap();
+        assert_eq!(params, [0x02, 0x01]);
+        let err = encode_key_count(&mut params, 0x1_0000).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+}
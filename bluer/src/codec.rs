@@ -0,0 +1,371 @@
+//! Framed codecs for L2CAP and RFCOMM connections.
+//!
+//! Stream-oriented connections, i.e. [L2CAP streams](crate::l2cap::Stream) and
+//! [RFCOMM streams](crate::rfcomm::Stream), do not preserve message boundaries.
+//! Use their `framed` method to frame messages using any [Decoder] and [Encoder]
+//! or their `length_delimited` method to prefix each message by its length.
+//!
+//! [L2CAP sequential packet connections](crate::l2cap::SeqPacket) preserve message boundaries,
+//! but limit the size of a packet to the MTU.
+//! [SeqPacketFramed] provides a [Stream] of received messages and a [Sink] for sending messages
+//! and optionally fragments messages exceeding the MTU.
+
+pub use bytes::Bytes;
+pub use tokio_util::codec::{Decoder, Encoder, Framed, LengthDelimitedCodec};
+
+#[cfg(feature = "l2cap")]
+use crate::l2cap::SeqPacket;
+#[cfg(feature = "l2cap")]
+use bytes::{Buf, BytesMut};
+#[cfg(feature = "l2cap")]
+use futures::{ready, Sink, Stream};
+#[cfg(feature = "l2cap")]
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::Shutdown,
+    pin::Pin,
+    task::{Context, Poll},
+};
+#[cfg(feature = "l2cap")]
+use tokio::io::ReadBuf;
+
+/// Fragment header flag indicating that more fragments of the message follow.
+#[cfg(feature = "l2cap")]
+const FRAGMENT_MORE: u8 = 0x01;
+
+/// Length of fragment header.
+#[cfg(feature = "l2cap")]
+const FRAGMENT_HEADER_LEN: usize = 1;
+
+/// Encodes the next fragment of a message into `buf`, which must be empty.
+///
+/// The fragment including its header does not exceed `mtu` bytes.
+/// Returns the number of bytes of the message contained in the fragment and
+/// whether more fragments follow.
+#[cfg(feature = "l2cap")]
+fn encode_fragment(buf: &mut Vec<u8>, msg: &[u8], mtu: usize) -> (usize, bool) {
+    let len = msg.len().min(mtu - FRAGMENT_HEADER_LEN);
+    let more = len < msg.len();
+    buf.push(if more { FRAGMENT_MORE } else { 0 });
+    buf.extend_from_slice(&msg[..len]);
+    (len, more)
+}
+
+/// Reassembly of received fragments into messages.
+#[cfg(feature = "l2cap")]
+#[derive(Debug, Default)]
+struct Reassembly {
+    buf: BytesMut,
+    /// Whether the remaining fragments of an invalid message are dropped.
+    skip: bool,
+}
+
+#[cfg(feature = "l2cap")]
+impl Reassembly {
+    /// Processes a received fragment.
+    ///
+    /// Returns the message once its final fragment has been received or
+    /// an error if the message is invalid.
+    /// After an error, the remaining fragments of the invalid message are dropped.
+    fn push(&mut self, fragment: &[u8], max_message_len: usize) -> Option<Result<Bytes>> {
+        let (&header, payload) = match fragment.split_first() {
+            Some(split) => split,
+            None => return Some(Err(Error::new(ErrorKind::InvalidData, "empty fragment"))),
+        };
+        let more = header & FRAGMENT_MORE != 0;
+
+        if self.skip {
+            self.skip = more;
+            return None;
+        }
+
+        if header & !FRAGMENT_MORE != 0 {
+            self.buf.clear();
+            self.skip = more;
+            return Some(Err(Error::new(ErrorKind::InvalidData, "invalid fragment header")));
+        }
+
+        if self.buf.len() + payload.len() > max_message_len {
+            self.buf.clear();
+            self.skip = more;
+            return Some(Err(Error::new(ErrorKind::InvalidData, "message too long")));
+        }
+        self.buf.extend_from_slice(payload);
+
+        if more {
+            None
+        } else {
+            Some(Ok(self.buf.split().freeze()))
+        }
+    }
+
+    /// Ends reassembly because the end of the stream has been reached.
+    ///
+    /// Fails if a message is incomplete.
+    fn finish(&mut self) -> Result<()> {
+        self.skip = false;
+        if self.buf.is_empty() {
+            Ok(())
+        } else {
+            self.buf.clear();
+            Err(Error::new(ErrorKind::UnexpectedEof, "incomplete message"))
+        }
+    }
+}
+
+/// Message-preserving framing of an L2CAP sequential packet connection.
+///
+/// Implements [Stream] for receiving messages and [Sink] for sending messages.
+///
+/// By default, each message is transmitted as exactly one packet and
+/// thus messages must not exceed the send MTU.
+///
+/// When created using [SeqPacketFramed::fragmented], messages are split into
+/// fragments fitting the MTU and reassembled by the receiver.
+/// Each fragment is prefixed by a one byte header; bit 0 is set if more fragments
+/// of the message follow and all other bits are zero.
+/// The remote device must use the same framing.
+/// After a received message has been reported as invalid, its remaining fragments
+/// are dropped.
+///
+/// A received packet of zero length indicates the end of the stream.
+/// Thus, empty messages can only be sent when fragmentation is used.
+#[cfg(feature = "l2cap")]
+#[cfg_attr(docsrs, doc(cfg(feature = "l2cap")))]
+#[derive(Debug)]
+pub struct SeqPacketFramed {
+    socket: SeqPacket,
+    send_mtu: usize,
+    max_message_len: Option<usize>,
+    recv_buf: Vec<u8>,
+    reassembly: Reassembly,
+    recv_closed: bool,
+    send_msg: Option<Bytes>,
+    send_buf: Vec<u8>,
+}
+
+#[cfg(feature = "l2cap")]
+impl SeqPacketFramed {
+    /// Frames the sequential packet connection, transmitting each message as one packet.
+    pub fn new(socket: SeqPacket) -> Result<Self> {
+        Self::with_fragmentation(socket, None)
+    }
+
+    /// Frames the sequential packet connection, fragmenting messages exceeding the MTU.
+    ///
+    /// Sent and received messages must not exceed `max_message_len` bytes.
+    pub fn fragmented(socket: SeqPacket, max_message_len: usize) -> Result<Self> {
+        Self::with_fragmentation(socket, Some(max_message_len))
+    }
+
+    fn with_fragmentation(socket: SeqPacket, max_message_len: Option<usize>) -> Result<Self> {
+        let send_mtu = socket.send_mtu()?;
+        let recv_mtu = socket.recv_mtu()?;
+        if max_message_len.is_some() && (send_mtu <= FRAGMENT_HEADER_LEN || recv_mtu <= FRAGMENT_HEADER_LEN) {
+            return Err(Error::new(ErrorKind::InvalidInput, "MTU too small for fragmentation"));
+        }
+
+        Ok(Self {
+            socket,
+            send_mtu,
+            max_message_len,
+            recv_buf: vec![0; recv_mtu],
+            reassembly: Reassembly::default(),
+            recv_closed: false,
+            send_msg: None,
+            send_buf: Vec::new(),
+        })
+    }
+
+    /// Maximum length of a message that can be sent.
+    pub fn max_send_len(&self) -> usize {
+        match self.max_message_len {
+            Some(max_message_len) => max_message_len,
+            None => self.send_mtu,
+        }
+    }
+
+    /// Gets a reference to the underlying sequential packet connection.
+    pub fn get_ref(&self) -> &SeqPacket {
+        &self.socket
+    }
+
+    /// Consumes the framing, returning the underlying sequential packet connection.
+    ///
+    /// Partially sent or received messages are lost.
+    pub fn into_inner(self) -> SeqPacket {
+        self.socket
+    }
+}
+
+#[cfg(feature = "l2cap")]
+impl Stream for SeqPacketFramed {
+    type Item = Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if this.recv_closed {
+                return Poll::Ready(None);
+            }
+
+            let mut buf = ReadBuf::new(&mut this.recv_buf);
+            ready!(this.socket.poll_recv(cx, &mut buf))?;
+            let packet = buf.filled();
+
+            if packet.is_empty() {
+                this.recv_closed = true;
+                return Poll::Ready(this.reassembly.finish().err().map(Err));
+            }
+
+            match this.max_message_len {
+                Some(max_message_len) => {
+                    if let Some(res) = this.reassembly.push(packet, max_message_len) {
+                        return Poll::Ready(Some(res));
+                    }
+                }
+                None => return Poll::Ready(Some(Ok(Bytes::copy_from_slice(packet)))),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "l2cap")]
+impl Sink<Bytes> for SeqPacketFramed {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        self.poll_flush(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Bytes) -> Result<()> {
+        let this = self.get_mut();
+        if item.len() > this.max_send_len() {
+            return Err(Error::new(ErrorKind::InvalidInput, "message too long"));
+        }
+        if item.is_empty() && this.max_message_len.is_none() {
+            return Err(Error::new(ErrorKind::InvalidInput, "empty message would end the stream"));
+        }
+        if this.send_msg.is_some() {
+            return Err(Error::new(ErrorKind::Other, "sink not ready"));
+        }
+        this.send_msg = Some(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        let this = self.get_mut();
+
+        while let Some(msg) = &mut this.send_msg {
+            let (packet, consumed, more) = if this.max_message_len.is_some() {
+                this.send_buf.clear();
+                let (len, more) = encode_fragment(&mut this.send_buf, msg, this.send_mtu);
+                (&this.send_buf[..], len, more)
+            } else {
+                (&msg[..], msg.len(), false)
+            };
+
+            let n = ready!(this.socket.poll_send(cx, packet))?;
+            if n != packet.len() {
+                return Poll::Ready(Err(Error::new(ErrorKind::WriteZero, "packet was truncated")));
+            }
+
+            if more {
+                msg.advance(consumed);
+            } else {
+                this.send_msg = None;
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        Poll::Ready(self.socket.shutdown(Shutdown::Write))
+    }
+}
+
+#[cfg(all(test, feature = "l2cap"))]
+mod tests {
+    use super::*;
+
+    const MTU: usize = 4;
+    const MAX_MESSAGE_LEN: usize = 8;
+
+    fn fragments(msg: &[u8]) -> Vec<Vec<u8>> {
+        let mut fragments = Vec::new();
+        let mut pos = 0;
+        loop {
+            let mut buf = Vec::new();
+            let (len, more) = encode_fragment(&mut buf, &msg[pos..], MTU);
+            fragments.push(buf);
+            pos += len;
+            if !more {
+                return fragments;
+            }
+        }
+    }
+
+    fn reassemble(reassembly: &mut Reassembly, fragments: &[Vec<u8>]) -> Vec<Result<Bytes>> {
+        fragments.iter().filter_map(|fragment| reassembly.push(fragment, MAX_MESSAGE_LEN)).collect()
+    }
+
+    #[test]
+    fn multi_fragment_message() {
+        let fragments = fragments(b"abcdefg");
+        assert_eq!(fragments, vec![b"\x01abc".to_vec(), b"\x01def".to_vec(), b"\x00g".to_vec()]);
+
+        let mut reassembly = Reassembly::default();
+        let msgs = reassemble(&mut reassembly, &fragments);
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].as_ref().unwrap(), &b"abcdefg"[..]);
+        reassembly.finish().unwrap();
+    }
+
+    #[test]
+    fn message_too_long() {
+        let mut all = fragments(b"0123456789");
+        all.extend(fragments(b"abc"));
+
+        let mut reassembly = Reassembly::default();
+        let msgs = reassemble(&mut reassembly, &all);
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].as_ref().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(msgs[1].as_ref().unwrap(), &b"abc"[..]);
+    }
+
+    #[test]
+    fn invalid_header() {
+        let mut all = vec![vec![0x81, b'a'], vec![FRAGMENT_MORE, b'b'], vec![0x00, b'c']];
+        all.extend(fragments(b"def"));
+
+        let mut reassembly = Reassembly::default();
+        let msgs = reassemble(&mut reassembly, &all);
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].as_ref().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(msgs[1].as_ref().unwrap(), &b"def"[..]);
+    }
+
+    #[test]
+    fn empty_message() {
+        let fragments = fragments(b"");
+        assert_eq!(fragments, vec![vec![0x00]]);
+
+        let mut reassembly = Reassembly::default();
+        let msgs = reassemble(&mut reassembly, &fragments);
+        assert_eq!(msgs.len(), 1);
+        assert!(msgs[0].as_ref().unwrap().is_empty());
+    }
+
+    #[test]
+    fn eof_during_reassembly() {
+        let fragments = fragments(b"abcdefg");
+
+        let mut reassembly = Reassembly::default();
+        assert!(reassemble(&mut reassembly, &fragments[..2]).is_empty());
+        assert_eq!(reassembly.finish().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        reassembly.finish().unwrap();
+    }
+}
@@ -11,15 +11,17 @@ use std::{
     fmt,
     sync::Arc,
 };
-use tokio::{sync::oneshot, time::sleep};
+use tokio::{sync::oneshot, task, time::sleep};
 use uuid::Uuid;
 
 use crate::{
-    all_dbus_objects,
+    all_dbus_objects, bond,
     gatt::{self, remote::Service, SERVICE_INTERFACE},
-    Adapter, Address, AddressType, Error, ErrorKind, Event, InternalErrorKind, Modalias, Result, SessionInner,
-    SERVICE_NAME, TIMEOUT,
+    Adapter, Address, AddressType, ConnectionParameters, Error, ErrorKind, Event, InternalErrorKind, Modalias,
+    Result, SessionInner, SERVICE_NAME, TIMEOUT,
 };
+#[cfg(feature = "hci")]
+use crate::{hci, monitor};
 
 pub(crate) const INTERFACE: &str = "org.bluez.Device1";
 
@@ -236,6 +238,109 @@ impl Device {
         self.call_method("DisconnectProfile", (uuid.to_string(),)).await
     }
 
+    /// Queries the SDP server of this device for the RFCOMM channel
+    /// of the specified service class.
+    ///
+    /// The SDP connection is made from the adapter this device belongs to.
+    /// Returns [None] if the device does not offer the service over RFCOMM.
+    #[cfg(all(feature = "sdp", feature = "l2cap"))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "sdp", feature = "l2cap"))))]
+    pub async fn find_rfcomm_channel(&self, service_class: Uuid) -> Result<Option<u8>> {
+        use crate::{l2cap, sdp};
+
+        let adapter = Adapter::new(self.inner.clone(), &self.adapter_name)?;
+        let socket = l2cap::Socket::new_stream()?;
+        socket.bind(l2cap::SocketAddr::new(adapter.address().await?, AddressType::BrEdr, 0))?;
+        let stream = socket.connect(l2cap::SocketAddr::new(self.address, AddressType::BrEdr, sdp::PSM)).await?;
+        Ok(sdp::Client::new(stream).find_rfcomm_channel(service_class).await?)
+    }
+
+    /// Sets the Bluetooth LE connection parameters used for this device.
+    ///
+    /// The parameters are loaded into the kernel using the management interface,
+    /// which requires the `CAP_NET_ADMIN` capability.
+    /// They apply to the next connection and, on recent kernels, are also
+    /// requested for an existing connection.
+    /// The Bluetooth daemon may replace them when the device requests other parameters.
+    ///
+    /// Fails for classic Bluetooth (BR/EDR) devices and for [invalid](ConnectionParameters::is_valid) parameters.
+    pub async fn set_connection_parameters(&self, params: &ConnectionParameters) -> Result<()> {
+        let adapter = Adapter::new(self.inner.clone(), &self.adapter_name)?;
+        let address_type = self.address_type().await?;
+        adapter.mgmt_controller()?.load_connection_parameters(&[(self.address, address_type, *params)]).await?;
+        Ok(())
+    }
+
+    /// Reads the Bluetooth LE connection parameters stored for this device by the Bluetooth daemon.
+    ///
+    /// These are not the parameters of the current connection, which are available
+    /// from `connection_parameters` with the `hci` feature.
+    /// They are the parameters last requested by the device and persisted by the Bluetooth
+    /// daemon in the `info` file of the device below [bond::STORAGE_DIR],
+    /// which are loaded into the kernel when the adapter is powered on.
+    /// The connection may use other values chosen by the controller or the device.
+    ///
+    /// Reading the storage directory usually requires root privileges.
+    /// Returns [None] if no parameters are stored.
+    pub async fn stored_connection_parameters(&self) -> Result<Option<ConnectionParameters>> {
+        let adapter = Adapter::new(self.inner.clone(), &self.adapter_name)?;
+        let adapter_address = adapter.address().await?;
+        let address = self.address;
+        let device = task::spawn_blocking(move || {
+            bond::BondedDevice::read_storage(bond::STORAGE_DIR, adapter_address, address)
+        })
+        .await??;
+        Ok(device.and_then(|dev| dev.connection_parameters))
+    }
+
+    /// Streams the parameters of the Bluetooth LE connection to this device.
+    ///
+    /// These are the parameters in use, as reported by the controller in the
+    /// LE Connection Complete and LE Connection Update Complete events captured on the
+    /// [HCI monitor channel](crate::monitor).
+    /// The controller only reports them when the connection is established or updated,
+    /// thus for an existing connection nothing is received until the parameters change.
+    ///
+    /// This requires the `CAP_NET_RAW` capability.
+    #[cfg(feature = "hci")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "hci")))]
+    pub async fn connection_parameters(&self) -> Result<impl Stream<Item = Result<ConnectionParameters>>> {
+        let index = Adapter::new(self.inner.clone(), &self.adapter_name)?.index()?;
+        let address = self.address;
+
+        // Open the monitor before looking up the connection to not miss events in between.
+        let monitor = monitor::Monitor::open()?;
+        let handle = hci::connections(index)?
+            .into_iter()
+            .find(|conn| conn.link_type == hci::LE_LINK && conn.address == address)
+            .map(|conn| conn.handle);
+
+        Ok(stream::try_unfold((monitor, handle), move |(mut monitor, mut handle)| async move {
+            loop {
+                let record = monitor.recv().await?;
+                let event = match record.packet {
+                    monitor::Packet::Event(event) if record.index == index => event,
+                    _ => continue,
+                };
+
+                if let Some(update) = event.le_connection_update() {
+                    let ours = match update.peer {
+                        Some((peer, _)) => peer == address,
+                        None => handle == Some(update.handle),
+                    };
+                    if ours {
+                        handle = Some(update.handle);
+                        return Ok(Some((update.params, (monitor, handle))));
+                    } else if handle == Some(update.handle) {
+                        handle = None;
+                    }
+                } else if handle.is_some() && event.disconnection_complete() == handle {
+                    handle = None;
+                }
+            }
+        }))
+    }
+
     /// This method will connect to the remote device,
     /// initiate pairing and then retrieve all SDP records
     /// (or GATT primary services).
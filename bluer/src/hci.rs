@@ -0,0 +1,1345 @@
+//! Host controller interface (HCI) sockets.
+//!
+//! HCI sockets provide direct access to the commands, events and data packets
+//! exchanged between the host and a Bluetooth controller.
+//! This is useful for diagnostics and vendor-specific commands that are not
+//! available through the Bluetooth daemon.
+//!
+//! A socket is bound to one of the following [channels](Channel):
+//!
+//! * [raw](Channel::Raw) sockets receive a copy of all packets of a controller that pass
+//!   the [filter](Socket::set_filter) and can send commands while the kernel keeps
+//!   managing the controller,
+//! * [user channel](Channel::User) sockets take exclusive control of a controller,
+//!   which must be powered off and not used by the Bluetooth daemon,
+//! * [monitor](Channel::Monitor) sockets receive all packets of all controllers
+//!   together with a monitor header; use [Socket::recv_raw] to receive them.
+//!
+//! Most operations require the `CAP_NET_RAW` or `CAP_NET_ADMIN` capability.
+//!
+
+use crate::{
+    sock::{self, OwnedFd},
+    sys::{
+        bdaddr_t, hci_conn_list_req, hci_dev_info, hci_dev_list_req, hci_filter, sockaddr_hci, BTPROTO_HCI,
+        HCIGETCONNLIST, HCIGETDEVINFO, HCIGETDEVLIST, HCI_ACLDATA_PKT, HCI_CHANNEL_MONITOR, HCI_CHANNEL_RAW,
+        HCI_CHANNEL_USER, HCI_COMMAND_PKT, HCI_DEV_NONE, HCI_EVENT_PKT, HCI_FILTER, HCI_FLT_EVENT_BITS,
+        HCI_FLT_TYPE_BITS, HCI_ISODATA_PKT, HCI_MAX_CONN, HCI_MAX_DEV, HCI_SCODATA_PKT, HCI_VENDOR_PKT, SOL_HCI,
+    },
+    Address, AddressType, ConnectionParameters, Phy,
+};
+use libc::{AF_BLUETOOTH, SOCK_RAW};
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::FromPrimitive;
+use std::{
+    convert::TryInto,
+    fmt,
+    io::{Error, ErrorKind, Result},
+    os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+    time::Duration,
+};
+use tokio::io::{unix::AsyncFd, ReadBuf};
+
+/// Device index that specifies no controller.
+///
+/// Used when binding to the [monitor channel](Channel::Monitor).
+pub const DEV_NONE: u16 = HCI_DEV_NONE;
+
+/// Default time to wait for the completion of a command.
+///
+/// This matches the command timeout of the Linux kernel.
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Maximum size of an HCI packet including its packet type indicator.
+pub const MAX_PACKET_SIZE: usize = 1 + 4 + u16::MAX as usize;
+
+/// Disconnection complete event code.
+pub const EVT_DISCONN_COMPLETE: u8 = 0x05;
+/// Command complete event code.
+pub const EVT_CMD_COMPLETE: u8 = 0x0e;
+/// Command status event code.
+pub const EVT_CMD_STATUS: u8 = 0x0f;
+/// LE meta event code.
+pub const EVT_LE_META: u8 = 0x3e;
+/// Vendor-specific event code.
+pub const EVT_VENDOR: u8 = 0xff;
+
+const EVT_LE_CONN_COMPLETE: u8 = 0x01;
+const EVT_LE_CONN_UPDATE_COMPLETE: u8 = 0x03;
+const EVT_LE_ENHANCED_CONN_COMPLETE: u8 = 0x0a;
+const EVT_LE_ENHANCED_CONN_COMPLETE_V2: u8 = 0x29;
+
+/// Synchronous (SCO) link type.
+pub const SCO_LINK: u8 = 0x00;
+/// Asynchronous (ACL) link type of classic Bluetooth (BR/EDR) connections.
+pub const ACL_LINK: u8 = 0x01;
+/// Extended synchronous (eSCO) link type.
+pub const ESCO_LINK: u8 = 0x02;
+/// Bluetooth LE link type.
+pub const LE_LINK: u8 = 0x80;
+/// Isochronous (ISO) link type.
+pub const ISO_LINK: u8 = 0x82;
+
+/// Opcode group field (OGF) of host controller and baseband commands.
+pub const OGF_HOST_CTL: u8 = 0x03;
+/// Opcode group field (OGF) of informational parameters commands.
+pub const OGF_INFO_PARAM: u8 = 0x04;
+/// Opcode group field (OGF) of LE controller commands.
+pub const OGF_LE_CTL: u8 = 0x08;
+/// Opcode group field (OGF) of vendor-specific commands.
+pub const OGF_VENDOR_CMD: u8 = 0x3f;
+
+const OCF_RESET: u16 = 0x0003;
+const OCF_READ_BD_ADDR: u16 = 0x0009;
+const OCF_LE_SET_SCAN_PARAMETERS: u16 = 0x000b;
+const OCF_LE_SET_PHY: u16 = 0x0032;
+
+/// Builds an opcode from opcode group field (OGF) and opcode command field (OCF).
+pub const fn opcode(ogf: u8, ocf: u16) -> u16 {
+    ((ogf as u16) << 10) | (ocf & 0x03ff)
+}
+
+/// Opcode group field (OGF) of an opcode.
+pub const fn opcode_ogf(opcode: u16) -> u8 {
+    (opcode >> 10) as u8
+}
+
+/// Opcode command field (OCF) of an opcode.
+pub const fn opcode_ocf(opcode: u16) -> u16 {
+    opcode & 0x03ff
+}
+
+/// HCI socket channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Channel {
+    /// Raw access to a controller that remains managed by the kernel.
+    Raw,
+    /// Exclusive access to a controller.
+    User,
+    /// Monitoring of all controllers.
+    Monitor,
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self::Raw
+    }
+}
+
+/// An HCI socket address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SocketAddr {
+    /// Controller index, for example 0 for `hci0`.
+    ///
+    /// Specify [DEV_NONE] for the monitor channel.
+    pub dev: u16,
+    /// Channel.
+    pub channel: Channel,
+}
+
+impl SocketAddr {
+    /// Creates a new HCI socket address.
+    pub const fn new(dev: u16, channel: Channel) -> Self {
+        Self { dev, channel }
+    }
+
+    /// Raw channel of the specified controller.
+    pub const fn raw(dev: u16) -> Self {
+        Self { dev, channel: Channel::Raw }
+    }
+
+    /// User channel of the specified controller.
+    pub const fn user(dev: u16) -> Self {
+        Self { dev, channel: Channel::User }
+    }
+
+    /// Monitor channel.
+    pub const fn monitor() -> Self {
+        Self { dev: DEV_NONE, channel: Channel::Monitor }
+    }
+}
+
+impl sock::SysSockAddr for SocketAddr {
+    type SysSockAddr = sockaddr_hci;
+
+    fn into_sys_sock_addr(self) -> Self::SysSockAddr {
+        sockaddr_hci {
+            hci_family: AF_BLUETOOTH as _,
+            hci_dev: self.dev,
+            hci_channel: match self.channel {
+                Channel::Raw => HCI_CHANNEL_RAW,
+                Channel::User => HCI_CHANNEL_USER,
+                Channel::Monitor => HCI_CHANNEL_MONITOR,
+            },
+        }
+    }
+
+    fn try_from_sys_sock_addr(saddr: Self::SysSockAddr) -> Result<Self> {
+        if saddr.hci_family != AF_BLUETOOTH as _ {
+            return Err(Error::new(ErrorKind::InvalidInput, "sockaddr_hci::hci_family is not AF_BLUETOOTH"));
+        }
+        let channel = match saddr.hci_channel {
+            HCI_CHANNEL_RAW => Channel::Raw,
+            HCI_CHANNEL_USER => Channel::User,
+            HCI_CHANNEL_MONITOR => Channel::Monitor,
+            _ => return Err(Error::new(ErrorKind::InvalidInput, "invalid sockaddr_hci::hci_channel")),
+        };
+        Ok(Self { dev: saddr.hci_dev, channel })
+    }
+}
+
+/// HCI packet type indicator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, FromPrimitive, ToPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PacketType {
+    /// Command.
+    Command = HCI_COMMAND_PKT as _,
+    /// ACL data.
+    AclData = HCI_ACLDATA_PKT as _,
+    /// SCO data.
+    ScoData = HCI_SCODATA_PKT as _,
+    /// Event.
+    Event = HCI_EVENT_PKT as _,
+    /// ISO data.
+    IsoData = HCI_ISODATA_PKT as _,
+    /// Vendor-specific.
+    Vendor = HCI_VENDOR_PKT as _,
+}
+
+impl PacketType {
+    /// Bit in the packet type mask of the kernel filter.
+    fn filter_bit(self) -> u32 {
+        match self {
+            Self::Vendor => 0,
+            other => (other as u8 & HCI_FLT_TYPE_BITS).into(),
+        }
+    }
+}
+
+/// Filter for packets received on a [raw channel](Channel::Raw) socket.
+///
+/// By default no packets are allowed to pass.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Filter {
+    type_mask: u32,
+    event_mask: [u32; 2],
+    opcode: u16,
+}
+
+impl Filter {
+    /// Creates a new filter that blocks all packets.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new filter that allows all packets to pass.
+    pub fn all() -> Self {
+        Self { type_mask: !0, event_mask: [!0, !0], opcode: 0 }
+    }
+
+    /// Allows packets of the specified type to pass.
+    pub fn allow_packet_type(mut self, packet_type: PacketType) -> Self {
+        self.type_mask |= 1 << packet_type.filter_bit();
+        self
+    }
+
+    /// Allows events with the specified event code to pass.
+    ///
+    /// Event packets must also be [allowed](Self::allow_packet_type).
+    pub fn allow_event(mut self, code: u8) -> Self {
+        let bit = code & HCI_FLT_EVENT_BITS;
+        self.event_mask[usize::from(bit >> 5)] |= 1 << (bit & 31);
+        self
+    }
+
+    /// Allows all events to pass.
+    pub fn allow_all_events(mut self) -> Self {
+        self.event_mask = [!0, !0];
+        self
+    }
+
+    /// Only allows command complete and command status events for the specified opcode to pass.
+    pub fn match_opcode(mut self, opcode: u16) -> Self {
+        self.opcode = opcode;
+        self
+    }
+
+    /// Whether packets of the specified type are allowed to pass.
+    pub fn is_packet_type_allowed(&self, packet_type: PacketType) -> bool {
+        self.type_mask & (1 << packet_type.filter_bit()) != 0
+    }
+
+    /// Whether events with the specified event code are allowed to pass.
+    pub fn is_event_allowed(&self, code: u8) -> bool {
+        let bit = code & HCI_FLT_EVENT_BITS;
+        self.event_mask[usize::from(bit >> 5)] & (1 << (bit & 31)) != 0
+    }
+
+    /// Opcode that command complete and command status events must match, if any.
+    pub fn opcode(&self) -> Option<u16> {
+        match self.opcode {
+            0 => None,
+            opcode => Some(opcode),
+        }
+    }
+
+    fn to_sys(&self) -> hci_filter {
+        hci_filter { type_mask: self.type_mask, event_mask: self.event_mask, opcode: self.opcode.to_le() }
+    }
+
+    fn from_sys(flt: hci_filter) -> Self {
+        Self { type_mask: flt.type_mask, event_mask: flt.event_mask, opcode: u16::from_le(flt.opcode) }
+    }
+}
+
+/// LE scan type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, FromPrimitive, ToPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LeScanType {
+    /// No scan requests are sent.
+    Passive = 0x00,
+    /// Scan requests may be sent.
+    Active = 0x01,
+}
+
+impl Default for LeScanType {
+    fn default() -> Self {
+        Self::Passive
+    }
+}
+
+/// Parameters of the LE Set Scan Parameters command.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LeScanParameters {
+    /// Scan type.
+    pub scan_type: LeScanType,
+    /// Scan interval in units of 0.625 ms.
+    pub interval: u16,
+    /// Scan window in units of 0.625 ms.
+    ///
+    /// Must not be greater than the scan interval.
+    pub window: u16,
+    /// Own address type.
+    pub own_address_type: u8,
+    /// Scanning filter policy.
+    pub filter_policy: u8,
+    #[doc(hidden)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub _non_exhaustive: (),
+}
+
+impl Default for LeScanParameters {
+    fn default() -> Self {
+        Self {
+            scan_type: LeScanType::Passive,
+            interval: 0x0010,
+            window: 0x0010,
+            own_address_type: 0,
+            filter_policy: 0,
+            _non_exhaustive: (),
+        }
+    }
+}
+
+/// HCI command.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Command {
+    /// Reset the controller.
+    Reset,
+    /// Read the public device address of the controller.
+    ReadBdAddr,
+    /// Set LE scan parameters.
+    LeSetScanParameters(LeScanParameters),
+    /// Set the preferred PHYs of an LE connection.
+    LeSetPhy {
+        /// Connection handle.
+        handle: u16,
+        /// Preferred PHYs for transmission and reception.
+        ///
+        /// Only the LE PHYs are used.
+        /// If no LE PHY is set for a direction, the host has no preference for it.
+        phy: Phy,
+    },
+    /// Vendor-specific command.
+    Vendor {
+        /// Opcode command field (OCF).
+        ocf: u16,
+        /// Parameters.
+        params: Vec<u8>,
+    },
+    /// Other command.
+    Other {
+        /// Opcode.
+        opcode: u16,
+        /// Parameters.
+        params: Vec<u8>,
+    },
+}
+
+impl Command {
+    /// Opcode of the command.
+    pub fn opcode(&self) -> u16 {
+        match self {
+            Self::Reset => opcode(OGF_HOST_CTL, OCF_RESET),
+            Self::ReadBdAddr => opcode(OGF_INFO_PARAM, OCF_READ_BD_ADDR),
+            Self::LeSetScanParameters(_) => opcode(OGF_LE_CTL, OCF_LE_SET_SCAN_PARAMETERS),
+            Self::LeSetPhy { .. } => opcode(OGF_LE_CTL, OCF_LE_SET_PHY),
+            Self::Vendor { ocf, .. } => opcode(OGF_VENDOR_CMD, *ocf),
+            Self::Other { opcode, .. } => *opcode,
+        }
+    }
+
+    /// Encoded parameters of the command.
+    pub fn params(&self) -> Vec<u8> {
+        match self {
+            Self::Reset | Self::ReadBdAddr => Vec::new(),
+            Self::LeSetScanParameters(p) => {
+                let mut buf = vec![p.scan_type as u8];
+                buf.extend_from_slice(&p.interval.to_le_bytes());
+                buf.extend_from_slice(&p.window.to_le_bytes());
+                buf.push(p.own_address_type);
+                buf.push(p.filter_policy);
+                buf
+            }
+            Self::LeSetPhy { handle, phy } => {
+                let tx = le_phys(*phy, [Phy::LE_1M_TX, Phy::LE_2M_TX, Phy::LE_CODED_TX]);
+                let rx = le_phys(*phy, [Phy::LE_1M_RX, Phy::LE_2M_RX, Phy::LE_CODED_RX]);
+                let mut buf = handle.to_le_bytes().to_vec();
+                buf.push(u8::from(tx == 0) | u8::from(rx == 0) << 1);
+                buf.push(tx);
+                buf.push(rx);
+                buf.extend_from_slice(&0u16.to_le_bytes());
+                buf
+            }
+            Self::Vendor { params, .. } | Self::Other { params, .. } => params.clone(),
+        }
+    }
+
+    /// Decodes a command from its opcode and parameters.
+    pub fn decode(opcode: u16, params: &[u8]) -> Result<Self> {
+        let invalid =
+            || Error::new(ErrorKind::InvalidData, format!("invalid parameters of command 0x{:04x}", opcode));
+        let cmd = match (opcode_ogf(opcode), opcode_ocf(opcode)) {
+            (OGF_HOST_CTL, OCF_RESET) => Self::Reset,
+            (OGF_INFO_PARAM, OCF_READ_BD_ADDR) => Self::ReadBdAddr,
+            (OGF_LE_CTL, OCF_LE_SET_SCAN_PARAMETERS) => {
+                if params.len() != 7 {
+                    return Err(invalid());
+                }
+                Self::LeSetScanParameters(LeScanParameters {
+                    scan_type: LeScanType::from_u8(params[0]).ok_or_else(invalid)?,
+                    interval: u16::from_le_bytes([params[1], params[2]]),
+                    window: u16::from_le_bytes([params[3], params[4]]),
+                    own_address_type: params[5],
+                    filter_policy: params[6],
+                    _non_exhaustive: (),
+                })
+            }
+            (OGF_LE_CTL, OCF_LE_SET_PHY) => {
+                if params.len() != 7 {
+                    return Err(invalid());
+                }
+                let (all_phys, tx, rx) = (params[2], params[3], params[4]);
+                let mut phy = Phy::empty();
+                if all_phys & 0x01 == 0 {
+                    phy |= from_le_phys(tx, [Phy::LE_1M_TX, Phy::LE_2M_TX, Phy::LE_CODED_TX]);
+                }
+                if all_phys & 0x02 == 0 {
+                    phy |= from_le_phys(rx, [Phy::LE_1M_RX, Phy::LE_2M_RX, Phy::LE_CODED_RX]);
+                }
+                Self::LeSetPhy { handle: u16::from_le_bytes([params[0], params[1]]), phy }
+            }
+            (OGF_VENDOR_CMD, ocf) => Self::Vendor { ocf, params: params.to_vec() },
+            _ => Self::Other { opcode, params: params.to_vec() },
+        };
+        Ok(cmd)
+    }
+}
+
+/// Encodes the LE PHYs of one direction as used by HCI commands.
+fn le_phys(phy: Phy, bits: [Phy; 3]) -> u8 {
+    bits.iter().enumerate().filter(|(_, bit)| phy.contains(**bit)).fold(0, |acc, (i, _)| acc | 1 << i)
+}
+
+/// Decodes the LE PHYs of one direction as used by HCI commands.
+fn from_le_phys(value: u8, bits: [Phy; 3]) -> Phy {
+    bits.iter().enumerate().filter(|(i, _)| value & 1 << i != 0).fold(Phy::empty(), |acc, (_, bit)| acc | *bit)
+}
+
+/// HCI event.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Event {
+    /// A command has completed.
+    CommandComplete {
+        /// Number of command packets the host may send.
+        num_packets: u8,
+        /// Opcode of the completed command.
+        opcode: u16,
+        /// Return parameters, usually starting with a status byte.
+        return_params: Vec<u8>,
+    },
+    /// A command has been received and is being processed.
+    CommandStatus {
+        /// Status.
+        status: u8,
+        /// Number of command packets the host may send.
+        num_packets: u8,
+        /// Opcode of the command.
+        opcode: u16,
+    },
+    /// LE meta event.
+    LeMeta {
+        /// Subevent code.
+        subevent: u8,
+        /// Parameters.
+        params: Vec<u8>,
+    },
+    /// Vendor-specific event.
+    Vendor(Vec<u8>),
+    /// Other event.
+    Other {
+        /// Event code.
+        code: u8,
+        /// Parameters.
+        params: Vec<u8>,
+    },
+}
+
+impl Event {
+    /// Event code.
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::CommandComplete { .. } => EVT_CMD_COMPLETE,
+            Self::CommandStatus { .. } => EVT_CMD_STATUS,
+            Self::LeMeta { .. } => EVT_LE_META,
+            Self::Vendor(_) => EVT_VENDOR,
+            Self::Other { code, .. } => *code,
+        }
+    }
+
+    /// Encoded parameters of the event.
+    pub fn params(&self) -> Vec<u8> {
+        match self {
+            Self::CommandComplete { num_packets, opcode, return_params } => {
+                let mut buf = vec![*num_packets];
+                buf.extend_from_slice(&opcode.to_le_bytes());
+                buf.extend_from_slice(return_params);
+                buf
+            }
+            Self::CommandStatus { status, num_packets, opcode } => {
+                let mut buf = vec![*status, *num_packets];
+                buf.extend_from_slice(&opcode.to_le_bytes());
+                buf
+            }
+            Self::LeMeta { subevent, params } => {
+                let mut buf = vec![*subevent];
+                buf.extend_from_slice(params);
+                buf
+            }
+            Self::Vendor(params) | Self::Other { params, .. } => params.clone(),
+        }
+    }
+
+    /// Decodes an event from its event code and parameters.
+    pub fn decode(code: u8, params: &[u8]) -> Result<Self> {
+        let invalid =
+            || Error::new(ErrorKind::InvalidData, format!("invalid parameters of event 0x{:02x}", code));
+        let evt = match code {
+            EVT_CMD_COMPLETE => {
+                if params.len() < 3 {
+                    return Err(invalid());
+                }
+                Self::CommandComplete {
+                    num_packets: params[0],
+                    opcode: u16::from_le_bytes([params[1], params[2]]),
+                    return_params: params[3..].to_vec(),
+                }
+            }
+            EVT_CMD_STATUS => {
+                if params.len() != 4 {
+                    return Err(invalid());
+                }
+                Self::CommandStatus {
+                    status: params[0],
+                    num_packets: params[1],
+                    opcode: u16::from_le_bytes([params[2], params[3]]),
+                }
+            }
+            EVT_LE_META => {
+                let (&subevent, params) = params.split_first().ok_or_else(invalid)?;
+                Self::LeMeta { subevent, params: params.to_vec() }
+            }
+            EVT_VENDOR => Self::Vendor(params.to_vec()),
+            _ => Self::Other { code, params: params.to_vec() },
+        };
+        Ok(evt)
+    }
+}
+
+/// Parameters of an LE connection reported by the controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LeConnectionUpdate {
+    /// Connection handle.
+    pub handle: u16,
+    /// Address and address type of the peer.
+    ///
+    /// This is only reported when the connection is established.
+    /// The identity address is reported if the controller has resolved
+    /// the private address of the peer.
+    pub peer: Option<(Address, AddressType)>,
+    /// Connection parameters in use.
+    ///
+    /// The minimum and maximum connection interval are both set to the
+    /// connection interval in use.
+    pub params: ConnectionParameters,
+}
+
+impl Event {
+    /// Parameters of an LE connection, if this is a successful LE connection complete,
+    /// LE enhanced connection complete or LE connection update complete event.
+    pub fn le_connection_update(&self) -> Option<LeConnectionUpdate> {
+        let (subevent, params) = match self {
+            Self::LeMeta { subevent, params } => (*subevent, params.as_slice()),
+            _ => return None,
+        };
+        let (peer, rest) = match subevent {
+            EVT_LE_CONN_COMPLETE if params.len() == 18 => (Some(&params[4..11]), &params[11..]),
+            EVT_LE_ENHANCED_CONN_COMPLETE if params.len() == 30 => (Some(&params[4..11]), &params[23..]),
+            EVT_LE_ENHANCED_CONN_COMPLETE_V2 if params.len() == 33 => (Some(&params[4..11]), &params[23..]),
+            EVT_LE_CONN_UPDATE_COMPLETE if params.len() == 9 => (None, &params[3..]),
+            _ => return None,
+        };
+        if params[0] != 0 {
+            return None;
+        }
+
+        let u16_at = |pos: usize| u16::from_le_bytes([rest[pos], rest[pos + 1]]);
+        let interval = u16_at(0);
+        Some(LeConnectionUpdate {
+            handle: u16::from_le_bytes([params[1], params[2]]) & 0x0fff,
+            peer: peer.map(|peer| {
+                let addr_type = match peer[0] & 0x01 {
+                    0 => AddressType::LePublic,
+                    _ => AddressType::LeRandom,
+                };
+                (Address::from(bdaddr_t { b: peer[1..].try_into().unwrap() }), addr_type)
+            }),
+            params: ConnectionParameters {
+                min_interval: interval,
+                max_interval: interval,
+                latency: u16_at(2),
+                supervision_timeout: u16_at(4),
+            },
+        })
+    }
+
+    /// Handle of the disconnected connection, if this is a successful disconnection complete event.
+    pub fn disconnection_complete(&self) -> Option<u16> {
+        match self {
+            Self::Other { code: EVT_DISCONN_COMPLETE, params } if params.len() == 4 && params[0] == 0 => {
+                Some(u16::from_le_bytes([params[1], params[2]]) & 0x0fff)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// HCI packet.
+///
+/// Data packets contain their HCI data header, i.e. the connection handle
+/// and length, followed by the payload.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Packet {
+    /// Command.
+    Command(Command),
+    /// ACL data.
+    AclData(Vec<u8>),
+    /// SCO data.
+    ScoData(Vec<u8>),
+    /// Event.
+    Event(Event),
+    /// ISO data.
+    IsoData(Vec<u8>),
+    /// Vendor-specific.
+    Vendor(Vec<u8>),
+}
+
+impl Packet {
+    /// Packet type indicator.
+    pub fn packet_type(&self) -> PacketType {
+        match self {
+            Self::Command(_) => PacketType::Command,
+            Self::AclData(_) => PacketType::AclData,
+            Self::ScoData(_) => PacketType::ScoData,
+            Self::Event(_) => PacketType::Event,
+            Self::IsoData(_) => PacketType::IsoData,
+            Self::Vendor(_) => PacketType::Vendor,
+        }
+    }
+
+    /// Encodes the packet including its packet type indicator.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![self.packet_type() as u8];
+        match self {
+            Self::Command(cmd) => {
+                let params = cmd.params();
+                buf.extend_from_slice(&cmd.opcode().to_le_bytes());
+                buf.push(
+                    params
+                        .len()
+                        .try_into()
+                        .map_err(|_| Error::new(ErrorKind::InvalidInput, "command parameters too long"))?,
+                );
+                buf.extend_from_slice(&params);
+            }
+            Self::Event(evt) => {
+                let params = evt.params();
+                buf.push(evt.code());
+                buf.push(
+                    params
+                        .len()
+                        .try_into()
+                        .map_err(|_| Error::new(ErrorKind::InvalidInput, "event parameters too long"))?,
+                );
+                buf.extend_from_slice(&params);
+            }
+            Self::AclData(data) | Self::ScoData(data) | Self::IsoData(data) | Self::Vendor(data) => {
+                buf.extend_from_slice(data)
+            }
+        }
+        Ok(buf)
+    }
+
+    /// Decodes a packet starting with its packet type indicator.
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        let (&ty, data) =
+            buf.split_first().ok_or_else(|| Error::new(ErrorKind::InvalidData, "empty HCI packet"))?;
+        let packet_type = PacketType::from_u8(ty)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("unknown HCI packet type 0x{:02x}", ty)))?;
+        let truncated = || Error::new(ErrorKind::InvalidData, "truncated HCI packet");
+        let packet = match packet_type {
+            PacketType::Command => {
+                if data.len() < 3 || data.len() != 3 + usize::from(data[2]) {
+                    return Err(truncated());
+                }
+                Self::Command(Command::decode(u16::from_le_bytes([data[0], data[1]]), &data[3..])?)
+            }
+            PacketType::Event => {
+                if data.len() < 2 || data.len() != 2 + usize::from(data[1]) {
+                    return Err(truncated());
+                }
+                Self::Event(Event::decode(data[0], &data[2..])?)
+            }
+            PacketType::AclData => Self::AclData(data.to_vec()),
+            PacketType::ScoData => Self::ScoData(data.to_vec()),
+            PacketType::IsoData => Self::IsoData(data.to_vec()),
+            PacketType::Vendor => Self::Vendor(data.to_vec()),
+        };
+        Ok(packet)
+    }
+}
+
+/// Finds the index of the controller with the specified address.
+///
+/// The index can be used to bind an HCI socket to the controller,
+/// for example using [SocketAddr::raw].
+/// Returns an error of kind [ErrorKind::NotFound] if no such controller exists.
+///
+/// This uses the `HCIGETDEVLIST` and `HCIGETDEVINFO` ioctls.
+pub fn find_dev(address: Address) -> Result<u16> {
+    let socket = Socket::new()?;
+    let fd = socket.fd.get_ref();
+
+    for dev_id in dev_ids(fd)? {
+        let mut info = hci_dev_info { dev_id, ..Default::default() };
+        match sock::ioctl_read_write(fd, HCIGETDEVINFO, &mut info) {
+            Ok(_) if Address::from(info.bdaddr) == address => return Ok(dev_id),
+            Ok(_) => (),
+            // The controller may have been removed in the meantime.
+            Err(err) if err.raw_os_error() == Some(libc::ENODEV) => (),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Err(Error::new(ErrorKind::NotFound, format!("no controller with address {}", address)))
+}
+
+/// Finds the index of the controller that has the connection with the specified handle
+/// to the peer with the specified address.
+///
+/// Unlike [find_dev] this works regardless of the local address used by the connection.
+/// Returns an error of kind [ErrorKind::NotFound] if no such connection exists.
+///
+/// This uses the `HCIGETDEVLIST` and `HCIGETCONNLIST` ioctls.
+pub fn find_conn_dev(handle: u16, peer: Address) -> Result<u16> {
+    let socket = Socket::new()?;
+    let fd = socket.fd.get_ref();
+
+    for dev_id in dev_ids(fd)? {
+        match conn_list(fd, dev_id) {
+            Ok(conns) if conns.iter().any(|conn| conn.handle == handle && conn.address == peer) => {
+                return Ok(dev_id)
+            }
+            Ok(_) => (),
+            Err(err) if err.raw_os_error() == Some(libc::ENODEV) => (),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Err(Error::new(ErrorKind::NotFound, format!("no connection with handle 0x{:04x} to {}", handle, peer)))
+}
+
+/// Connection of a controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Connection {
+    /// Connection handle.
+    pub handle: u16,
+    /// Address of the peer.
+    pub address: Address,
+    /// Link type, for example [ACL_LINK] or [LE_LINK].
+    pub link_type: u8,
+    /// Whether the connection was initiated by the local controller.
+    pub outgoing: bool,
+}
+
+/// Lists the connections of the controller with the specified index.
+///
+/// This uses the `HCIGETCONNLIST` ioctl.
+pub fn connections(dev: u16) -> Result<Vec<Connection>> {
+    let socket = Socket::new()?;
+    conn_list(socket.fd.get_ref(), dev)
+}
+
+/// Indices of all controllers.
+fn dev_ids(fd: &OwnedFd) -> Result<Vec<u16>> {
+    let mut list = hci_dev_list_req { dev_num: HCI_MAX_DEV as _, ..Default::default() };
+    sock::ioctl_read_write(fd, HCIGETDEVLIST, &mut list)?;
+    Ok(list.dev_req.iter().take(list.dev_num.into()).map(|req| req.dev_id).collect())
+}
+
+fn conn_list(fd: &OwnedFd, dev: u16) -> Result<Vec<Connection>> {
+    let mut list = Box::new(hci_conn_list_req { dev_id: dev, conn_num: HCI_MAX_CONN as _, ..Default::default() });
+    sock::ioctl_read_write(fd, HCIGETCONNLIST, &mut *list)?;
+    Ok(list
+        .conn_info
+        .iter()
+        .take(list.conn_num.into())
+        .map(|info| Connection {
+            handle: info.handle,
+            address: Address::from(info.bdaddr),
+            link_type: info.type_,
+            outgoing: info.out != 0,
+        })
+        .collect())
+}
+
+/// Error status returned by the controller for a failed command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Status(pub u8);
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HCI command failed with status 0x{:02x}", self.0)
+    }
+}
+
+impl std::error::Error for Status {}
+
+impl From<Status> for Error {
+    fn from(status: Status) -> Self {
+        Error::new(ErrorKind::Other, status)
+    }
+}
+
+/// An HCI socket.
+pub struct Socket {
+    fd: AsyncFd<OwnedFd>,
+}
+
+impl fmt::Debug for Socket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Socket").field("fd", &self.fd.as_raw_fd()).finish()
+    }
+}
+
+impl Socket {
+    /// Creates a new, unbound HCI socket.
+    pub fn new() -> Result<Self> {
+        Ok(Self { fd: AsyncFd::new(sock::socket(AF_BLUETOOTH, SOCK_RAW, BTPROTO_HCI)?)? })
+    }
+
+    /// Creates a new HCI socket bound to the specified address.
+    pub fn open(sa: SocketAddr) -> Result<Self> {
+        let socket = Self::new()?;
+        socket.bind(sa)?;
+        Ok(socket)
+    }
+
+    /// Bind the socket to the given address.
+    pub fn bind(&self, sa: SocketAddr) -> Result<()> {
+        sock::bind(self.fd.get_ref(), sa)
+    }
+
+    /// Get the local address of this socket.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        sock::getsockname(self.fd.get_ref())
+    }
+
+    /// Gets the packet filter.
+    ///
+    /// This is only supported on the [raw channel](Channel::Raw).
+    ///
+    /// This corresponds to the `HCI_FILTER` socket option.
+    pub fn filter(&self) -> Result<Filter> {
+        let flt: hci_filter = sock::getsockopt(self.fd.get_ref(), SOL_HCI, HCI_FILTER)?;
+        Ok(Filter::from_sys(flt))
+    }
+
+    /// Sets the packet filter.
+    ///
+    /// This is only supported on the [raw channel](Channel::Raw).
+    ///
+    /// This corresponds to the `HCI_FILTER` socket option.
+    pub fn set_filter(&self, filter: &Filter) -> Result<()> {
+        sock::setsockopt(self.fd.get_ref(), SOL_HCI, HCI_FILTER, &filter.to_sys())
+    }
+
+    /// Sends a raw packet starting with its packet type indicator.
+    pub async fn send_raw(&self, buf: &[u8]) -> Result<usize> {
+        loop {
+            let mut guard = self.fd.writable().await?;
+            match guard.try_io(|inner| sock::send(inner.get_ref(), buf, 0)) {
+                Ok(result) => return result,
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    /// Receives a raw packet.
+    ///
+    /// On the raw and user channels the packet starts with its packet type indicator.
+    /// On the monitor channel the packet starts with the monitor header.
+    pub async fn recv_raw(&self, buf: &mut [u8]) -> Result<usize> {
+        let mut buf = ReadBuf::new(buf);
+        loop {
+            let mut guard = self.fd.readable().await?;
+            match guard.try_io(|inner| sock::recv(inner.get_ref(), &mut buf, 0)) {
+                Ok(result) => return result,
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    /// Sends a packet.
+    pub async fn send(&self, packet: &Packet) -> Result<()> {
+        let buf = packet.encode()?;
+        let n = self.send_raw(&buf).await?;
+        if n != buf.len() {
+            return Err(Error::new(ErrorKind::WriteZero, "incomplete HCI packet sent"));
+        }
+        Ok(())
+    }
+
+    /// Receives a packet.
+    ///
+    /// This is not supported on the monitor channel.
+    pub async fn recv(&self) -> Result<Packet> {
+        let mut buf = vec![0; MAX_PACKET_SIZE];
+        let n = self.recv_raw(&mut buf).await?;
+        Packet::decode(&buf[..n])
+    }
+
+    /// Sends a command and waits for its completion.
+    ///
+    /// Returns the return parameters of the command after the status byte.
+    /// If the controller only reports the command status, empty return parameters are returned.
+    /// A non-zero status is returned as an error containing [Status].
+    /// If the command does not complete within [DEFAULT_COMMAND_TIMEOUT],
+    /// an error of kind [ErrorKind::TimedOut] is returned.
+    ///
+    /// On the raw channel the packet filter is temporarily replaced.
+    /// It is restored when the command completes or the returned future is dropped.
+    /// Other packets received while waiting are discarded.
+    pub async fn command(&self, cmd: &Command) -> Result<Vec<u8>> {
+        self.command_with_timeout(cmd, DEFAULT_COMMAND_TIMEOUT).await
+    }
+
+    /// Sends a command and waits at most the specified time for its completion.
+    ///
+    /// See [command](Self::command) for details.
+    pub async fn command_with_timeout(&self, cmd: &Command, timeout: Duration) -> Result<Vec<u8>> {
+        let opcode = cmd.opcode();
+        let old_filter = self.filter().ok();
+        if old_filter.is_some() {
+            self.set_filter(
+                &Filter::new()
+                    .allow_packet_type(PacketType::Event)
+                    .allow_event(EVT_CMD_COMPLETE)
+                    .allow_event(EVT_CMD_STATUS)
+                    .match_opcode(opcode),
+            )?;
+        }
+        let filter_guard = FilterGuard { socket: self, filter: old_filter };
+
+        let result = match tokio::time::timeout(timeout, self.command_priv(cmd, opcode)).await {
+            Ok(result) => result,
+            Err(_) => {
+                log::debug!("HCI command 0x{:04x} timed out", opcode);
+                Err(Error::new(ErrorKind::TimedOut, "HCI command timed out"))
+            }
+        };
+
+        filter_guard.restore()?;
+        result
+    }
+
+    async fn command_priv(&self, cmd: &Command, opcode: u16) -> Result<Vec<u8>> {
+        log::trace!("HCI command 0x{:04x}: {:x?}", opcode, cmd);
+        self.send(&Packet::Command(cmd.clone())).await?;
+
+        loop {
+            match self.recv().await? {
+                Packet::Event(Event::CommandComplete { opcode: ev_opcode, return_params, .. })
+                    if ev_opcode == opcode =>
+                {
+                    log::trace!("HCI command 0x{:04x} completed: {:x?}", opcode, return_params);
+                    return match return_params.split_first() {
+                        Some((0, params)) => Ok(params.to_vec()),
+                        Some((&status, _)) => Err(Status(status).into()),
+                        None => Ok(Vec::new()),
+                    };
+                }
+                Packet::Event(Event::CommandStatus { status, opcode: ev_opcode, .. }) if ev_opcode == opcode => {
+                    log::trace!("HCI command 0x{:04x} status: 0x{:02x}", opcode, status);
+                    return match status {
+                        0 => Ok(Vec::new()),
+                        status => Err(Status(status).into()),
+                    };
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    /// Resets the controller.
+    pub async fn reset(&self) -> Result<()> {
+        self.command(&Command::Reset).await?;
+        Ok(())
+    }
+
+    /// Reads the public device address of the controller.
+    pub async fn read_bd_addr(&self) -> Result<Address> {
+        let params = self.command(&Command::ReadBdAddr).await?;
+        let b: [u8; 6] = params
+            .get(..6)
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid Read BD_ADDR response"))?;
+        Ok(Address::from(bdaddr_t { b }))
+    }
+
+    /// Sets the LE scan parameters.
+    pub async fn le_set_scan_parameters(&self, params: &LeScanParameters) -> Result<()> {
+        self.command(&Command::LeSetScanParameters(params.clone())).await?;
+        Ok(())
+    }
+
+    /// Requests the PHYs of an LE connection.
+    ///
+    /// The connection handle of an L2CAP connection is available from
+    /// the `hci_handle` field of `l2cap::Socket::conn_info`;
+    /// `l2cap::Socket::set_phy` sends this command for an L2CAP connection.
+    /// The controller negotiates the PHYs with the remote device and
+    /// may choose others if the preferred PHYs are not supported.
+    /// Use `mgmt::Controller::set_phy_configuration` to set the PHYs preferred for new connections.
+    pub async fn le_set_phy(&self, handle: u16, phy: Phy) -> Result<()> {
+        self.command(&Command::LeSetPhy { handle, phy }).await?;
+        Ok(())
+    }
+
+    /// Sends a vendor-specific command and returns its return parameters after the status byte.
+    pub async fn vendor_command(&self, ocf: u16, params: &[u8]) -> Result<Vec<u8>> {
+        self.command(&Command::Vendor { ocf, params: params.to_vec() }).await
+    }
+
+    /// Constructs a new [Socket] from the given raw file descriptor.
+    ///
+    /// The file descriptor must have been set to non-blocking mode.
+    ///
+    /// This function *consumes ownership* of the specified file descriptor.
+    /// The returned object will take responsibility for closing it when the object goes out of scope.
+    ///
+    /// # Safety
+    /// If the passed file descriptor is invalid, undefined behavior may occur.
+    pub unsafe fn from_raw_fd(fd: RawFd) -> Result<Self> {
+        Ok(Self { fd: AsyncFd::new(OwnedFd::new(fd))? })
+    }
+}
+
+/// Restores the packet filter of a socket when dropped.
+struct FilterGuard<'a> {
+    socket: &'a Socket,
+    filter: Option<Filter>,
+}
+
+impl<'a> FilterGuard<'a> {
+    /// Restores the packet filter, returning any error.
+    fn restore(mut self) -> Result<()> {
+        match self.filter.take() {
+            Some(filter) => self.socket.set_filter(&filter),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> Drop for FilterGuard<'a> {
+    fn drop(&mut self) {
+        if let Some(filter) = self.filter.take() {
+            if let Err(err) = self.socket.set_filter(&filter) {
+                log::warn!("Restoring HCI filter failed: {}", err);
+            }
+        }
+    }
+}
+
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl IntoRawFd for Socket {
+    fn into_raw_fd(self) -> RawFd {
+        self.fd.into_inner().into_raw_fd()
+    }
+}
+
+impl FromRawFd for Socket {
+    /// Constructs a new instance of `Self` from the given raw file
+    /// descriptor.
+    ///
+    /// The file descriptor must have been set to non-blocking mode.
+    ///
+    /// # Panics
+    /// Panics when the conversion fails.
+    /// Use [Socket::from_raw_fd] for a non-panicking variant.
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        Self::from_raw_fd(fd).expect("from_raw_fd failed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Link type of pcap files containing HCI packets with a direction header.
+    const LINKTYPE_BLUETOOTH_HCI_H4_WITH_PHDR: u32 = 201;
+
+    /// Packet of a pcap fixture.
+    struct Record {
+        /// Whether the packet was received from the controller.
+        received: bool,
+        /// Packet including its packet type indicator.
+        data: Vec<u8>,
+    }
+
+    fn fixture(name: &str) -> Vec<Record> {
+        let path = format!("{}/tests/fixtures/hci/{}", env!("CARGO_MANIFEST_DIR"), name);
+        let buf = std::fs::read(path).unwrap();
+        let u32_at = |pos: usize| u32::from_le_bytes(buf[pos..pos + 4].try_into().unwrap());
+        assert_eq!(u32_at(0), 0xa1b2_c3d4);
+        assert_eq!(u32_at(20), LINKTYPE_BLUETOOTH_HCI_H4_WITH_PHDR);
+
+        let mut records = Vec::new();
+        let mut pos = 24;
+        while pos < buf.len() {
+            let len = u32_at(pos + 8) as usize;
+            let data = &buf[pos + 16..pos + 16 + len];
+            let direction = u32::from_be_bytes(data[..4].try_into().unwrap());
+            records.push(Record { received: direction != 0, data: data[4..].to_vec() });
+            pos += 16 + len;
+        }
+        records
+    }
+
+    fn socket_pair() -> (Socket, Socket) {
+        let mut fds = [0; 2];
+        let ret = unsafe {
+            libc::socketpair(libc::AF_UNIX, libc::SOCK_SEQPACKET | libc::SOCK_NONBLOCK, 0, fds.as_mut_ptr())
+        };
+        assert_eq!(ret, 0);
+        unsafe { (Socket::from_raw_fd(fds[0]).unwrap(), Socket::from_raw_fd(fds[1]).unwrap()) }
+    }
+
+    /// Acts as the controller by checking sent commands and replying received events.
+    async fn replay(controller: Socket, records: Vec<Record>) {
+        let mut buf = vec![0; MAX_PACKET_SIZE];
+        for record in records {
+            if record.received {
+                controller.send_raw(&record.data).await.unwrap();
+            } else {
+                let n = controller.recv_raw(&mut buf).await.unwrap();
+                assert_eq!(&buf[..n], &record.data[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_fixture() {
+        let records = fixture("commands.pcap");
+        assert_eq!(records.len(), 12);
+
+        for record in &records {
+            let packet = Packet::decode(&record.data).unwrap();
+            assert_eq!(packet.encode().unwrap(), record.data);
+            let expected_type = if record.received { PacketType::Event } else { PacketType::Command };
+            assert_eq!(packet.packet_type(), expected_type);
+        }
+
+        let packets: Vec<_> = records.iter().map(|record| Packet::decode(&record.data).unwrap()).collect();
+        assert_eq!(packets[0], Packet::Command(Command::Reset));
+        assert_eq!(
+            packets[1],
+            Packet::Event(Event::CommandComplete { num_packets: 1, opcode: 0x0c03, return_params: vec![0] })
+        );
+        assert_eq!(packets[3], Packet::Event(Event::LeMeta { subevent: 0x02, params: vec![0] }));
+        assert_eq!(
+            packets[5],
+            Packet::Command(Command::LeSetScanParameters(LeScanParameters {
+                scan_type: LeScanType::Active,
+                interval: 0x0060,
+                window: 0x0030,
+                ..Default::default()
+            }))
+        );
+        assert_eq!(packets[7], Packet::Command(Command::LeSetPhy { handle: 0x0040, phy: Phy::LE_2M }));
+        assert_eq!(packets[8], Packet::Event(Event::CommandStatus { status: 0, num_packets: 1, opcode: 0x2032 }));
+        assert_eq!(packets[9], Packet::Command(Command::Vendor { ocf: 0x0001, params: vec![0xaa] }));
+    }
+
+    #[test]
+    fn reject_truncated() {
+        assert!(Packet::decode(&[]).is_err());
+        assert!(Packet::decode(&[0x01, 0x03, 0x0c]).is_err());
+        assert!(Packet::decode(&[0x01, 0x03, 0x0c, 0x01]).is_err());
+        assert!(Packet::decode(&[0x04, 0x0e, 0x04, 0x01, 0x03]).is_err());
+        assert!(Packet::decode(&[0x04, 0x0f, 0x01, 0x00]).is_err());
+        assert!(Packet::decode(&[0x06]).is_err());
+    }
+
+    #[test]
+    fn le_connection_update() {
+        let params = ConnectionParameters {
+            min_interval: 0x0018,
+            max_interval: 0x0018,
+            latency: 2,
+            supervision_timeout: 0x01f4,
+        };
+        let peer = Address::new([0x11, 0x22, 0x33, 0x44, 0x55, 0x66]);
+
+        let complete = Packet::decode(&[
+            0x04, 0x3e, 0x13, 0x01, 0x00, 0x40, 0x00, 0x00, 0x01, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, 0x18, 0x00,
+            0x02, 0x00, 0xf4, 0x01, 0x00,
+        ])
+        .unwrap();
+        let update = match complete {
+            Packet::Event(evt) => evt.le_connection_update(),
+            _ => panic!("not an event"),
+        };
+        assert_eq!(
+            update,
+            Some(LeConnectionUpdate { handle: 0x0040, peer: Some((peer, AddressType::LeRandom)), params })
+        );
+
+        let mut enhanced = vec![0x00, 0x41, 0x00, 0x01, 0x02, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11];
+        enhanced.extend_from_slice(&[0; 12]);
+        enhanced.extend_from_slice(&[0x18, 0x00, 0x02, 0x00, 0xf4, 0x01, 0x00]);
+        let evt = Event::LeMeta { subevent: 0x0a, params: enhanced.clone() };
+        assert_eq!(
+            evt.le_connection_update(),
+            Some(LeConnectionUpdate { handle: 0x0041, peer: Some((peer, AddressType::LePublic)), params })
+        );
+        enhanced.extend_from_slice(&[0xff, 0xff, 0xff]);
+        let evt = Event::LeMeta { subevent: 0x29, params: enhanced };
+        assert_eq!(evt.le_connection_update().unwrap().handle, 0x0041);
+
+        let evt =
+            Event::LeMeta { subevent: 0x03, params: vec![0x00, 0x40, 0x00, 0x18, 0x00, 0x02, 0x00, 0xf4, 0x01] };
+        assert_eq!(evt.le_connection_update(), Some(LeConnectionUpdate { handle: 0x0040, peer: None, params }));
+
+        let failed =
+            Event::LeMeta { subevent: 0x03, params: vec![0x3b, 0x40, 0x00, 0x18, 0x00, 0x02, 0x00, 0xf4, 0x01] };
+        assert_eq!(failed.le_connection_update(), None);
+        let truncated = Event::LeMeta { subevent: 0x03, params: vec![0x00, 0x40, 0x00] };
+        assert_eq!(truncated.le_connection_update(), None);
+        assert_eq!(Event::LeMeta { subevent: 0x02, params: vec![0] }.le_connection_update(), None);
+
+        let disconnected = Event::decode(EVT_DISCONN_COMPLETE, &[0x00, 0x40, 0x00, 0x13]).unwrap();
+        assert_eq!(disconnected.disconnection_complete(), Some(0x0040));
+        let failed = Event::decode(EVT_DISCONN_COMPLETE, &[0x0c, 0x40, 0x00, 0x13]).unwrap();
+        assert_eq!(failed.disconnection_complete(), None);
+    }
+
+    #[test]
+    fn conn_list_layout() {
+        assert_eq!(std::mem::size_of::<crate::sys::hci_conn_info>(), 16);
+        assert_eq!(std::mem::size_of::<hci_conn_list_req>(), 4 + 16 * HCI_MAX_CONN);
+    }
+
+    #[test]
+    fn filter() {
+        let filter = Filter::new()
+            .allow_packet_type(PacketType::Event)
+            .allow_event(EVT_CMD_COMPLETE)
+            .allow_event(EVT_LE_META)
+            .match_opcode(0x0c03);
+        assert!(filter.is_packet_type_allowed(PacketType::Event));
+        assert!(!filter.is_packet_type_allowed(PacketType::AclData));
+        assert!(filter.is_event_allowed(EVT_CMD_COMPLETE));
+        assert!(filter.is_event_allowed(EVT_LE_META));
+        assert!(!filter.is_event_allowed(EVT_CMD_STATUS));
+        assert_eq!(filter.opcode(), Some(0x0c03));
+        assert_eq!(Filter::from_sys(filter.to_sys()), filter);
+        assert_eq!(Filter::new().opcode(), None);
+    }
+
+    #[tokio::test]
+    async fn commands() {
+        let (host, controller) = socket_pair();
+        let controller = tokio::spawn(replay(controller, fixture("commands.pcap")));
+
+        host.reset().await.unwrap();
+        assert_eq!(host.read_bd_addr().await.unwrap(), Address::new([0x11, 0x22, 0x33, 0x44, 0x55, 0x66]));
+
+        let params = LeScanParameters {
+            scan_type: LeScanType::Active,
+            interval: 0x0060,
+            window: 0x0030,
+            ..Default::default()
+        };
+        let err = host.le_set_scan_parameters(&params).await.unwrap_err();
+        let status = err.get_ref().and_then(|err| err.downcast_ref::<Status>()).copied();
+        assert_eq!(status, Some(Status(0x0c)));
+
+        host.le_set_phy(0x0040, Phy::LE_2M).await.unwrap();
+        assert_eq!(host.vendor_command(0x0001, &[0xaa]).await.unwrap(), vec![0x01, 0x02, 0x03]);
+
+        controller.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn command_timeout() {
+        let (host, _controller) = socket_pair();
+        let err = host.command_with_timeout(&Command::Reset, Duration::from_millis(50)).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,784 @@
+//! Isochronous channel (ISO) sockets.
+//!
+//! ISO sockets carry time-bounded data, such as LE Audio streams, over Bluetooth Low Energy.
+//!
+//! Unicast data is exchanged over connected isochronous streams (CIS) that belong to
+//! a connected isochronous group (CIG).
+//! Use [SeqPacket::connect] to establish a CIS with a peer and a [Listener]
+//! to accept incoming CISes.
+//! The CIG and CIS parameters are configured using [Socket::set_unicast_qos].
+//!
+//! Broadcast data is sent over broadcast isochronous streams (BIS) that belong to
+//! a broadcast isochronous group (BIG).
+//! Use [SeqPacket::broadcast] to become a broadcast source and [Listener::bind_broadcast]
+//! to synchronize to a broadcast source as a broadcast sink.
+//! The BIG and BIS parameters, including the broadcast code for encrypted broadcasts,
+//! are configured using [Socket::set_broadcast_qos].
+//!
+//! A broadcast sink can inspect the broadcast audio source endpoint (BASE) of a
+//! broadcast source before synchronizing to its BISes.
+//! For this, enable [deferred setup](Socket::set_defer_setup) on the listening socket.
+//! The first connection obtained by [Listener::accept_deferred] will then be synchronized
+//! to the periodic advertising train (PA sync) of the broadcast source and provide its
+//! [BASE](Socket::base).
+//! Then call [PendingSeqPacket::sync_bis] to synchronize to the selected BISes.
+//!
+//! ISO sockets are an experimental feature of the Linux kernel and must be enabled,
+//! for example by starting the Bluetooth daemon with the `--experimental` option.
+//!
+
+use crate::{
+    sock::{self, OwnedFd},
+    sys::{
+        bt_iso_qos, sockaddr_iso, sockaddr_iso_bc, BASE_MAX_LENGTH, BTPROTO_ISO, BT_DEFER_SETUP, BT_ISO_BASE,
+        BT_ISO_QOS, BT_PHY, ISO_MAX_NUM_BIS, SOCKADDR_ISO_UCAST_LEN,
+    },
+    Address, AddressType, Phy,
+};
+use futures::ready;
+use libc::{
+    c_int, AF_BLUETOOTH, EAGAIN, EINPROGRESS, SHUT_RD, SHUT_RDWR, SHUT_WR, SOCK_SEQPACKET, SOL_BLUETOOTH,
+    SOL_SOCKET, SO_ERROR, SO_RCVBUF, TIOCINQ, TIOCOUTQ,
+};
+use num_traits::FromPrimitive;
+use std::{
+    convert::TryInto,
+    fmt,
+    io::{Error, ErrorKind, Result},
+    mem::size_of,
+    net::Shutdown,
+    os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{unix::AsyncFd, AsyncRead, AsyncWrite, ReadBuf};
+
+pub use crate::sock::Timestamp;
+pub use crate::sys::{
+    bt_iso_bcast_qos as BroadcastQos, bt_iso_io_qos as IoQos, bt_iso_ucast_qos as UnicastQos,
+    BT_ISO_QOS_BIG_UNSET as BIG_UNSET, BT_ISO_QOS_BIS_UNSET as BIS_UNSET, BT_ISO_QOS_CIG_UNSET as CIG_UNSET,
+    BT_ISO_QOS_CIS_UNSET as CIS_UNSET,
+};
+
+/// Possible bit values for the [PHY field](IoQos::phy) of the quality of service parameters.
+pub mod phy {
+    pub use crate::sys::{
+        BT_ISO_PHY_1M as LE_1M, BT_ISO_PHY_2M as LE_2M, BT_ISO_PHY_ANY as ANY, BT_ISO_PHY_CODED as LE_CODED,
+    };
+}
+
+/// Maximum number of broadcast isochronous streams (BIS) within a [BroadcastAddr].
+pub const MAX_NUM_BIS: usize = ISO_MAX_NUM_BIS;
+
+/// Maximum length of a broadcast audio source endpoint (BASE).
+pub const MAX_BASE_LEN: usize = BASE_MAX_LENGTH;
+
+/// An ISO socket address.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SocketAddr {
+    /// Device address.
+    ///
+    /// When listening or binding, specify [Address::any] for any local adapter address.
+    /// When connecting a broadcast source, specify [Address::any].
+    pub addr: Address,
+    /// Device address type.
+    ///
+    /// Must be a Bluetooth Low Energy address type.
+    pub addr_type: AddressType,
+    /// Broadcast part of the address.
+    ///
+    /// When binding a broadcast sink, this specifies the broadcast source to synchronize to.
+    pub broadcast: Option<BroadcastAddr>,
+}
+
+impl SocketAddr {
+    /// Creates a new ISO socket address for unicast.
+    pub const fn new(addr: Address, addr_type: AddressType) -> Self {
+        Self { addr, addr_type, broadcast: None }
+    }
+
+    /// Creates a new ISO socket address for synchronizing to the specified broadcast source.
+    pub const fn new_broadcast(addr: Address, addr_type: AddressType, broadcast: BroadcastAddr) -> Self {
+        Self { addr, addr_type, broadcast: Some(broadcast) }
+    }
+
+    /// When specified to [Socket::bind] binds to any public, local adapter address.
+    ///
+    /// When specified to [Socket::connect] creates a broadcast source.
+    pub const fn any_le() -> Self {
+        Self { addr: Address::any(), addr_type: AddressType::LePublic, broadcast: None }
+    }
+}
+
+impl sock::SysSockAddr for SocketAddr {
+    type SysSockAddr = sockaddr_iso;
+
+    fn into_sys_sock_addr(self) -> Self::SysSockAddr {
+        sockaddr_iso {
+            iso_family: AF_BLUETOOTH as _,
+            iso_bdaddr: self.addr.into(),
+            iso_bdaddr_type: self.addr_type as _,
+            iso_bc: self.broadcast.map(|bc| bc.into_sys()).unwrap_or_default(),
+        }
+    }
+
+    fn try_from_sys_sock_addr(saddr: Self::SysSockAddr) -> Result<Self> {
+        Self::try_from_sys_sock_addr_len(saddr, size_of::<sockaddr_iso>())
+    }
+
+    fn sys_sock_addr_len(&self) -> usize {
+        match self.broadcast {
+            Some(_) => size_of::<sockaddr_iso>(),
+            None => SOCKADDR_ISO_UCAST_LEN,
+        }
+    }
+
+    fn try_from_sys_sock_addr_len(saddr: Self::SysSockAddr, len: usize) -> Result<Self> {
+        if saddr.iso_family != AF_BLUETOOTH as _ {
+            return Err(Error::new(ErrorKind::InvalidInput, "sockaddr_iso::iso_family is not AF_BLUETOOTH"));
+        }
+        let broadcast = if len == size_of::<sockaddr_iso>() {
+            Some(BroadcastAddr::try_from_sys(saddr.iso_bc)?)
+        } else if len == SOCKADDR_ISO_UCAST_LEN {
+            None
+        } else {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid sockaddr_iso length"));
+        };
+        Ok(Self {
+            addr: Address::from(saddr.iso_bdaddr),
+            addr_type: AddressType::from_u8(saddr.iso_bdaddr_type)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid sockaddr_iso::iso_bdaddr_type"))?,
+            broadcast,
+        })
+    }
+}
+
+/// Broadcast part of an ISO socket address.
+///
+/// This specifies a broadcast source and the broadcast isochronous streams (BIS)
+/// to synchronize to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BroadcastAddr {
+    /// Address of the broadcast source.
+    pub addr: Address,
+    /// Address type of the broadcast source.
+    pub addr_type: AddressType,
+    /// Advertising set identifier (SID) of the periodic advertising train of the broadcast source.
+    pub sid: u8,
+    num_bis: u8,
+    bis: [u8; MAX_NUM_BIS],
+}
+
+impl BroadcastAddr {
+    /// Creates a new broadcast address without any selected BISes.
+    pub const fn new(addr: Address, addr_type: AddressType, sid: u8) -> Self {
+        Self { addr, addr_type, sid, num_bis: 0, bis: [0; MAX_NUM_BIS] }
+    }
+
+    /// Indices of the selected broadcast isochronous streams (BIS).
+    pub fn bis(&self) -> &[u8] {
+        &self.bis[..self.num_bis.into()]
+    }
+
+    /// Sets the indices of the selected broadcast isochronous streams (BIS).
+    ///
+    /// Indices start at 1 and at most [MAX_NUM_BIS] BISes can be selected.
+    pub fn set_bis(&mut self, bis: &[u8]) -> Result<()> {
+        if bis.len() > MAX_NUM_BIS {
+            return Err(Error::new(ErrorKind::InvalidInput, "too many BISes"));
+        }
+        self.bis = [0; MAX_NUM_BIS];
+        self.bis[..bis.len()].copy_from_slice(bis);
+        self.num_bis = bis.len() as _;
+        Ok(())
+    }
+
+    fn into_sys(self) -> sockaddr_iso_bc {
+        sockaddr_iso_bc {
+            bc_bdaddr: self.addr.into(),
+            bc_bdaddr_type: self.addr_type as _,
+            bc_sid: self.sid,
+            bc_num_bis: self.num_bis,
+            bc_bis: self.bis,
+        }
+    }
+
+    fn try_from_sys(bc: sockaddr_iso_bc) -> Result<Self> {
+        if bc.bc_num_bis as usize > MAX_NUM_BIS {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid sockaddr_iso_bc::bc_num_bis"));
+        }
+        Ok(Self {
+            addr: Address::from(bc.bc_bdaddr),
+            addr_type: AddressType::from_u8(bc.bc_bdaddr_type)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid sockaddr_iso_bc::bc_bdaddr_type"))?,
+            sid: bc.bc_sid,
+            num_bis: bc.bc_num_bis,
+            bis: bc.bc_bis,
+        })
+    }
+}
+
+/// An ISO socket that has not yet been converted to a [Listener] or [SeqPacket].
+///
+/// The primary use of this is to configure the socket before connecting or listening.
+pub struct Socket {
+    fd: AsyncFd<OwnedFd>,
+}
+
+impl fmt::Debug for Socket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Socket").field("fd", &self.fd.as_raw_fd()).finish()
+    }
+}
+
+impl Socket {
+    /// Creates a new socket of sequential packet type.
+    pub fn new() -> Result<Self> {
+        Ok(Self { fd: AsyncFd::new(sock::socket(AF_BLUETOOTH, SOCK_SEQPACKET, BTPROTO_ISO)?)? })
+    }
+
+    /// Convert the socket into a [Listener].
+    ///
+    /// `backlog` defines the maximum number of pending connections are queued by the operating system
+    /// at any given time.
+    ///
+    /// If the socket is bound to a [broadcast address](SocketAddr::broadcast), the kernel
+    /// starts synchronizing to the specified broadcast source.
+    pub fn listen(self, backlog: u32) -> Result<Listener> {
+        sock::listen(
+            self.fd.get_ref(),
+            backlog.try_into().map_err(|_| Error::new(ErrorKind::InvalidInput, "invalid backlog"))?,
+        )?;
+        Ok(Listener { socket: self })
+    }
+
+    /// Establish an ISO connection with a peer at the specified socket address.
+    ///
+    /// If the address is [Address::any], a broadcast isochronous group (BIG) is created
+    /// and this socket becomes a broadcast source.
+    /// Otherwise a connected isochronous stream (CIS) to the peer is established.
+    /// For a CIS, an LE connection to the peer must already exist.
+    pub async fn connect(self, sa: SocketAddr) -> Result<SeqPacket> {
+        self.connect_priv(sa).await?;
+        Ok(SeqPacket { socket: self })
+    }
+
+    /// Bind the socket to the given address.
+    pub fn bind(&self, sa: SocketAddr) -> Result<()> {
+        sock::bind(self.fd.get_ref(), sa)
+    }
+
+    /// Get the local address of this socket.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        sock::getsockname(self.fd.get_ref())
+    }
+
+    /// Get the peer address of this socket.
+    fn peer_addr_priv(&self) -> Result<SocketAddr> {
+        sock::getpeername(self.fd.get_ref())
+    }
+
+    fn qos(&self) -> Result<bt_iso_qos> {
+        sock::getsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_ISO_QOS)
+    }
+
+    fn set_qos(&self, qos: &bt_iso_qos) -> Result<()> {
+        sock::setsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_ISO_QOS, qos)
+    }
+
+    /// Gets the quality of service parameters of a connected isochronous stream (CIS).
+    ///
+    /// This corresponds to the `BT_ISO_QOS` socket option.
+    pub fn unicast_qos(&self) -> Result<UnicastQos> {
+        Ok(unsafe { self.qos()?.ucast })
+    }
+
+    /// Sets the quality of service parameters of a connected isochronous stream (CIS).
+    ///
+    /// Must be set before connecting or, for incoming connections, on the listener
+    /// socket before listening.
+    ///
+    /// This corresponds to the `BT_ISO_QOS` socket option.
+    pub fn set_unicast_qos(&self, qos: &UnicastQos) -> Result<()> {
+        let mut value: bt_iso_qos = unsafe { std::mem::zeroed() };
+        value.ucast = *qos;
+        self.set_qos(&value)
+    }
+
+    /// Gets the quality of service parameters of a broadcast isochronous stream (BIS).
+    ///
+    /// This corresponds to the `BT_ISO_QOS` socket option.
+    pub fn broadcast_qos(&self) -> Result<BroadcastQos> {
+        Ok(unsafe { self.qos()?.bcast })
+    }
+
+    /// Sets the quality of service parameters of a broadcast isochronous stream (BIS).
+    ///
+    /// Must be set before connecting a broadcast source or listening as a broadcast sink.
+    /// Set [encryption](BroadcastQos::encryption) and the [broadcast code](BroadcastQos::bcode)
+    /// to create or synchronize to an encrypted broadcast.
+    ///
+    /// This corresponds to the `BT_ISO_QOS` socket option.
+    pub fn set_broadcast_qos(&self, qos: &BroadcastQos) -> Result<()> {
+        let mut value: bt_iso_qos = unsafe { std::mem::zeroed() };
+        value.bcast = *qos;
+        self.set_qos(&value)
+    }
+
+    /// Gets the broadcast audio source endpoint (BASE).
+    ///
+    /// For a socket synchronized to the periodic advertising train of a broadcast source,
+    /// this is the BASE received from the broadcast source.
+    ///
+    /// This corresponds to the `BT_ISO_BASE` socket option.
+    pub fn base(&self) -> Result<Vec<u8>> {
+        sock::getsockopt_bytes(self.fd.get_ref(), SOL_BLUETOOTH, BT_ISO_BASE, MAX_BASE_LEN)
+    }
+
+    /// Sets the broadcast audio source endpoint (BASE) advertised by a broadcast source.
+    ///
+    /// Must be set before connecting the broadcast source.
+    /// At most [MAX_BASE_LEN] bytes can be set.
+    ///
+    /// This corresponds to the `BT_ISO_BASE` socket option.
+    pub fn set_base(&self, base: &[u8]) -> Result<()> {
+        if base.len() > MAX_BASE_LEN {
+            return Err(Error::new(ErrorKind::InvalidInput, "BASE too long"));
+        }
+        sock::setsockopt_bytes(self.fd.get_ref(), SOL_BLUETOOTH, BT_ISO_BASE, base)
+    }
+
+    /// Gets whether connection setup is deferred until the connection is accepted.
+    ///
+    /// This corresponds to the `BT_DEFER_SETUP` socket option.
+    pub fn is_defer_setup(&self) -> Result<bool> {
+        let value: u32 = sock::getsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_DEFER_SETUP)?;
+        Ok(value != 0)
+    }
+
+    /// Sets whether connection setup is deferred until the connection is accepted.
+    ///
+    /// Must be set on the socket before listening.
+    /// Incoming unicast connections are then obtained using [Listener::accept_deferred].
+    /// A broadcast sink first accepts a connection synchronized to the periodic advertising
+    /// train of the broadcast source using [Listener::accept_deferred] and then uses
+    /// [PendingSeqPacket::sync_bis].
+    ///
+    /// This corresponds to the `BT_DEFER_SETUP` socket option.
+    pub fn set_defer_setup(&self, defer_setup: bool) -> Result<()> {
+        let value: u32 = defer_setup.into();
+        sock::setsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_DEFER_SETUP, &value)
+    }
+
+    /// Gets whether receive timestamps are reported.
+    ///
+    /// This corresponds to the `SO_TIMESTAMPING` socket option.
+    pub fn is_timestamping(&self) -> Result<bool> {
+        sock::is_timestamping(self.fd.get_ref())
+    }
+
+    /// Sets whether software and hardware receive timestamps are reported.
+    ///
+    /// When enabled, the timestamps are returned by [SeqPacket::recv_with_timestamp].
+    ///
+    /// This corresponds to the `SO_TIMESTAMPING` socket option.
+    pub fn set_timestamping(&self, timestamping: bool) -> Result<()> {
+        sock::set_timestamping(self.fd.get_ref(), timestamping)
+    }
+
+    /// Get PHYs used by the connection.
+    ///
+    /// This corresponds to the `BT_PHY` socket option.
+    pub fn phy(&self) -> Result<Phy> {
+        let phy: u32 = sock::getsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_PHY)?;
+        Ok(Phy::from_bits_truncate(phy))
+    }
+
+    /// Gets the maximum socket receive buffer in bytes.
+    ///
+    /// This corresponds to the `SO_RCVBUF` socket option.
+    pub fn recv_buffer(&self) -> Result<i32> {
+        sock::getsockopt(self.fd.get_ref(), SOL_SOCKET, SO_RCVBUF)
+    }
+
+    /// Sets the maximum socket receive buffer in bytes.
+    ///
+    /// This corresponds to the `SO_RCVBUF` socket option.
+    pub fn set_recv_buffer(&self, recv_buffer: i32) -> Result<()> {
+        sock::setsockopt(self.fd.get_ref(), SOL_SOCKET, SO_RCVBUF, &recv_buffer)
+    }
+
+    /// Get the number of bytes in the input buffer.
+    ///
+    /// This corresponds to the `TIOCINQ` IOCTL.
+    pub fn input_buffer(&self) -> Result<u32> {
+        let value: c_int = sock::ioctl_read(self.fd.get_ref(), TIOCINQ)?;
+        Ok(value as _)
+    }
+
+    /// Get the number of bytes in the output buffer.
+    ///
+    /// This corresponds to the `TIOCOUTQ` IOCTL.
+    pub fn output_buffer(&self) -> Result<u32> {
+        let value: c_int = sock::ioctl_read(self.fd.get_ref(), TIOCOUTQ)?;
+        Ok(value as _)
+    }
+
+    /// Constructs a new [Socket] from the given raw file descriptor.
+    ///
+    /// The file descriptor must have been set to non-blocking mode.
+    ///
+    /// This function *consumes ownership* of the specified file descriptor.
+    /// The returned object will take responsibility for closing it when the object goes out of scope.
+    ///
+    /// # Safety
+    /// If the passed file descriptor is invalid, undefined behavior may occur.
+    pub unsafe fn from_raw_fd(fd: RawFd) -> Result<Self> {
+        Ok(Self { fd: AsyncFd::new(OwnedFd::new(fd))? })
+    }
+
+    fn from_owned_fd(fd: OwnedFd) -> Result<Self> {
+        Ok(Self { fd: AsyncFd::new(fd)? })
+    }
+
+    sock_priv!(accept_deferred, recvmsg, poll_recvmsg);
+}
+
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl IntoRawFd for Socket {
+    fn into_raw_fd(self) -> RawFd {
+        self.fd.into_inner().into_raw_fd()
+    }
+}
+
+impl FromRawFd for Socket {
+    /// Constructs a new instance of `Self` from the given raw file
+    /// descriptor.
+    ///
+    /// The file descriptor must have been set to non-blocking mode.
+    ///
+    /// # Panics
+    /// Panics when the conversion fails.
+    /// Use [Socket::from_raw_fd] for a non-panicking variant.
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        Self::from_raw_fd(fd).expect("from_raw_fd failed")
+    }
+}
+
+/// An ISO socket server, listening for [SeqPacket] connections.
+#[derive(Debug)]
+pub struct Listener {
+    socket: Socket,
+}
+
+impl Listener {
+    /// Creates a new Listener for connected isochronous streams (CIS),
+    /// which will be bound to the specified socket address.
+    ///
+    /// Incoming connections use the default quality of service parameters.
+    /// Use [Socket::set_unicast_qos] and [Socket::listen] to accept connections
+    /// with other parameters.
+    pub async fn bind(sa: SocketAddr) -> Result<Self> {
+        let socket = Socket::new()?;
+        socket.bind(sa)?;
+        socket.listen(1)
+    }
+
+    /// Creates a new Listener for synchronizing to a broadcast source as a broadcast sink.
+    ///
+    /// The [broadcast part](SocketAddr::broadcast) of the socket address specifies the
+    /// broadcast source and the BISes to synchronize to.
+    /// Each synchronized BIS is returned by [accept](Self::accept).
+    pub async fn bind_broadcast(sa: SocketAddr, qos: &BroadcastQos) -> Result<Self> {
+        if sa.broadcast.is_none() {
+            return Err(Error::new(ErrorKind::InvalidInput, "broadcast address required"));
+        }
+        let socket = Socket::new()?;
+        socket.bind(sa)?;
+        socket.set_broadcast_qos(qos)?;
+        socket.listen(1)
+    }
+
+    /// Accepts a new incoming connection from this listener.
+    pub async fn accept(&self) -> Result<(SeqPacket, SocketAddr)> {
+        let (socket, sa) = self.socket.accept_priv().await?;
+        Ok((SeqPacket { socket }, sa))
+    }
+
+    /// Polls to accept a new incoming connection to this listener.
+    pub fn poll_accept(&self, cx: &mut Context) -> Poll<Result<(SeqPacket, SocketAddr)>> {
+        let (socket, sa) = ready!(self.socket.poll_accept_priv(cx))?;
+        Poll::Ready(Ok((SeqPacket { socket }, sa)))
+    }
+
+    /// Accepts a new incoming connection whose setup has been deferred.
+    ///
+    /// This allows to inspect the connection, for example its peer address or the
+    /// [BASE](Socket::base) of a broadcast source, before deciding whether to
+    /// [accept](PendingSeqPacket::accept) or [reject](PendingSeqPacket::reject) it
+    /// or to [synchronize](PendingSeqPacket::sync_bis) to broadcast isochronous streams.
+    /// Deferred setup must have been enabled using [Socket::set_defer_setup]
+    /// before listening, otherwise an error is returned.
+    pub async fn accept_deferred(&self) -> Result<PendingSeqPacket> {
+        if !self.socket.is_defer_setup()? {
+            return Err(Error::new(ErrorKind::InvalidInput, "deferred setup is not enabled"));
+        }
+        let (socket, _sa) = self.socket.accept_priv().await?;
+        Ok(PendingSeqPacket { socket })
+    }
+
+    /// Constructs a new [Listener] from the given raw file descriptor.
+    ///
+    /// The file descriptor must have been set to non-blocking mode.
+    ///
+    /// This function *consumes ownership* of the specified file descriptor.
+    /// The returned object will take responsibility for closing it when the object goes out of scope.
+    ///
+    /// # Safety
+    /// If the passed file descriptor is invalid, undefined behavior may occur.
+    pub unsafe fn from_raw_fd(fd: RawFd) -> Result<Self> {
+        Ok(Self { socket: Socket::from_raw_fd(fd)? })
+    }
+}
+
+impl AsRef<Socket> for Listener {
+    fn as_ref(&self) -> &Socket {
+        &self.socket
+    }
+}
+
+impl AsRawFd for Listener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+impl FromRawFd for Listener {
+    /// Constructs a new instance of `Self` from the given raw file
+    /// descriptor.
+    ///
+    /// The file descriptor must have been set to non-blocking mode.
+    ///
+    /// # Panics
+    /// Panics when the conversion fails.
+    /// Use [Listener::from_raw_fd] for a non-panicking variant.
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        Self::from_raw_fd(fd).expect("from_raw_fd failed")
+    }
+}
+
+/// An incoming ISO connection whose setup has been deferred.
+///
+/// Obtained from [Listener::accept_deferred].
+/// A unicast connection is established by [accept](Self::accept).
+/// A connection synchronized to the periodic advertising train of a broadcast source
+/// is used to [synchronize](Self::sync_bis) to its broadcast isochronous streams.
+/// Dropping it rejects the connection.
+#[derive(Debug)]
+pub struct PendingSeqPacket {
+    socket: Socket,
+}
+
+impl PendingSeqPacket {
+    /// Gets the peer address of this connection.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.socket.peer_addr_priv()
+    }
+
+    /// Accepts the connection and waits until it is established.
+    pub async fn accept(self) -> Result<SeqPacket> {
+        self.socket.accept_deferred_priv().await?;
+        Ok(SeqPacket { socket: self.socket })
+    }
+
+    /// Rejects the connection.
+    pub fn reject(self) {
+        drop(self);
+    }
+
+    /// Synchronizes to broadcast isochronous streams (BIS) of a broadcast source.
+    ///
+    /// This connection must have been accepted from a broadcast sink listener and thus
+    /// be synchronized to the periodic advertising train of the broadcast source.
+    /// `bis` are the indices of the BISes to synchronize to, as described by the
+    /// [BASE](Socket::base).
+    ///
+    /// The returned listener accepts one connection per synchronized BIS.
+    pub fn sync_bis(self, bis: &[u8]) -> Result<Listener> {
+        let local = self.socket.local_addr()?;
+        let peer = self.peer_addr()?;
+        let mut broadcast = BroadcastAddr::new(peer.addr, peer.addr_type, peer.broadcast.unwrap_or_default().sid);
+        broadcast.set_bis(bis)?;
+        self.socket.bind(SocketAddr::new_broadcast(local.addr, local.addr_type, broadcast))?;
+        // Reading from the deferred connection starts the BIG synchronization.
+        sock::recv(self.socket.fd.get_ref(), &mut ReadBuf::new(&mut []), 0)?;
+        Ok(Listener { socket: self.socket })
+    }
+}
+
+impl AsRef<Socket> for PendingSeqPacket {
+    fn as_ref(&self) -> &Socket {
+        &self.socket
+    }
+}
+
+impl AsRawFd for PendingSeqPacket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+/// An ISO sequential packet socket carrying isochronous data.
+///
+/// Each packet is one service data unit (SDU) and should not exceed the SDU size
+/// specified in the quality of service parameters.
+/// [AsyncRead] and [AsyncWrite] are implemented for convenience;
+/// each read returns one packet and each write sends one packet.
+#[derive(Debug)]
+pub struct SeqPacket {
+    socket: Socket,
+}
+
+impl SeqPacket {
+    /// Establish a connected isochronous stream (CIS) with a peer at the specified socket address
+    /// using the default quality of service parameters.
+    ///
+    /// Uses any local Bluetooth adapter.
+    /// Use [Socket::set_unicast_qos] and [Socket::connect] to connect with other parameters.
+    pub async fn connect(addr: SocketAddr) -> Result<Self> {
+        let socket = Socket::new()?;
+        socket.bind(SocketAddr::any_le())?;
+        socket.connect(addr).await
+    }
+
+    /// Creates a broadcast isochronous group (BIG) and becomes its broadcast source.
+    ///
+    /// `base` is the broadcast audio source endpoint (BASE) describing the broadcast.
+    ///
+    /// Uses any local Bluetooth adapter.
+    /// Use [Socket::bind] with a [broadcast address](SocketAddr::broadcast) and [Socket::connect]
+    /// to specify the advertising set identifier.
+    pub async fn broadcast(qos: &BroadcastQos, base: &[u8]) -> Result<Self> {
+        let socket = Socket::new()?;
+        socket.bind(SocketAddr::any_le())?;
+        socket.set_broadcast_qos(qos)?;
+        socket.set_base(base)?;
+        socket.connect(SocketAddr::any_le()).await
+    }
+
+    /// Gets the peer address of this connection.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.socket.peer_addr_priv()
+    }
+
+    /// Sends a packet.
+    pub async fn send(&self, buf: &[u8]) -> Result<usize> {
+        self.socket.send_priv(buf).await
+    }
+
+    /// Attempts to send a packet.
+    pub fn poll_send(&self, cx: &mut Context, buf: &[u8]) -> Poll<Result<usize>> {
+        self.socket.poll_send_priv(cx, buf)
+    }
+
+    /// Receives a packet.
+    pub async fn recv(&self, buf: &mut [u8]) -> Result<usize> {
+        self.socket.recv_priv(buf).await
+    }
+
+    /// Attempts to receive a packet.
+    pub fn poll_recv(&self, cx: &mut Context, buf: &mut ReadBuf) -> Poll<Result<()>> {
+        self.socket.poll_recv_priv(cx, buf)
+    }
+
+    /// Receives a packet together with its timestamps.
+    ///
+    /// The timestamps are only available if [timestamping](Socket::set_timestamping)
+    /// is enabled.
+    pub async fn recv_with_timestamp(&self, buf: &mut [u8]) -> Result<(usize, Option<Timestamp>)> {
+        let (n, cmsgs) = self.socket.recvmsg_priv(buf).await?;
+        Ok((n, Timestamp::from_cmsgs(&cmsgs)))
+    }
+
+    /// Attempts to receive a packet together with its timestamps.
+    ///
+    /// The timestamps are only available if [timestamping](Socket::set_timestamping)
+    /// is enabled.
+    pub fn poll_recv_with_timestamp(
+        &self, cx: &mut Context, buf: &mut ReadBuf,
+    ) -> Poll<Result<Option<Timestamp>>> {
+        let cmsgs = ready!(self.socket.poll_recvmsg_priv(cx, buf))?;
+        Poll::Ready(Ok(Timestamp::from_cmsgs(&cmsgs)))
+    }
+
+    /// Shuts down the read, write, or both halves of this connection.
+    pub fn shutdown(&self, how: Shutdown) -> Result<()> {
+        self.socket.shutdown_priv(how)
+    }
+
+    /// Constructs a new [SeqPacket] from the given raw file descriptor.
+    ///
+    /// The file descriptor must have been set to non-blocking mode.
+    ///
+    /// This function *consumes ownership* of the specified file descriptor.
+    /// The returned object will take responsibility for closing it when the object goes out of scope.
+    ///
+    /// # Safety
+    /// If the passed file descriptor is invalid, undefined behavior may occur.
+    pub unsafe fn from_raw_fd(fd: RawFd) -> Result<Self> {
+        Ok(Self { socket: Socket::from_raw_fd(fd)? })
+    }
+}
+
+impl AsRef<Socket> for SeqPacket {
+    fn as_ref(&self) -> &Socket {
+        &self.socket
+    }
+}
+
+impl AsRawFd for SeqPacket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+impl FromRawFd for SeqPacket {
+    /// Constructs a new instance of `Self` from the given raw file
+    /// descriptor.
+    ///
+    /// The file descriptor must have been set to non-blocking mode.
+    ///
+    /// # Panics
+    /// Panics when the conversion fails.
+    /// Use [SeqPacket::from_raw_fd] for a non-panicking variant.
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        Self::from_raw_fd(fd).expect("from_raw_fd failed")
+    }
+}
+
+impl AsyncRead for SeqPacket {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context, buf: &mut ReadBuf) -> Poll<Result<()>> {
+        self.socket.poll_recv_priv(cx, buf)
+    }
+}
+
+impl AsyncWrite for SeqPacket {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<Result<usize>> {
+        self.socket.poll_send_priv(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        self.socket.poll_flush_priv(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        self.socket.poll_shutdown_priv(cx, Shutdown::Write)
+    }
+}
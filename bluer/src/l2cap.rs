@@ -6,27 +6,37 @@
 //!
 //! L2CAP sockets work with both Bluetooth classic (BR/EDR) and Bluetooth Low Energy (LE).
 //!
+//! For classic Bluetooth, a profile with a PSM can be registered with the Bluetooth daemon
+//! using the `profile` module.
+//! This will register an SDP record and hand over incoming and outgoing L2CAP connections.
+//!
 
 use crate::{
     sock::{self, OwnedFd},
     sys::{
-        bt_power, bt_security, sockaddr_l2, BTPROTO_L2CAP, BT_MODE, BT_PHY, BT_POWER, BT_POWER_FORCE_ACTIVE_OFF,
+        bt_power, bt_security, sockaddr_l2, BTPROTO_L2CAP, BT_CHANNEL_POLICY, BT_CHANNEL_POLICY_AMP_PREFERRED,
+        BT_CHANNEL_POLICY_BREDR_ONLY, BT_CHANNEL_POLICY_BREDR_PREFERRED, BT_DEFER_SETUP, BT_FLUSHABLE,
+        BT_FLUSHABLE_OFF, BT_FLUSHABLE_ON, BT_MODE, BT_MODE_BASIC, BT_MODE_ERTM, BT_MODE_EXT_FLOWCTL,
+        BT_MODE_LE_FLOWCTL, BT_MODE_STREAMING, BT_PHY, BT_POWER, BT_POWER_FORCE_ACTIVE_OFF,
         BT_POWER_FORCE_ACTIVE_ON, BT_RCVMTU, BT_SECURITY, BT_SECURITY_FIPS, BT_SECURITY_HIGH, BT_SECURITY_LOW,
-        BT_SECURITY_MEDIUM, BT_SECURITY_SDP, BT_SNDMTU, L2CAP_CONNINFO, L2CAP_LM, L2CAP_OPTIONS, SOL_L2CAP,
+        BT_SECURITY_MEDIUM, BT_SECURITY_SDP, BT_SNDMTU, L2CAP_CONNINFO, L2CAP_ECRED_MAX_CID, L2CAP_LM,
+        L2CAP_LM_AUTH, L2CAP_LM_ENCRYPT, L2CAP_LM_FIPS, L2CAP_LM_MASTER, L2CAP_LM_RELIABLE, L2CAP_LM_SECURE,
+        L2CAP_LM_TRUSTED, L2CAP_MODE_BASIC, L2CAP_MODE_ERTM, L2CAP_MODE_EXT_FLOWCTL, L2CAP_MODE_LE_FLOWCTL,
+        L2CAP_MODE_STREAMING, L2CAP_OPTIONS, SOL_L2CAP,
     },
-    Address, AddressType,
+    Address, AddressType, Phy,
 };
-use futures::ready;
+use futures::{future, ready};
 use libc::{
-    AF_BLUETOOTH, EAGAIN, EINPROGRESS, MSG_PEEK, SHUT_RD, SHUT_RDWR, SHUT_WR, SOCK_DGRAM, SOCK_SEQPACKET,
-    SOCK_STREAM, SOL_BLUETOOTH, SOL_SOCKET, SO_ERROR, SO_RCVBUF, TIOCINQ, TIOCOUTQ,
+    AF_BLUETOOTH, EAGAIN, EINPROGRESS, MSG_PEEK, SHUT_RD, SHUT_RDWR, SHUT_WR, SOCK_DGRAM, SOCK_RAW,
+    SOCK_SEQPACKET, SOCK_STREAM, SOL_BLUETOOTH, SOL_SOCKET, SO_ERROR, SO_RCVBUF, SO_SNDBUF, TIOCINQ, TIOCOUTQ,
 };
 use num_derive::{FromPrimitive, ToPrimitive};
 use num_traits::FromPrimitive;
 use std::{
     convert::{TryFrom, TryInto},
     fmt,
-    io::{Error, ErrorKind, Result},
+    io::{Error, ErrorKind, IoSlice, IoSliceMut, Result},
     marker::PhantomData,
     mem::ManuallyDrop,
     net::Shutdown,
@@ -40,12 +50,20 @@ use std::{
         Arc,
     },
     task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{unix::AsyncFd, AsyncRead, AsyncWrite, ReadBuf},
+    time::{sleep, timeout_at},
 };
-use tokio::io::{unix::AsyncFd, AsyncRead, AsyncWrite, ReadBuf};
 
-pub use crate::sys::{l2cap_conninfo as ConnInfo, l2cap_options as Opts};
+pub use crate::{
+    sock::Timestamp,
+    sys::{l2cap_conninfo as ConnInfo, l2cap_options as Opts},
+};
 
 /// Possible bit values for the [link mode socket option](Socket::link_mode).
+#[deprecated(since = "0.16.0", note = "use LinkMode")]
 pub mod link_mode {
     pub use crate::sys::{
         L2CAP_LM_AUTH as AUTH, L2CAP_LM_ENCRYPT as ENCRYPT, L2CAP_LM_FIPS as FIPS, L2CAP_LM_MASTER as MASTER,
@@ -54,6 +72,7 @@ pub mod link_mode {
 }
 
 /// Possible bit values for the [PHY socket option](Socket::phy).
+#[deprecated(since = "0.16.0", note = "use the Phy flags returned by Socket::phy")]
 pub mod phy {
     pub use crate::sys::{
         BR1M1SLOT, BR1M3SLOT, BR1M5SLOT, EDR2M1SLOT, EDR2M3SLOT, EDR2M5SLOT, EDR3M1SLOT, EDR3M3SLOT, EDR3M5SLOT,
@@ -61,6 +80,31 @@ pub mod phy {
     };
 }
 
+bitflags::bitflags! {
+    /// L2CAP link mode.
+    ///
+    /// This is the legacy interface for specifying the security requirements of a socket.
+    /// Setting the [security level](Socket::set_security) is preferred.
+    #[derive(Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct LinkMode: u32 {
+        /// Request the central (master) role of the link.
+        const MASTER = L2CAP_LM_MASTER;
+        /// Require authentication, i.e. security level low.
+        const AUTH = L2CAP_LM_AUTH;
+        /// Require encryption, i.e. security level medium.
+        const ENCRYPT = L2CAP_LM_ENCRYPT;
+        /// Trusted link.
+        const TRUSTED = L2CAP_LM_TRUSTED;
+        /// Reliable link.
+        const RELIABLE = L2CAP_LM_RELIABLE;
+        /// Require secure authentication, i.e. security level high.
+        const SECURE = L2CAP_LM_SECURE;
+        /// FIPS approved algorithms are required, i.e. security level FIPS.
+        const FIPS = L2CAP_LM_FIPS;
+    }
+}
+
 /// First unprivileged protocol service multiplexor (PSM) for
 /// Bluetooth classic (BR/EDR).
 ///
@@ -176,14 +220,101 @@ pub enum SecurityLevel {
     Fips = BT_SECURITY_FIPS as _,
 }
 
+impl Default for SecurityLevel {
+    fn default() -> Self {
+        Self::Low
+    }
+}
+
+/// L2CAP channel policy for classic Bluetooth (BR/EDR) connections.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, FromPrimitive, ToPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChannelPolicy {
+    /// Only use BR/EDR.
+    BrEdrOnly = BT_CHANNEL_POLICY_BREDR_ONLY as _,
+    /// Prefer BR/EDR.
+    BrEdrPreferred = BT_CHANNEL_POLICY_BREDR_PREFERRED as _,
+    /// Prefer an alternate MAC/PHY (AMP) controller.
+    AmpPreferred = BT_CHANNEL_POLICY_AMP_PREFERRED as _,
+}
+
+impl Default for ChannelPolicy {
+    fn default() -> Self {
+        Self::BrEdrOnly
+    }
+}
+
 /// L2CAP socket security.
-#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash)]
+///
+/// Besides the security level, this specifies further options
+/// that affect how connections are established.
+/// Options set to `None` are left unchanged by [Socket::set_security].
+///
+/// The maximum transmission unit for sending is chosen by the remote device
+/// and thus can only be queried using [Socket::send_mtu].
+///
+/// Use [Security::new] and the builder methods to specify the options,
+/// for example `Security::new(SecurityLevel::Medium).defer_setup(true)`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Security {
     /// Level.
     pub level: SecurityLevel,
     /// Key size.
+    ///
+    /// This is ignored when setting the security.
     pub key_size: u8,
+    /// Whether connection setup is deferred until the connection is accepted.
+    ///
+    /// This corresponds to the `BT_DEFER_SETUP` socket option.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub defer_setup: Option<bool>,
+    /// Whether sent packets can be flushed automatically when they cannot be delivered.
+    ///
+    /// This corresponds to the `BT_FLUSHABLE` socket option.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub flushable: Option<bool>,
+    /// Channel policy for classic Bluetooth (BR/EDR) connections.
+    ///
+    /// This corresponds to the `BT_CHANNEL_POLICY` socket option.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub channel_policy: Option<ChannelPolicy>,
+    /// Maximum transmission unit (MTU) for receiving.
+    ///
+    /// This corresponds to the `BT_RCVMTU` socket option or [Opts::imtu].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub recv_mtu: Option<u16>,
+}
+
+impl Security {
+    /// Creates socket security with the specified level, leaving all other options unchanged.
+    pub fn new(level: SecurityLevel) -> Self {
+        Self { level, ..Default::default() }
+    }
+
+    /// Sets whether connection setup is deferred until the connection is accepted.
+    pub fn defer_setup(mut self, defer_setup: bool) -> Self {
+        self.defer_setup = Some(defer_setup);
+        self
+    }
+
+    /// Sets whether sent packets can be flushed automatically.
+    pub fn flushable(mut self, flushable: bool) -> Self {
+        self.flushable = Some(flushable);
+        self
+    }
+
+    /// Sets the channel policy for classic Bluetooth (BR/EDR) connections.
+    pub fn channel_policy(mut self, channel_policy: ChannelPolicy) -> Self {
+        self.channel_policy = Some(channel_policy);
+        self
+    }
+
+    /// Sets the maximum transmission unit (MTU) for receiving.
+    pub fn recv_mtu(mut self, recv_mtu: u16) -> Self {
+        self.recv_mtu = Some(recv_mtu);
+        self
+    }
 }
 
 impl From<Security> for bt_security {
@@ -199,18 +330,92 @@ impl TryFrom<bt_security> for Security {
             level: SecurityLevel::from_u8(value.level)
                 .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid bt_security::level"))?,
             key_size: value.key_size,
+            ..Default::default()
         })
     }
 }
 
 /// L2CAP socket flow control mode.
+///
+/// The basic, enhanced retransmission and streaming modes are only supported by
+/// classic Bluetooth (BR/EDR) channels, while the LE and extended flow control modes
+/// are only supported by Bluetooth LE channels.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, FromPrimitive, ToPrimitive)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FlowControl {
-    /// LE flow control.
-    Le = 0x03,
-    /// Extended flow control.
-    Extended = 0x04,
+    /// Basic mode without flow control and retransmission.
+    Basic = BT_MODE_BASIC as _,
+    /// Enhanced retransmission mode (ERTM).
+    EnhancedRetransmission = BT_MODE_ERTM as _,
+    /// Streaming mode without retransmission.
+    Streaming = BT_MODE_STREAMING as _,
+    /// LE credit based flow control.
+    Le = BT_MODE_LE_FLOWCTL as _,
+    /// Enhanced credit based flow control.
+    Extended = BT_MODE_EXT_FLOWCTL as _,
+}
+
+impl FlowControl {
+    /// Whether the flow control mode is supported for channels using the specified address type.
+    pub fn is_supported(&self, addr_type: AddressType) -> bool {
+        match addr_type {
+            AddressType::BrEdr => matches!(self, Self::Basic | Self::EnhancedRetransmission | Self::Streaming),
+            AddressType::LePublic | AddressType::LeRandom => matches!(self, Self::Le | Self::Extended),
+        }
+    }
+
+    fn from_l2cap_mode(mode: u8) -> Option<Self> {
+        match mode {
+            L2CAP_MODE_BASIC => Some(Self::Basic),
+            L2CAP_MODE_ERTM => Some(Self::EnhancedRetransmission),
+            L2CAP_MODE_STREAMING => Some(Self::Streaming),
+            L2CAP_MODE_LE_FLOWCTL => Some(Self::Le),
+            L2CAP_MODE_EXT_FLOWCTL => Some(Self::Extended),
+            _ => None,
+        }
+    }
+
+    fn to_l2cap_mode(self) -> u8 {
+        match self {
+            Self::Basic => L2CAP_MODE_BASIC,
+            Self::EnhancedRetransmission => L2CAP_MODE_ERTM,
+            Self::Streaming => L2CAP_MODE_STREAMING,
+            Self::Le => L2CAP_MODE_LE_FLOWCTL,
+            Self::Extended => L2CAP_MODE_EXT_FLOWCTL,
+        }
+    }
+}
+
+/// Statistics snapshot of an L2CAP channel.
+///
+/// Obtain it using [Socket::stats] or periodically using [Socket::stats_stream].
+///
+/// The send queue contains data that has been written to the socket but has not
+/// yet been handed to the controller, for example because the remote device has not
+/// granted enough credits when using [LE](FlowControl::Le) or
+/// [extended](FlowControl::Extended) flow control.
+/// A send queue that is persistently close to the send buffer size indicates backpressure.
+///
+/// The kernel does not expose the number of available credits and the
+/// maximum PDU payload size (MPS) of a channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct ChannelStats {
+    /// Maximum transmission unit (MTU) for sending, if available.
+    pub send_mtu: Option<u16>,
+    /// Maximum transmission unit (MTU) for receiving, if available.
+    pub recv_mtu: Option<u16>,
+    /// Flow control mode, if available.
+    pub flow_control: Option<FlowControl>,
+    /// Size of the socket send buffer in bytes.
+    pub send_buffer: u32,
+    /// Number of bytes in the send queue.
+    pub send_queue: u32,
+    /// Size of the socket receive buffer in bytes.
+    pub recv_buffer: u32,
+    /// Length of the next received packet waiting to be read in bytes.
+    pub recv_queue: u32,
 }
 
 /// An L2CAP socket that has not yet been converted to a [StreamListener], [Stream], [SeqPacketListener],
@@ -239,15 +444,49 @@ impl<Type> Socket<Type> {
         sock::getsockname(self.fd.get_ref())
     }
 
+    /// Address type of the peer, if connected, or of the local address, if bound.
+    ///
+    /// An unbound socket reports the classic Bluetooth (BR/EDR) any address without a PSM and
+    /// thus has no known address type.
+    fn bound_addr_type(&self) -> Result<Option<AddressType>> {
+        if let Ok(peer) = self.peer_addr_priv() {
+            return Ok(Some(peer.addr_type));
+        }
+        let local = self.local_addr()?;
+        Ok(if local.addr == Address::any() && local.addr_type == AddressType::BrEdr && local.psm == 0 {
+            None
+        } else {
+            Some(local.addr_type)
+        })
+    }
+
     /// Get the peer address of this socket.
     fn peer_addr_priv(&self) -> Result<SocketAddr> {
         sock::getpeername(self.fd.get_ref())
     }
 
-    /// Get socket security.
+    /// Get socket security including all further options.
     ///
-    /// This corresponds to the `BT_SECURITY` socket option.
+    /// This corresponds to the `BT_SECURITY` socket option and the socket options
+    /// of the further fields of [Security].
+    /// The other options of [Security] are `None` if they are not available for this socket.
+    /// Use [Self::bt_security] to obtain only the security level and key size.
     pub fn security(&self) -> Result<Security> {
+        Ok(Security {
+            defer_setup: self.is_defer_setup().ok(),
+            flushable: self.is_flushable().ok(),
+            channel_policy: self.channel_policy().ok(),
+            recv_mtu: self.recv_mtu().ok(),
+            ..self.bt_security()?
+        })
+    }
+
+    /// Get socket security level and key size as reported by the kernel.
+    ///
+    /// This corresponds to the `BT_SECURITY` socket option.
+    /// All other options of [Security] are `None` and thus passing the result
+    /// to [Self::set_security] leaves them unchanged.
+    pub fn bt_security(&self) -> Result<Security> {
         let bts: bt_security = sock::getsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_SECURITY)?;
         Security::try_from(bts)
     }
@@ -255,9 +494,80 @@ impl<Type> Socket<Type> {
     /// Set socket security.
     ///
     /// This corresponds to the `BT_SECURITY` socket option.
+    /// The other options of [Security] are set when specified.
+    /// Setting the receive MTU requires the socket to be bound.
     pub fn set_security(&self, security: Security) -> Result<()> {
         let bts: bt_security = security.into();
-        sock::setsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_SECURITY, &bts)
+        sock::setsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_SECURITY, &bts)?;
+        if let Some(defer_setup) = security.defer_setup {
+            self.set_defer_setup(defer_setup)?;
+        }
+        if let Some(flushable) = security.flushable {
+            self.set_flushable(flushable)?;
+        }
+        if let Some(channel_policy) = security.channel_policy {
+            self.set_channel_policy(channel_policy)?;
+        }
+        if let Some(recv_mtu) = security.recv_mtu {
+            self.set_recv_mtu(recv_mtu)?;
+        }
+        Ok(())
+    }
+
+    /// Gets whether connection setup is deferred until the connection is accepted.
+    ///
+    /// This corresponds to the `BT_DEFER_SETUP` socket option.
+    pub fn is_defer_setup(&self) -> Result<bool> {
+        let value: u32 = sock::getsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_DEFER_SETUP)?;
+        Ok(value != 0)
+    }
+
+    /// Sets whether connection setup is deferred until the connection is accepted.
+    ///
+    /// Must be set on the socket before listening.
+    /// Incoming stream connections are then obtained using [StreamListener::accept_deferred].
+    /// Other accepted connections are established by the first read from them.
+    ///
+    /// This corresponds to the `BT_DEFER_SETUP` socket option.
+    pub fn set_defer_setup(&self, defer_setup: bool) -> Result<()> {
+        let value: u32 = defer_setup.into();
+        sock::setsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_DEFER_SETUP, &value)
+    }
+
+    /// Gets whether sent packets can be flushed automatically when they cannot be delivered.
+    ///
+    /// This corresponds to the `BT_FLUSHABLE` socket option.
+    pub fn is_flushable(&self) -> Result<bool> {
+        let value: u32 = sock::getsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_FLUSHABLE)?;
+        Ok(value != BT_FLUSHABLE_OFF)
+    }
+
+    /// Sets whether sent packets can be flushed automatically when they cannot be delivered.
+    ///
+    /// This corresponds to the `BT_FLUSHABLE` socket option.
+    pub fn set_flushable(&self, flushable: bool) -> Result<()> {
+        let value = if flushable { BT_FLUSHABLE_ON } else { BT_FLUSHABLE_OFF };
+        sock::setsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_FLUSHABLE, &value)
+    }
+
+    /// Gets the channel policy for classic Bluetooth (BR/EDR) connections.
+    ///
+    /// This corresponds to the `BT_CHANNEL_POLICY` socket option.
+    pub fn channel_policy(&self) -> Result<ChannelPolicy> {
+        let value: u32 = sock::getsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_CHANNEL_POLICY)?;
+        ChannelPolicy::from_u32(value)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid channel policy"))
+    }
+
+    /// Sets the channel policy for classic Bluetooth (BR/EDR) connections.
+    ///
+    /// Kernels without support for alternate MAC/PHY (AMP) controllers
+    /// may reject this option.
+    ///
+    /// This corresponds to the `BT_CHANNEL_POLICY` socket option.
+    pub fn set_channel_policy(&self, channel_policy: ChannelPolicy) -> Result<()> {
+        let value = channel_policy as u32;
+        sock::setsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_CHANNEL_POLICY, &value)
     }
 
     /// Get forced power state.
@@ -319,19 +629,48 @@ impl<Type> Socket<Type> {
 
     /// Get flow control mode.
     ///
-    /// This corresponds to the `BT_MODE` socket option.
+    /// This corresponds to the `BT_MODE` socket option or [Opts::mode].
     pub fn flow_control(&self) -> Result<FlowControl> {
-        let value: u8 = sock::getsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_MODE)?;
-        FlowControl::from_u8(value)
-            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid flow control mode"))
+        let flow_control = match self.local_addr()?.addr_type {
+            AddressType::BrEdr => FlowControl::from_l2cap_mode(self.l2cap_opts()?.mode),
+            _ => FlowControl::from_u8(sock::getsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_MODE)?),
+        };
+        flow_control.ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid flow control mode"))
     }
 
     /// Set flow control mode.
     ///
-    /// This corresponds to the `BT_MODE` socket option.
+    /// An error is returned if the socket is bound or connected and the flow control mode
+    /// is not [supported](FlowControl::is_supported) for its address type.
+    /// On an unbound socket the mode is passed to the kernel, which rejects it if it
+    /// is not applicable.
+    ///
+    /// This corresponds to the `BT_MODE` socket option or [Opts::mode] for
+    /// the classic Bluetooth (BR/EDR) modes.
     pub fn set_flow_control(&self, flow_control: FlowControl) -> Result<()> {
-        let value = flow_control as u8;
-        sock::setsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_MODE, &value)
+        if let Some(addr_type) = self.bound_addr_type()? {
+            if !flow_control.is_supported(addr_type) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    match addr_type {
+                        AddressType::BrEdr => "flow control mode not supported by classic Bluetooth",
+                        _ => "flow control mode not supported by Bluetooth LE",
+                    },
+                ));
+            }
+        }
+
+        match flow_control {
+            FlowControl::Basic | FlowControl::EnhancedRetransmission | FlowControl::Streaming => {
+                let mut opts = self.l2cap_opts()?;
+                opts.mode = flow_control.to_l2cap_mode();
+                self.set_l2cap_opts(&opts)
+            }
+            FlowControl::Le | FlowControl::Extended => {
+                let value = flow_control as u8;
+                sock::setsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_MODE, &value)
+            }
+        }
     }
 
     /// Gets the maximum socket receive buffer in bytes.
@@ -348,6 +687,37 @@ impl<Type> Socket<Type> {
         sock::setsockopt(self.fd.get_ref(), SOL_SOCKET, SO_RCVBUF, &recv_buffer)
     }
 
+    /// Gets the maximum socket send buffer in bytes.
+    ///
+    /// This corresponds to the `SO_SNDBUF` socket option.
+    pub fn send_buffer(&self) -> Result<i32> {
+        sock::getsockopt(self.fd.get_ref(), SOL_SOCKET, SO_SNDBUF)
+    }
+
+    /// Sets the maximum socket send buffer in bytes.
+    ///
+    /// This corresponds to the `SO_SNDBUF` socket option.
+    pub fn set_send_buffer(&self, send_buffer: i32) -> Result<()> {
+        sock::setsockopt(self.fd.get_ref(), SOL_SOCKET, SO_SNDBUF, &send_buffer)
+    }
+
+    /// Gets whether receive timestamps are reported.
+    ///
+    /// This corresponds to the `SO_TIMESTAMPING` socket option.
+    pub fn is_timestamping(&self) -> Result<bool> {
+        sock::is_timestamping(self.fd.get_ref())
+    }
+
+    /// Sets whether software and hardware receive timestamps are reported.
+    ///
+    /// When enabled, the timestamps are returned by [SeqPacket::recv_with_timestamp]
+    /// and the batched receive functions.
+    ///
+    /// This corresponds to the `SO_TIMESTAMPING` socket option.
+    pub fn set_timestamping(&self, timestamping: bool) -> Result<()> {
+        sock::set_timestamping(self.fd.get_ref(), timestamping)
+    }
+
     /// Gets the raw L2CAP socket options.
     ///
     /// This corresponds to the `L2CAP_OPTIONS` socket option.
@@ -366,20 +736,22 @@ impl<Type> Socket<Type> {
         sock::setsockopt(self.fd.get_ref(), SOL_L2CAP, L2CAP_OPTIONS, l2cap_opts)
     }
 
-    /// Gets the raw L2CAP link mode bit field.
+    /// Gets the L2CAP link mode.
     ///
-    /// Possible values are defined in the [link_mode] module.
     /// This corresponds to the `L2CAP_LM` socket option.
-    pub fn link_mode(&self) -> Result<i32> {
-        sock::getsockopt(self.fd.get_ref(), SOL_L2CAP, L2CAP_LM)
+    pub fn link_mode(&self) -> Result<LinkMode> {
+        let value: u32 = sock::getsockopt(self.fd.get_ref(), SOL_L2CAP, L2CAP_LM)?;
+        Ok(LinkMode::from_bits_truncate(value))
     }
 
-    /// Sets the raw L2CAP link mode bit field.
+    /// Sets the L2CAP link mode.
+    ///
+    /// This also sets the security level corresponding to the highest required security.
+    /// [LinkMode::FIPS] cannot be set; use [SecurityLevel::Fips] instead.
     ///
-    /// Possible values are defined in the [link_mode] module.
     /// This corresponds to the `L2CAP_LM` socket option.
-    pub fn set_link_mode(&self, link_mode: i32) -> Result<()> {
-        sock::setsockopt(self.fd.get_ref(), SOL_L2CAP, L2CAP_LM, &link_mode)
+    pub fn set_link_mode(&self, link_mode: LinkMode) -> Result<()> {
+        sock::setsockopt(self.fd.get_ref(), SOL_L2CAP, L2CAP_LM, &link_mode.bits())
     }
 
     /// Gets the L2CAP socket connection information.
@@ -389,12 +761,34 @@ impl<Type> Socket<Type> {
         sock::getsockopt(self.fd.get_ref(), SOL_L2CAP, L2CAP_CONNINFO)
     }
 
-    /// Gets the supported PHYs bit field.
+    /// Gets the PHYs supported by the connection.
     ///
-    /// Possible values are defined in the [phy] module.
     /// This corresponds to the `BT_PHY` socket option.
-    pub fn phy(&self) -> Result<i32> {
-        sock::getsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_PHY)
+    /// Use [set_phy](Self::set_phy) to request other PHYs for an LE connection.
+    pub fn phy(&self) -> Result<Phy> {
+        let phy: u32 = sock::getsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_PHY)?;
+        Ok(Phy::from_bits_truncate(phy))
+    }
+
+    /// Requests the PHYs of the LE connection.
+    ///
+    /// Only the LE PHYs of `phy` are used.
+    /// The controller negotiates the PHYs with the remote device and
+    /// may choose others if the preferred PHYs are not supported;
+    /// use [phy](Self::phy) to check the result.
+    ///
+    /// The kernel does not allow setting the PHYs through the socket.
+    /// Instead, an LE Set PHY command is sent for the `hci_handle` of the
+    /// [connection information](Self::conn_info) using an HCI socket bound to the
+    /// controller that has this connection to the peer.
+    /// This requires the `CAP_NET_RAW` capability.
+    #[cfg(feature = "hci")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "hci")))]
+    pub async fn set_phy(&self, phy: Phy) -> Result<()> {
+        let handle = self.conn_info()?.hci_handle;
+        let dev = crate::hci::find_conn_dev(handle, self.peer_addr_priv()?.addr)?;
+        let socket = crate::hci::Socket::open(crate::hci::SocketAddr::raw(dev))?;
+        socket.le_set_phy(handle, phy).await
     }
 
     /// Get the number of bytes in the input buffer.
@@ -405,7 +799,7 @@ impl<Type> Socket<Type> {
         Ok(value as _)
     }
 
-    /// Get the number of bytes in the output buffer.
+    /// Get the number of free bytes in the output buffer.
     ///
     /// This corresponds to the `TIOCOUTQ` IOCTL.
     pub fn output_buffer(&self) -> Result<u32> {
@@ -413,6 +807,37 @@ impl<Type> Socket<Type> {
         Ok(value as _)
     }
 
+    /// Gets a statistics snapshot of the channel.
+    ///
+    /// The send queue length is derived from the `TIOCOUTQ` IOCTL, which reports
+    /// the free space in the send buffer.
+    /// The receive queue length corresponds to the `TIOCINQ` IOCTL.
+    pub fn stats(&self) -> Result<ChannelStats> {
+        let send_buffer = self.send_buffer()?.max(0) as u32;
+        let recv_buffer = self.recv_buffer()?.max(0) as u32;
+        Ok(ChannelStats {
+            send_mtu: self.send_mtu().ok(),
+            recv_mtu: self.recv_mtu().ok(),
+            flow_control: self.flow_control().ok(),
+            send_buffer,
+            send_queue: send_buffer.saturating_sub(self.output_buffer()?),
+            recv_buffer,
+            recv_queue: self.input_buffer()?,
+        })
+    }
+
+    /// Periodically obtains statistics snapshots of the channel.
+    ///
+    /// The first snapshot is taken immediately and then every `period`.
+    pub fn stats_stream(&self, period: Duration) -> impl futures::Stream<Item = Result<ChannelStats>> + '_ {
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        futures::stream::unfold(interval, move |mut interval| async move {
+            interval.tick().await;
+            Some((self.stats(), interval))
+        })
+    }
+
     /// Constructs a new [Socket] from the given raw file descriptor.
     ///
     /// The file descriptor must have been set to non-blocking mode.
@@ -430,7 +855,7 @@ impl<Type> Socket<Type> {
         Ok(Self { fd: AsyncFd::new(fd)?, _type: PhantomData })
     }
 
-    sock_priv!();
+    sock_priv!(accept_deferred, recvmsg, send_vectored, recv_vectored, send_batch, recv_batch, peek, poll_peek);
 }
 
 impl<Type> AsRawFd for Socket<Type> {
@@ -513,6 +938,45 @@ impl Socket<SeqPacket> {
         self.connect_priv(sa).await?;
         Ok(SeqPacket { socket: self })
     }
+
+    /// Concurrently establishes sequential packet connections of all specified sockets
+    /// with a peer at the specified socket address using [extended flow control](FlowControl::Extended).
+    ///
+    /// The sockets must be bound to a Bluetooth LE address and may be configured
+    /// beforehand, for example to set the security level or receive MTU.
+    /// The connections are requested in groups of up to five channels using a single
+    /// enhanced credit based connection request each.
+    /// For this purpose, deferred setup is enabled for all but the last socket of each group.
+    ///
+    /// The connections are returned in the order of the specified sockets.
+    /// If any connection fails, all connections are closed and the error is returned.
+    pub async fn connect_group(sockets: Vec<Self>, sa: SocketAddr) -> Result<Vec<SeqPacket>> {
+        if !FlowControl::Extended.is_supported(sa.addr_type) {
+            return Err(Error::new(ErrorKind::InvalidInput, "extended flow control requires Bluetooth LE"));
+        }
+
+        let mut groups = Vec::new();
+        let mut sockets = sockets.into_iter();
+        loop {
+            let group: Vec<_> = sockets.by_ref().take(L2CAP_ECRED_MAX_CID).collect();
+            if group.is_empty() {
+                break;
+            }
+
+            let last = group.len() - 1;
+            for (i, socket) in group.iter().enumerate() {
+                socket.set_flow_control(FlowControl::Extended)?;
+                socket.set_defer_setup(i != last)?;
+            }
+
+            // Deferred connections are sent by the kernel together with the
+            // non-deferred connection, thus connect must be called in order.
+            groups.push(future::try_join_all(group.into_iter().map(|socket| socket.connect(sa))));
+        }
+
+        let groups = future::try_join_all(groups).await?;
+        Ok(groups.into_iter().flatten().collect())
+    }
 }
 
 impl Socket<Datagram> {
@@ -527,6 +991,23 @@ impl Socket<Datagram> {
     }
 }
 
+impl Socket<Pinger> {
+    /// Creates a new raw socket for sending echo requests over the signalling channel.
+    ///
+    /// This requires the `CAP_NET_RAW` capability.
+    pub fn new_pinger() -> Result<Socket<Pinger>> {
+        Ok(Self { fd: AsyncFd::new(sock::socket(AF_BLUETOOTH, SOCK_RAW, BTPROTO_L2CAP)?)?, _type: PhantomData })
+    }
+
+    /// Establishes the signalling channel to the peer with the specified address.
+    ///
+    /// The PSM of the socket address is ignored.
+    pub async fn connect(self, sa: SocketAddr) -> Result<Pinger> {
+        self.connect_priv(SocketAddr { psm: 0, cid: 0, ..sa }).await?;
+        Ok(Pinger { socket: self, ident: 0, buf: Vec::new() })
+    }
+}
+
 /// An L2CAP socket server, listening for [Stream] connections.
 #[derive(Debug)]
 pub struct StreamListener {
@@ -556,6 +1037,20 @@ impl StreamListener {
         Poll::Ready(Ok((Stream::from_socket(socket)?, sa)))
     }
 
+    /// Accepts a new incoming connection whose setup has been deferred.
+    ///
+    /// This allows to inspect the connection, for example its peer address and security,
+    /// before deciding whether to [accept](PendingStream::accept) or [reject](PendingStream::reject) it.
+    /// Deferred setup must have been enabled using [Socket::set_defer_setup]
+    /// before listening, otherwise an error is returned.
+    pub async fn accept_deferred(&self) -> Result<PendingStream> {
+        if !self.socket.is_defer_setup()? {
+            return Err(Error::new(ErrorKind::InvalidInput, "deferred setup is not enabled"));
+        }
+        let (socket, _sa) = self.socket.accept_priv().await?;
+        Ok(PendingStream { socket })
+    }
+
     /// Constructs a new [StreamListener] from the given raw file descriptor.
     ///
     /// The file descriptor must have been set to non-blocking mode.
@@ -596,6 +1091,46 @@ impl FromRawFd for StreamListener {
     }
 }
 
+/// An incoming L2CAP stream connection whose setup has been deferred.
+///
+/// Obtained from [StreamListener::accept_deferred].
+/// The connection is established by [accept](Self::accept) and
+/// dropping it rejects the connection.
+#[derive(Debug)]
+pub struct PendingStream {
+    socket: Socket<Stream>,
+}
+
+impl PendingStream {
+    /// Gets the peer address of this connection.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.socket.peer_addr_priv()
+    }
+
+    /// Accepts the connection and waits until it is established.
+    pub async fn accept(self) -> Result<Stream> {
+        self.socket.accept_deferred_priv().await?;
+        Stream::from_socket(self.socket)
+    }
+
+    /// Rejects the connection.
+    pub fn reject(self) {
+        drop(self);
+    }
+}
+
+impl AsRef<Socket<Stream>> for PendingStream {
+    fn as_ref(&self) -> &Socket<Stream> {
+        &self.socket
+    }
+}
+
+impl AsRawFd for PendingStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
 /// An L2CAP stream between a local and remote socket (sequenced, reliable, two-way, connection-based).
 #[derive(Debug)]
 pub struct Stream {
@@ -656,6 +1191,24 @@ impl Stream {
         (r, w)
     }
 
+    /// Frames this stream using the specified codec.
+    #[cfg(feature = "codec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "codec")))]
+    pub fn framed<C>(self, codec: C) -> crate::codec::Framed<Self, C> {
+        crate::codec::Framed::new(self, codec)
+    }
+
+    /// Frames this stream by prefixing each message with its length.
+    ///
+    /// The length is encoded as a 32-bit big-endian integer and the maximum message length is 8 MiB.
+    /// Use [LengthDelimitedCodec::builder](crate::codec::LengthDelimitedCodec::builder) and
+    /// [Self::framed] for other configurations.
+    #[cfg(feature = "codec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "codec")))]
+    pub fn length_delimited(self) -> crate::codec::Framed<Self, crate::codec::LengthDelimitedCodec> {
+        self.framed(crate::codec::LengthDelimitedCodec::new())
+    }
+
     fn poll_write_priv(&self, cx: &mut Context, buf: &[u8]) -> Poll<Result<usize>> {
         // Trying to send more than the MTU on an L2CAP socket results in an error,
         // disregarding stream socket semantics. Thus we truncate the send buffer appropriately.
@@ -820,6 +1373,38 @@ impl FromRawFd for SeqPacketListener {
     }
 }
 
+/// Information about a packet received by a batched receive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct RecvMeta {
+    /// Length of the packet in bytes.
+    pub len: usize,
+    /// Address of the sender.
+    ///
+    /// This is only available for [datagram sockets](Datagram).
+    pub addr: Option<SocketAddr>,
+    /// Receive timestamps.
+    ///
+    /// This is only available if [timestamping](Socket::set_timestamping) is enabled.
+    pub timestamp: Option<Timestamp>,
+    /// Whether the packet was truncated because the buffer was too small.
+    ///
+    /// In this case [len](Self::len) is the length of the received part.
+    pub truncated: bool,
+}
+
+impl RecvMeta {
+    fn from_mmsg(mmsg: sock::MMsg<SocketAddr>) -> Self {
+        Self {
+            len: mmsg.len,
+            addr: mmsg.addr,
+            timestamp: Timestamp::from_cmsgs(&mmsg.cmsgs),
+            truncated: mmsg.truncated,
+        }
+    }
+}
+
 /// An L2CAP sequential packet socket (sequenced, reliable, two-way connection-based data transmission path for
 /// datagrams of fixed maximum length).
 #[derive(Debug)]
@@ -837,6 +1422,22 @@ impl SeqPacket {
         socket.connect(addr).await
     }
 
+    /// Concurrently establishes `count` sequential packet connections with a peer
+    /// at the specified socket address using [extended flow control](FlowControl::Extended).
+    ///
+    /// Uses any local Bluetooth adapter.
+    /// See [Socket::connect_group] for details.
+    pub async fn connect_group(addr: SocketAddr, count: usize) -> Result<Vec<Self>> {
+        let sockets = (0..count)
+            .map(|_| {
+                let socket = Socket::<SeqPacket>::new_seq_packet()?;
+                socket.bind(any_bind_addr(&addr))?;
+                Ok(socket)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Socket::connect_group(sockets, addr).await
+    }
+
     /// Gets the peer address of this stream.
     pub fn peer_addr(&self) -> Result<SocketAddr> {
         self.socket.peer_addr_priv()
@@ -872,6 +1473,62 @@ impl SeqPacket {
         self.socket.poll_recv_priv(cx, buf)
     }
 
+    /// Sends a packet gathered from multiple buffers.
+    ///
+    /// The total packet length must not exceed the [Self::send_mtu].
+    pub async fn send_vectored(&self, bufs: &[IoSlice<'_>]) -> Result<usize> {
+        self.socket.send_vectored_priv(bufs).await
+    }
+
+    /// Receives a packet scattered into multiple buffers.
+    ///
+    /// The total length of the provided buffers must be at least [Self::recv_mtu],
+    /// otherwise the packet may be truncated.
+    pub async fn recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> Result<usize> {
+        let (n, _cmsgs) = self.socket.recv_vectored_priv(bufs).await?;
+        Ok(n)
+    }
+
+    /// Receives a packet together with its timestamps.
+    ///
+    /// The timestamps are only available if [timestamping](Socket::set_timestamping)
+    /// is enabled.
+    pub async fn recv_with_timestamp(&self, buf: &mut [u8]) -> Result<(usize, Option<Timestamp>)> {
+        let (n, cmsgs) = self.socket.recvmsg_priv(buf).await?;
+        Ok((n, Timestamp::from_cmsgs(&cmsgs)))
+    }
+
+    /// Sends multiple packets using a single system call.
+    ///
+    /// Returns the number of packets sent, which may be less than the number of
+    /// provided packets if the send buffer of the socket is full.
+    /// Each packet length must not exceed the [Self::send_mtu].
+    pub async fn send_batch(&self, packets: &[&[u8]]) -> Result<usize> {
+        self.socket.send_batch_priv(packets, None).await
+    }
+
+    /// Receives multiple packets using a single system call, one into each buffer.
+    ///
+    /// Waits until at least one packet is available and then receives
+    /// the available packets, up to the number of provided buffers.
+    /// Each buffer must be of length [Self::recv_mtu], otherwise
+    /// the packet may be [truncated](RecvMeta::truncated).
+    pub async fn recv_batch(&self, bufs: &mut [&mut [u8]]) -> Result<Vec<RecvMeta>> {
+        let mmsgs = self.socket.recv_batch_priv(bufs).await?;
+        Ok(mmsgs.into_iter().map(RecvMeta::from_mmsg).collect())
+    }
+
+    /// Frames this connection as a [Stream](futures::Stream) of received messages and
+    /// a [Sink](futures::Sink) for sending messages, each message being one packet.
+    ///
+    /// Use [SeqPacketFramed::fragmented](crate::codec::SeqPacketFramed::fragmented)
+    /// to send and receive messages exceeding the MTU.
+    #[cfg(feature = "codec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "codec")))]
+    pub fn framed(self) -> Result<crate::codec::SeqPacketFramed> {
+        crate::codec::SeqPacketFramed::new(self)
+    }
+
     /// Shuts down the read, write, or both halves of this connection.
     pub fn shutdown(&self, how: Shutdown) -> Result<()> {
         self.socket.shutdown_priv(how)
@@ -1014,6 +1671,51 @@ impl Datagram {
         self.socket.poll_recv_from_priv(cx, buf)
     }
 
+    /// Sends a packet gathered from multiple buffers to the connected peer.
+    ///
+    /// The total packet length must not exceed the [Self::send_mtu].
+    pub async fn send_vectored(&self, bufs: &[IoSlice<'_>]) -> Result<usize> {
+        self.socket.send_vectored_priv(bufs).await
+    }
+
+    /// Receives a packet from the connected peer scattered into multiple buffers.
+    ///
+    /// The total length of the provided buffers must be at least [Self::recv_mtu],
+    /// otherwise the packet may be truncated.
+    pub async fn recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> Result<usize> {
+        let (n, _cmsgs) = self.socket.recv_vectored_priv(bufs).await?;
+        Ok(n)
+    }
+
+    /// Sends multiple packets to the connected peer using a single system call.
+    ///
+    /// Returns the number of packets sent, which may be less than the number of
+    /// provided packets if the send buffer of the socket is full.
+    /// Each packet length must not exceed the [Self::send_mtu].
+    pub async fn send_batch(&self, packets: &[&[u8]]) -> Result<usize> {
+        self.socket.send_batch_priv(packets, None).await
+    }
+
+    /// Sends multiple packets to the specified target address using a single system call.
+    ///
+    /// Returns the number of packets sent, which may be less than the number of
+    /// provided packets if the send buffer of the socket is full.
+    /// Each packet length must not exceed the [Self::send_mtu].
+    pub async fn send_batch_to(&self, packets: &[&[u8]], target: SocketAddr) -> Result<usize> {
+        self.socket.send_batch_priv(packets, Some(target)).await
+    }
+
+    /// Receives multiple packets from anywhere using a single system call, one into each buffer.
+    ///
+    /// Waits until at least one packet is available and then receives
+    /// the available packets, up to the number of provided buffers.
+    /// Each buffer must be of length [Self::recv_mtu], otherwise
+    /// the packet may be [truncated](RecvMeta::truncated).
+    pub async fn recv_batch(&self, bufs: &mut [&mut [u8]]) -> Result<Vec<RecvMeta>> {
+        let mmsgs = self.socket.recv_batch_priv(bufs).await?;
+        Ok(mmsgs.into_iter().map(RecvMeta::from_mmsg).collect())
+    }
+
     /// Shuts down the read, write, or both halves of this connection.
     pub fn shutdown(&self, how: Shutdown) -> Result<()> {
         self.socket.shutdown_priv(how)
@@ -1068,3 +1770,159 @@ impl FromRawFd for Datagram {
         Self::from_raw_fd(fd).expect("from_raw_fd failed")
     }
 }
+
+/// Signalling command code of a command reject.
+const SIG_COMMAND_REJECT: u8 = 0x01;
+/// Signalling command code of an echo request.
+const SIG_ECHO_REQUEST: u8 = 0x08;
+/// Signalling command code of an echo response.
+const SIG_ECHO_RESPONSE: u8 = 0x09;
+/// Size of a signalling command header.
+const SIG_HDR_SIZE: usize = 4;
+
+/// Default time to wait for an echo response used by [ping].
+pub const PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default interval between echo requests used by [ping].
+pub const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Sender of echo requests over the L2CAP signalling channel of a classic Bluetooth (BR/EDR) link,
+/// equivalent to the `l2ping` tool.
+///
+/// Use [ping] to send a series of echo requests with default settings.
+#[derive(Debug)]
+pub struct Pinger {
+    socket: Socket<Pinger>,
+    ident: u8,
+    /// Buffer for requests and responses, sized to the largest echo data sent so far.
+    buf: Vec<u8>,
+}
+
+impl Pinger {
+    /// Establishes the signalling channel to the peer with the specified address
+    /// using any local adapter.
+    ///
+    /// An ACL link to the peer is established if necessary.
+    /// This requires the `CAP_NET_RAW` capability.
+    pub async fn connect(addr: Address) -> Result<Self> {
+        let socket = Socket::<Pinger>::new_pinger()?;
+        socket.bind(SocketAddr::any_br_edr())?;
+        socket.connect(SocketAddr::new(addr, AddressType::BrEdr, 0)).await
+    }
+
+    /// Gets the peer address of the signalling channel.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.socket.peer_addr_priv()
+    }
+
+    /// Sends an echo request containing the specified data and waits for the response.
+    ///
+    /// Returns the round-trip time or `None` if no response was received within the timeout.
+    /// A response containing different data than sent is returned as an error.
+    pub async fn echo(&mut self, data: &[u8], timeout: Duration) -> Result<Option<Duration>> {
+        let len: u16 =
+            data.len().try_into().map_err(|_| Error::new(ErrorKind::InvalidInput, "echo data too long"))?;
+        self.ident = self.ident.checked_add(1).unwrap_or(1);
+        let ident = self.ident;
+
+        let size = SIG_HDR_SIZE + data.len();
+        if self.buf.len() < size {
+            self.buf.resize(size, 0);
+        }
+        let buf = &mut self.buf[..size];
+        buf[0] = SIG_ECHO_REQUEST;
+        buf[1] = ident;
+        buf[2..SIG_HDR_SIZE].copy_from_slice(&len.to_le_bytes());
+        buf[SIG_HDR_SIZE..].copy_from_slice(data);
+
+        let start = Instant::now();
+        let deadline = tokio::time::Instant::from_std(start + timeout);
+        self.socket.send_priv(buf).await?;
+
+        // A response longer than the request is truncated, but then its length field differs.
+        loop {
+            let n = match timeout_at(deadline, self.socket.recv_priv(buf)).await {
+                Ok(res) => res?,
+                Err(_) => return Ok(None),
+            };
+            if n < SIG_HDR_SIZE || buf[1] != ident {
+                continue;
+            }
+            let rtt = start.elapsed();
+            let rsp_len = usize::from(u16::from_le_bytes([buf[2], buf[3]]));
+            let payload = &buf[SIG_HDR_SIZE..n.min(SIG_HDR_SIZE + rsp_len)];
+            match buf[0] {
+                SIG_ECHO_RESPONSE if rsp_len == data.len() && payload == data => return Ok(Some(rtt)),
+                SIG_ECHO_RESPONSE => {
+                    return Err(Error::new(ErrorKind::InvalidData, "echo response contains different data"))
+                }
+                SIG_COMMAND_REJECT => {
+                    return Err(Error::new(ErrorKind::Unsupported, "echo request rejected by peer"))
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    /// Constructs a new [Pinger] from the given raw file descriptor.
+    ///
+    /// The file descriptor must have been set to non-blocking mode.
+    ///
+    /// This function *consumes ownership* of the specified file descriptor.
+    /// The returned object will take responsibility for closing it when the object goes out of scope.
+    ///
+    /// # Safety
+    /// If the passed file descriptor is invalid, undefined behavior may occur.
+    pub unsafe fn from_raw_fd(fd: RawFd) -> Result<Self> {
+        Ok(Self { socket: Socket::from_raw_fd(fd)?, ident: 0, buf: Vec::new() })
+    }
+}
+
+impl AsRef<Socket<Pinger>> for Pinger {
+    fn as_ref(&self) -> &Socket<Pinger> {
+        &self.socket
+    }
+}
+
+impl AsRawFd for Pinger {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+impl FromRawFd for Pinger {
+    /// Constructs a new instance of `Self` from the given raw file
+    /// descriptor.
+    ///
+    /// The file descriptor must have been set to non-blocking mode.
+    ///
+    /// # Panics
+    /// Panics when the conversion fails.
+    /// Use [Pinger::from_raw_fd] for a non-panicking variant.
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        Self::from_raw_fd(fd).expect("from_raw_fd failed")
+    }
+}
+
+/// Sends `count` echo requests of `size` bytes each to the classic Bluetooth (BR/EDR) device
+/// with the specified address, equivalent to the `l2ping` tool.
+///
+/// Requests are sent every [PING_INTERVAL] and each request waits at most [PING_TIMEOUT]
+/// for its response.
+/// Returns the round-trip time of each request or `None` if its response was lost.
+///
+/// This requires the `CAP_NET_RAW` capability.
+pub async fn ping(addr: Address, size: usize, count: usize) -> Result<Vec<Option<Duration>>> {
+    let mut pinger = Pinger::connect(addr).await?;
+    let data: Vec<u8> = (0..size).map(|i| (i % 40) as u8 + b'A').collect();
+
+    let mut rtts = Vec::with_capacity(count);
+    for i in 0..count {
+        let start = Instant::now();
+        rtts.push(pinger.echo(&data, PING_TIMEOUT).await?);
+        if i + 1 < count {
+            sleep(PING_INTERVAL.saturating_sub(start.elapsed())).await;
+        }
+    }
+    Ok(rtts)
+}
@@ -20,6 +20,8 @@
 //!     * Bluetooth Low Energy advertisements
 //!     * [change events stream](Adapter::events)
 //!     * connecting and pairing
+//!     * [export and import of bonds](Adapter::export_bonds)
+//!     * [LE connection parameters](Device::set_connection_parameters)
 //! * [consumption of remote GATT services](Device::services)
 //!     * GATT service discovery
 //!     * read, write and notify operations on characteristics
@@ -33,6 +35,7 @@
 //!         * low-overhead [AsyncRead] and [AsyncWrite] streams
 //! * [sending Bluetooth Low Energy advertisements](Adapter::advertise)
 //! * [Bluetooth authorization agent](agent::Agent)
+//! * [out-of-band pairing data](oob)
 //! * efficient event dispatching
 //!     * not affected by D-Bus match rule count
 //!     * O(1) in number of subscriptions
@@ -42,10 +45,41 @@
 //!     * sequential packet oriented
 //!     * datagram oriented
 //!     * async IO interface with [AsyncRead] and [AsyncWrite] support
+//!     * round-trip time measurement using echo requests
+//!     * [framed codecs](codec) with message fragmentation
 //! * [RFCOMM sockets](rfcomm)
 //!     * support for classic Bluetooth (BR/EDR)
 //!     * stream oriented
 //!     * async IO interface with [AsyncRead] and [AsyncWrite] support
+//! * [SCO sockets](sco)
+//!     * voice links for headset and hands-free audio
+//!     * CVSD and transparent (mSBC, LC3-SWB) voice settings
+//!     * erroneous data reporting through packet status
+//! * [HCI sockets](hci) for direct controller access
+//!     * raw, user and monitor channels with packet filters
+//!     * typed encoding of common commands and events
+//!     * vendor-specific command passthrough
+//! * [traffic capture](monitor) using the HCI monitor channel
+//!     * typed packets with timestamps
+//!     * writing and reading of btsnoop and pcapng files
+//! * [ISO sockets](iso) for LE Audio
+//!     * connected isochronous streams (CIS) with CIG/CIS quality of service parameters
+//!     * broadcast source and sink with BIG/BIS parameters, encryption and PA sync
+//!     * receive timestamps
+//! * [kernel management interface](mgmt) usable without the Bluetooth daemon
+//!     * controller information, power, static address, PHY configuration and blocked keys
+//!     * experimental features, default and per-device connection parameters
+//!     * concurrent commands and event streams
+//! * [Bluetooth profiles](profile) for L2CAP and RFCOMM connections
+//!     * automatic SDP registration and discovery by the Bluetooth daemon
+//! * [Serial Port Profile](rfcomm::spp) with virtual serial ports for legacy applications
+//! * [Hands-Free Profile](rfcomm::hfp) signalling using [AT commands](rfcomm::at)
+//! * [OBEX protocol](obex) client and server
+//!     * over RFCOMM and L2CAP with single response mode
+//!     * [client](obex::obexd) for the BlueZ OBEX daemon (obexd)
+//! * [SDP service records](sdp)
+//!     * builder and accessors for common attributes
+//!     * BlueZ XML and binary encoding
 //! * [database of assigned numbers](id)
 //!     * manufacturer ids
 //!     * services classes, GATT services, characteristics and descriptors
@@ -57,9 +91,16 @@
 //! The following crate features are available.
 //!
 //! * `bluetoothd`: Enables all functions requiring a running Bluetooth daemon.
+//! * `codec`: Enables framed codecs for L2CAP and RFCOMM connections.
+//! * `hci`: Enables HCI sockets and traffic capture.
 //! * `id`: Enables database of assigned numbers.
+//! * `iso`: Enables ISO sockets.
 //! * `l2cap`: Enables L2CAP sockets.
+//! * `mgmt`: Enables the kernel management interface.
+//! * `obex`: Enables the OBEX protocol.
 //! * `rfcomm`: Enables RFCOMM sockets.
+//! * `sco`: Enables SCO sockets.
+//! * `sdp`: Enables SDP service records.
 //! * `serde`: Enables serialization and deserialization of some data types.
 //!
 //! To enable all crate features specify the `full` crate feature.
@@ -69,8 +110,8 @@
 //! Then obtain a Bluetooth adapter using [Session::adapter].
 //! From there on you can access most of the functionality using the methods provided by [Adapter].
 //!
-//! ## L2CAP and RFCOMM sockets
-//! Refer to the [l2cap] and [rfcomm] modules.
+//! ## L2CAP, RFCOMM, SCO, ISO and HCI sockets
+//! Refer to the [l2cap], [rfcomm], [sco], [iso] and [hci] modules.
 //! No [Session] and therefore no running Bluetooth daemon is required.
 //!
 //! [Linux Bluetooth protocol stack (BlueZ)]: http://www.bluez.org/
@@ -110,6 +151,8 @@ pub(crate) const SERVICE_NAME: &str = "org.bluez";
 #[cfg(feature = "bluetoothd")]
 pub(crate) const ERR_PREFIX: &str = "org.bluez.Error.";
 #[cfg(feature = "bluetoothd")]
+pub(crate) const OBEX_ERR_PREFIX: &str = "org.bluez.obex.Error.";
+#[cfg(feature = "bluetoothd")]
 pub(crate) const TIMEOUT: Duration = Duration::from_secs(120);
 
 #[cfg(feature = "bluetoothd")]
@@ -501,7 +544,19 @@ macro_rules! read_opt_prop {
     };
 }
 
-#[cfg(any(feature = "l2cap", feature = "rfcomm"))]
+#[cfg(any(
+    feature = "bluetoothd",
+    feature = "hci",
+    feature = "iso",
+    feature = "l2cap",
+    feature = "mgmt",
+    feature = "rfcomm",
+    feature = "sco"
+))]
+#[cfg_attr(
+    not(any(feature = "iso", feature = "l2cap", feature = "rfcomm", feature = "sco")),
+    allow(dead_code, unused_macros)
+)]
 #[macro_use]
 mod sock;
 
@@ -514,16 +569,47 @@ pub mod adv;
 #[cfg_attr(docsrs, doc(cfg(feature = "bluetoothd")))]
 pub mod agent;
 #[cfg(feature = "bluetoothd")]
+#[cfg_attr(docsrs, doc(cfg(feature = "bluetoothd")))]
+pub mod bond;
+#[cfg(all(feature = "codec", any(feature = "l2cap", feature = "rfcomm")))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "codec", any(feature = "l2cap", feature = "rfcomm")))))]
+pub mod codec;
+#[cfg(feature = "bluetoothd")]
 mod device;
 #[cfg(feature = "bluetoothd")]
 #[cfg_attr(docsrs, doc(cfg(feature = "bluetoothd")))]
 pub mod gatt;
+#[cfg(feature = "hci")]
+#[cfg_attr(docsrs, doc(cfg(feature = "hci")))]
+pub mod hci;
+#[cfg(feature = "iso")]
+#[cfg_attr(docsrs, doc(cfg(feature = "iso")))]
+pub mod iso;
 #[cfg(feature = "l2cap")]
 #[cfg_attr(docsrs, doc(cfg(feature = "l2cap")))]
 pub mod l2cap;
+#[cfg(feature = "mgmt")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mgmt")))]
+pub mod mgmt;
+#[cfg(feature = "hci")]
+#[cfg_attr(docsrs, doc(cfg(feature = "hci")))]
+pub mod monitor;
+#[cfg(feature = "obex")]
+#[cfg_attr(docsrs, doc(cfg(feature = "obex")))]
+pub mod obex;
+pub mod oob;
+#[cfg(all(feature = "bluetoothd", any(feature = "l2cap", feature = "rfcomm")))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "bluetoothd", any(feature = "l2cap", feature = "rfcomm")))))]
+pub mod profile;
 #[cfg(feature = "rfcomm")]
 #[cfg_attr(docsrs, doc(cfg(feature = "rfcomm")))]
 pub mod rfcomm;
+#[cfg(feature = "sco")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sco")))]
+pub mod sco;
+#[cfg(feature = "sdp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sdp")))]
+pub mod sdp;
 #[cfg(feature = "bluetoothd")]
 mod session;
 mod sys;
@@ -681,7 +767,7 @@ impl From<dbus::Error> for Error {
         }
         let kind = match err
             .name()
-            .and_then(|name| name.strip_prefix(ERR_PREFIX))
+            .and_then(|name| name.strip_prefix(ERR_PREFIX).or_else(|| name.strip_prefix(OBEX_ERR_PREFIX)))
             .and_then(|s| ErrorKind::from_str(s).ok())
         {
             Some(kind) => kind,
@@ -888,6 +974,96 @@ impl Default for AddressType {
     }
 }
 
+bitflags::bitflags! {
+    /// Bluetooth PHYs.
+    ///
+    /// The same bits are used by the `BT_PHY` socket option and the kernel management interface.
+    #[derive(Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Phy: u32 {
+        /// BR 1 Mbit/s with 1-slot packets.
+        const BR_1M_1SLOT = 1 << 0;
+        /// BR 1 Mbit/s with 3-slot packets.
+        const BR_1M_3SLOT = 1 << 1;
+        /// BR 1 Mbit/s with 5-slot packets.
+        const BR_1M_5SLOT = 1 << 2;
+        /// EDR 2 Mbit/s with 1-slot packets.
+        const EDR_2M_1SLOT = 1 << 3;
+        /// EDR 2 Mbit/s with 3-slot packets.
+        const EDR_2M_3SLOT = 1 << 4;
+        /// EDR 2 Mbit/s with 5-slot packets.
+        const EDR_2M_5SLOT = 1 << 5;
+        /// EDR 3 Mbit/s with 1-slot packets.
+        const EDR_3M_1SLOT = 1 << 6;
+        /// EDR 3 Mbit/s with 3-slot packets.
+        const EDR_3M_3SLOT = 1 << 7;
+        /// EDR 3 Mbit/s with 5-slot packets.
+        const EDR_3M_5SLOT = 1 << 8;
+        /// LE 1M for transmission.
+        const LE_1M_TX = 1 << 9;
+        /// LE 1M for reception.
+        const LE_1M_RX = 1 << 10;
+        /// LE 2M for transmission.
+        const LE_2M_TX = 1 << 11;
+        /// LE 2M for reception.
+        const LE_2M_RX = 1 << 12;
+        /// LE Coded for transmission.
+        const LE_CODED_TX = 1 << 13;
+        /// LE Coded for reception.
+        const LE_CODED_RX = 1 << 14;
+        /// LE 1M for transmission and reception.
+        const LE_1M = Self::LE_1M_TX.bits | Self::LE_1M_RX.bits;
+        /// LE 2M for transmission and reception.
+        const LE_2M = Self::LE_2M_TX.bits | Self::LE_2M_RX.bits;
+        /// LE Coded for transmission and reception.
+        const LE_CODED = Self::LE_CODED_TX.bits | Self::LE_CODED_RX.bits;
+    }
+}
+
+/// Bluetooth LE connection parameters.
+///
+/// The default values are the defaults of the Linux kernel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectionParameters {
+    /// Minimum connection interval in units of 1.25 ms.
+    pub min_interval: u16,
+    /// Maximum connection interval in units of 1.25 ms.
+    pub max_interval: u16,
+    /// Peripheral latency in number of connection events.
+    pub latency: u16,
+    /// Supervision timeout in units of 10 ms.
+    pub supervision_timeout: u16,
+}
+
+impl Default for ConnectionParameters {
+    fn default() -> Self {
+        Self { min_interval: 0x0018, max_interval: 0x0028, latency: 0, supervision_timeout: 0x002a }
+    }
+}
+
+impl ConnectionParameters {
+    /// Whether the parameters are within the ranges allowed by the Bluetooth specification.
+    ///
+    /// The supervision timeout must be long enough to cover the
+    /// maximum connection interval multiplied by the latency.
+    /// The kernel ignores invalid parameters.
+    pub fn is_valid(&self) -> bool {
+        let (min, max, latency, timeout) = (
+            u32::from(self.min_interval),
+            u32::from(self.max_interval),
+            u32::from(self.latency),
+            u32::from(self.supervision_timeout),
+        );
+        (6..=3200).contains(&min)
+            && (min..=3200).contains(&max)
+            && (10..=3200).contains(&timeout)
+            && max < timeout * 8
+            && latency <= 499
+            && latency < timeout * 4 / max
+    }
+}
+
 /// Linux kernel modalias information.
 #[cfg(feature = "bluetoothd")]
 #[cfg_attr(docsrs, doc(cfg(feature = "bluetoothd")))]
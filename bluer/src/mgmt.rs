@@ -0,0 +1,1073 @@
+//! Bluetooth management (mgmt) interface.
+//!
+//! The management interface of the Linux kernel provides access to functionality
+//! of Bluetooth controllers that is not exposed by the Bluetooth daemon.
+//! It does not require a running Bluetooth daemon and is thus also usable on minimal systems.
+//! Most commands require the `CAP_NET_ADMIN` capability.
+//!
+//! Use a [Client] to query the available controllers and to receive events.
+//! Commands for a particular controller are sent using a [Controller], which is obtained
+//! by its index from [Client::controller] or, when the `bluetoothd` feature is enabled,
+//! from `Adapter::mgmt_controller`.
+//!
+
+use futures::{stream, Stream};
+use libc::{AF_BLUETOOTH, SOCK_RAW};
+use std::{
+    collections::VecDeque,
+    convert::TryInto,
+    fmt,
+    io::{Error, ErrorKind, Result},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::{
+    io::{unix::AsyncFd, ReadBuf},
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
+use uuid::Uuid;
+
+use crate::{
+    oob::{LeOobRecord, OobData},
+    sock::{self, OwnedFd},
+    sys::{bdaddr_t, sockaddr_hci, BTPROTO_HCI, HCI_CHANNEL_CONTROL, HCI_DEV_NONE, MGMT_HDR_SIZE},
+    Address, AddressType, ConnectionParameters, Phy,
+};
+
+/// Controller index used for commands and events that are not specific to a controller.
+pub const INDEX_NONE: u16 = HCI_DEV_NONE;
+
+/// Read management version information.
+pub const OP_READ_VERSION: u16 = 0x0001;
+/// Read controller index list.
+pub const OP_READ_INDEX_LIST: u16 = 0x0003;
+/// Read controller information.
+pub const OP_READ_INFO: u16 = 0x0004;
+/// Set powered.
+pub const OP_SET_POWERED: u16 = 0x0005;
+/// Load link keys.
+pub const OP_LOAD_LINK_KEYS: u16 = 0x0012;
+/// Load long term keys.
+pub const OP_LOAD_LONG_TERM_KEYS: u16 = 0x0013;
+/// Read local out of band data.
+pub const OP_READ_LOCAL_OOB_DATA: u16 = 0x0020;
+/// Add remote out of band data.
+pub const OP_ADD_REMOTE_OOB_DATA: u16 = 0x0021;
+/// Remove remote out of band data.
+pub const OP_REMOVE_REMOTE_OOB_DATA: u16 = 0x0022;
+/// Set static address.
+pub const OP_SET_STATIC_ADDRESS: u16 = 0x002b;
+/// Load identity resolving keys.
+pub const OP_LOAD_IRKS: u16 = 0x0030;
+/// Load connection parameters.
+pub const OP_LOAD_CONN_PARAM: u16 = 0x0035;
+/// Read local out of band extended data.
+pub const OP_READ_LOCAL_OOB_EXT_DATA: u16 = 0x003b;
+/// Get PHY configuration.
+pub const OP_GET_PHY_CONFIGURATION: u16 = 0x0044;
+/// Set PHY configuration.
+pub const OP_SET_PHY_CONFIGURATION: u16 = 0x0045;
+/// Set blocked keys.
+pub const OP_SET_BLOCKED_KEYS: u16 = 0x0046;
+/// Set wideband speech.
+pub const OP_SET_WIDEBAND_SPEECH: u16 = 0x0047;
+/// Read experimental features information.
+pub const OP_READ_EXP_FEATURES_INFO: u16 = 0x0049;
+/// Set experimental feature.
+pub const OP_SET_EXP_FEATURE: u16 = 0x004a;
+/// Read default system configuration.
+pub const OP_READ_DEF_SYSTEM_CONFIG: u16 = 0x004b;
+/// Set default system configuration.
+pub const OP_SET_DEF_SYSTEM_CONFIG: u16 = 0x004c;
+
+const EV_CMD_COMPLETE: u16 = 0x0001;
+const EV_CMD_STATUS: u16 = 0x0002;
+const EV_CONTROLLER_ERROR: u16 = 0x0003;
+const EV_INDEX_ADDED: u16 = 0x0004;
+const EV_INDEX_REMOVED: u16 = 0x0005;
+const EV_NEW_SETTINGS: u16 = 0x0006;
+const EV_DEVICE_CONNECTED: u16 = 0x000b;
+const EV_DEVICE_DISCONNECTED: u16 = 0x000c;
+const EV_NEW_CONN_PARAM: u16 = 0x001c;
+const EV_PHY_CONFIGURATION_CHANGED: u16 = 0x0026;
+const EV_EXP_FEATURE_CHANGED: u16 = 0x0027;
+
+/// Default system configuration type of the minimum LE connection interval.
+const DEF_SYSTEM_CONFIG_LE_MIN_CONN_INTERVAL: u16 = 0x0017;
+/// Default system configuration type of the maximum LE connection interval.
+const DEF_SYSTEM_CONFIG_LE_MAX_CONN_INTERVAL: u16 = 0x0018;
+/// Default system configuration type of the LE connection latency.
+const DEF_SYSTEM_CONFIG_LE_CONN_LATENCY: u16 = 0x0019;
+/// Default system configuration type of the LE supervision timeout.
+const DEF_SYSTEM_CONFIG_LE_SUPERVISION_TIMEOUT: u16 = 0x001a;
+
+/// Experimental feature enabling ISO sockets.
+pub const EXP_FEATURE_ISO_SOCKET: Uuid = Uuid::from_u128(0x6fbaf188_05e0_496a_9885_d6ddfdb4e03e);
+/// Experimental feature enabling debug functionality.
+pub const EXP_FEATURE_DEBUG: Uuid = Uuid::from_u128(0xd4992530_b9ec_469f_ab01_6c481c47da1c);
+
+/// Possible bit values of [controller settings](ControllerInfo::current_settings).
+pub mod settings {
+    /// Powered.
+    pub const POWERED: u32 = 1 << 0;
+    /// Connectable.
+    pub const CONNECTABLE: u32 = 1 << 1;
+    /// Fast connectable.
+    pub const FAST_CONNECTABLE: u32 = 1 << 2;
+    /// Discoverable.
+    pub const DISCOVERABLE: u32 = 1 << 3;
+    /// Bondable.
+    pub const BONDABLE: u32 = 1 << 4;
+    /// Link level security.
+    pub const LINK_SECURITY: u32 = 1 << 5;
+    /// Secure simple pairing.
+    pub const SSP: u32 = 1 << 6;
+    /// Classic Bluetooth (BR/EDR).
+    pub const BREDR: u32 = 1 << 7;
+    /// High speed.
+    pub const HS: u32 = 1 << 8;
+    /// Bluetooth Low Energy.
+    pub const LE: u32 = 1 << 9;
+    /// Advertising.
+    pub const ADVERTISING: u32 = 1 << 10;
+    /// Secure connections.
+    pub const SECURE_CONN: u32 = 1 << 11;
+    /// Debug keys.
+    pub const DEBUG_KEYS: u32 = 1 << 12;
+    /// Privacy.
+    pub const PRIVACY: u32 = 1 << 13;
+    /// Controller configuration.
+    pub const CONFIGURATION: u32 = 1 << 14;
+    /// Static address.
+    pub const STATIC_ADDRESS: u32 = 1 << 15;
+    /// PHY configuration.
+    pub const PHY_CONFIGURATION: u32 = 1 << 16;
+    /// Wideband speech.
+    pub const WIDEBAND_SPEECH: u32 = 1 << 17;
+    /// Connected isochronous stream (CIS) central.
+    pub const CIS_CENTRAL: u32 = 1 << 18;
+    /// Connected isochronous stream (CIS) peripheral.
+    pub const CIS_PERIPHERAL: u32 = 1 << 19;
+    /// Isochronous broadcaster.
+    pub const ISO_BROADCASTER: u32 = 1 << 20;
+    /// Synchronized receiver.
+    pub const ISO_SYNC_RECEIVER: u32 = 1 << 21;
+}
+
+/// Maximum size of a management packet.
+const MAX_PACKET_SIZE: usize = MGMT_HDR_SIZE + u16::MAX as usize;
+
+/// Status of a management command.
+///
+/// A failed command is returned as an error containing the status.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Status {
+    /// Success.
+    Success,
+    /// Unknown command.
+    UnknownCommand,
+    /// Not connected.
+    NotConnected,
+    /// Failed.
+    Failed,
+    /// Connect failed.
+    ConnectFailed,
+    /// Authentication failed.
+    AuthenticationFailed,
+    /// Not paired.
+    NotPaired,
+    /// No resources.
+    NoResources,
+    /// Timeout.
+    Timeout,
+    /// Already connected.
+    AlreadyConnected,
+    /// Busy.
+    Busy,
+    /// Rejected.
+    Rejected,
+    /// Not supported.
+    NotSupported,
+    /// Invalid parameters.
+    InvalidParameters,
+    /// Disconnected.
+    Disconnected,
+    /// Not powered.
+    NotPowered,
+    /// Cancelled.
+    Cancelled,
+    /// Invalid index.
+    InvalidIndex,
+    /// Blocked through rfkill.
+    RfKilled,
+    /// Already paired.
+    AlreadyPaired,
+    /// Permission denied.
+    PermissionDenied,
+    /// Unknown status.
+    Unknown(u8),
+}
+
+impl From<u8> for Status {
+    fn from(status: u8) -> Self {
+        match status {
+            0x00 => Self::Success,
+            0x01 => Self::UnknownCommand,
+            0x02 => Self::NotConnected,
+            0x03 => Self::Failed,
+            0x04 => Self::ConnectFailed,
+            0x05 => Self::AuthenticationFailed,
+            0x06 => Self::NotPaired,
+            0x07 => Self::NoResources,
+            0x08 => Self::Timeout,
+            0x09 => Self::AlreadyConnected,
+            0x0a => Self::Busy,
+            0x0b => Self::Rejected,
+            0x0c => Self::NotSupported,
+            0x0d => Self::InvalidParameters,
+            0x0e => Self::Disconnected,
+            0x0f => Self::NotPowered,
+            0x10 => Self::Cancelled,
+            0x11 => Self::InvalidIndex,
+            0x12 => Self::RfKilled,
+            0x13 => Self::AlreadyPaired,
+            0x14 => Self::PermissionDenied,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Success => write!(f, "success"),
+            Self::UnknownCommand => write!(f, "unknown command"),
+            Self::NotConnected => write!(f, "not connected"),
+            Self::Failed => write!(f, "failed"),
+            Self::ConnectFailed => write!(f, "connect failed"),
+            Self::AuthenticationFailed => write!(f, "authentication failed"),
+            Self::NotPaired => write!(f, "not paired"),
+            Self::NoResources => write!(f, "no resources"),
+            Self::Timeout => write!(f, "timeout"),
+            Self::AlreadyConnected => write!(f, "already connected"),
+            Self::Busy => write!(f, "busy"),
+            Self::Rejected => write!(f, "rejected"),
+            Self::NotSupported => write!(f, "not supported"),
+            Self::InvalidParameters => write!(f, "invalid parameters"),
+            Self::Disconnected => write!(f, "disconnected"),
+            Self::NotPowered => write!(f, "not powered"),
+            Self::Cancelled => write!(f, "cancelled"),
+            Self::InvalidIndex => write!(f, "invalid index"),
+            Self::RfKilled => write!(f, "blocked through rfkill"),
+            Self::AlreadyPaired => write!(f, "already paired"),
+            Self::PermissionDenied => write!(f, "permission denied"),
+            Self::Unknown(status) => write!(f, "unknown status 0x{:02x}", status),
+        }
+    }
+}
+
+impl std::error::Error for Status {}
+
+impl From<Status> for Error {
+    fn from(status: Status) -> Self {
+        let kind = match status {
+            Status::PermissionDenied => ErrorKind::PermissionDenied,
+            Status::InvalidParameters => ErrorKind::InvalidInput,
+            Status::Timeout => ErrorKind::TimedOut,
+            Status::NotConnected | Status::Disconnected => ErrorKind::NotConnected,
+            Status::InvalidIndex => ErrorKind::NotFound,
+            Status::AlreadyConnected | Status::AlreadyPaired => ErrorKind::AlreadyExists,
+            _ => ErrorKind::Other,
+        };
+        Error::new(kind, status)
+    }
+}
+
+/// Socket address of a management socket.
+#[derive(Clone, Copy, Debug)]
+struct SocketAddr;
+
+impl sock::SysSockAddr for SocketAddr {
+    type SysSockAddr = sockaddr_hci;
+
+    fn into_sys_sock_addr(self) -> Self::SysSockAddr {
+        sockaddr_hci { hci_family: AF_BLUETOOTH as _, hci_dev: HCI_DEV_NONE, hci_channel: HCI_CHANNEL_CONTROL }
+    }
+
+    fn try_from_sys_sock_addr(saddr: Self::SysSockAddr) -> Result<Self> {
+        if saddr.hci_family != AF_BLUETOOTH as _ {
+            return Err(Error::new(ErrorKind::InvalidInput, "sockaddr_hci::hci_family is not AF_BLUETOOTH"));
+        }
+        Ok(Self)
+    }
+}
+
+/// Management socket.
+struct Socket {
+    fd: AsyncFd<OwnedFd>,
+}
+
+impl Socket {
+    /// Opens a management socket.
+    fn new() -> Result<Self> {
+        let fd = sock::socket(AF_BLUETOOTH, SOCK_RAW, BTPROTO_HCI)?;
+        sock::bind(&fd, SocketAddr)?;
+        Ok(Self { fd: AsyncFd::new(fd)? })
+    }
+
+    async fn send(&self, buf: &[u8]) -> Result<usize> {
+        loop {
+            let mut guard = self.fd.writable().await?;
+            match guard.try_io(|inner| sock::send(inner.get_ref(), buf, 0)) {
+                Ok(result) => return result,
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> Result<usize> {
+        let mut buf = ReadBuf::new(buf);
+        loop {
+            let mut guard = self.fd.readable().await?;
+            match guard.try_io(|inner| sock::recv(inner.get_ref(), &mut buf, 0)) {
+                Ok(result) => return result,
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+/// Sends a command to the controller with the specified index using
+/// a newly opened management client.
+#[cfg(feature = "bluetoothd")]
+pub(crate) async fn command(index: u16, opcode: u16, params: &[u8]) -> Result<Vec<u8>> {
+    Client::new()?.command(index, opcode, params).await
+}
+
+/// Encodes an address and its type as used by management commands.
+pub(crate) fn encode_addr_info(buf: &mut Vec<u8>, addr: Address, addr_type: AddressType) {
+    let mut addr = addr.0;
+    addr.reverse();
+    buf.extend_from_slice(&addr);
+    buf.push(addr_type as u8);
+}
+
+/// Decodes an address and its type as used by management events.
+fn decode_addr_info(buf: &[u8]) -> Option<(Address, AddressType)> {
+    let b: [u8; 6] = buf.get(..6)?.try_into().ok()?;
+    let addr_type = match buf.get(6)? {
+        0 => AddressType::BrEdr,
+        1 => AddressType::LePublic,
+        2 => AddressType::LeRandom,
+        _ => return None,
+    };
+    Some((Address::from(bdaddr_t { b }), addr_type))
+}
+
+fn read_u16(buf: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(buf.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(buf.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+/// Decodes a UUID in the little endian byte order used by the management interface.
+fn read_uuid(buf: &[u8], pos: usize) -> Option<Uuid> {
+    let mut b: [u8; 16] = buf.get(pos..pos + 16)?.try_into().ok()?;
+    b.reverse();
+    Some(Uuid::from_bytes(b))
+}
+
+fn encode_uuid(buf: &mut Vec<u8>, uuid: &Uuid) {
+    let mut b = *uuid.as_bytes();
+    b.reverse();
+    buf.extend_from_slice(&b);
+}
+
+fn invalid_response() -> Error {
+    Error::new(ErrorKind::InvalidData, "invalid management response")
+}
+
+fn socket_closed() -> Error {
+    Error::new(ErrorKind::BrokenPipe, "management socket closed")
+}
+
+/// Decodes a null-terminated string of fixed length.
+fn read_str(buf: &[u8]) -> String {
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+/// Management interface version.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Version {
+    /// Version.
+    pub version: u8,
+    /// Revision.
+    pub revision: u16,
+}
+
+/// Controller information.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct ControllerInfo {
+    /// Public address.
+    pub address: Address,
+    /// Bluetooth version.
+    pub bluetooth_version: u8,
+    /// Manufacturer id.
+    pub manufacturer: u16,
+    /// Supported [settings].
+    pub supported_settings: u32,
+    /// Current [settings].
+    pub current_settings: u32,
+    /// Class of device.
+    pub class_of_device: [u8; 3],
+    /// Name.
+    pub name: String,
+    /// Short name.
+    pub short_name: String,
+}
+
+impl ControllerInfo {
+    fn from_mgmt(buf: &[u8]) -> Option<Self> {
+        if buf.len() < 280 {
+            return None;
+        }
+        Some(Self {
+            address: Address::from(bdaddr_t { b: buf[0..6].try_into().ok()? }),
+            bluetooth_version: buf[6],
+            manufacturer: read_u16(buf, 7)?,
+            supported_settings: read_u32(buf, 9)?,
+            current_settings: read_u32(buf, 13)?,
+            class_of_device: buf[17..20].try_into().ok()?,
+            name: read_str(&buf[20..269]),
+            short_name: read_str(&buf[269..280]),
+        })
+    }
+}
+
+/// PHY configuration of a controller.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhyConfiguration {
+    /// PHYs supported by the controller.
+    pub supported: Phy,
+    /// PHYs whose selection can be changed.
+    pub configurable: Phy,
+    /// Currently selected PHYs.
+    pub selected: Phy,
+}
+
+/// Type of a blocked key.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlockedKeyType {
+    /// Link key.
+    LinkKey,
+    /// Long term key.
+    LongTermKey,
+    /// Identity resolving key.
+    IdentityResolvingKey,
+}
+
+/// A key that is rejected by the kernel when it is distributed or loaded.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlockedKey {
+    /// Key type.
+    pub key_type: BlockedKeyType,
+    /// Key value.
+    pub value: [u8; 16],
+}
+
+/// Experimental feature and its state.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExperimentalFeature {
+    /// UUID identifying the feature.
+    pub uuid: Uuid,
+    /// Feature flags.
+    ///
+    /// Bit 0 indicates whether the feature is enabled.
+    pub flags: u32,
+}
+
+impl ExperimentalFeature {
+    /// Whether the feature is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.flags & 1 != 0
+    }
+}
+
+/// Encodes connection parameters as default system configuration entries.
+fn encode_def_conn_params(params: &ConnectionParameters) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for (ty, value) in [
+        (DEF_SYSTEM_CONFIG_LE_MIN_CONN_INTERVAL, params.min_interval),
+        (DEF_SYSTEM_CONFIG_LE_MAX_CONN_INTERVAL, params.max_interval),
+        (DEF_SYSTEM_CONFIG_LE_CONN_LATENCY, params.latency),
+        (DEF_SYSTEM_CONFIG_LE_SUPERVISION_TIMEOUT, params.supervision_timeout),
+    ] {
+        buf.extend_from_slice(&ty.to_le_bytes());
+        buf.push(2);
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+    buf
+}
+
+/// Decodes connection parameters from default system configuration entries.
+///
+/// Missing entries are set to their default values.
+fn decode_def_conn_params(tlvs: &[(u16, Vec<u8>)]) -> ConnectionParameters {
+    let get = |ty| {
+        tlvs.iter()
+            .find(|(t, _)| *t == ty)
+            .and_then(|(_, v)| Some(u16::from_le_bytes(v.as_slice().try_into().ok()?)))
+    };
+    let default = ConnectionParameters::default();
+    ConnectionParameters {
+        min_interval: get(DEF_SYSTEM_CONFIG_LE_MIN_CONN_INTERVAL).unwrap_or(default.min_interval),
+        max_interval: get(DEF_SYSTEM_CONFIG_LE_MAX_CONN_INTERVAL).unwrap_or(default.max_interval),
+        latency: get(DEF_SYSTEM_CONFIG_LE_CONN_LATENCY).unwrap_or(default.latency),
+        supervision_timeout: get(DEF_SYSTEM_CONFIG_LE_SUPERVISION_TIMEOUT).unwrap_or(default.supervision_timeout),
+    }
+}
+
+fn read_conn_params(buf: &[u8], pos: usize) -> Option<ConnectionParameters> {
+    Some(ConnectionParameters {
+        min_interval: read_u16(buf, pos)?,
+        max_interval: read_u16(buf, pos + 2)?,
+        latency: read_u16(buf, pos + 4)?,
+        supervision_timeout: read_u16(buf, pos + 6)?,
+    })
+}
+
+/// Management event.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Event {
+    /// A controller error occurred.
+    ControllerError {
+        /// Error code.
+        code: u8,
+    },
+    /// A controller has been added.
+    IndexAdded,
+    /// A controller has been removed.
+    IndexRemoved,
+    /// The current [settings] of a controller changed.
+    NewSettings(u32),
+    /// A device has connected.
+    DeviceConnected {
+        /// Device address.
+        address: Address,
+        /// Device address type.
+        address_type: AddressType,
+        /// Flags.
+        flags: u32,
+        /// Extended inquiry response or advertising data.
+        eir: Vec<u8>,
+    },
+    /// A device has disconnected.
+    DeviceDisconnected {
+        /// Device address.
+        address: Address,
+        /// Device address type.
+        address_type: AddressType,
+        /// Reason.
+        reason: u8,
+    },
+    /// The selected PHYs changed.
+    PhyConfigurationChanged(Phy),
+    /// A device requested new connection parameters.
+    NewConnectionParameters {
+        /// Device address.
+        address: Address,
+        /// Device address type.
+        address_type: AddressType,
+        /// Whether the parameters should be stored for future connections.
+        store_hint: bool,
+        /// Requested connection parameters.
+        parameters: ConnectionParameters,
+    },
+    /// An experimental feature changed.
+    ExperimentalFeatureChanged(ExperimentalFeature),
+    /// Other event.
+    Other {
+        /// Event code.
+        code: u16,
+        /// Parameters.
+        params: Vec<u8>,
+    },
+}
+
+impl Event {
+    /// Decodes an event from its code and parameters.
+    ///
+    /// Malformed events are returned as [Event::Other].
+    pub fn decode(code: u16, params: &[u8]) -> Self {
+        Self::decode_known(code, params).unwrap_or_else(|| Self::Other { code, params: params.to_vec() })
+    }
+
+    fn decode_known(code: u16, params: &[u8]) -> Option<Self> {
+        let event = match code {
+            EV_CONTROLLER_ERROR => Self::ControllerError { code: *params.first()? },
+            EV_INDEX_ADDED => Self::IndexAdded,
+            EV_INDEX_REMOVED => Self::IndexRemoved,
+            EV_NEW_SETTINGS => Self::NewSettings(read_u32(params, 0)?),
+            EV_DEVICE_CONNECTED => {
+                let (address, address_type) = decode_addr_info(params)?;
+                let eir_len: usize = read_u16(params, 11)?.into();
+                Self::DeviceConnected {
+                    address,
+                    address_type,
+                    flags: read_u32(params, 7)?,
+                    eir: params.get(13..13 + eir_len)?.to_vec(),
+                }
+            }
+            EV_DEVICE_DISCONNECTED => {
+                let (address, address_type) = decode_addr_info(params)?;
+                Self::DeviceDisconnected { address, address_type, reason: *params.get(7)? }
+            }
+            EV_PHY_CONFIGURATION_CHANGED => {
+                Self::PhyConfigurationChanged(Phy::from_bits_truncate(read_u32(params, 0)?))
+            }
+            EV_NEW_CONN_PARAM => {
+                let (address, address_type) = decode_addr_info(params)?;
+                Self::NewConnectionParameters {
+                    address,
+                    address_type,
+                    store_hint: *params.get(7)? != 0,
+                    parameters: read_conn_params(params, 8)?,
+                }
+            }
+            EV_EXP_FEATURE_CHANGED => Self::ExperimentalFeatureChanged(ExperimentalFeature {
+                uuid: read_uuid(params, 0)?,
+                flags: read_u32(params, 16)?,
+            }),
+            _ => return None,
+        };
+        Some(event)
+    }
+}
+
+/// A command waiting for its response.
+struct PendingCommand {
+    id: u64,
+    index: u16,
+    opcode: u16,
+    tx: oneshot::Sender<Result<Vec<u8>>>,
+}
+
+/// Commands waiting for their responses.
+#[derive(Default)]
+struct Pending {
+    commands: VecDeque<PendingCommand>,
+    /// Set when the receive task has terminated and no more responses will arrive.
+    closed: bool,
+}
+
+/// State shared between a client and its receive task.
+struct Shared {
+    socket: Socket,
+    next_id: AtomicU64,
+    pending: Mutex<Pending>,
+    event_txs: Mutex<Vec<mpsc::UnboundedSender<(u16, Event)>>>,
+}
+
+impl Shared {
+    async fn recv_task(self: Arc<Self>) {
+        let mut buf = vec![0; MAX_PACKET_SIZE];
+        loop {
+            let n = match self.socket.recv(&mut buf).await {
+                Ok(n) => n,
+                Err(err) => {
+                    log::warn!("receiving from management socket failed: {}", &err);
+                    break;
+                }
+            };
+            if n < MGMT_HDR_SIZE {
+                continue;
+            }
+            let code = u16::from_le_bytes([buf[0], buf[1]]);
+            let index = u16::from_le_bytes([buf[2], buf[3]]);
+            let params = &buf[MGMT_HDR_SIZE..n];
+
+            match code {
+                EV_CMD_COMPLETE | EV_CMD_STATUS if params.len() >= 3 => {
+                    let opcode = u16::from_le_bytes([params[0], params[1]]);
+                    let status = Status::from(params[2]);
+                    if code == EV_CMD_STATUS && status == Status::Success {
+                        continue;
+                    }
+                    log::trace!("mgmt command 0x{:04x} on index {} completed: {}", opcode, index, status);
+                    let result = match status {
+                        Status::Success => Ok(params[3..].to_vec()),
+                        status => Err(status.into()),
+                    };
+                    let commands = &mut self.pending.lock().unwrap().commands;
+                    commands.retain(|p| !p.tx.is_closed());
+                    if let Some(pos) = commands.iter().position(|p| p.index == index && p.opcode == opcode) {
+                        let cmd = commands.remove(pos).unwrap();
+                        let _ = cmd.tx.send(result);
+                    }
+                }
+                _ => {
+                    let event = Event::decode(code, params);
+                    log::trace!("mgmt event on index {}: {:?}", index, &event);
+                    self.event_txs.lock().unwrap().retain(|tx| tx.send((index, event.clone())).is_ok());
+                }
+            }
+        }
+
+        let mut pending = self.pending.lock().unwrap();
+        pending.closed = true;
+        pending.commands.clear();
+        drop(pending);
+        self.event_txs.lock().unwrap().clear();
+    }
+}
+
+/// Removes a pending command when dropped, for example when the command is cancelled.
+struct PendingGuard<'a> {
+    shared: &'a Shared,
+    id: u64,
+}
+
+impl<'a> Drop for PendingGuard<'a> {
+    fn drop(&mut self) {
+        self.shared.pending.lock().unwrap().commands.retain(|p| p.id != self.id);
+    }
+}
+
+struct ClientInner {
+    shared: Arc<Shared>,
+    recv_task: JoinHandle<()>,
+}
+
+impl Drop for ClientInner {
+    fn drop(&mut self) {
+        self.recv_task.abort();
+    }
+}
+
+/// Client of the kernel management interface.
+///
+/// Commands can be sent concurrently and events are received by all
+/// event streams obtained using [events](Self::events).
+///
+/// Cloning the client is cheap and shares the underlying management socket.
+#[derive(Clone)]
+pub struct Client {
+    inner: Arc<ClientInner>,
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Client").finish()
+    }
+}
+
+impl Client {
+    /// Opens a management socket.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn new() -> Result<Self> {
+        let shared = Arc::new(Shared {
+            socket: Socket::new()?,
+            next_id: AtomicU64::new(0),
+            pending: Mutex::new(Pending::default()),
+            event_txs: Mutex::new(Vec::new()),
+        });
+        let recv_task = tokio::spawn(shared.clone().recv_task());
+        Ok(Self { inner: Arc::new(ClientInner { shared, recv_task }) })
+    }
+
+    /// Sends a command to the controller with the specified index and
+    /// waits for its completion.
+    ///
+    /// Use [INDEX_NONE] for commands that are not specific to a controller.
+    /// Returns the return parameters of the command.
+    /// A failed command is returned as an error containing its [Status].
+    /// Once receiving from the management socket has failed, an error of kind
+    /// [ErrorKind::BrokenPipe] is returned.
+    pub async fn command(&self, index: u16, opcode: u16, params: &[u8]) -> Result<Vec<u8>> {
+        let len: u16 = params
+            .len()
+            .try_into()
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "command parameters too long"))?;
+        let mut pkt = Vec::with_capacity(MGMT_HDR_SIZE + params.len());
+        pkt.extend_from_slice(&opcode.to_le_bytes());
+        pkt.extend_from_slice(&index.to_le_bytes());
+        pkt.extend_from_slice(&len.to_le_bytes());
+        pkt.extend_from_slice(params);
+
+        let shared = &self.inner.shared;
+        let id = shared.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        {
+            let mut pending = shared.pending.lock().unwrap();
+            if pending.closed {
+                return Err(socket_closed());
+            }
+            pending.commands.push_back(PendingCommand { id, index, opcode, tx });
+        }
+        let _pending_guard = PendingGuard { shared, id };
+
+        log::trace!("mgmt command 0x{:04x} on index {}: {:x?}", opcode, index, params);
+        shared.socket.send(&pkt).await?;
+
+        rx.await.map_err(|_| socket_closed())?
+    }
+
+    /// Streams events of all controllers.
+    ///
+    /// Each event is accompanied by the index of the controller it originates from.
+    pub fn events(&self) -> impl Stream<Item = (u16, Event)> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        self.inner.shared.event_txs.lock().unwrap().push(tx);
+        stream::poll_fn(move |cx| rx.poll_recv(cx))
+    }
+
+    /// Reads the version of the management interface.
+    pub async fn version(&self) -> Result<Version> {
+        let rsp = self.command(INDEX_NONE, OP_READ_VERSION, &[]).await?;
+        Ok(Version {
+            version: *rsp.first().ok_or_else(invalid_response)?,
+            revision: read_u16(&rsp, 1).ok_or_else(invalid_response)?,
+        })
+    }
+
+    /// Reads the indices of all configured controllers.
+    pub async fn controller_indices(&self) -> Result<Vec<u16>> {
+        let rsp = self.command(INDEX_NONE, OP_READ_INDEX_LIST, &[]).await?;
+        let count: usize = read_u16(&rsp, 0).ok_or_else(invalid_response)?.into();
+        (0..count).map(|i| read_u16(&rsp, 2 + 2 * i).ok_or_else(invalid_response)).collect()
+    }
+
+    /// Accesses the controller with the specified index, for example 0 for `hci0`.
+    pub fn controller(&self, index: u16) -> Controller {
+        Controller { client: self.clone(), index }
+    }
+
+    /// Reads experimental features that are not specific to a controller.
+    pub async fn experimental_features(&self) -> Result<Vec<ExperimentalFeature>> {
+        self.controller(INDEX_NONE).experimental_features().await
+    }
+
+    /// Enables or disables an experimental feature that is not specific to a controller.
+    pub async fn set_experimental_feature(&self, uuid: Uuid, enabled: bool) -> Result<ExperimentalFeature> {
+        self.controller(INDEX_NONE).set_experimental_feature(uuid, enabled).await
+    }
+}
+
+/// A controller accessed through the kernel management interface.
+#[derive(Clone, Debug)]
+pub struct Controller {
+    client: Client,
+    index: u16,
+}
+
+impl Controller {
+    /// Controller index.
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Management client used to access the controller.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Sends a command to the controller and waits for its completion.
+    ///
+    /// Returns the return parameters of the command.
+    pub async fn command(&self, opcode: u16, params: &[u8]) -> Result<Vec<u8>> {
+        self.client.command(self.index, opcode, params).await
+    }
+
+    /// Streams events of the controller.
+    pub fn events(&self) -> impl Stream<Item = Event> {
+        let index = self.index;
+        futures::StreamExt::filter_map(self.client.events(), move |(ev_index, event)| async move {
+            if ev_index == index {
+                Some(event)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Reads the controller information.
+    pub async fn info(&self) -> Result<ControllerInfo> {
+        let rsp = self.command(OP_READ_INFO, &[]).await?;
+        ControllerInfo::from_mgmt(&rsp).ok_or_else(invalid_response)
+    }
+
+    /// Powers the controller on or off.
+    ///
+    /// Returns the current [settings].
+    pub async fn set_powered(&self, powered: bool) -> Result<u32> {
+        let rsp = self.command(OP_SET_POWERED, &[powered.into()]).await?;
+        read_u32(&rsp, 0).ok_or_else(invalid_response)
+    }
+
+    /// Sets the static random address used for Bluetooth LE.
+    ///
+    /// The controller must be powered off.
+    /// Specify [Address::any] to remove a configured static address.
+    pub async fn set_static_address(&self, address: Address) -> Result<()> {
+        let addr: bdaddr_t = address.into();
+        self.command(OP_SET_STATIC_ADDRESS, &addr.b).await?;
+        Ok(())
+    }
+
+    /// Gets the PHY configuration.
+    pub async fn phy_configuration(&self) -> Result<PhyConfiguration> {
+        let rsp = self.command(OP_GET_PHY_CONFIGURATION, &[]).await?;
+        Ok(PhyConfiguration {
+            supported: Phy::from_bits_truncate(read_u32(&rsp, 0).ok_or_else(invalid_response)?),
+            configurable: Phy::from_bits_truncate(read_u32(&rsp, 4).ok_or_else(invalid_response)?),
+            selected: Phy::from_bits_truncate(read_u32(&rsp, 8).ok_or_else(invalid_response)?),
+        })
+    }
+
+    /// Selects the PHYs used by the controller.
+    ///
+    /// For Bluetooth LE these are the preferred PHYs of new connections.
+    /// PHYs that are not [configurable](PhyConfiguration::configurable) must remain selected.
+    pub async fn set_phy_configuration(&self, selected: Phy) -> Result<()> {
+        self.command(OP_SET_PHY_CONFIGURATION, &selected.bits().to_le_bytes()).await?;
+        Ok(())
+    }
+
+    /// Sets the keys that are rejected by the kernel.
+    ///
+    /// This replaces all previously blocked keys.
+    pub async fn set_blocked_keys(&self, keys: &[BlockedKey]) -> Result<()> {
+        let count: u16 =
+            keys.len().try_into().map_err(|_| Error::new(ErrorKind::InvalidInput, "too many blocked keys"))?;
+        let mut params = count.to_le_bytes().to_vec();
+        for key in keys {
+            params.push(match key.key_type {
+                BlockedKeyType::LinkKey => 0x00,
+                BlockedKeyType::LongTermKey => 0x01,
+                BlockedKeyType::IdentityResolvingKey => 0x02,
+            });
+            params.extend_from_slice(&key.value);
+        }
+        self.command(OP_SET_BLOCKED_KEYS, &params).await?;
+        Ok(())
+    }
+
+    /// Enables or disables wideband speech support.
+    ///
+    /// The controller must be powered off.
+    /// Returns the current [settings].
+    pub async fn set_wideband_speech(&self, enabled: bool) -> Result<u32> {
+        let rsp = self.command(OP_SET_WIDEBAND_SPEECH, &[enabled.into()]).await?;
+        read_u32(&rsp, 0).ok_or_else(invalid_response)
+    }
+
+    /// Reads the experimental features of the controller.
+    pub async fn experimental_features(&self) -> Result<Vec<ExperimentalFeature>> {
+        let rsp = self.command(OP_READ_EXP_FEATURES_INFO, &[]).await?;
+        let count: usize = read_u16(&rsp, 0).ok_or_else(invalid_response)?.into();
+        (0..count)
+            .map(|i| {
+                let pos = 2 + 20 * i;
+                Some(ExperimentalFeature { uuid: read_uuid(&rsp, pos)?, flags: read_u32(&rsp, pos + 16)? })
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(invalid_response)
+    }
+
+    /// Enables or disables an experimental feature of the controller.
+    pub async fn set_experimental_feature(&self, uuid: Uuid, enabled: bool) -> Result<ExperimentalFeature> {
+        let mut params = Vec::new();
+        encode_uuid(&mut params, &uuid);
+        params.push(enabled.into());
+        let rsp = self.command(OP_SET_EXP_FEATURE, &params).await?;
+        Ok(ExperimentalFeature {
+            uuid: read_uuid(&rsp, 0).ok_or_else(invalid_response)?,
+            flags: read_u32(&rsp, 16).ok_or_else(invalid_response)?,
+        })
+    }
+
+    /// Reads the default system configuration as a list of type and value pairs.
+    pub async fn default_system_configuration(&self) -> Result<Vec<(u16, Vec<u8>)>> {
+        let rsp = self.command(OP_READ_DEF_SYSTEM_CONFIG, &[]).await?;
+        let mut tlvs = Vec::new();
+        let mut pos = 0;
+        while pos < rsp.len() {
+            let ty = read_u16(&rsp, pos).ok_or_else(invalid_response)?;
+            let len: usize = (*rsp.get(pos + 2).ok_or_else(invalid_response)?).into();
+            let value = rsp.get(pos + 3..pos + 3 + len).ok_or_else(invalid_response)?;
+            tlvs.push((ty, value.to_vec()));
+            pos += 3 + len;
+        }
+        Ok(tlvs)
+    }
+
+    /// Gets the default LE connection parameters.
+    pub async fn default_connection_parameters(&self) -> Result<ConnectionParameters> {
+        Ok(decode_def_conn_params(&self.default_system_configuration().await?))
+    }
+
+    /// Sets the default LE connection parameters used for new connections.
+    pub async fn set_default_connection_parameters(&self, params: &ConnectionParameters) -> Result<()> {
+        self.command(OP_SET_DEF_SYSTEM_CONFIG, &encode_def_conn_params(params)).await?;
+        Ok(())
+    }
+
+    /// Loads the connection parameters used for the specified Bluetooth LE devices.
+    ///
+    /// When multiple devices are specified, previously loaded parameters of
+    /// devices that are not connected and not used for auto-connection are cleared.
+    /// The parameters take effect on the next connection to a device.
+    /// Recent kernels also update the parameters of an existing connection
+    /// when a single device is specified.
+    pub async fn load_connection_parameters(
+        &self, params: &[(Address, AddressType, ConnectionParameters)],
+    ) -> Result<()> {
+        let count: u16 = params
+            .len()
+            .try_into()
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "too many connection parameters"))?;
+        let mut buf = count.to_le_bytes().to_vec();
+        for (address, address_type, cp) in params {
+            if *address_type == AddressType::BrEdr {
+                return Err(Error::new(ErrorKind::InvalidInput, "connection parameters require an LE address"));
+            }
+            if !cp.is_valid() {
+                return Err(Error::new(ErrorKind::InvalidInput, "invalid connection parameters"));
+            }
+            encode_addr_info(&mut buf, *address, *address_type);
+            for value in [cp.min_interval, cp.max_interval, cp.latency, cp.supervision_timeout] {
+                buf.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        self.command(OP_LOAD_CONN_PARAM, &buf).await?;
+        Ok(())
+    }
+
+    /// Reads the local out-of-band pairing data of the controller.
+    pub async fn local_oob_data(&self) -> Result<OobData> {
+        let rsp = self.command(OP_READ_LOCAL_OOB_DATA, &[]).await?;
+        OobData::from_mgmt(&rsp).ok_or_else(invalid_response)
+    }
+
+    /// Reads the local Bluetooth LE out-of-band record of the controller.
+    pub async fn local_le_oob_record(&self) -> Result<LeOobRecord> {
+        let rsp = self.command(OP_READ_LOCAL_OOB_EXT_DATA, &[LeOobRecord::MGMT_ADDRESS_TYPES]).await?;
+        LeOobRecord::from_mgmt(&rsp).ok_or_else(invalid_response)
+    }
+}
@@ -0,0 +1,967 @@
+//! Capture of Bluetooth traffic using the HCI monitor channel.
+//!
+//! A [Monitor] receives a copy of all HCI packets exchanged between the host and
+//! all Bluetooth controllers, together with controller lifecycle notifications and
+//! system notes, similar to the `btmon` tool.
+//! Opening the monitor channel requires the `CAP_NET_RAW` capability.
+//!
+//! Captured [records](Record) can be stored in btsnoop or pcapng files
+//! using a [Writer] and read back using a [Reader] for offline analysis.
+//! Btsnoop files can be opened by `btmon -r` and both formats are understood by Wireshark.
+//!
+
+use futures::{ready, Stream};
+use libc::{c_int, timespec, AF_BLUETOOTH, SCM_TIMESTAMPNS, SOCK_RAW, SOL_SOCKET, SO_TIMESTAMPNS};
+use std::{
+    convert::TryInto,
+    fmt,
+    io::{Error, ErrorKind, Read, Result, Write},
+    mem::size_of,
+    os::unix::io::{AsRawFd, RawFd},
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::io::{unix::AsyncFd, ReadBuf};
+
+use crate::{
+    hci::{self, MAX_PACKET_SIZE},
+    sock::{self, OwnedFd},
+    sys::{bdaddr_t, BTPROTO_HCI},
+    Address,
+};
+
+const OP_NEW_INDEX: u16 = 0;
+const OP_DEL_INDEX: u16 = 1;
+const OP_COMMAND_PKT: u16 = 2;
+const OP_EVENT_PKT: u16 = 3;
+const OP_ACL_TX_PKT: u16 = 4;
+const OP_ACL_RX_PKT: u16 = 5;
+const OP_SCO_TX_PKT: u16 = 6;
+const OP_SCO_RX_PKT: u16 = 7;
+const OP_OPEN_INDEX: u16 = 8;
+const OP_CLOSE_INDEX: u16 = 9;
+const OP_INDEX_INFO: u16 = 10;
+const OP_VENDOR_DIAG: u16 = 11;
+const OP_SYSTEM_NOTE: u16 = 12;
+const OP_USER_LOGGING: u16 = 13;
+const OP_ISO_TX_PKT: u16 = 18;
+const OP_ISO_RX_PKT: u16 = 19;
+
+/// Size of the header preceding each packet on the monitor channel.
+const MONITOR_HDR_SIZE: usize = 6;
+
+/// Direction of a data packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    /// Sent by the host to the controller.
+    Sent,
+    /// Received by the host from the controller.
+    Received,
+}
+
+/// Packet captured on the monitor channel.
+///
+/// Data packets contain their HCI data header, i.e. the connection handle
+/// and length, followed by the payload.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Packet {
+    /// A controller has been added.
+    NewIndex {
+        /// Controller type.
+        controller_type: u8,
+        /// Bus the controller is attached to.
+        bus: u8,
+        /// Public address.
+        address: Address,
+        /// Name of the controller, for example `hci0`.
+        name: String,
+    },
+    /// A controller has been removed.
+    DeleteIndex,
+    /// Command sent to the controller.
+    Command(hci::Command),
+    /// Event received from the controller.
+    Event(hci::Event),
+    /// ACL data.
+    AclData {
+        /// Direction.
+        direction: Direction,
+        /// Data.
+        data: Vec<u8>,
+    },
+    /// SCO data.
+    ScoData {
+        /// Direction.
+        direction: Direction,
+        /// Data.
+        data: Vec<u8>,
+    },
+    /// ISO data.
+    IsoData {
+        /// Direction.
+        direction: Direction,
+        /// Data.
+        data: Vec<u8>,
+    },
+    /// A controller has been opened.
+    OpenIndex,
+    /// A controller has been closed.
+    CloseIndex,
+    /// Information about a controller.
+    IndexInfo {
+        /// Public address.
+        address: Address,
+        /// Manufacturer id.
+        manufacturer: u16,
+    },
+    /// Vendor-specific diagnostic data.
+    VendorDiagnostic(Vec<u8>),
+    /// Note from the kernel, for example the kernel version.
+    SystemNote(String),
+    /// Log message of a user-space program, for example the Bluetooth daemon.
+    UserLogging {
+        /// Syslog priority.
+        priority: u8,
+        /// Identity of the program.
+        ident: String,
+        /// Message.
+        message: String,
+    },
+    /// Other packet.
+    Other {
+        /// Monitor opcode.
+        opcode: u16,
+        /// Data.
+        data: Vec<u8>,
+    },
+}
+
+impl Packet {
+    /// Monitor opcode of the packet.
+    pub fn opcode(&self) -> u16 {
+        match self {
+            Self::NewIndex { .. } => OP_NEW_INDEX,
+            Self::DeleteIndex => OP_DEL_INDEX,
+            Self::Command(_) => OP_COMMAND_PKT,
+            Self::Event(_) => OP_EVENT_PKT,
+            Self::AclData { direction: Direction::Sent, .. } => OP_ACL_TX_PKT,
+            Self::AclData { direction: Direction::Received, .. } => OP_ACL_RX_PKT,
+            Self::ScoData { direction: Direction::Sent, .. } => OP_SCO_TX_PKT,
+            Self::ScoData { direction: Direction::Received, .. } => OP_SCO_RX_PKT,
+            Self::IsoData { direction: Direction::Sent, .. } => OP_ISO_TX_PKT,
+            Self::IsoData { direction: Direction::Received, .. } => OP_ISO_RX_PKT,
+            Self::OpenIndex => OP_OPEN_INDEX,
+            Self::CloseIndex => OP_CLOSE_INDEX,
+            Self::IndexInfo { .. } => OP_INDEX_INFO,
+            Self::VendorDiagnostic(_) => OP_VENDOR_DIAG,
+            Self::SystemNote(_) => OP_SYSTEM_NOTE,
+            Self::UserLogging { .. } => OP_USER_LOGGING,
+            Self::Other { opcode, .. } => *opcode,
+        }
+    }
+
+    /// Encodes the packet data following the monitor header.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let too_long = || Error::new(ErrorKind::InvalidInput, "monitor packet too long");
+        let mut buf = Vec::new();
+        match self {
+            Self::NewIndex { controller_type, bus, address, name } => {
+                let name = name.as_bytes();
+                if name.len() > 8 {
+                    return Err(Error::new(ErrorKind::InvalidInput, "controller name too long"));
+                }
+                buf.push(*controller_type);
+                buf.push(*bus);
+                buf.extend_from_slice(&bdaddr_t::from(*address).b);
+                buf.extend_from_slice(name);
+                buf.resize(16, 0);
+            }
+            Self::DeleteIndex | Self::OpenIndex | Self::CloseIndex => (),
+            Self::Command(cmd) => {
+                let params = cmd.params();
+                buf.extend_from_slice(&cmd.opcode().to_le_bytes());
+                buf.push(params.len().try_into().map_err(|_| too_long())?);
+                buf.extend_from_slice(&params);
+            }
+            Self::Event(evt) => {
+                let params = evt.params();
+                buf.push(evt.code());
+                buf.push(params.len().try_into().map_err(|_| too_long())?);
+                buf.extend_from_slice(&params);
+            }
+            Self::AclData { data, .. }
+            | Self::ScoData { data, .. }
+            | Self::IsoData { data, .. }
+            | Self::VendorDiagnostic(data)
+            | Self::Other { data, .. } => buf.extend_from_slice(data),
+            Self::IndexInfo { address, manufacturer } => {
+                buf.extend_from_slice(&bdaddr_t::from(*address).b);
+                buf.extend_from_slice(&manufacturer.to_le_bytes());
+            }
+            Self::SystemNote(note) => {
+                buf.extend_from_slice(note.as_bytes());
+                buf.push(0);
+            }
+            Self::UserLogging { priority, ident, message } => {
+                buf.push(*priority);
+                buf.push((ident.len() + 1).try_into().map_err(|_| too_long())?);
+                buf.extend_from_slice(ident.as_bytes());
+                buf.push(0);
+                buf.extend_from_slice(message.as_bytes());
+                buf.push(0);
+            }
+        }
+        if buf.len() > u16::MAX.into() {
+            return Err(too_long());
+        }
+        Ok(buf)
+    }
+
+    /// Decodes a packet from its monitor opcode and data.
+    ///
+    /// Malformed packets are returned as [Packet::Other].
+    pub fn decode(opcode: u16, data: &[u8]) -> Self {
+        Self::decode_known(opcode, data).unwrap_or_else(|| Self::Other { opcode, data: data.to_vec() })
+    }
+
+    fn decode_known(opcode: u16, data: &[u8]) -> Option<Self> {
+        let packet = match opcode {
+            OP_NEW_INDEX => {
+                if data.len() != 16 {
+                    return None;
+                }
+                Self::NewIndex {
+                    controller_type: data[0],
+                    bus: data[1],
+                    address: decode_address(&data[2..8])?,
+                    name: decode_str(&data[8..16]),
+                }
+            }
+            OP_DEL_INDEX if data.is_empty() => Self::DeleteIndex,
+            OP_COMMAND_PKT => {
+                if data.len() < 3 || data.len() != 3 + usize::from(data[2]) {
+                    return None;
+                }
+                Self::Command(hci::Command::decode(u16::from_le_bytes([data[0], data[1]]), &data[3..]).ok()?)
+            }
+            OP_EVENT_PKT => {
+                if data.len() < 2 || data.len() != 2 + usize::from(data[1]) {
+                    return None;
+                }
+                Self::Event(hci::Event::decode(data[0], &data[2..]).ok()?)
+            }
+            OP_ACL_TX_PKT => Self::AclData { direction: Direction::Sent, data: data.to_vec() },
+            OP_ACL_RX_PKT => Self::AclData { direction: Direction::Received, data: data.to_vec() },
+            OP_SCO_TX_PKT => Self::ScoData { direction: Direction::Sent, data: data.to_vec() },
+            OP_SCO_RX_PKT => Self::ScoData { direction: Direction::Received, data: data.to_vec() },
+            OP_ISO_TX_PKT => Self::IsoData { direction: Direction::Sent, data: data.to_vec() },
+            OP_ISO_RX_PKT => Self::IsoData { direction: Direction::Received, data: data.to_vec() },
+            OP_OPEN_INDEX if data.is_empty() => Self::OpenIndex,
+            OP_CLOSE_INDEX if data.is_empty() => Self::CloseIndex,
+            OP_INDEX_INFO => {
+                if data.len() != 8 {
+                    return None;
+                }
+                Self::IndexInfo {
+                    address: decode_address(&data[0..6])?,
+                    manufacturer: u16::from_le_bytes([data[6], data[7]]),
+                }
+            }
+            OP_VENDOR_DIAG => Self::VendorDiagnostic(data.to_vec()),
+            OP_SYSTEM_NOTE => Self::SystemNote(decode_str(data)),
+            OP_USER_LOGGING => {
+                let (&priority, data) = data.split_first()?;
+                let (&ident_len, data) = data.split_first()?;
+                let ident = data.get(..ident_len.into())?;
+                Self::UserLogging {
+                    priority,
+                    ident: decode_str(ident),
+                    message: decode_str(&data[ident.len()..]),
+                }
+            }
+            _ => return None,
+        };
+        Some(packet)
+    }
+}
+
+fn decode_address(buf: &[u8]) -> Option<Address> {
+    Some(Address::from(bdaddr_t { b: buf.try_into().ok()? }))
+}
+
+/// Decodes a string that is optionally null-terminated.
+fn decode_str(buf: &[u8]) -> String {
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+/// Captured packet together with its controller index and timestamp.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Record {
+    /// Controller index, for example 0 for `hci0`.
+    ///
+    /// This is [hci::DEV_NONE] for packets not specific to a controller.
+    pub index: u16,
+    /// Time of capture.
+    pub timestamp: SystemTime,
+    /// Packet.
+    pub packet: Packet,
+}
+
+/// Live capture from the HCI monitor channel.
+///
+/// Use [recv](Self::recv) or the [Stream] implementation to receive captured records.
+/// On opening, the kernel reports all existing controllers and their state.
+pub struct Monitor {
+    fd: AsyncFd<OwnedFd>,
+    buf: Vec<u8>,
+}
+
+impl fmt::Debug for Monitor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Monitor").field(&self.fd.as_raw_fd()).finish()
+    }
+}
+
+impl Monitor {
+    /// Opens the HCI monitor channel.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn open() -> Result<Self> {
+        let fd = sock::socket(AF_BLUETOOTH, SOCK_RAW, BTPROTO_HCI)?;
+        sock::bind(&fd, hci::SocketAddr::monitor())?;
+        let enable: c_int = 1;
+        sock::setsockopt(&fd, SOL_SOCKET, SO_TIMESTAMPNS, &enable)?;
+        Ok(Self { fd: AsyncFd::new(fd)?, buf: vec![0; MONITOR_HDR_SIZE + MAX_PACKET_SIZE] })
+    }
+
+    /// Receives the next captured record.
+    pub async fn recv(&mut self) -> Result<Record> {
+        futures::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Attempts to receive the next captured record.
+    pub fn poll_recv(&mut self, cx: &mut Context) -> Poll<Result<Record>> {
+        let mut buf = ReadBuf::new(&mut self.buf);
+        let cmsgs = loop {
+            let mut guard = ready!(self.fd.poll_read_ready(cx))?;
+            match guard.try_io(|inner| sock::recvmsg(inner.get_ref(), &mut buf, 0)) {
+                Ok(result) => break result?.1,
+                Err(_would_block) => continue,
+            }
+        };
+        let buf = buf.filled();
+
+        if buf.len() < MONITOR_HDR_SIZE {
+            return Poll::Ready(Err(Error::new(ErrorKind::InvalidData, "truncated monitor header")));
+        }
+        let opcode = u16::from_le_bytes([buf[0], buf[1]]);
+        let index = u16::from_le_bytes([buf[2], buf[3]]);
+        let len = usize::from(u16::from_le_bytes([buf[4], buf[5]]));
+        let data = &buf[MONITOR_HDR_SIZE..];
+        if data.len() != len {
+            return Poll::Ready(Err(Error::new(ErrorKind::InvalidData, "invalid monitor packet length")));
+        }
+
+        let timestamp = cmsgs
+            .iter()
+            .find(|cmsg| {
+                cmsg.level == SOL_SOCKET && cmsg.ty == SCM_TIMESTAMPNS && cmsg.data.len() >= size_of::<timespec>()
+            })
+            .map(|cmsg| {
+                let ts: timespec = unsafe { (cmsg.data.as_ptr() as *const timespec).read_unaligned() };
+                UNIX_EPOCH + Duration::new(ts.tv_sec as _, ts.tv_nsec as _)
+            })
+            .unwrap_or_else(SystemTime::now);
+
+        Poll::Ready(Ok(Record { index, timestamp, packet: Packet::decode(opcode, data) }))
+    }
+}
+
+impl Stream for Monitor {
+    type Item = Result<Record>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_recv(cx).map(Some)
+    }
+}
+
+impl AsRawFd for Monitor {
+    /// Get underlying file descriptor.
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// Capture file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Format {
+    /// Btsnoop with the Linux monitor datalink type 2001.
+    Btsnoop,
+    /// Pcapng with link type `LINKTYPE_BLUETOOTH_LINUX_MONITOR` (254).
+    Pcapng,
+}
+
+const BTSNOOP_MAGIC: &[u8; 8] = b"btsnoop\0";
+const BTSNOOP_VERSION: u32 = 1;
+const BTSNOOP_TYPE_MONITOR: u32 = 2001;
+/// Microseconds between the btsnoop epoch, i.e. midnight January 1st 0 AD, and the Unix epoch.
+const BTSNOOP_EPOCH_DELTA: i64 = 0x00dc_ddb3_0f2f_8000;
+
+const PCAPNG_SHB: u32 = 0x0a0d_0d0a;
+const PCAPNG_IDB: u32 = 0x0000_0001;
+const PCAPNG_EPB: u32 = 0x0000_0006;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+const PCAPNG_OPT_END: u16 = 0;
+const PCAPNG_OPT_IF_TSRESOL: u16 = 9;
+const LINKTYPE_BLUETOOTH_LINUX_MONITOR: u16 = 254;
+/// Maximum size of a pcapng block that is read into memory.
+const PCAPNG_MAX_BLOCK_SIZE: usize = 1 << 20;
+
+/// Signed number of microseconds since the Unix epoch.
+fn unix_micros(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_micros() as i64,
+        Err(err) => -(err.duration().as_micros() as i64),
+    }
+}
+
+fn from_unix_micros(micros: i64) -> SystemTime {
+    if micros >= 0 {
+        UNIX_EPOCH + Duration::from_micros(micros as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_micros(micros.unsigned_abs())
+    }
+}
+
+/// Writes captured records to a btsnoop or pcapng file.
+#[derive(Debug)]
+pub struct Writer<W> {
+    inner: W,
+    format: Format,
+}
+
+impl<W: Write> Writer<W> {
+    /// Creates a new writer and writes the file header.
+    pub fn new(mut inner: W, format: Format) -> Result<Self> {
+        match format {
+            Format::Btsnoop => {
+                inner.write_all(BTSNOOP_MAGIC)?;
+                inner.write_all(&BTSNOOP_VERSION.to_be_bytes())?;
+                inner.write_all(&BTSNOOP_TYPE_MONITOR.to_be_bytes())?;
+            }
+            Format::Pcapng => {
+                let mut shb = Vec::new();
+                shb.extend_from_slice(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+                shb.extend_from_slice(&1u16.to_le_bytes());
+                shb.extend_from_slice(&0u16.to_le_bytes());
+                shb.extend_from_slice(&(-1i64).to_le_bytes());
+                write_pcapng_block(&mut inner, PCAPNG_SHB, &shb)?;
+
+                let mut idb = Vec::new();
+                idb.extend_from_slice(&LINKTYPE_BLUETOOTH_LINUX_MONITOR.to_le_bytes());
+                idb.extend_from_slice(&0u16.to_le_bytes());
+                idb.extend_from_slice(&0u32.to_le_bytes());
+                write_pcapng_block(&mut inner, PCAPNG_IDB, &idb)?;
+            }
+        }
+        Ok(Self { inner, format })
+    }
+
+    /// File format.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Writes a record.
+    pub fn write(&mut self, record: &Record) -> Result<()> {
+        let data = record.packet.encode()?;
+        let micros = unix_micros(record.timestamp);
+        match self.format {
+            Format::Btsnoop => {
+                let len = data.len() as u32;
+                let flags = u32::from(record.index) << 16 | u32::from(record.packet.opcode());
+                let mut hdr = Vec::with_capacity(24);
+                hdr.extend_from_slice(&len.to_be_bytes());
+                hdr.extend_from_slice(&len.to_be_bytes());
+                hdr.extend_from_slice(&flags.to_be_bytes());
+                hdr.extend_from_slice(&0u32.to_be_bytes());
+                hdr.extend_from_slice(&(micros + BTSNOOP_EPOCH_DELTA).to_be_bytes());
+                self.inner.write_all(&hdr)?;
+                self.inner.write_all(&data)?;
+            }
+            Format::Pcapng => {
+                let ts = micros as u64;
+                let len = (4 + data.len()) as u32;
+                let mut epb = Vec::with_capacity(24 + data.len());
+                epb.extend_from_slice(&0u32.to_le_bytes());
+                epb.extend_from_slice(&((ts >> 32) as u32).to_le_bytes());
+                epb.extend_from_slice(&(ts as u32).to_le_bytes());
+                epb.extend_from_slice(&len.to_le_bytes());
+                epb.extend_from_slice(&len.to_le_bytes());
+                epb.extend_from_slice(&record.index.to_be_bytes());
+                epb.extend_from_slice(&record.packet.opcode().to_be_bytes());
+                epb.extend_from_slice(&data);
+                write_pcapng_block(&mut self.inner, PCAPNG_EPB, &epb)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes buffered data to the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Consumes this writer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Writes a little endian pcapng block, padding the body to 32 bits.
+fn write_pcapng_block(w: &mut impl Write, block_type: u32, body: &[u8]) -> Result<()> {
+    let padding = (4 - body.len() % 4) % 4;
+    let len = (12 + body.len() + padding) as u32;
+    w.write_all(&block_type.to_le_bytes())?;
+    w.write_all(&len.to_le_bytes())?;
+    w.write_all(body)?;
+    w.write_all(&[0; 3][..padding])?;
+    w.write_all(&len.to_le_bytes())
+}
+
+/// Reads exactly the size of the buffer unless the end of file is reached before the first byte.
+///
+/// Returns whether the buffer was filled.
+fn read_exact_or_eof(r: &mut impl Read, buf: &mut [u8]) -> Result<bool> {
+    let mut pos = 0;
+    while pos < buf.len() {
+        match r.read(&mut buf[pos..]) {
+            Ok(0) if pos == 0 => return Ok(false),
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(n) => pos += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(true)
+}
+
+fn invalid_data(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// Interface described by a pcapng interface description block.
+#[derive(Debug, Clone, Copy)]
+struct PcapngInterface {
+    link_type: u16,
+    tsresol: u8,
+}
+
+impl PcapngInterface {
+    /// Converts a timestamp in units of the interface resolution to a duration since the Unix epoch.
+    fn duration(&self, ts: u64) -> Duration {
+        let nanos = if self.tsresol & 0x80 == 0 {
+            u128::from(ts) * 1_000_000_000 / 10u128.pow(u32::from(self.tsresol).min(38))
+        } else {
+            (u128::from(ts) * 1_000_000_000) >> (self.tsresol & 0x7f).min(127)
+        };
+        Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32)
+    }
+}
+
+/// State of a reader, depending on the file format.
+#[derive(Debug)]
+enum ReaderState {
+    Btsnoop,
+    Pcapng { big_endian: bool, interfaces: Vec<PcapngInterface> },
+}
+
+/// Reads captured records from a btsnoop or pcapng file.
+///
+/// The file format is detected automatically.
+/// Records can be read using [read](Self::read) or the [Iterator] implementation.
+#[derive(Debug)]
+pub struct Reader<R> {
+    inner: R,
+    state: ReaderState,
+}
+
+impl<R: Read> Reader<R> {
+    /// Creates a new reader and reads the file header.
+    pub fn new(mut inner: R) -> Result<Self> {
+        let mut magic = [0; 4];
+        inner.read_exact(&mut magic)?;
+        let state = if magic == BTSNOOP_MAGIC[..4] {
+            let mut hdr = [0; 12];
+            inner.read_exact(&mut hdr)?;
+            if hdr[..4] != BTSNOOP_MAGIC[4..] {
+                return Err(invalid_data("invalid btsnoop header"));
+            }
+            let version = u32::from_be_bytes(hdr[4..8].try_into().unwrap());
+            let datalink = u32::from_be_bytes(hdr[8..12].try_into().unwrap());
+            if version != BTSNOOP_VERSION {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("btsnoop version {} is not supported", version),
+                ));
+            }
+            if datalink != BTSNOOP_TYPE_MONITOR {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("btsnoop datalink type {} is not supported", datalink),
+                ));
+            }
+            ReaderState::Btsnoop
+        } else if u32::from_le_bytes(magic) == PCAPNG_SHB {
+            let mut state = ReaderState::Pcapng { big_endian: false, interfaces: Vec::new() };
+            Self::read_pcapng_shb(&mut inner, &mut state)?;
+            state
+        } else {
+            return Err(invalid_data("unknown capture file format"));
+        };
+        Ok(Self { inner, state })
+    }
+
+    /// File format.
+    pub fn format(&self) -> Format {
+        match self.state {
+            ReaderState::Btsnoop => Format::Btsnoop,
+            ReaderState::Pcapng { .. } => Format::Pcapng,
+        }
+    }
+
+    /// Reads the next record.
+    ///
+    /// Returns `None` when the end of the file has been reached.
+    pub fn read(&mut self) -> Result<Option<Record>> {
+        match self.state {
+            ReaderState::Btsnoop => self.read_btsnoop(),
+            ReaderState::Pcapng { .. } => self.read_pcapng(),
+        }
+    }
+
+    fn read_btsnoop(&mut self) -> Result<Option<Record>> {
+        let mut hdr = [0; 24];
+        if !read_exact_or_eof(&mut self.inner, &mut hdr)? {
+            return Ok(None);
+        }
+        let len = u32::from_be_bytes(hdr[4..8].try_into().unwrap()) as usize;
+        let flags = u32::from_be_bytes(hdr[8..12].try_into().unwrap());
+        let ts = i64::from_be_bytes(hdr[16..24].try_into().unwrap());
+        if len > u16::MAX.into() {
+            return Err(invalid_data("btsnoop record too long"));
+        }
+        let mut data = vec![0; len];
+        self.inner.read_exact(&mut data)?;
+
+        Ok(Some(Record {
+            index: (flags >> 16) as u16,
+            timestamp: from_unix_micros(ts.wrapping_sub(BTSNOOP_EPOCH_DELTA)),
+            packet: Packet::decode(flags as u16, &data),
+        }))
+    }
+
+    /// Reads the remainder of a section header block after its block type.
+    fn read_pcapng_shb(r: &mut R, state: &mut ReaderState) -> Result<()> {
+        let mut hdr = [0; 8];
+        r.read_exact(&mut hdr)?;
+        let big_endian = match u32::from_le_bytes(hdr[4..8].try_into().unwrap()) {
+            PCAPNG_BYTE_ORDER_MAGIC => false,
+            m if m.swap_bytes() == PCAPNG_BYTE_ORDER_MAGIC => true,
+            _ => return Err(invalid_data("invalid pcapng byte order magic")),
+        };
+        let raw_len: [u8; 4] = hdr[0..4].try_into().unwrap();
+        let len = if big_endian { u32::from_be_bytes(raw_len) } else { u32::from_le_bytes(raw_len) } as usize;
+        if len < 28 || len % 4 != 0 || len > PCAPNG_MAX_BLOCK_SIZE {
+            return Err(invalid_data("invalid pcapng section header block length"));
+        }
+        let mut rest = vec![0; len - 12];
+        r.read_exact(&mut rest)?;
+        *state = ReaderState::Pcapng { big_endian, interfaces: Vec::new() };
+        Ok(())
+    }
+
+    fn read_pcapng(&mut self) -> Result<Option<Record>> {
+        loop {
+            let mut raw_type = [0; 4];
+            if !read_exact_or_eof(&mut self.inner, &mut raw_type)? {
+                return Ok(None);
+            }
+            if u32::from_le_bytes(raw_type) == PCAPNG_SHB {
+                Self::read_pcapng_shb(&mut self.inner, &mut self.state)?;
+                continue;
+            }
+
+            let (big_endian, interfaces) = match &mut self.state {
+                ReaderState::Pcapng { big_endian, interfaces } => (*big_endian, interfaces),
+                ReaderState::Btsnoop => unreachable!(),
+            };
+            let u16_at = |buf: &[u8], pos: usize| {
+                let b = [buf[pos], buf[pos + 1]];
+                if big_endian {
+                    u16::from_be_bytes(b)
+                } else {
+                    u16::from_le_bytes(b)
+                }
+            };
+            let u32_at = |buf: &[u8], pos: usize| {
+                let b = buf[pos..pos + 4].try_into().unwrap();
+                if big_endian {
+                    u32::from_be_bytes(b)
+                } else {
+                    u32::from_le_bytes(b)
+                }
+            };
+
+            let block_type = u32_at(&raw_type, 0);
+            let mut raw_len = [0; 4];
+            self.inner.read_exact(&mut raw_len)?;
+            let len = u32_at(&raw_len, 0) as usize;
+            if len < 12 || len % 4 != 0 || len > PCAPNG_MAX_BLOCK_SIZE {
+                return Err(invalid_data("invalid pcapng block length"));
+            }
+            let mut body = vec![0; len - 8];
+            self.inner.read_exact(&mut body)?;
+            let body = &body[..len - 12];
+
+            match block_type {
+                PCAPNG_IDB => {
+                    if body.len() < 8 {
+                        return Err(invalid_data("truncated pcapng interface description block"));
+                    }
+                    let mut interface = PcapngInterface { link_type: u16_at(body, 0), tsresol: 6 };
+                    let mut pos = 8;
+                    while pos + 4 <= body.len() {
+                        let code = u16_at(body, pos);
+                        let opt_len = usize::from(u16_at(body, pos + 2));
+                        if code == PCAPNG_OPT_END {
+                            break;
+                        }
+                        if code == PCAPNG_OPT_IF_TSRESOL && opt_len == 1 && pos + 4 < body.len() {
+                            interface.tsresol = body[pos + 4];
+                        }
+                        pos += 4 + (opt_len + 3) / 4 * 4;
+                    }
+                    interfaces.push(interface);
+                }
+                PCAPNG_EPB => {
+                    if body.len() < 20 {
+                        return Err(invalid_data("truncated pcapng enhanced packet block"));
+                    }
+                    let interface = interfaces
+                        .get(u32_at(body, 0) as usize)
+                        .ok_or_else(|| invalid_data("unknown pcapng interface"))?;
+                    if interface.link_type != LINKTYPE_BLUETOOTH_LINUX_MONITOR {
+                        return Err(Error::new(
+                            ErrorKind::Unsupported,
+                            format!("pcapng link type {} is not supported", interface.link_type),
+                        ));
+                    }
+                    let ts = u64::from(u32_at(body, 4)) << 32 | u64::from(u32_at(body, 8));
+                    let cap_len = u32_at(body, 12) as usize;
+                    let data =
+                        body.get(20..20 + cap_len).ok_or_else(|| invalid_data("truncated pcapng packet"))?;
+                    if data.len() < 4 {
+                        return Err(invalid_data("truncated monitor pseudo-header"));
+                    }
+                    return Ok(Some(Record {
+                        index: u16::from_be_bytes([data[0], data[1]]),
+                        timestamp: UNIX_EPOCH + interface.duration(ts),
+                        packet: Packet::decode(u16::from_be_bytes([data[2], data[3]]), &data[4..]),
+                    }));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Consumes this reader, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: Address = Address::new([0x00, 0x1a, 0x7d, 0xda, 0x71, 0x13]);
+
+    fn fixture(name: &str) -> Vec<u8> {
+        let path = format!("{}/tests/fixtures/monitor/{}", env!("CARGO_MANIFEST_DIR"), name);
+        std::fs::read(path).unwrap()
+    }
+
+    /// Controller index and packet of each record contained in the fixtures.
+    fn records() -> Vec<(u16, Packet)> {
+        vec![
+            (hci::DEV_NONE, Packet::SystemNote("Linux version 6.1.0 (x86_64)".to_string())),
+            (0, Packet::NewIndex { controller_type: 0, bus: 1, address: ADDRESS, name: "hci0".to_string() }),
+            (0, Packet::OpenIndex),
+            (0, Packet::IndexInfo { address: ADDRESS, manufacturer: 10 }),
+            (0, Packet::Command(hci::Command::Reset)),
+            (
+                0,
+                Packet::Event(hci::Event::CommandComplete {
+                    num_packets: 1,
+                    opcode: 0x0c03,
+                    return_params: vec![0],
+                }),
+            ),
+            (
+                0,
+                Packet::AclData {
+                    direction: Direction::Received,
+                    data: vec![0x40, 0x20, 0x04, 0x00, 0x01, 0x02, 0x03, 0x04],
+                },
+            ),
+            (
+                hci::DEV_NONE,
+                Packet::UserLogging {
+                    priority: 6,
+                    ident: "bluetoothd".to_string(),
+                    message: "Bluetooth daemon 5.66".to_string(),
+                },
+            ),
+        ]
+    }
+
+    fn read_all(data: &[u8]) -> Vec<Record> {
+        Reader::new(data).unwrap().collect::<Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn read_btsnoop() {
+        let read = read_all(&fixture("capture.btsnoop"));
+        assert_eq!(read.len(), records().len());
+        for (i, (record, (index, packet))) in read.into_iter().zip(records()).enumerate() {
+            assert_eq!(record.index, index);
+            assert_eq!(record.packet, packet);
+            let micros = 1_700_000_000_000_000 + i as u64 * 1000 + 123;
+            assert_eq!(record.timestamp, UNIX_EPOCH + Duration::from_micros(micros));
+        }
+    }
+
+    #[test]
+    fn read_pcapng_big_endian_nanoseconds() {
+        let data = fixture("capture.pcapng");
+        let mut reader = Reader::new(&data[..]).unwrap();
+        assert_eq!(reader.format(), Format::Pcapng);
+        let read = reader.by_ref().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(read.len(), records().len());
+        for (i, (record, (index, packet))) in read.into_iter().zip(records()).enumerate() {
+            assert_eq!(record.index, index);
+            assert_eq!(record.packet, packet);
+            let nanos = 1_700_000_000_000_000_000 + i as u64 * 1_000_000 + 123_456;
+            assert_eq!(record.timestamp, UNIX_EPOCH + Duration::from_nanos(nanos));
+        }
+    }
+
+    #[test]
+    fn write_btsnoop() {
+        let mut writer = Writer::new(Vec::new(), Format::Btsnoop).unwrap();
+        for record in read_all(&fixture("capture.btsnoop")) {
+            writer.write(&record).unwrap();
+        }
+        assert_eq!(writer.into_inner(), fixture("capture.btsnoop"));
+    }
+
+    #[test]
+    fn write_pcapng() {
+        let records = read_all(&fixture("capture.btsnoop"));
+        let mut writer = Writer::new(Vec::new(), Format::Pcapng).unwrap();
+        for record in &records {
+            writer.write(record).unwrap();
+        }
+        let data = writer.into_inner();
+        assert_eq!(data.len() % 4, 0);
+
+        let mut reader = Reader::new(&data[..]).unwrap();
+        assert_eq!(reader.format(), Format::Pcapng);
+        assert_eq!(reader.by_ref().collect::<Result<Vec<_>>>().unwrap(), records);
+    }
+
+    #[test]
+    fn write_before_unix_epoch() {
+        let record = Record {
+            index: 0,
+            timestamp: UNIX_EPOCH - Duration::from_micros(1_500_000),
+            packet: Packet::CloseIndex,
+        };
+        let mut writer = Writer::new(Vec::new(), Format::Btsnoop).unwrap();
+        writer.write(&record).unwrap();
+        assert_eq!(read_all(&writer.into_inner()), vec![record]);
+    }
+
+    #[test]
+    fn malformed_packets() {
+        assert_eq!(
+            Packet::decode(OP_COMMAND_PKT, &[0x03, 0x0c]),
+            Packet::Other { opcode: OP_COMMAND_PKT, data: vec![0x03, 0x0c] }
+        );
+        assert_eq!(
+            Packet::decode(OP_NEW_INDEX, &[0; 4]),
+            Packet::Other { opcode: OP_NEW_INDEX, data: vec![0; 4] }
+        );
+        assert_eq!(
+            Packet::decode(OP_USER_LOGGING, &[6, 20, b'a']),
+            Packet::Other { opcode: OP_USER_LOGGING, data: vec![6, 20, b'a'] }
+        );
+        assert_eq!(Packet::decode(0x1234, &[1]), Packet::Other { opcode: 0x1234, data: vec![1] });
+
+        let name_too_long =
+            Packet::NewIndex { controller_type: 0, bus: 0, address: ADDRESS, name: "controller".to_string() };
+        assert_eq!(name_too_long.encode().unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn reject_invalid_files() {
+        let err = Reader::new(&b"not a capture file"[..]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let mut h4 = fixture("capture.btsnoop");
+        h4[12..16].copy_from_slice(&1002u32.to_be_bytes());
+        assert_eq!(Reader::new(&h4[..]).unwrap_err().kind(), ErrorKind::Unsupported);
+
+        let mut truncated = fixture("capture.btsnoop");
+        truncated.truncate(truncated.len() - 1);
+        let result: Result<Vec<_>> = Reader::new(&truncated[..]).unwrap().collect();
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+
+        let mut other_link_type = fixture("capture.pcapng");
+        let idb = u32::from_be_bytes(other_link_type[4..8].try_into().unwrap()) as usize;
+        other_link_type[idb + 8..idb + 10].copy_from_slice(&201u16.to_be_bytes());
+        let result: Result<Vec<_>> = Reader::new(&other_link_type[..]).unwrap().collect();
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::Unsupported);
+
+        let mut bad_block_len = fixture("capture.pcapng");
+        bad_block_len[idb + 4..idb + 8].copy_from_slice(&13u32.to_be_bytes());
+        let result: Result<Vec<_>> = Reader::new(&bad_block_len[..]).unwrap().collect();
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
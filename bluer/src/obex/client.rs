@@ -0,0 +1,254 @@
+//! OBEX client.
+
+use std::io::{Error, ErrorKind, Result};
+use uuid::Uuid;
+
+use super::{
+    header_id, ConnectParams, Header, Opcode, Request, Response, ResponseCode, Transport, HEADER_HDR_LEN,
+    MIN_PACKET_LEN, PACKET_HDR_LEN, SET_PATH_BACKUP,
+};
+
+/// OBEX client.
+///
+/// Connects to an OBEX server and performs operations on it.
+/// Objects are split into multiple packets as required by the maximum
+/// packet length negotiated with the server.
+/// Single response mode (SRM) is used when supported by the transport and the server.
+#[derive(Debug)]
+pub struct Client<T> {
+    transport: T,
+    max_packet_len: u16,
+    connection_id: Option<u32>,
+}
+
+impl<T> Client<T>
+where
+    T: Transport,
+{
+    /// Establishes an OBEX session over the specified transport.
+    ///
+    /// If a target is specified, the session is directed to the corresponding
+    /// service of the server, for example [FILE_TRANSFER](super::target::FILE_TRANSFER).
+    pub async fn connect(transport: T, target: Option<&Uuid>) -> Result<Self> {
+        let headers = target.map(|target| vec![Header::target(target)]).unwrap_or_default();
+        let (client, _) = Self::connect_with_headers(transport, headers).await?;
+        Ok(client)
+    }
+
+    /// Establishes an OBEX session sending the specified headers in the CONNECT request.
+    ///
+    /// Returns the client and the response of the server.
+    pub async fn connect_with_headers(transport: T, headers: Vec<Header>) -> Result<(Self, Response)> {
+        let mut this = Self { max_packet_len: transport.max_packet_len(), transport, connection_id: None };
+
+        let req = Request {
+            connect: Some(ConnectParams::new(this.max_packet_len)),
+            headers,
+            ..Request::new(Opcode::Connect)
+        };
+        this.transport.send(&req.to_bytes()?).await?;
+        let rsp = this.recv_response(true).await?;
+        if !rsp.code.is_success() {
+            return Err(rsp.code.into());
+        }
+
+        let params = rsp.connect.unwrap();
+        this.max_packet_len = this.max_packet_len.min(params.max_packet_len).max(MIN_PACKET_LEN);
+        this.connection_id = rsp.header(header_id::CONNECTION_ID).and_then(Header::as_u32);
+
+        Ok((this, rsp))
+    }
+
+    /// Connection id assigned by the server.
+    pub fn connection_id(&self) -> Option<u32> {
+        self.connection_id
+    }
+
+    /// Maximum packet length negotiated with the server.
+    pub fn max_packet_len(&self) -> u16 {
+        self.max_packet_len
+    }
+
+    /// Gets a reference to the underlying transport.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Consumes the client, returning the underlying transport.
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    async fn recv_response(&mut self, connect: bool) -> Result<Response> {
+        match self.transport.recv().await? {
+            Some(packet) => Ok(Response::from_bytes(&packet, connect)?),
+            None => Err(ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    /// Headers sent at the start of each request.
+    fn session_headers(&self) -> Vec<Header> {
+        self.connection_id.map(|id| vec![Header::connection_id(id)]).unwrap_or_default()
+    }
+
+    fn srm_headers(&self) -> Vec<Header> {
+        if self.transport.srm_supported() {
+            vec![Header::srm(true)]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Sends a single request packet and receives the response.
+    ///
+    /// The connection id is not added automatically.
+    pub async fn request(&mut self, req: &Request) -> Result<Response> {
+        let packet = req.to_bytes()?;
+        if packet.len() > self.max_packet_len as usize {
+            return Err(Error::new(ErrorKind::InvalidInput, "OBEX request exceeds maximum packet length"));
+        }
+        self.transport.send(&packet).await?;
+        self.recv_response(req.opcode == Opcode::Connect).await
+    }
+
+    /// Sends an object to the server.
+    ///
+    /// The headers, for example [name](Header::name) and [type](Header::mime_type), describe the object.
+    /// Returns the final response of the server.
+    pub async fn put(&mut self, headers: Vec<Header>, body: &[u8]) -> Result<Response> {
+        let mut first_headers = self.session_headers();
+        first_headers.extend(headers);
+        first_headers.extend(self.srm_headers());
+
+        let mut headers = first_headers;
+        let mut remaining = body;
+        let mut srm = false;
+        let mut first = true;
+        loop {
+            let headers_len: usize = headers.iter().map(Header::encoded_len).sum();
+            let space = (self.max_packet_len as usize)
+                .checked_sub(PACKET_HDR_LEN + headers_len + HEADER_HDR_LEN)
+                .ok_or_else(|| {
+                    Error::new(ErrorKind::InvalidInput, "OBEX headers exceed maximum packet length")
+                })?;
+
+            let final_packet = remaining.len() <= space;
+            let (chunk, rest) = remaining.split_at(remaining.len().min(space));
+            remaining = rest;
+            headers.push(if final_packet {
+                Header::end_of_body(chunk.to_vec())
+            } else {
+                Header::body(chunk.to_vec())
+            });
+
+            let req = Request { final_packet, headers, ..Request::new(Opcode::Put) };
+            self.transport.send(&req.to_bytes()?).await?;
+
+            if final_packet {
+                let rsp = self.recv_response(false).await?;
+                return if rsp.code.is_success() { Ok(rsp) } else { Err(rsp.code.into()) };
+            }
+
+            if !srm {
+                let rsp = self.recv_response(false).await?;
+                if rsp.code != ResponseCode::Continue {
+                    return Err(rsp.code.into());
+                }
+                if first {
+                    srm = self.transport.srm_supported()
+                        && rsp.header(header_id::SRM).and_then(Header::as_u8) == Some(0x01);
+                }
+            }
+
+            headers = Vec::new();
+            first = false;
+        }
+    }
+
+    /// Retrieves an object from the server.
+    ///
+    /// The headers, for example [name](Header::name) and [type](Header::mime_type), identify the object.
+    /// Returns the headers received from the server, not including the body, and the object body.
+    pub async fn get(&mut self, headers: Vec<Header>) -> Result<(Vec<Header>, Vec<u8>)> {
+        let mut req_headers = self.session_headers();
+        req_headers.extend(headers);
+        req_headers.extend(self.srm_headers());
+        let req = Request { headers: req_headers, ..Request::new(Opcode::Get) };
+        let packet = req.to_bytes()?;
+        if packet.len() > self.max_packet_len as usize {
+            return Err(Error::new(ErrorKind::InvalidInput, "OBEX headers exceed maximum packet length"));
+        }
+        self.transport.send(&packet).await?;
+
+        let mut rsp_headers = Vec::new();
+        let mut body = Vec::new();
+        let mut srm = false;
+        loop {
+            let rsp = self.recv_response(false).await?;
+            if rsp.code != ResponseCode::Continue && !rsp.code.is_success() {
+                return Err(rsp.code.into());
+            }
+
+            for header in rsp.headers {
+                match header.id {
+                    header_id::BODY | header_id::END_OF_BODY => {
+                        body.extend(header.as_bytes().unwrap_or_default())
+                    }
+                    header_id::SRM => {
+                        srm = self.transport.srm_supported() && header.as_u8() == Some(0x01);
+                    }
+                    _ => rsp_headers.push(header),
+                }
+            }
+
+            if rsp.code.is_success() {
+                return Ok((rsp_headers, body));
+            }
+            if !srm {
+                let req = Request { headers: self.session_headers(), ..Request::new(Opcode::Get) };
+                self.transport.send(&req.to_bytes()?).await?;
+            }
+        }
+    }
+
+    /// Changes the current folder on the server.
+    ///
+    /// Specify [None] as name to change to the root folder or, if [SET_PATH_BACKUP]
+    /// is set in flags, to the parent folder.
+    pub async fn set_path(&mut self, name: Option<&str>, flags: u8) -> Result<()> {
+        let mut headers = self.session_headers();
+        match name {
+            Some(name) => headers.push(Header::name(name)),
+            None if flags & SET_PATH_BACKUP == 0 => headers.push(Header::name("")),
+            None => (),
+        }
+        let req = Request { flags, headers, ..Request::new(Opcode::SetPath) };
+        let rsp = self.request(&req).await?;
+        if !rsp.code.is_success() {
+            return Err(rsp.code.into());
+        }
+        Ok(())
+    }
+
+    /// Deletes the object with the specified name on the server.
+    pub async fn delete(&mut self, name: &str) -> Result<()> {
+        let mut headers = self.session_headers();
+        headers.push(Header::name(name));
+        let req = Request { headers, ..Request::new(Opcode::Put) };
+        let rsp = self.request(&req).await?;
+        if !rsp.code.is_success() {
+            return Err(rsp.code.into());
+        }
+        Ok(())
+    }
+
+    /// Terminates the OBEX session.
+    pub async fn disconnect(mut self) -> Result<()> {
+        let req = Request { headers: self.session_headers(), ..Request::new(Opcode::Disconnect) };
+        let rsp = self.request(&req).await?;
+        if !rsp.code.is_success() {
+            return Err(rsp.code.into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,937 @@
+//! Object exchange (OBEX) protocol.
+//!
+//! OBEX is the session protocol used by the Object Push, File Transfer,
+//! Phonebook Access and Message Access profiles.
+//!
+//! This module provides encoding and decoding of OBEX [requests](Request) and
+//! [responses](Response) consisting of [headers](Header), as well as an
+//! asynchronous [Client] and [Server].
+//! Client and server handle connection ids, targets and single response mode (SRM)
+//! and split large objects into multiple packets.
+//!
+//! Packets are exchanged over a [Transport].
+//! Use [StreamTransport] to run OBEX over an RFCOMM [Stream](crate::rfcomm::Stream)
+//! and an L2CAP [SeqPacket](crate::l2cap::SeqPacket) to run OBEX over L2CAP.
+//!
+//! Alternatively, the [obexd] module provides a client for the OBEX daemon of BlueZ,
+//! which performs the transfers out of process.
+
+use std::fmt;
+use uuid::Uuid;
+
+mod client;
+#[cfg(feature = "bluetoothd")]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+pub mod obexd;
+mod server;
+mod transport;
+
+pub use client::Client;
+pub use server::{Operation, Server};
+pub use transport::{StreamTransport, Transport};
+
+/// OBEX protocol version 1.0.
+pub const VERSION: u8 = 0x10;
+
+/// Minimum maximum packet length that must be supported by all OBEX implementations.
+pub const MIN_PACKET_LEN: u16 = 255;
+
+/// Length of the packet header consisting of opcode or response code and packet length.
+const PACKET_HDR_LEN: usize = 3;
+
+/// Length of the header id and length prefix of variable length headers.
+const HEADER_HDR_LEN: usize = 3;
+
+/// SETPATH flag: go to the parent folder before applying the name.
+pub const SET_PATH_BACKUP: u8 = 0x01;
+
+/// SETPATH flag: do not create the folder if it does not exist.
+pub const SET_PATH_NO_CREATE: u8 = 0x02;
+
+/// Header ids.
+pub mod header_id {
+    /// Number of objects.
+    pub const COUNT: u8 = 0xc0;
+    /// Name of the object.
+    pub const NAME: u8 = 0x01;
+    /// Type of the object.
+    pub const TYPE: u8 = 0x42;
+    /// Length of the object in bytes.
+    pub const LENGTH: u8 = 0xc3;
+    /// Date and time in ISO 8601 format.
+    pub const TIME_ISO: u8 = 0x44;
+    /// Date and time as seconds since the Unix epoch.
+    pub const TIME_4: u8 = 0xc4;
+    /// Description of the object.
+    pub const DESCRIPTION: u8 = 0x05;
+    /// Service targeted by the operation.
+    pub const TARGET: u8 = 0x46;
+    /// HTTP 1.x header.
+    pub const HTTP: u8 = 0x47;
+    /// Chunk of the object body.
+    pub const BODY: u8 = 0x48;
+    /// Final chunk of the object body.
+    pub const END_OF_BODY: u8 = 0x49;
+    /// Identifies the responding service.
+    pub const WHO: u8 = 0x4a;
+    /// Connection id.
+    pub const CONNECTION_ID: u8 = 0xcb;
+    /// Application parameters.
+    pub const APP_PARAMETERS: u8 = 0x4c;
+    /// Authentication challenge.
+    pub const AUTH_CHALLENGE: u8 = 0x4d;
+    /// Authentication response.
+    pub const AUTH_RESPONSE: u8 = 0x4e;
+    /// Creator of the object.
+    pub const CREATOR_ID: u8 = 0xcf;
+    /// WAN UUID.
+    pub const WAN_UUID: u8 = 0x50;
+    /// Object class.
+    pub const OBJECT_CLASS: u8 = 0x51;
+    /// Session parameters.
+    pub const SESSION_PARAMETERS: u8 = 0x52;
+    /// Session sequence number.
+    pub const SESSION_SEQUENCE_NUMBER: u8 = 0x93;
+    /// Action id of an ACTION operation.
+    pub const ACTION_ID: u8 = 0x94;
+    /// Destination name of an ACTION operation.
+    pub const DEST_NAME: u8 = 0x15;
+    /// Permissions of an object.
+    pub const PERMISSIONS: u8 = 0xd6;
+    /// Single response mode.
+    pub const SRM: u8 = 0x97;
+    /// Single response mode parameters.
+    pub const SRM_PARAMETERS: u8 = 0x98;
+}
+
+/// Well-known targets of OBEX services.
+pub mod target {
+    use uuid::Uuid;
+
+    /// Folder browsing service of the File Transfer Profile.
+    pub const FILE_TRANSFER: Uuid = Uuid::from_u128(0xf9ec7bc4_953c_11d2_984e_525400dc9e09);
+    /// Phonebook Access Profile.
+    pub const PHONEBOOK_ACCESS: Uuid = Uuid::from_u128(0x796135f0_f0c5_11d8_0966_0800200c9a66);
+    /// Message access service of the Message Access Profile.
+    pub const MESSAGE_ACCESS: Uuid = Uuid::from_u128(0xbb582b40_420c_11db_b0de_0800200c9a66);
+    /// Message notification service of the Message Access Profile.
+    pub const MESSAGE_NOTIFICATION: Uuid = Uuid::from_u128(0xbb582b41_420c_11db_b0de_0800200c9a66);
+}
+
+/// Invalid OBEX packet error.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParseError(pub String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid OBEX packet: {}", &self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for std::io::Error {
+    fn from(err: ParseError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+    }
+}
+
+fn parse_error(msg: &str) -> ParseError {
+    ParseError(msg.to_string())
+}
+
+/// Operation code of a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Opcode {
+    /// Establish a session.
+    Connect,
+    /// Terminate the session.
+    Disconnect,
+    /// Send an object.
+    Put,
+    /// Retrieve an object.
+    Get,
+    /// Change the current folder.
+    SetPath,
+    /// Perform an action on an object, such as copy or move.
+    Action,
+    /// Manage reliable sessions.
+    Session,
+    /// Abort the current operation.
+    Abort,
+    /// Unknown opcode.
+    Unknown(u8),
+}
+
+impl From<u8> for Opcode {
+    fn from(code: u8) -> Self {
+        match code & 0x7f {
+            0x00 => Self::Connect,
+            0x01 => Self::Disconnect,
+            0x02 => Self::Put,
+            0x03 => Self::Get,
+            0x05 => Self::SetPath,
+            0x06 => Self::Action,
+            0x07 => Self::Session,
+            0x7f => Self::Abort,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl From<Opcode> for u8 {
+    fn from(opcode: Opcode) -> Self {
+        match opcode {
+            Opcode::Connect => 0x00,
+            Opcode::Disconnect => 0x01,
+            Opcode::Put => 0x02,
+            Opcode::Get => 0x03,
+            Opcode::SetPath => 0x05,
+            Opcode::Action => 0x06,
+            Opcode::Session => 0x07,
+            Opcode::Abort => 0x7f,
+            Opcode::Unknown(code) => code & 0x7f,
+        }
+    }
+}
+
+/// Response code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ResponseCode {
+    /// Continue.
+    Continue,
+    /// OK, success.
+    Success,
+    /// Created.
+    Created,
+    /// Accepted.
+    Accepted,
+    /// Partial content.
+    PartialContent,
+    /// Bad request.
+    BadRequest,
+    /// Unauthorized.
+    Unauthorized,
+    /// Forbidden.
+    Forbidden,
+    /// Not found.
+    NotFound,
+    /// Method not allowed.
+    MethodNotAllowed,
+    /// Not acceptable.
+    NotAcceptable,
+    /// Request timed out.
+    RequestTimeout,
+    /// Conflict.
+    Conflict,
+    /// Length required.
+    LengthRequired,
+    /// Precondition failed.
+    PreconditionFailed,
+    /// Requested entity too large.
+    EntityTooLarge,
+    /// Unsupported media type.
+    UnsupportedMediaType,
+    /// Internal server error.
+    InternalServerError,
+    /// Not implemented.
+    NotImplemented,
+    /// Service unavailable.
+    ServiceUnavailable,
+    /// Database full.
+    DatabaseFull,
+    /// Database locked.
+    DatabaseLocked,
+    /// Unknown response code.
+    Unknown(u8),
+}
+
+impl ResponseCode {
+    /// Whether the response code indicates success.
+    pub fn is_success(&self) -> bool {
+        (0x20..0x30).contains(&u8::from(*self))
+    }
+}
+
+impl From<u8> for ResponseCode {
+    fn from(code: u8) -> Self {
+        match code & 0x7f {
+            0x10 => Self::Continue,
+            0x20 => Self::Success,
+            0x21 => Self::Created,
+            0x22 => Self::Accepted,
+            0x26 => Self::PartialContent,
+            0x40 => Self::BadRequest,
+            0x41 => Self::Unauthorized,
+            0x43 => Self::Forbidden,
+            0x44 => Self::NotFound,
+            0x45 => Self::MethodNotAllowed,
+            0x46 => Self::NotAcceptable,
+            0x48 => Self::RequestTimeout,
+            0x49 => Self::Conflict,
+            0x4b => Self::LengthRequired,
+            0x4c => Self::PreconditionFailed,
+            0x4d => Self::EntityTooLarge,
+            0x4f => Self::UnsupportedMediaType,
+            0x50 => Self::InternalServerError,
+            0x51 => Self::NotImplemented,
+            0x53 => Self::ServiceUnavailable,
+            0x60 => Self::DatabaseFull,
+            0x61 => Self::DatabaseLocked,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl From<ResponseCode> for u8 {
+    fn from(code: ResponseCode) -> Self {
+        match code {
+            ResponseCode::Continue => 0x10,
+            ResponseCode::Success => 0x20,
+            ResponseCode::Created => 0x21,
+            ResponseCode::Accepted => 0x22,
+            ResponseCode::PartialContent => 0x26,
+            ResponseCode::BadRequest => 0x40,
+            ResponseCode::Unauthorized => 0x41,
+            ResponseCode::Forbidden => 0x43,
+            ResponseCode::NotFound => 0x44,
+            ResponseCode::MethodNotAllowed => 0x45,
+            ResponseCode::NotAcceptable => 0x46,
+            ResponseCode::RequestTimeout => 0x48,
+            ResponseCode::Conflict => 0x49,
+            ResponseCode::LengthRequired => 0x4b,
+            ResponseCode::PreconditionFailed => 0x4c,
+            ResponseCode::EntityTooLarge => 0x4d,
+            ResponseCode::UnsupportedMediaType => 0x4f,
+            ResponseCode::InternalServerError => 0x50,
+            ResponseCode::NotImplemented => 0x51,
+            ResponseCode::ServiceUnavailable => 0x53,
+            ResponseCode::DatabaseFull => 0x60,
+            ResponseCode::DatabaseLocked => 0x61,
+            ResponseCode::Unknown(code) => code & 0x7f,
+        }
+    }
+}
+
+impl fmt::Display for ResponseCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unknown(code) => write!(f, "OBEX response code 0x{:02x}", code),
+            other => write!(f, "OBEX response {:?}", other),
+        }
+    }
+}
+
+impl std::error::Error for ResponseCode {}
+
+impl From<ResponseCode> for std::io::Error {
+    fn from(code: ResponseCode) -> Self {
+        let kind = match code {
+            ResponseCode::NotFound => std::io::ErrorKind::NotFound,
+            ResponseCode::Unauthorized | ResponseCode::Forbidden => std::io::ErrorKind::PermissionDenied,
+            ResponseCode::RequestTimeout => std::io::ErrorKind::TimedOut,
+            ResponseCode::BadRequest | ResponseCode::NotAcceptable => std::io::ErrorKind::InvalidInput,
+            _ => std::io::ErrorKind::Other,
+        };
+        std::io::Error::new(kind, code)
+    }
+}
+
+/// Value of a header.
+///
+/// The kind of value is determined by the upper two bits of the header id.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HeaderValue {
+    /// Unicode text, transmitted as null-terminated UTF-16.
+    Unicode(String),
+    /// Byte sequence.
+    Bytes(Vec<u8>),
+    /// Single byte.
+    U8(u8),
+    /// Four byte quantity.
+    U32(u32),
+}
+
+/// Header of a request or response.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Header {
+    /// Header id, see [header_id].
+    pub id: u8,
+    /// Value.
+    pub value: HeaderValue,
+}
+
+impl Header {
+    /// Creates a header.
+    ///
+    /// The kind of value must match the header id.
+    pub fn new(id: u8, value: HeaderValue) -> Self {
+        Self { id, value }
+    }
+
+    /// Name header.
+    pub fn name(name: &str) -> Self {
+        Self::new(header_id::NAME, HeaderValue::Unicode(name.to_string()))
+    }
+
+    /// Type header containing a MIME type.
+    pub fn mime_type(mime_type: &str) -> Self {
+        let mut value = mime_type.as_bytes().to_vec();
+        value.push(0);
+        Self::new(header_id::TYPE, HeaderValue::Bytes(value))
+    }
+
+    /// Description header.
+    pub fn description(description: &str) -> Self {
+        Self::new(header_id::DESCRIPTION, HeaderValue::Unicode(description.to_string()))
+    }
+
+    /// Length header.
+    pub fn length(length: u32) -> Self {
+        Self::new(header_id::LENGTH, HeaderValue::U32(length))
+    }
+
+    /// Count header.
+    pub fn count(count: u32) -> Self {
+        Self::new(header_id::COUNT, HeaderValue::U32(count))
+    }
+
+    /// Target header.
+    pub fn target(target: &Uuid) -> Self {
+        Self::new(header_id::TARGET, HeaderValue::Bytes(target.as_bytes().to_vec()))
+    }
+
+    /// Who header.
+    pub fn who(who: &[u8]) -> Self {
+        Self::new(header_id::WHO, HeaderValue::Bytes(who.to_vec()))
+    }
+
+    /// Connection id header.
+    pub fn connection_id(connection_id: u32) -> Self {
+        Self::new(header_id::CONNECTION_ID, HeaderValue::U32(connection_id))
+    }
+
+    /// Body header.
+    pub fn body(body: Vec<u8>) -> Self {
+        Self::new(header_id::BODY, HeaderValue::Bytes(body))
+    }
+
+    /// End of body header.
+    pub fn end_of_body(body: Vec<u8>) -> Self {
+        Self::new(header_id::END_OF_BODY, HeaderValue::Bytes(body))
+    }
+
+    /// Application parameters header.
+    pub fn app_parameters(params: Vec<u8>) -> Self {
+        Self::new(header_id::APP_PARAMETERS, HeaderValue::Bytes(params))
+    }
+
+    /// Single response mode header.
+    pub fn srm(enable: bool) -> Self {
+        Self::new(header_id::SRM, HeaderValue::U8(if enable { 0x01 } else { 0x00 }))
+    }
+
+    /// Returns the value if it is Unicode text.
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.value {
+            HeaderValue::Unicode(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a byte sequence.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match &self.value {
+            HeaderValue::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a single byte.
+    pub fn as_u8(&self) -> Option<u8> {
+        match &self.value {
+            HeaderValue::U8(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a four byte quantity.
+    pub fn as_u32(&self) -> Option<u32> {
+        match &self.value {
+            HeaderValue::U32(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a type header as string, without the null terminator.
+    pub fn as_mime_type(&self) -> Option<&str> {
+        let bytes = self.as_bytes()?;
+        std::str::from_utf8(bytes.strip_suffix(&[0]).unwrap_or(bytes)).ok()
+    }
+
+    /// Length of the encoded header in bytes.
+    pub fn encoded_len(&self) -> usize {
+        match &self.value {
+            HeaderValue::Unicode(s) if s.is_empty() => HEADER_HDR_LEN,
+            HeaderValue::Unicode(s) => HEADER_HDR_LEN + 2 * (s.encode_utf16().count() + 1),
+            HeaderValue::Bytes(b) => HEADER_HDR_LEN + b.len(),
+            HeaderValue::U8(_) => 2,
+            HeaderValue::U32(_) => 5,
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(self.id);
+        match &self.value {
+            HeaderValue::Unicode(s) => {
+                buf.extend_from_slice(&(self.encoded_len() as u16).to_be_bytes());
+                if !s.is_empty() {
+                    for c in s.encode_utf16().chain(Some(0)) {
+                        buf.extend_from_slice(&c.to_be_bytes());
+                    }
+                }
+            }
+            HeaderValue::Bytes(b) => {
+                buf.extend_from_slice(&(self.encoded_len() as u16).to_be_bytes());
+                buf.extend_from_slice(b);
+            }
+            HeaderValue::U8(v) => buf.push(*v),
+            HeaderValue::U32(v) => buf.extend_from_slice(&v.to_be_bytes()),
+        }
+    }
+
+    fn decode(buf: &[u8]) -> Result<(Self, &[u8]), ParseError> {
+        let id = *buf.first().ok_or_else(|| parse_error("missing header id"))?;
+        let (value, len) = match id & 0xc0 {
+            0x00 | 0x40 => {
+                if buf.len() < HEADER_HDR_LEN {
+                    return Err(parse_error("truncated header length"));
+                }
+                let len = u16::from_be_bytes([buf[1], buf[2]]) as usize;
+                if len < HEADER_HDR_LEN || buf.len() < len {
+                    return Err(parse_error("invalid header length"));
+                }
+                let data = &buf[HEADER_HDR_LEN..len];
+                if id & 0xc0 == 0x40 {
+                    (HeaderValue::Bytes(data.to_vec()), len)
+                } else {
+                    if data.len() % 2 != 0 {
+                        return Err(parse_error("odd length of Unicode header"));
+                    }
+                    let mut units: Vec<u16> =
+                        data.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+                    if units.last() == Some(&0) {
+                        units.pop();
+                    }
+                    let s = String::from_utf16(&units).map_err(|_| parse_error("invalid UTF-16 in header"))?;
+                    (HeaderValue::Unicode(s), len)
+                }
+            }
+            0x80 => (HeaderValue::U8(*buf.get(1).ok_or_else(|| parse_error("truncated header"))?), 2),
+            _ => {
+                let v = buf.get(1..5).ok_or_else(|| parse_error("truncated header"))?;
+                (HeaderValue::U32(u32::from_be_bytes(v.try_into().unwrap())), 5)
+            }
+        };
+        Ok((Self { id, value }, &buf[len..]))
+    }
+}
+
+fn encode_headers(buf: &mut Vec<u8>, headers: &[Header]) {
+    for header in headers {
+        header.encode(buf);
+    }
+}
+
+fn decode_headers(mut buf: &[u8]) -> Result<Vec<Header>, ParseError> {
+    let mut headers = Vec::new();
+    while !buf.is_empty() {
+        let (header, rest) = Header::decode(buf)?;
+        headers.push(header);
+        buf = rest;
+    }
+    Ok(headers)
+}
+
+fn find_header(headers: &[Header], id: u8) -> Option<&Header> {
+    headers.iter().find(|h| h.id == id)
+}
+
+/// Sets the packet length field and checks that the packet is not too long.
+fn finish_packet(mut buf: Vec<u8>) -> std::io::Result<Vec<u8>> {
+    let len: u16 = buf
+        .len()
+        .try_into()
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "OBEX packet too long"))?;
+    buf[1..3].copy_from_slice(&len.to_be_bytes());
+    Ok(buf)
+}
+
+/// Returns the total length of a packet from its first three bytes.
+pub fn packet_len(hdr: &[u8; 3]) -> u16 {
+    u16::from_be_bytes([hdr[1], hdr[2]])
+}
+
+/// Parameters of a CONNECT request or response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectParams {
+    /// OBEX protocol version.
+    pub version: u8,
+    /// Connect flags.
+    pub flags: u8,
+    /// Maximum packet length the sender can receive.
+    pub max_packet_len: u16,
+}
+
+impl ConnectParams {
+    /// Creates connect parameters for the current OBEX version.
+    pub fn new(max_packet_len: u16) -> Self {
+        Self { version: VERSION, flags: 0, max_packet_len }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(self.version);
+        buf.push(self.flags);
+        buf.extend_from_slice(&self.max_packet_len.to_be_bytes());
+    }
+
+    fn decode(buf: &[u8]) -> Result<(Self, &[u8]), ParseError> {
+        if buf.len() < 4 {
+            return Err(parse_error("truncated connect parameters"));
+        }
+        let params =
+            Self { version: buf[0], flags: buf[1], max_packet_len: u16::from_be_bytes([buf[2], buf[3]]) };
+        Ok((params, &buf[4..]))
+    }
+}
+
+/// Request packet.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Request {
+    /// Operation code.
+    pub opcode: Opcode,
+    /// Whether this is the final packet of the request.
+    pub final_packet: bool,
+    /// Parameters of a CONNECT request.
+    ///
+    /// Must be present for CONNECT requests and is ignored otherwise.
+    pub connect: Option<ConnectParams>,
+    /// Flags of a SETPATH request.
+    pub flags: u8,
+    /// Headers.
+    pub headers: Vec<Header>,
+}
+
+impl Request {
+    /// Creates a final request without headers.
+    pub fn new(opcode: Opcode) -> Self {
+        Self { opcode, final_packet: true, connect: None, flags: 0, headers: Vec::new() }
+    }
+
+    /// Returns the first header with the specified id.
+    pub fn header(&self, id: u8) -> Option<&Header> {
+        find_header(&self.headers, id)
+    }
+
+    /// Encodes the request.
+    ///
+    /// Fails if the encoded request exceeds the maximum OBEX packet length.
+    pub fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        let final_bit = if self.final_packet { 0x80 } else { 0x00 };
+        let mut buf = vec![u8::from(self.opcode) | final_bit, 0, 0];
+        match self.opcode {
+            Opcode::Connect => {
+                self.connect.unwrap_or_else(|| ConnectParams::new(MIN_PACKET_LEN)).encode(&mut buf)
+            }
+            Opcode::SetPath => buf.extend_from_slice(&[self.flags, 0]),
+            _ => (),
+        }
+        encode_headers(&mut buf, &self.headers);
+        finish_packet(buf)
+    }
+
+    /// Decodes a request.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        let body = packet_body(buf)?;
+        let opcode = Opcode::from(buf[0]);
+        let final_packet = buf[0] & 0x80 != 0;
+
+        let (connect, flags, body) = match opcode {
+            Opcode::Connect => {
+                let (params, rest) = ConnectParams::decode(body)?;
+                (Some(params), 0, rest)
+            }
+            Opcode::SetPath => {
+                if body.len() < 2 {
+                    return Err(parse_error("truncated SETPATH parameters"));
+                }
+                (None, body[0], &body[2..])
+            }
+            _ => (None, 0, body),
+        };
+
+        Ok(Self { opcode, final_packet, connect, flags, headers: decode_headers(body)? })
+    }
+}
+
+/// Response packet.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Response {
+    /// Response code.
+    pub code: ResponseCode,
+    /// Parameters of a response to a CONNECT request.
+    ///
+    /// Must be present for responses to CONNECT requests and is ignored otherwise.
+    pub connect: Option<ConnectParams>,
+    /// Headers.
+    pub headers: Vec<Header>,
+}
+
+impl Response {
+    /// Creates a response without headers.
+    pub fn new(code: ResponseCode) -> Self {
+        Self { code, connect: None, headers: Vec::new() }
+    }
+
+    /// Returns the first header with the specified id.
+    pub fn header(&self, id: u8) -> Option<&Header> {
+        find_header(&self.headers, id)
+    }
+
+    /// Encodes the response.
+    ///
+    /// Fails if the encoded response exceeds the maximum OBEX packet length.
+    pub fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        let mut buf = vec![u8::from(self.code) | 0x80, 0, 0];
+        if let Some(connect) = &self.connect {
+            connect.encode(&mut buf);
+        }
+        encode_headers(&mut buf, &self.headers);
+        finish_packet(buf)
+    }
+
+    /// Decodes a response.
+    ///
+    /// Set `connect` when decoding the response to a CONNECT request,
+    /// since it contains additional parameters.
+    pub fn from_bytes(buf: &[u8], connect: bool) -> Result<Self, ParseError> {
+        let body = packet_body(buf)?;
+        let code = ResponseCode::from(buf[0]);
+
+        let (connect, body) = if connect {
+            let (params, rest) = ConnectParams::decode(body)?;
+            (Some(params), rest)
+        } else {
+            (None, body)
+        };
+
+        Ok(Self { code, connect, headers: decode_headers(body)? })
+    }
+}
+
+/// Checks the packet length and returns the packet without its header.
+fn packet_body(buf: &[u8]) -> Result<&[u8], ParseError> {
+    if buf.len() < PACKET_HDR_LEN {
+        return Err(parse_error("truncated packet header"));
+    }
+    let len = packet_len(&[buf[0], buf[1], buf[2]]) as usize;
+    if len != buf.len() {
+        return Err(parse_error("packet length mismatch"));
+    }
+    Ok(&buf[PACKET_HDR_LEN..])
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{
+        channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+        future::BoxFuture,
+        StreamExt,
+    };
+
+    use super::*;
+
+    /// In-memory transport exchanging packets over channels.
+    struct MemTransport {
+        tx: UnboundedSender<Vec<u8>>,
+        rx: UnboundedReceiver<Vec<u8>>,
+        max_packet_len: u16,
+        srm: bool,
+    }
+
+    impl MemTransport {
+        fn pair(max_packet_len: u16, srm: bool) -> (Self, Self) {
+            let (a_tx, b_rx) = unbounded();
+            let (b_tx, a_rx) = unbounded();
+            (Self { tx: a_tx, rx: a_rx, max_packet_len, srm }, Self { tx: b_tx, rx: b_rx, max_packet_len, srm })
+        }
+    }
+
+    impl Transport for MemTransport {
+        fn send<'a>(&'a mut self, packet: &'a [u8]) -> BoxFuture<'a, std::io::Result<()>> {
+            assert!(packet.len() <= self.max_packet_len as usize, "packet exceeds maximum length");
+            let res = self.tx.unbounded_send(packet.to_vec()).map_err(|_| std::io::ErrorKind::BrokenPipe.into());
+            Box::pin(async move { res })
+        }
+
+        fn recv(&mut self) -> BoxFuture<'_, std::io::Result<Option<Vec<u8>>>> {
+            Box::pin(async move { Ok(self.rx.next().await) })
+        }
+
+        fn max_packet_len(&self) -> u16 {
+            self.max_packet_len
+        }
+
+        fn srm_supported(&self) -> bool {
+            self.srm
+        }
+    }
+
+    fn object(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    async fn serve(server: &mut Server<MemTransport>) -> Vec<Operation> {
+        let mut ops = Vec::new();
+        while let Some(op) = server.next_operation().await.unwrap() {
+            match op.opcode {
+                Opcode::Get => {
+                    let body = object(op.name().unwrap().parse().unwrap());
+                    server.respond(ResponseCode::Success, vec![Header::length(body.len() as u32)], &body).await
+                }
+                _ => server.respond(ResponseCode::Success, Vec::new(), &[]).await,
+            }
+            .unwrap();
+            ops.push(op);
+        }
+        ops
+    }
+
+    #[test]
+    fn request_round_trip() {
+        let req = Request {
+            connect: Some(ConnectParams::new(1024)),
+            headers: vec![Header::target(&target::FILE_TRANSFER), Header::count(3)],
+            ..Request::new(Opcode::Connect)
+        };
+        assert_eq!(Request::from_bytes(&req.to_bytes().unwrap()).unwrap(), req);
+
+        let req = Request {
+            final_packet: false,
+            headers: vec![Header::name("file.txt"), Header::mime_type("text/plain"), Header::body(object(10))],
+            ..Request::new(Opcode::Put)
+        };
+        assert_eq!(Request::from_bytes(&req.to_bytes().unwrap()).unwrap(), req);
+
+        let req = Request { flags: SET_PATH_BACKUP, ..Request::new(Opcode::SetPath) };
+        assert_eq!(Request::from_bytes(&req.to_bytes().unwrap()).unwrap(), req);
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let rsp = Response {
+            connect: Some(ConnectParams::new(MIN_PACKET_LEN)),
+            headers: vec![Header::connection_id(1), Header::who(target::FILE_TRANSFER.as_bytes())],
+            ..Response::new(ResponseCode::Success)
+        };
+        assert_eq!(Response::from_bytes(&rsp.to_bytes().unwrap(), true).unwrap(), rsp);
+
+        let rsp = Response { headers: vec![Header::srm(true)], ..Response::new(ResponseCode::Continue) };
+        assert_eq!(Response::from_bytes(&rsp.to_bytes().unwrap(), false).unwrap(), rsp);
+    }
+
+    #[test]
+    fn reject_malformed_packets() {
+        assert!(Request::from_bytes(&[0x82, 0x00]).is_err());
+        assert!(Request::from_bytes(&[0x82, 0x00, 0x04]).is_err());
+        assert!(Request::from_bytes(&[0x85, 0x00, 0x03]).is_err());
+        assert!(Response::from_bytes(&[0xa0, 0x00, 0x03], true).is_err());
+    }
+
+    async fn put_and_get(srm: bool) {
+        let (client_transport, server_transport) = MemTransport::pair(MIN_PACKET_LEN, srm);
+        let (client, server) = futures::join!(
+            Client::connect(client_transport, Some(&target::FILE_TRANSFER)),
+            Server::accept(server_transport, Some(target::FILE_TRANSFER))
+        );
+        let (mut client, mut server) = (client.unwrap(), server.unwrap());
+        assert_eq!(client.connection_id(), Some(1));
+        assert_eq!(client.max_packet_len(), MIN_PACKET_LEN);
+
+        let body = object(2000);
+        let (ops, ()) = futures::join!(serve(&mut server), async {
+            client.put(vec![Header::name("file.bin"), Header::length(2000)], &body).await.unwrap();
+            let (headers, received) = client.get(vec![Header::name("1500")]).await.unwrap();
+            assert_eq!(received, object(1500));
+            assert_eq!(headers, vec![Header::length(1500)]);
+            client.delete("file.bin").await.unwrap();
+            client.set_path(Some("folder"), 0).await.unwrap();
+            client.disconnect().await.unwrap();
+        });
+
+        assert_eq!(ops.len(), 4);
+        assert_eq!(ops[0].opcode, Opcode::Put);
+        assert_eq!(ops[0].name(), Some("file.bin"));
+        assert_eq!(ops[0].body, body);
+        assert!(!ops[0].is_delete());
+        assert_eq!(ops[1].opcode, Opcode::Get);
+        assert!(ops[2].is_delete());
+        assert_eq!(ops[3].opcode, Opcode::SetPath);
+        assert_eq!(ops[3].name(), Some("folder"));
+    }
+
+    #[tokio::test]
+    async fn put_and_get_without_srm() {
+        put_and_get(false).await;
+    }
+
+    #[tokio::test]
+    async fn put_and_get_with_srm() {
+        put_and_get(true).await;
+    }
+
+    async fn reject_large_put(srm: bool, announce_length: bool) {
+        let (client_transport, server_transport) = MemTransport::pair(MIN_PACKET_LEN, srm);
+        let (client, server) =
+            futures::join!(Client::connect(client_transport, None), Server::accept(server_transport, None));
+        let (mut client, mut server) = (client.unwrap(), server.unwrap());
+        server.set_max_body_len(1000);
+
+        let (ops, ()) = futures::join!(serve(&mut server), async {
+            let mut headers = vec![Header::name("large.bin")];
+            if announce_length {
+                headers.push(Header::length(3000));
+            }
+            let err = client.put(headers, &object(3000)).await.unwrap_err();
+            let code = err.get_ref().and_then(|err| err.downcast_ref::<ResponseCode>()).copied();
+            assert_eq!(code, Some(ResponseCode::EntityTooLarge));
+
+            client.put(vec![Header::name("small.bin")], &object(1000)).await.unwrap();
+            client.disconnect().await.unwrap();
+        });
+
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops[0].name(), Some("small.bin"));
+        assert_eq!(ops[0].body, object(1000));
+    }
+
+    #[tokio::test]
+    async fn reject_large_put_without_srm() {
+        reject_large_put(false, false).await;
+        reject_large_put(false, true).await;
+    }
+
+    #[tokio::test]
+    async fn reject_large_put_with_srm() {
+        reject_large_put(true, false).await;
+        reject_large_put(true, true).await;
+    }
+}
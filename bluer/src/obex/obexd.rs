@@ -0,0 +1,1205 @@
+//! Client for the BlueZ OBEX daemon (obexd).
+//!
+//! As an alternative to the in-process OBEX [Client](super::Client),
+//! file transfers can be delegated to the OBEX daemon shipped with BlueZ.
+//! obexd runs on the D-Bus session bus of the user and establishes the
+//! Bluetooth connection itself.
+//!
+//! Use [Client::new] to connect to obexd and [Client::create_session] to establish an
+//! OBEX session with a remote device.
+//! Depending on the [Target] of the session, use [ObjectPush], [FileTransfer],
+//! [PhonebookAccess] or [MessageAccess] to perform operations.
+//! Operations that transfer objects return a [Transfer], which provides
+//! progress information through its properties and [events](Transfer::events).
+
+use dbus::{
+    arg::{PropMap, RefArg, Variant},
+    nonblock::{Proxy, SyncConnection},
+    Path,
+};
+use dbus_tokio::connection;
+use futures::{channel::mpsc, pin_mut, stream, FutureExt, Stream, StreamExt};
+use std::{fmt, path::PathBuf, sync::Arc};
+use strum::{Display, EnumString};
+use tokio::task::{spawn_blocking, JoinHandle};
+use uuid::Uuid;
+
+use crate::{Address, Error, ErrorKind, Event, InternalErrorKind, Result, SubscriptionReq, TIMEOUT};
+
+pub(crate) const SERVICE_NAME: &str = "org.bluez.obex";
+pub(crate) const PATH: &str = "/org/bluez/obex";
+pub(crate) const CLIENT_INTERFACE: &str = "org.bluez.obex.Client1";
+pub(crate) const SESSION_INTERFACE: &str = "org.bluez.obex.Session1";
+pub(crate) const TRANSFER_INTERFACE: &str = "org.bluez.obex.Transfer1";
+pub(crate) const OBJECT_PUSH_INTERFACE: &str = "org.bluez.obex.ObjectPush1";
+pub(crate) const FILE_TRANSFER_INTERFACE: &str = "org.bluez.obex.FileTransfer1";
+pub(crate) const PHONEBOOK_ACCESS_INTERFACE: &str = "org.bluez.obex.PhonebookAccess1";
+pub(crate) const MESSAGE_ACCESS_INTERFACE: &str = "org.bluez.obex.MessageAccess1";
+pub(crate) const MESSAGE_INTERFACE: &str = "org.bluez.obex.Message1";
+
+/// Shared state of all objects of an obexd client.
+pub(crate) struct ObexdInner {
+    connection: Arc<SyncConnection>,
+    event_sub_tx: mpsc::Sender<SubscriptionReq>,
+    dbus_task: JoinHandle<connection::IOResourceError>,
+}
+
+impl ObexdInner {
+    async fn events(&self, path: Path<'static>, child_objects: bool) -> Result<mpsc::UnboundedReceiver<Event>> {
+        Event::subscribe(&mut self.event_sub_tx.clone(), path, child_objects).await
+    }
+}
+
+impl Drop for ObexdInner {
+    fn drop(&mut self) {
+        // documentation for dbus_tokio::connection::IOResource indicates it is abortable
+        self.dbus_task.abort();
+    }
+}
+
+/// Converts a local file path into a method argument.
+fn file_arg(path: &std::path::Path) -> Result<String> {
+    match path.to_str() {
+        Some(path) => Ok(path.to_string()),
+        None => Err(Error {
+            kind: ErrorKind::InvalidArguments,
+            message: format!("file path {} is not valid UTF-8", path.display()),
+        }),
+    }
+}
+
+/// Client of the BlueZ OBEX daemon.
+///
+/// Encapsulates a connection to obexd over the D-Bus session bus.
+/// All sessions created by this client are removed by obexd when the
+/// client and all its clones are dropped.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Clone)]
+pub struct Client {
+    inner: Arc<ObexdInner>,
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Client {{ {} }}", self.inner.connection.unique_name())
+    }
+}
+
+impl Client {
+    /// Create a new client of the OBEX daemon.
+    ///
+    /// This establishes a connection to the D-Bus session bus.
+    /// obexd is started by D-Bus activation if it is not running.
+    pub async fn new() -> Result<Self> {
+        let (resource, connection) = spawn_blocking(connection::new_session_sync).await??;
+        let dbus_task = tokio::spawn(resource);
+        log::trace!("Connected to D-Bus session bus with unique name {}", &connection.unique_name());
+
+        let (event_sub_tx, event_sub_rx) = mpsc::channel(1);
+        Event::handle_connection(connection.clone(), SERVICE_NAME, event_sub_rx).await?;
+
+        Ok(Self { inner: Arc::new(ObexdInner { connection, event_sub_tx, dbus_task }) })
+    }
+
+    fn proxy(&self) -> Proxy<'_, &SyncConnection> {
+        Proxy::new(SERVICE_NAME, PATH, TIMEOUT, &*self.inner.connection)
+    }
+
+    dbus_interface!();
+    dbus_default_interface!(CLIENT_INTERFACE);
+
+    /// Creates a new OBEX session with the remote device.
+    ///
+    /// obexd connects to the service of the remote device specified by `target`.
+    pub async fn create_session(
+        &self, destination: Address, target: Target, options: SessionOptions,
+    ) -> Result<Session> {
+        let mut args = options.to_dict();
+        args.insert("Target".to_string(), Variant(target.to_string().box_clone()));
+        let (dbus_path,): (Path<'static>,) =
+            self.call_method("CreateSession", (destination.to_string(), args)).await?;
+        Ok(Session::new(self.inner.clone(), dbus_path))
+    }
+
+    /// Disconnects and removes the session.
+    pub async fn remove_session(&self, session: Session) -> Result<()> {
+        self.call_method("RemoveSession", (session.dbus_path,)).await
+    }
+}
+
+/// OBEX service of a remote device.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Target {
+    /// File transfer.
+    #[strum(serialize = "ftp")]
+    FileTransfer,
+    /// Message access.
+    #[strum(serialize = "map")]
+    MessageAccess,
+    /// Object push.
+    #[strum(serialize = "opp")]
+    ObjectPush,
+    /// Phonebook access.
+    #[strum(serialize = "pbap")]
+    PhonebookAccess,
+    /// Synchronization.
+    #[strum(serialize = "sync")]
+    Synchronization,
+}
+
+/// Options for creating an OBEX session.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Debug, Clone, Default)]
+pub struct SessionOptions {
+    /// Address of the local adapter to use.
+    ///
+    /// If unspecified, the default adapter is used.
+    pub source: Option<Address>,
+    /// RFCOMM channel of the service.
+    ///
+    /// If unspecified, the channel is looked up using SDP.
+    pub channel: Option<u8>,
+    /// L2CAP PSM of the service.
+    ///
+    /// If unspecified, the PSM is looked up using SDP.
+    pub psm: Option<u16>,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl SessionOptions {
+    fn to_dict(&self) -> PropMap {
+        let mut pm = PropMap::new();
+        if let Some(source) = &self.source {
+            pm.insert("Source".to_string(), Variant(source.to_string().box_clone()));
+        }
+        if let Some(channel) = &self.channel {
+            pm.insert("Channel".to_string(), Variant(channel.box_clone()));
+        }
+        if let Some(psm) = &self.psm {
+            pm.insert("PSM".to_string(), Variant(psm.box_clone()));
+        }
+        pm
+    }
+}
+
+/// OBEX session with a remote device established by obexd.
+///
+/// The session stays established until it is removed using
+/// [Client::remove_session] or the client is dropped.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Clone)]
+pub struct Session {
+    inner: Arc<ObexdInner>,
+    dbus_path: Path<'static>,
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Session {{ {} }}", &self.dbus_path)
+    }
+}
+
+impl Session {
+    fn new(inner: Arc<ObexdInner>, dbus_path: Path<'static>) -> Self {
+        Self { inner, dbus_path }
+    }
+
+    fn proxy(&self) -> Proxy<'_, &SyncConnection> {
+        Proxy::new(SERVICE_NAME, &self.dbus_path, TIMEOUT, &*self.inner.connection)
+    }
+
+    dbus_interface!();
+    dbus_default_interface!(SESSION_INTERFACE);
+
+    /// Object push operations.
+    ///
+    /// Available if the session target is [Target::ObjectPush].
+    pub fn object_push(&self) -> ObjectPush {
+        ObjectPush { session: self.clone() }
+    }
+
+    /// File transfer operations.
+    ///
+    /// Available if the session target is [Target::FileTransfer].
+    pub fn file_transfer(&self) -> FileTransfer {
+        FileTransfer { session: self.clone() }
+    }
+
+    /// Phonebook access operations.
+    ///
+    /// Available if the session target is [Target::PhonebookAccess].
+    pub fn phonebook_access(&self) -> PhonebookAccess {
+        PhonebookAccess { session: self.clone() }
+    }
+
+    /// Message access operations.
+    ///
+    /// Available if the session target is [Target::MessageAccess].
+    pub fn message_access(&self) -> MessageAccess {
+        MessageAccess { session: self.clone() }
+    }
+
+    /// Retrieves the capabilities object of the remote device.
+    ///
+    /// The capabilities are returned in XML format.
+    pub async fn capabilities(&self) -> Result<String> {
+        let (capabilities,): (String,) = self.call_method("GetCapabilities", ()).await?;
+        Ok(capabilities)
+    }
+
+    /// Creates a transfer from the reply of a method call.
+    fn transfer(&self, (dbus_path, _props): (Path<'static>, PropMap)) -> Transfer {
+        Transfer { inner: self.inner.clone(), dbus_path }
+    }
+
+    async fn call_transfer_method<A>(&self, name: &str, args: A, interface: &str) -> Result<Transfer>
+    where
+        A: dbus::arg::AppendAll + fmt::Debug,
+    {
+        let reply = self.call_method_with_interface(name, args, interface).await?;
+        Ok(self.transfer(reply))
+    }
+}
+
+define_properties!(
+    Session,
+    /// OBEX session property.
+    pub SessionProperty => {
+        /// Address of the local adapter.
+        property(
+            Source, Address,
+            dbus: (SESSION_INTERFACE, "Source", String, OPTIONAL),
+            get: (source, v => {v.parse()?}),
+        );
+
+        /// Address of the remote device.
+        property(
+            Destination, Address,
+            dbus: (SESSION_INTERFACE, "Destination", String, MANDATORY),
+            get: (destination, v => {v.parse()?}),
+        );
+
+        /// RFCOMM channel of the connection.
+        property(
+            Channel, u8,
+            dbus: (SESSION_INTERFACE, "Channel", u8, OPTIONAL),
+            get: (channel, v => {v.to_owned()}),
+        );
+
+        /// L2CAP PSM of the connection.
+        property(
+            Psm, u16,
+            dbus: (SESSION_INTERFACE, "PSM", u16, OPTIONAL),
+            get: (psm, v => {v.to_owned()}),
+        );
+
+        /// UUID of the OBEX service of the remote device.
+        property(
+            Target, Uuid,
+            dbus: (SESSION_INTERFACE, "Target", String, OPTIONAL),
+            get: (target, v => {
+                v.parse().map_err(|_| Error::new(ErrorKind::Internal(InternalErrorKind::InvalidUuid(v.to_string()))))?
+            }),
+        );
+
+        /// Root path of the session on the remote device.
+        property(
+            Root, String,
+            dbus: (SESSION_INTERFACE, "Root", String, OPTIONAL),
+            get: (root, v => {v.to_owned()}),
+        );
+    }
+);
+
+/// Object push operations of an OBEX session.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Debug, Clone)]
+pub struct ObjectPush {
+    session: Session,
+}
+
+impl ObjectPush {
+    /// Sends the local file to the remote device.
+    ///
+    /// The path must be absolute.
+    pub async fn send_file(&self, source_file: impl AsRef<std::path::Path>) -> Result<Transfer> {
+        let args = (file_arg(source_file.as_ref())?,);
+        self.session.call_transfer_method("SendFile", args, OBJECT_PUSH_INTERFACE).await
+    }
+
+    /// Retrieves the business card of the remote device and stores it in the local file.
+    ///
+    /// If the path is empty, obexd stores the business card in a temporary file.
+    pub async fn pull_business_card(&self, target_file: impl AsRef<std::path::Path>) -> Result<Transfer> {
+        let args = (file_arg(target_file.as_ref())?,);
+        self.session.call_transfer_method("PullBusinessCard", args, OBJECT_PUSH_INTERFACE).await
+    }
+
+    /// Pushes the local business card to the remote device and retrieves
+    /// the business card of the remote device.
+    pub async fn exchange_business_cards(
+        &self, client_file: impl AsRef<std::path::Path>, target_file: impl AsRef<std::path::Path>,
+    ) -> Result<Transfer> {
+        let args = (file_arg(client_file.as_ref())?, file_arg(target_file.as_ref())?);
+        self.session.call_transfer_method("ExchangeBusinessCards", args, OBJECT_PUSH_INTERFACE).await
+    }
+}
+
+/// File transfer operations of an OBEX session.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Debug, Clone)]
+pub struct FileTransfer {
+    session: Session,
+}
+
+impl FileTransfer {
+    /// Changes the current folder of the remote device.
+    ///
+    /// Use `..` to change to the parent folder.
+    pub async fn change_folder(&self, folder: &str) -> Result<()> {
+        self.session.call_method_with_interface("ChangeFolder", (folder,), FILE_TRANSFER_INTERFACE).await
+    }
+
+    /// Creates a new folder on the remote device and changes into it.
+    pub async fn create_folder(&self, folder: &str) -> Result<()> {
+        self.session.call_method_with_interface("CreateFolder", (folder,), FILE_TRANSFER_INTERFACE).await
+    }
+
+    /// Lists the contents of the current folder of the remote device.
+    pub async fn list_folder(&self) -> Result<Vec<FolderEntry>> {
+        let (entries,): (Vec<PropMap>,) =
+            self.session.call_method_with_interface("ListFolder", (), FILE_TRANSFER_INTERFACE).await?;
+        Ok(entries.iter().map(FolderEntry::from_dict).collect())
+    }
+
+    /// Copies the file from the current folder of the remote device into the local file.
+    ///
+    /// If the local path is empty, obexd stores the file in a temporary file.
+    pub async fn get_file(
+        &self, target_file: impl AsRef<std::path::Path>, source_file: &str,
+    ) -> Result<Transfer> {
+        let args = (file_arg(target_file.as_ref())?, source_file);
+        self.session.call_transfer_method("GetFile", args, FILE_TRANSFER_INTERFACE).await
+    }
+
+    /// Copies the local file into the current folder of the remote device.
+    pub async fn put_file(
+        &self, source_file: impl AsRef<std::path::Path>, target_file: &str,
+    ) -> Result<Transfer> {
+        let args = (file_arg(source_file.as_ref())?, target_file);
+        self.session.call_transfer_method("PutFile", args, FILE_TRANSFER_INTERFACE).await
+    }
+
+    /// Copies a file within the remote device.
+    pub async fn copy_file(&self, source_file: &str, target_file: &str) -> Result<()> {
+        self.session
+            .call_method_with_interface("CopyFile", (source_file, target_file), FILE_TRANSFER_INTERFACE)
+            .await
+    }
+
+    /// Moves a file within the remote device.
+    pub async fn move_file(&self, source_file: &str, target_file: &str) -> Result<()> {
+        self.session
+            .call_method_with_interface("MoveFile", (source_file, target_file), FILE_TRANSFER_INTERFACE)
+            .await
+    }
+
+    /// Deletes the file or empty folder from the current folder of the remote device.
+    pub async fn delete(&self, file: &str) -> Result<()> {
+        self.session.call_method_with_interface("Delete", (file,), FILE_TRANSFER_INTERFACE).await
+    }
+}
+
+/// Kind of a folder entry.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FolderEntryKind {
+    /// Folder.
+    #[strum(serialize = "folder")]
+    Folder,
+    /// File.
+    #[strum(serialize = "file")]
+    File,
+}
+
+/// Entry of a folder listing.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct FolderEntry {
+    /// Name.
+    pub name: String,
+    /// Kind.
+    pub kind: Option<FolderEntryKind>,
+    /// Size in bytes.
+    pub size: Option<u64>,
+    /// Permissions of the owner.
+    pub user_permissions: Option<String>,
+    /// Permissions of the group.
+    pub group_permissions: Option<String>,
+    /// Permissions of others.
+    pub other_permissions: Option<String>,
+    /// Time of last access.
+    pub accessed: Option<String>,
+    /// Time of last modification.
+    pub modified: Option<String>,
+    /// Time of creation.
+    pub created: Option<String>,
+}
+
+impl FolderEntry {
+    fn from_dict(dict: &PropMap) -> Self {
+        Self {
+            name: read_opt_prop!(dict, "Name", String).unwrap_or_default(),
+            kind: read_opt_prop!(dict, "Type", String).and_then(|v| v.parse().ok()),
+            size: read_opt_prop!(dict, "Size", u64),
+            user_permissions: read_opt_prop!(dict, "User-perm", String),
+            group_permissions: read_opt_prop!(dict, "Group-perm", String),
+            other_permissions: read_opt_prop!(dict, "Other-perm", String),
+            accessed: read_opt_prop!(dict, "Accessed", String),
+            modified: read_opt_prop!(dict, "Modified", String),
+            created: read_opt_prop!(dict, "Created", String),
+        }
+    }
+}
+
+/// Phonebook access operations of an OBEX session.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Clone)]
+pub struct PhonebookAccess {
+    session: Session,
+}
+
+impl fmt::Debug for PhonebookAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PhonebookAccess {{ {} }}", &self.session.dbus_path)
+    }
+}
+
+impl PhonebookAccess {
+    fn proxy(&self) -> Proxy<'_, &SyncConnection> {
+        self.session.proxy()
+    }
+
+    dbus_interface!();
+    dbus_default_interface!(PHONEBOOK_ACCESS_INTERFACE);
+
+    /// Selects the phonebook object for further operations.
+    ///
+    /// `location` is either `int` for the internal phonebook or `sim1`, `sim2`, ...
+    /// for a SIM card.
+    /// `phonebook` is one of `pb` (contacts), `ich` (incoming calls), `och` (outgoing calls),
+    /// `mch` (missed calls), `cch` (combined calls), `spd` (speed dials) or `fav` (favorites).
+    pub async fn select(&self, location: &str, phonebook: &str) -> Result<()> {
+        self.call_method("Select", (location, phonebook)).await
+    }
+
+    /// Retrieves the entire selected phonebook into the local file.
+    ///
+    /// If the path is empty, obexd stores the phonebook in a temporary file.
+    pub async fn pull_all(
+        &self, target_file: impl AsRef<std::path::Path>, filter: &PhonebookFilter,
+    ) -> Result<Transfer> {
+        let args = (file_arg(target_file.as_ref())?, filter.to_dict());
+        self.session.call_transfer_method("PullAll", args, PHONEBOOK_ACCESS_INTERFACE).await
+    }
+
+    /// Lists the entries of the selected phonebook.
+    pub async fn list(&self, filter: &PhonebookFilter) -> Result<Vec<PhonebookEntry>> {
+        let (entries,): (Vec<(String, String)>,) = self.call_method("List", (filter.to_dict(),)).await?;
+        Ok(entries.into_iter().map(|(handle, name)| PhonebookEntry { handle, name }).collect())
+    }
+
+    /// Retrieves the vCard with the specified handle into the local file.
+    pub async fn pull(
+        &self, handle: &str, target_file: impl AsRef<std::path::Path>, filter: &PhonebookFilter,
+    ) -> Result<Transfer> {
+        let args = (handle, file_arg(target_file.as_ref())?, filter.to_dict());
+        self.session.call_transfer_method("Pull", args, PHONEBOOK_ACCESS_INTERFACE).await
+    }
+
+    /// Searches the selected phonebook.
+    ///
+    /// `field` is one of `name`, `number` or `sound`.
+    pub async fn search(
+        &self, field: &str, value: &str, filter: &PhonebookFilter,
+    ) -> Result<Vec<PhonebookEntry>> {
+        let (entries,): (Vec<(String, String)>,) =
+            self.call_method("Search", (field, value, filter.to_dict())).await?;
+        Ok(entries.into_iter().map(|(handle, name)| PhonebookEntry { handle, name }).collect())
+    }
+
+    /// Number of entries in the selected phonebook.
+    pub async fn size(&self) -> Result<u16> {
+        let (size,): (u16,) = self.call_method("GetSize", ()).await?;
+        Ok(size)
+    }
+
+    /// Attempts to notify the remote device that its phonebook has been accessed.
+    pub async fn update_version(&self) -> Result<()> {
+        self.call_method("UpdateVersion", ()).await
+    }
+
+    /// vCard fields that can be used in [PhonebookFilter::fields].
+    pub async fn filter_fields(&self) -> Result<Vec<String>> {
+        let (fields,): (Vec<String>,) = self.call_method("ListFilterFields", ()).await?;
+        Ok(fields)
+    }
+}
+
+define_properties!(
+    PhonebookAccess,
+    /// Phonebook access property.
+    pub PhonebookAccessProperty => {
+        /// Currently selected phonebook folder.
+        property(
+            Folder, String,
+            dbus: (PHONEBOOK_ACCESS_INTERFACE, "Folder", String, MANDATORY),
+            get: (folder, v => {v.to_owned()}),
+        );
+
+        /// Identifier of the phonebook database.
+        property(
+            DatabaseIdentifier, String,
+            dbus: (PHONEBOOK_ACCESS_INTERFACE, "DatabaseIdentifier", String, OPTIONAL),
+            get: (database_identifier, v => {v.to_owned()}),
+        );
+
+        /// Primary version counter of the selected folder.
+        property(
+            PrimaryCounter, String,
+            dbus: (PHONEBOOK_ACCESS_INTERFACE, "PrimaryCounter", String, OPTIONAL),
+            get: (primary_counter, v => {v.to_owned()}),
+        );
+
+        /// Secondary version counter of the selected folder.
+        property(
+            SecondaryCounter, String,
+            dbus: (PHONEBOOK_ACCESS_INTERFACE, "SecondaryCounter", String, OPTIONAL),
+            get: (secondary_counter, v => {v.to_owned()}),
+        );
+
+        /// Whether images in vCards have a fixed size.
+        property(
+            FixedImageSize, bool,
+            dbus: (PHONEBOOK_ACCESS_INTERFACE, "FixedImageSize", bool, OPTIONAL),
+            get: (fixed_image_size, v => {v.to_owned()}),
+        );
+    }
+);
+
+/// Entry of a phonebook listing.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhonebookEntry {
+    /// vCard handle, for use with [PhonebookAccess::pull].
+    pub handle: String,
+    /// Name of the contact.
+    pub name: String,
+}
+
+/// vCard format.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VCardFormat {
+    /// vCard 2.1.
+    #[strum(serialize = "vcard21")]
+    VCard21,
+    /// vCard 3.0.
+    #[strum(serialize = "vcard30")]
+    VCard30,
+}
+
+/// Sort order of phonebook listings.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PhonebookOrder {
+    /// By index.
+    #[strum(serialize = "indexed")]
+    Indexed,
+    /// Alphabetical by name.
+    #[strum(serialize = "alphanumeric")]
+    Alphanumeric,
+    /// By phonetic sound of the name.
+    #[strum(serialize = "phonetic")]
+    Phonetic,
+}
+
+/// Filter for phonebook operations.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Debug, Clone, Default)]
+pub struct PhonebookFilter {
+    /// vCard format of retrieved entries.
+    pub format: Option<VCardFormat>,
+    /// Sort order of listings.
+    pub order: Option<PhonebookOrder>,
+    /// Offset of the first entry.
+    pub offset: Option<u16>,
+    /// Maximum number of entries.
+    pub max_count: Option<u16>,
+    /// vCard fields to include in retrieved entries.
+    ///
+    /// See [PhonebookAccess::filter_fields] for available fields.
+    pub fields: Option<Vec<String>>,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl PhonebookFilter {
+    fn to_dict(&self) -> PropMap {
+        let mut pm = PropMap::new();
+        if let Some(format) = &self.format {
+            pm.insert("Format".to_string(), Variant(format.to_string().box_clone()));
+        }
+        if let Some(order) = &self.order {
+            pm.insert("Order".to_string(), Variant(order.to_string().box_clone()));
+        }
+        if let Some(offset) = &self.offset {
+            pm.insert("Offset".to_string(), Variant(offset.box_clone()));
+        }
+        if let Some(max_count) = &self.max_count {
+            pm.insert("MaxCount".to_string(), Variant(max_count.box_clone()));
+        }
+        if let Some(fields) = &self.fields {
+            pm.insert("Fields".to_string(), Variant(fields.box_clone()));
+        }
+        pm
+    }
+}
+
+/// Message access operations of an OBEX session.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Clone)]
+pub struct MessageAccess {
+    session: Session,
+}
+
+impl fmt::Debug for MessageAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MessageAccess {{ {} }}", &self.session.dbus_path)
+    }
+}
+
+impl MessageAccess {
+    fn proxy(&self) -> Proxy<'_, &SyncConnection> {
+        self.session.proxy()
+    }
+
+    dbus_interface!();
+    dbus_default_interface!(MESSAGE_ACCESS_INTERFACE);
+
+    /// Changes the current folder of the remote device.
+    ///
+    /// Use `..` to change to the parent folder and an empty name to change to the root folder.
+    pub async fn set_folder(&self, folder: &str) -> Result<()> {
+        self.call_method("SetFolder", (folder,)).await
+    }
+
+    /// Lists the subfolders of the current folder.
+    pub async fn list_folders(&self, offset: Option<u16>, max_count: Option<u16>) -> Result<Vec<String>> {
+        let mut filter = PropMap::new();
+        if let Some(offset) = offset {
+            filter.insert("Offset".to_string(), Variant(offset.box_clone()));
+        }
+        if let Some(max_count) = max_count {
+            filter.insert("MaxCount".to_string(), Variant(max_count.box_clone()));
+        }
+        let (folders,): (Vec<PropMap>,) = self.call_method("ListFolders", (filter,)).await?;
+        Ok(folders.iter().filter_map(|folder| read_opt_prop!(folder, "Name", String)).collect())
+    }
+
+    /// Message fields that can be used in [MessageFilter::fields].
+    pub async fn filter_fields(&self) -> Result<Vec<String>> {
+        let (fields,): (Vec<String>,) = self.call_method("ListFilterFields", ()).await?;
+        Ok(fields)
+    }
+
+    /// Lists the messages in the specified subfolder of the current folder.
+    ///
+    /// Use an empty name to list the messages of the current folder.
+    pub async fn list_messages(&self, folder: &str, filter: &MessageFilter) -> Result<Vec<Message>> {
+        let (messages,): (Vec<(Path<'static>, PropMap)>,) =
+            self.call_method("ListMessages", (folder, filter.to_dict())).await?;
+        Ok(messages
+            .into_iter()
+            .map(|(dbus_path, _props)| Message { session: self.session.clone(), dbus_path })
+            .collect())
+    }
+
+    /// Requests the remote device to check for new messages.
+    pub async fn update_inbox(&self) -> Result<()> {
+        self.call_method("UpdateInbox", ()).await
+    }
+
+    /// Sends the message in the local file to the specified subfolder of the current folder.
+    ///
+    /// The file must contain a message in bMessage format.
+    pub async fn push_message(
+        &self, source_file: impl AsRef<std::path::Path>, folder: &str, options: &PushMessageOptions,
+    ) -> Result<Transfer> {
+        let args = (file_arg(source_file.as_ref())?, folder, options.to_dict());
+        self.session.call_transfer_method("PushMessage", args, MESSAGE_ACCESS_INTERFACE).await
+    }
+}
+
+define_properties!(
+    MessageAccess,
+    /// Message access property.
+    pub MessageAccessProperty => {
+        /// Message types supported by the remote device.
+        property(
+            SupportedTypes, Vec<String>,
+            dbus: (MESSAGE_ACCESS_INTERFACE, "SupportedTypes", Vec<String>, OPTIONAL),
+            get: (supported_types, v => {v.to_owned()}),
+        );
+    }
+);
+
+/// Filter for listing messages.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Debug, Clone, Default)]
+pub struct MessageFilter {
+    /// Offset of the first message.
+    pub offset: Option<u16>,
+    /// Maximum number of messages.
+    pub max_count: Option<u16>,
+    /// Maximum length of the subject.
+    pub subject_length: Option<u8>,
+    /// Message fields to include.
+    ///
+    /// See [MessageAccess::filter_fields] for available fields.
+    pub fields: Option<Vec<String>>,
+    /// Message types to include, for example `sms` or `email`.
+    pub types: Option<Vec<String>>,
+    /// Only include messages newer than the timestamp, in the format `YYYYMMDDTHHMMSS`.
+    pub period_begin: Option<String>,
+    /// Only include messages older than the timestamp, in the format `YYYYMMDDTHHMMSS`.
+    pub period_end: Option<String>,
+    /// Only include read or unread messages.
+    pub read: Option<bool>,
+    /// Only include messages with a matching recipient.
+    pub recipient: Option<String>,
+    /// Only include messages with a matching sender.
+    pub sender: Option<String>,
+    /// Only include messages with or without high priority.
+    pub priority: Option<bool>,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl MessageFilter {
+    fn to_dict(&self) -> PropMap {
+        let mut pm = PropMap::new();
+        if let Some(offset) = &self.offset {
+            pm.insert("Offset".to_string(), Variant(offset.box_clone()));
+        }
+        if let Some(max_count) = &self.max_count {
+            pm.insert("MaxCount".to_string(), Variant(max_count.box_clone()));
+        }
+        if let Some(subject_length) = &self.subject_length {
+            pm.insert("SubjectLength".to_string(), Variant(subject_length.box_clone()));
+        }
+        if let Some(fields) = &self.fields {
+            pm.insert("Fields".to_string(), Variant(fields.box_clone()));
+        }
+        if let Some(types) = &self.types {
+            pm.insert("Types".to_string(), Variant(types.box_clone()));
+        }
+        if let Some(period_begin) = &self.period_begin {
+            pm.insert("PeriodBegin".to_string(), Variant(period_begin.box_clone()));
+        }
+        if let Some(period_end) = &self.period_end {
+            pm.insert("PeriodEnd".to_string(), Variant(period_end.box_clone()));
+        }
+        if let Some(read) = &self.read {
+            pm.insert("Read".to_string(), Variant(read.box_clone()));
+        }
+        if let Some(recipient) = &self.recipient {
+            pm.insert("Recipient".to_string(), Variant(recipient.box_clone()));
+        }
+        if let Some(sender) = &self.sender {
+            pm.insert("Sender".to_string(), Variant(sender.box_clone()));
+        }
+        if let Some(priority) = &self.priority {
+            pm.insert("Priority".to_string(), Variant(priority.box_clone()));
+        }
+        pm
+    }
+}
+
+/// Options for pushing a message.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Debug, Clone, Default)]
+pub struct PushMessageOptions {
+    /// Do not keep a copy of the sent message in the sent folder.
+    pub transparent: Option<bool>,
+    /// Retry sending the message if it fails.
+    pub retry: Option<bool>,
+    /// Character set of the message, either `utf8` or `native`.
+    pub charset: Option<String>,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl PushMessageOptions {
+    fn to_dict(&self) -> PropMap {
+        let mut pm = PropMap::new();
+        if let Some(transparent) = &self.transparent {
+            pm.insert("Transparent".to_string(), Variant(transparent.box_clone()));
+        }
+        if let Some(retry) = &self.retry {
+            pm.insert("Retry".to_string(), Variant(retry.box_clone()));
+        }
+        if let Some(charset) = &self.charset {
+            pm.insert("Charset".to_string(), Variant(charset.box_clone()));
+        }
+        pm
+    }
+}
+
+/// Message stored on a remote device.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Clone)]
+pub struct Message {
+    session: Session,
+    dbus_path: Path<'static>,
+}
+
+impl fmt::Debug for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Message {{ {} }}", &self.dbus_path)
+    }
+}
+
+impl Message {
+    fn proxy(&self) -> Proxy<'_, &SyncConnection> {
+        Proxy::new(SERVICE_NAME, &self.dbus_path, TIMEOUT, &*self.session.inner.connection)
+    }
+
+    dbus_interface!();
+    dbus_default_interface!(MESSAGE_INTERFACE);
+
+    /// Retrieves the message into the local file.
+    ///
+    /// If the path is empty, obexd stores the message in a temporary file.
+    pub async fn get(&self, target_file: impl AsRef<std::path::Path>, attachment: bool) -> Result<Transfer> {
+        let reply = self.call_method("Get", (file_arg(target_file.as_ref())?, attachment)).await?;
+        Ok(self.session.transfer(reply))
+    }
+
+    /// Marks the message as deleted or undeleted.
+    pub async fn set_deleted(&self, deleted: bool) -> Result<()> {
+        self.set_property("Deleted", deleted).await
+    }
+}
+
+define_properties!(
+    Message,
+    /// Message property.
+    pub MessageProperty => {
+        /// Folder containing the message.
+        property(
+            Folder, String,
+            dbus: (MESSAGE_INTERFACE, "Folder", String, MANDATORY),
+            get: (folder, v => {v.to_owned()}),
+        );
+
+        /// Subject of the message.
+        property(
+            Subject, String,
+            dbus: (MESSAGE_INTERFACE, "Subject", String, OPTIONAL),
+            get: (subject, v => {v.to_owned()}),
+        );
+
+        /// Timestamp of the message in the format `YYYYMMDDTHHMMSS`.
+        property(
+            Timestamp, String,
+            dbus: (MESSAGE_INTERFACE, "Timestamp", String, OPTIONAL),
+            get: (timestamp, v => {v.to_owned()}),
+        );
+
+        /// Name of the sender.
+        property(
+            Sender, String,
+            dbus: (MESSAGE_INTERFACE, "Sender", String, OPTIONAL),
+            get: (sender, v => {v.to_owned()}),
+        );
+
+        /// Address of the sender.
+        property(
+            SenderAddress, String,
+            dbus: (MESSAGE_INTERFACE, "SenderAddress", String, OPTIONAL),
+            get: (sender_address, v => {v.to_owned()}),
+        );
+
+        /// Address to reply to.
+        property(
+            ReplyTo, String,
+            dbus: (MESSAGE_INTERFACE, "ReplyTo", String, OPTIONAL),
+            get: (reply_to, v => {v.to_owned()}),
+        );
+
+        /// Name of the recipient.
+        property(
+            Recipient, String,
+            dbus: (MESSAGE_INTERFACE, "Recipient", String, OPTIONAL),
+            get: (recipient, v => {v.to_owned()}),
+        );
+
+        /// Address of the recipient.
+        property(
+            RecipientAddress, String,
+            dbus: (MESSAGE_INTERFACE, "RecipientAddress", String, OPTIONAL),
+            get: (recipient_address, v => {v.to_owned()}),
+        );
+
+        /// Type of the message, for example `sms-gsm` or `email`.
+        property(
+            Type, String,
+            dbus: (MESSAGE_INTERFACE, "Type", String, OPTIONAL),
+            get: (message_type, v => {v.to_owned()}),
+        );
+
+        /// Size of the message in bytes.
+        property(
+            Size, u64,
+            dbus: (MESSAGE_INTERFACE, "Size", u64, OPTIONAL),
+            get: (size, v => {v.to_owned()}),
+        );
+
+        /// Reception status of the message.
+        property(
+            Status, String,
+            dbus: (MESSAGE_INTERFACE, "Status", String, OPTIONAL),
+            get: (status, v => {v.to_owned()}),
+        );
+
+        /// Whether the message has high priority.
+        property(
+            Priority, bool,
+            dbus: (MESSAGE_INTERFACE, "Priority", bool, OPTIONAL),
+            get: (is_priority, v => {v.to_owned()}),
+        );
+
+        /// Whether the message has been read.
+        property(
+            Read, bool,
+            dbus: (MESSAGE_INTERFACE, "Read", bool, OPTIONAL),
+            get: (is_read, v => {v.to_owned()}),
+            set: (set_read, v => {v}),
+        );
+
+        /// Whether the message has been sent.
+        property(
+            Sent, bool,
+            dbus: (MESSAGE_INTERFACE, "Sent", bool, OPTIONAL),
+            get: (is_sent, v => {v.to_owned()}),
+        );
+
+        /// Whether the message is DRM protected.
+        property(
+            Protected, bool,
+            dbus: (MESSAGE_INTERFACE, "Protected", bool, OPTIONAL),
+            get: (is_protected, v => {v.to_owned()}),
+        );
+    }
+);
+
+/// Object transfer performed by obexd.
+///
+/// The transfer is removed by obexd shortly after it has completed or failed.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Clone)]
+pub struct Transfer {
+    inner: Arc<ObexdInner>,
+    dbus_path: Path<'static>,
+}
+
+impl fmt::Debug for Transfer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Transfer {{ {} }}", &self.dbus_path)
+    }
+}
+
+impl Transfer {
+    fn proxy(&self) -> Proxy<'_, &SyncConnection> {
+        Proxy::new(SERVICE_NAME, &self.dbus_path, TIMEOUT, &*self.inner.connection)
+    }
+
+    dbus_interface!();
+    dbus_default_interface!(TRANSFER_INTERFACE);
+
+    /// Streams transfer property changes.
+    ///
+    /// Progress is reported by changes of [TransferProperty::Transferred].
+    /// The stream ends when the transfer is removed.
+    pub async fn events(&self) -> Result<impl Stream<Item = TransferEvent>> {
+        let events = self.inner.events(self.dbus_path.clone(), false).await?;
+        let stream = events.flat_map(move |event| match event {
+            Event::PropertiesChanged { changed, .. } => stream::iter(
+                TransferProperty::from_prop_map(changed).into_iter().map(TransferEvent::PropertyChanged),
+            )
+            .boxed(),
+            _ => stream::empty().boxed(),
+        });
+
+        Ok(stream)
+    }
+
+    /// Waits for the transfer to complete.
+    ///
+    /// The final status is taken from the status change notification, since the OBEX daemon
+    /// removes the transfer as soon as it has finished.
+    /// Returns an error if the transfer fails or is cancelled.
+    pub async fn wait(&self) -> Result<()> {
+        let events = self.events().await?;
+        pin_mut!(events);
+
+        let mut status = match self.status().await {
+            Ok(status) => Some(status),
+            Err(err) if err.kind == ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+        // If the transfer has already been removed, its final status change has been received
+        // before the reply to the status query.
+        let removed = status.is_none();
+
+        loop {
+            match status {
+                Some(TransferStatus::Complete) => return Ok(()),
+                Some(TransferStatus::Error) => return Err(Error::new(ErrorKind::Failed)),
+                _ => (),
+            }
+
+            let event = if removed { events.next().now_or_never().flatten() } else { events.next().await };
+            match event {
+                Some(TransferEvent::PropertyChanged(TransferProperty::Status(new_status))) => {
+                    status = Some(new_status)
+                }
+                Some(_) => (),
+                None => return Err(Error::new(ErrorKind::NotFound)),
+            }
+        }
+    }
+
+    /// Session the transfer belongs to.
+    pub async fn session(&self) -> Result<Session> {
+        let dbus_path: Path<'static> = self.get_property("Session").await?;
+        Ok(Session::new(self.inner.clone(), dbus_path))
+    }
+
+    /// Stops the transfer.
+    pub async fn cancel(&self) -> Result<()> {
+        self.call_method("Cancel", ()).await
+    }
+
+    /// Suspends the transfer.
+    ///
+    /// Only queued or active transfers can be suspended.
+    pub async fn suspend(&self) -> Result<()> {
+        self.call_method("Suspend", ()).await
+    }
+
+    /// Resumes a suspended transfer.
+    pub async fn resume(&self) -> Result<()> {
+        self.call_method("Resume", ()).await
+    }
+}
+
+define_properties!(
+    Transfer,
+    /// Transfer property.
+    pub TransferProperty => {
+        /// Status of the transfer.
+        property(
+            Status, TransferStatus,
+            dbus: (TRANSFER_INTERFACE, "Status", String, MANDATORY),
+            get: (status, v => {v.parse()?}),
+        );
+
+        /// Name of the transferred object.
+        property(
+            Name, String,
+            dbus: (TRANSFER_INTERFACE, "Name", String, OPTIONAL),
+            get: (name, v => {v.to_owned()}),
+        );
+
+        /// MIME type of the transferred object.
+        property(
+            Type, String,
+            dbus: (TRANSFER_INTERFACE, "Type", String, OPTIONAL),
+            get: (mime_type, v => {v.to_owned()}),
+        );
+
+        /// Timestamp of the transferred object.
+        property(
+            Time, u64,
+            dbus: (TRANSFER_INTERFACE, "Time", u64, OPTIONAL),
+            get: (time, v => {v.to_owned()}),
+        );
+
+        /// Size of the transferred object in bytes.
+        ///
+        /// May be unknown for objects retrieved from the remote device.
+        property(
+            Size, u64,
+            dbus: (TRANSFER_INTERFACE, "Size", u64, OPTIONAL),
+            get: (size, v => {v.to_owned()}),
+        );
+
+        /// Number of bytes transferred so far.
+        ///
+        /// Only available while the transfer is active.
+        property(
+            Transferred, u64,
+            dbus: (TRANSFER_INTERFACE, "Transferred", u64, OPTIONAL),
+            get: (transferred, v => {v.to_owned()}),
+        );
+
+        /// Local file the object is read from or written to.
+        property(
+            Filename, PathBuf,
+            dbus: (TRANSFER_INTERFACE, "Filename", String, OPTIONAL),
+            get: (filename, v => {PathBuf::from(v)}),
+        );
+    }
+);
+
+/// Status of a transfer.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TransferStatus {
+    /// Waiting for other transfers of the session to finish.
+    #[strum(serialize = "queued")]
+    Queued,
+    /// In progress.
+    #[strum(serialize = "active")]
+    Active,
+    /// Suspended.
+    #[strum(serialize = "suspended")]
+    Suspended,
+    /// Completed successfully.
+    #[strum(serialize = "complete")]
+    Complete,
+    /// Failed or cancelled.
+    #[strum(serialize = "error")]
+    Error,
+}
+
+/// Transfer event.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "obex", feature = "bluetoothd"))))]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TransferEvent {
+    /// Property changed.
+    PropertyChanged(TransferProperty),
+}
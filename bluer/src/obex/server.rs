@@ -0,0 +1,354 @@
+//! OBEX server.
+
+use std::io::{Error, ErrorKind, Result};
+use uuid::Uuid;
+
+use super::{
+    header_id, ConnectParams, Header, Opcode, Request, Response, ResponseCode, Transport, HEADER_HDR_LEN,
+    MIN_PACKET_LEN, PACKET_HDR_LEN,
+};
+
+/// Default maximum length of the body of a PUT request accepted by a [Server].
+const DEFAULT_MAX_BODY_LEN: usize = 16 * 1024 * 1024;
+
+/// Operation requested by an OBEX client.
+///
+/// Multi-packet requests are assembled before the operation is returned.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Operation {
+    /// Operation code.
+    pub opcode: Opcode,
+    /// Flags of a SETPATH request.
+    pub flags: u8,
+    /// Headers of all request packets, not including connection id,
+    /// single response mode and body headers.
+    pub headers: Vec<Header>,
+    /// Body of a PUT request.
+    pub body: Vec<u8>,
+}
+
+impl Operation {
+    /// Returns the first header with the specified id.
+    pub fn header(&self, id: u8) -> Option<&Header> {
+        self.headers.iter().find(|h| h.id == id)
+    }
+
+    /// Name of the object.
+    pub fn name(&self) -> Option<&str> {
+        self.header(header_id::NAME).and_then(Header::as_str)
+    }
+
+    /// MIME type of the object.
+    pub fn mime_type(&self) -> Option<&str> {
+        self.header(header_id::TYPE).and_then(Header::as_mime_type)
+    }
+
+    /// Whether this is a PUT request without body, which requests deletion of the object.
+    pub fn is_delete(&self) -> bool {
+        self.opcode == Opcode::Put
+            && self.body.is_empty()
+            && !self.headers.iter().any(|h| h.id == header_id::LENGTH)
+    }
+}
+
+/// OBEX server.
+///
+/// Serves a single OBEX session over a transport.
+/// Use [next_operation](Self::next_operation) to receive operations requested by the client
+/// and reply to each of them using [respond](Self::respond).
+/// CONNECT, DISCONNECT and ABORT requests are handled automatically.
+#[derive(Debug)]
+pub struct Server<T> {
+    transport: T,
+    max_packet_len: u16,
+    target: Option<Uuid>,
+    connection_id: Option<u32>,
+    /// Operation awaiting a response.
+    current: Option<Opcode>,
+    /// Client requested single response mode for the current operation.
+    srm: bool,
+    max_body_len: usize,
+    /// Remaining packets of a rejected PUT request sent using SRM are discarded.
+    discard_put: bool,
+}
+
+impl<T> Server<T>
+where
+    T: Transport,
+{
+    /// Waits for an OBEX client to connect over the specified transport.
+    ///
+    /// If a target is specified, only CONNECT requests directed to it are accepted
+    /// and a connection id is assigned to the session.
+    pub async fn accept(transport: T, target: Option<Uuid>) -> Result<Self> {
+        let mut this = Self {
+            max_packet_len: transport.max_packet_len(),
+            transport,
+            target,
+            connection_id: None,
+            current: None,
+            srm: false,
+            max_body_len: DEFAULT_MAX_BODY_LEN,
+            discard_put: false,
+        };
+
+        loop {
+            let req = this.recv_request().await?.ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
+            match req.opcode {
+                Opcode::Connect => {
+                    if this.handle_connect(&req).await? {
+                        return Ok(this);
+                    }
+                }
+                opcode => {
+                    log::debug!("Rejecting OBEX {:?} request before connect", opcode);
+                    this.send(Response::new(ResponseCode::BadRequest)).await?;
+                }
+            }
+        }
+    }
+
+    /// Connection id assigned to the session.
+    pub fn connection_id(&self) -> Option<u32> {
+        self.connection_id
+    }
+
+    /// Maximum packet length negotiated with the client.
+    pub fn max_packet_len(&self) -> u16 {
+        self.max_packet_len
+    }
+
+    /// Maximum length of the body of a PUT request.
+    pub fn max_body_len(&self) -> usize {
+        self.max_body_len
+    }
+
+    /// Sets the maximum length of the body of a PUT request.
+    ///
+    /// Larger objects are rejected with [EntityTooLarge](ResponseCode::EntityTooLarge).
+    /// The default is 16 MiB.
+    pub fn set_max_body_len(&mut self, max_body_len: usize) {
+        self.max_body_len = max_body_len;
+    }
+
+    /// Gets a reference to the underlying transport.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Consumes the server, returning the underlying transport.
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    async fn recv_request(&mut self) -> Result<Option<Request>> {
+        match self.transport.recv().await? {
+            Some(packet) => Ok(Some(Request::from_bytes(&packet)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn send(&mut self, rsp: Response) -> Result<()> {
+        let packet = rsp.to_bytes()?;
+        if packet.len() > self.max_packet_len as usize {
+            return Err(Error::new(ErrorKind::InvalidInput, "OBEX response exceeds maximum packet length"));
+        }
+        self.transport.send(&packet).await
+    }
+
+    /// Handles a CONNECT request and returns whether it was accepted.
+    async fn handle_connect(&mut self, req: &Request) -> Result<bool> {
+        let local_max = self.transport.max_packet_len();
+        let mut rsp =
+            Response { connect: Some(ConnectParams::new(local_max)), ..Response::new(ResponseCode::Success) };
+
+        if let Some(target) = self.target {
+            let req_target = req.header(header_id::TARGET).and_then(Header::as_bytes);
+            if req_target != Some(&target.as_bytes()[..]) {
+                log::debug!("Rejecting OBEX connect to unknown target {:?}", req_target);
+                rsp.code = ResponseCode::ServiceUnavailable;
+                self.send(rsp).await?;
+                return Ok(false);
+            }
+
+            let connection_id = self.connection_id.unwrap_or(1);
+            self.connection_id = Some(connection_id);
+            rsp.headers.push(Header::connection_id(connection_id));
+            rsp.headers.push(Header::who(target.as_bytes()));
+        }
+
+        let peer_max = req.connect.map(|params| params.max_packet_len).unwrap_or(MIN_PACKET_LEN);
+        self.max_packet_len = local_max.min(peer_max).max(MIN_PACKET_LEN);
+        self.send(rsp).await?;
+        Ok(true)
+    }
+
+    /// Receives the next operation requested by the client.
+    ///
+    /// The previous operation must have been responded to.
+    /// PUT requests with a body exceeding the [maximum body length](Self::set_max_body_len)
+    /// are rejected automatically.
+    /// Returns [None] when the client disconnected or the connection has been closed.
+    pub async fn next_operation(&mut self) -> Result<Option<Operation>> {
+        if let Some(opcode) = self.current.take() {
+            log::warn!("OBEX {:?} operation was not responded to", opcode);
+            self.send(Response::new(ResponseCode::InternalServerError)).await?;
+        }
+
+        let mut op: Option<Operation> = None;
+        loop {
+            let req = match self.recv_request().await? {
+                Some(req) => req,
+                None => return Ok(None),
+            };
+
+            match req.opcode {
+                Opcode::Connect => {
+                    self.handle_connect(&req).await?;
+                    continue;
+                }
+                Opcode::Disconnect => {
+                    self.send(Response::new(ResponseCode::Success)).await?;
+                    return Ok(None);
+                }
+                Opcode::Abort => {
+                    op = None;
+                    self.send(Response::new(ResponseCode::Success)).await?;
+                    continue;
+                }
+                Opcode::Put if self.discard_put => {
+                    self.discard_put = !req.final_packet;
+                    continue;
+                }
+                _ => (),
+            }
+            self.discard_put = false;
+
+            let first = op.is_none();
+            let cur = op.get_or_insert_with(|| Operation {
+                opcode: req.opcode,
+                flags: req.flags,
+                headers: Vec::new(),
+                body: Vec::new(),
+            });
+            if cur.opcode != req.opcode {
+                log::debug!("OBEX {:?} request interrupted by {:?} request", cur.opcode, req.opcode);
+                self.send(Response::new(ResponseCode::BadRequest)).await?;
+                continue;
+            }
+
+            let mut srm_requested = false;
+            let mut too_large = false;
+            for header in req.headers {
+                match header.id {
+                    header_id::CONNECTION_ID => (),
+                    header_id::SRM => srm_requested = header.as_u8() == Some(0x01),
+                    header_id::BODY | header_id::END_OF_BODY => {
+                        let data = header.as_bytes().unwrap_or_default();
+                        too_large |= cur.body.len() + data.len() > self.max_body_len;
+                        if !too_large {
+                            cur.body.extend(data);
+                        }
+                    }
+                    header_id::LENGTH => {
+                        too_large |= header.as_u32().unwrap_or_default() as usize > self.max_body_len;
+                        cur.headers.push(header);
+                    }
+                    _ => cur.headers.push(header),
+                }
+            }
+            if first {
+                self.srm = srm_requested && self.transport.srm_supported();
+            }
+
+            if too_large && cur.opcode == Opcode::Put {
+                log::debug!("Rejecting OBEX PUT request exceeding maximum body length of {}", self.max_body_len);
+                // With single response mode, the client keeps sending after the first packet.
+                self.discard_put = self.srm && !first && !req.final_packet;
+                op = None;
+                self.send(Response::new(ResponseCode::EntityTooLarge)).await?;
+                continue;
+            }
+
+            if req.final_packet {
+                self.current = Some(cur.opcode);
+                return Ok(op.take());
+            }
+
+            // With single response mode, only the first packet of a PUT request is answered.
+            if first || !self.srm || cur.opcode != Opcode::Put {
+                let mut rsp = Response::new(ResponseCode::Continue);
+                if first && self.srm {
+                    rsp.headers.push(Header::srm(true));
+                }
+                self.send(rsp).await?;
+            }
+        }
+    }
+
+    /// Responds to the current operation.
+    ///
+    /// For GET operations the body is split into multiple packets as required.
+    /// For other operations the body must be empty.
+    pub async fn respond(&mut self, code: ResponseCode, headers: Vec<Header>, body: &[u8]) -> Result<()> {
+        let opcode = self
+            .current
+            .take()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "no OBEX operation to respond to"))?;
+        let mut headers = headers;
+        // A PUT request using SRM has already received the SRM confirmation with its first response.
+        if self.srm && opcode == Opcode::Get {
+            headers.push(Header::srm(true));
+        }
+
+        if opcode != Opcode::Get || !code.is_success() {
+            if !body.is_empty() {
+                return Err(Error::new(ErrorKind::InvalidInput, "body can only be sent in response to GET"));
+            }
+            return self.send(Response { headers, ..Response::new(code) }).await;
+        }
+
+        let mut remaining = body;
+        loop {
+            let headers_len: usize = headers.iter().map(Header::encoded_len).sum();
+            let space = (self.max_packet_len as usize)
+                .checked_sub(PACKET_HDR_LEN + headers_len + HEADER_HDR_LEN)
+                .ok_or_else(|| {
+                    Error::new(ErrorKind::InvalidInput, "OBEX headers exceed maximum packet length")
+                })?;
+
+            let final_packet = remaining.len() <= space;
+            let (chunk, rest) = remaining.split_at(remaining.len().min(space));
+            remaining = rest;
+            headers.push(if final_packet {
+                Header::end_of_body(chunk.to_vec())
+            } else {
+                Header::body(chunk.to_vec())
+            });
+
+            let code = if final_packet { code } else { ResponseCode::Continue };
+            self.send(Response { headers, ..Response::new(code) }).await?;
+            if final_packet {
+                return Ok(());
+            }
+            headers = Vec::new();
+
+            if !self.srm {
+                let req = self.recv_request().await?.ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
+                match req.opcode {
+                    Opcode::Get => (),
+                    Opcode::Abort => {
+                        log::debug!("OBEX GET operation aborted by client");
+                        return self.send(Response::new(ResponseCode::Success)).await;
+                    }
+                    other => {
+                        return Err(Error::new(
+                            ErrorKind::InvalidData,
+                            format!("unexpected OBEX {:?} request during GET operation", other),
+                        ))
+                    }
+                }
+            }
+        }
+    }
+}
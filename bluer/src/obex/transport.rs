@@ -0,0 +1,128 @@
+//! OBEX transports.
+
+use futures::future::BoxFuture;
+use std::io::{ErrorKind, Result};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::{packet_len, PACKET_HDR_LEN};
+
+/// Transport exchanging OBEX packets.
+pub trait Transport: Send {
+    /// Sends a complete packet.
+    fn send<'a>(&'a mut self, packet: &'a [u8]) -> BoxFuture<'a, Result<()>>;
+
+    /// Receives a complete packet.
+    ///
+    /// Returns [None] when the connection has been closed.
+    fn recv(&mut self) -> BoxFuture<'_, Result<Option<Vec<u8>>>>;
+
+    /// Maximum length of packets that can be sent and received over this transport.
+    fn max_packet_len(&self) -> u16 {
+        u16::MAX
+    }
+
+    /// Whether single response mode (SRM) may be used over this transport.
+    ///
+    /// SRM is only permitted when OBEX runs over L2CAP.
+    fn srm_supported(&self) -> bool {
+        false
+    }
+}
+
+/// Transport running OBEX over a byte stream, such as an RFCOMM [Stream](crate::rfcomm::Stream).
+///
+/// Packets are delimited using the length field of the packet header.
+#[derive(Debug)]
+pub struct StreamTransport<S> {
+    io: S,
+}
+
+impl<S> StreamTransport<S> {
+    /// Creates a transport over the specified byte stream.
+    pub fn new(io: S) -> Self {
+        Self { io }
+    }
+
+    /// Gets a reference to the underlying byte stream.
+    pub fn get_ref(&self) -> &S {
+        &self.io
+    }
+
+    /// Consumes the transport, returning the underlying byte stream.
+    pub fn into_inner(self) -> S {
+        self.io
+    }
+}
+
+impl<S> Transport for StreamTransport<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    fn send<'a>(&'a mut self, packet: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            self.io.write_all(packet).await?;
+            self.io.flush().await
+        })
+    }
+
+    fn recv(&mut self) -> BoxFuture<'_, Result<Option<Vec<u8>>>> {
+        Box::pin(async move {
+            let mut hdr = [0; PACKET_HDR_LEN];
+            match self.io.read_exact(&mut hdr).await {
+                Ok(_) => (),
+                Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                Err(err) => return Err(err),
+            }
+
+            let len = packet_len(&hdr) as usize;
+            if len < PACKET_HDR_LEN {
+                return Err(std::io::Error::new(ErrorKind::InvalidData, "invalid OBEX packet length"));
+            }
+            let mut packet = hdr.to_vec();
+            packet.resize(len, 0);
+            self.io.read_exact(&mut packet[PACKET_HDR_LEN..]).await?;
+            Ok(Some(packet))
+        })
+    }
+}
+
+/// OBEX over L2CAP.
+///
+/// Each packet is transmitted as one L2CAP SDU.
+/// The socket should use enhanced retransmission mode.
+#[cfg(feature = "l2cap")]
+#[cfg_attr(docsrs, doc(cfg(feature = "l2cap")))]
+impl Transport for crate::l2cap::SeqPacket {
+    fn send<'a>(&'a mut self, packet: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let n = crate::l2cap::SeqPacket::send(self, packet).await?;
+            if n != packet.len() {
+                return Err(std::io::Error::new(ErrorKind::WriteZero, "OBEX packet was truncated"));
+            }
+            Ok(())
+        })
+    }
+
+    fn recv(&mut self) -> BoxFuture<'_, Result<Option<Vec<u8>>>> {
+        Box::pin(async move {
+            let mut buf = vec![0; self.recv_mtu()?.max(PACKET_HDR_LEN)];
+            let n = crate::l2cap::SeqPacket::recv(self, &mut buf).await?;
+            if n == 0 {
+                return Ok(None);
+            }
+            buf.truncate(n);
+            Ok(Some(buf))
+        })
+    }
+
+    fn max_packet_len(&self) -> u16 {
+        match (self.send_mtu(), self.recv_mtu()) {
+            (Ok(send_mtu), Ok(recv_mtu)) => send_mtu.min(recv_mtu).min(u16::MAX as usize) as u16,
+            _ => super::MIN_PACKET_LEN,
+        }
+    }
+
+    fn srm_supported(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,486 @@
+//! Out-of-band (OOB) pairing data.
+//!
+//! Out-of-band pairing exchanges the values required for Secure Simple Pairing
+//! and LE Secure Connections pairing over a channel other than Bluetooth,
+//! for example NFC or a printed QR code.
+//!
+//! Use [Adapter::local_oob_data](crate::Adapter::local_oob_data) and
+//! [Adapter::local_le_oob_record](crate::Adapter::local_le_oob_record) to obtain the OOB data
+//! of a local adapter and [Adapter::add_remote_oob_data](crate::Adapter::add_remote_oob_data)
+//! to provide the OOB data of a remote device before pairing.
+//!
+//! [BrEdrOobRecord] and [LeOobRecord] encode and decode the OOB records
+//! used by the NFC Forum Connection Handover specification.
+
+use std::{
+    fmt,
+    io::{self, Error, ErrorKind},
+};
+
+use crate::{Address, AddressType};
+
+/// MIME type of a classic Bluetooth (BR/EDR) OOB record.
+pub const BR_EDR_MIME_TYPE: &str = "application/vnd.bluetooth.ep.oob";
+
+/// MIME type of a Bluetooth LE OOB record.
+pub const LE_MIME_TYPE: &str = "application/vnd.bluetooth.le.oob";
+
+const EIR_FLAGS: u8 = 0x01;
+const EIR_NAME_SHORT: u8 = 0x08;
+const EIR_NAME_COMPLETE: u8 = 0x09;
+const EIR_CLASS_OF_DEV: u8 = 0x0d;
+const EIR_SSP_HASH_C192: u8 = 0x0e;
+const EIR_SSP_RAND_R192: u8 = 0x0f;
+const EIR_SM_TK: u8 = 0x10;
+const EIR_APPEARANCE: u8 = 0x19;
+const EIR_LE_BDADDR: u8 = 0x1b;
+const EIR_LE_ROLE: u8 = 0x1c;
+const EIR_SSP_HASH_C256: u8 = 0x1d;
+const EIR_SSP_RAND_R256: u8 = 0x1e;
+const EIR_LE_SC_CONFIRM: u8 = 0x22;
+const EIR_LE_SC_RANDOM: u8 = 0x23;
+
+/// Out-of-band pairing data.
+///
+/// The hash (also known as confirmation value) and randomizer values
+/// are stored in the byte order used by the Bluetooth protocol.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OobData {
+    /// Hash value for pairing using P-192 elliptic curve.
+    pub hash_192: Option<[u8; 16]>,
+    /// Randomizer value for pairing using P-192 elliptic curve.
+    pub randomizer_192: Option<[u8; 16]>,
+    /// Hash value for pairing using P-256 elliptic curve (Secure Connections).
+    pub hash_256: Option<[u8; 16]>,
+    /// Randomizer value for pairing using P-256 elliptic curve (Secure Connections).
+    pub randomizer_256: Option<[u8; 16]>,
+}
+
+impl OobData {
+    /// Parses the return parameters of the management command
+    /// Read Local Out Of Band Data.
+    #[cfg(feature = "mgmt")]
+    pub(crate) fn from_mgmt(data: &[u8]) -> Option<Self> {
+        let value = |n: usize| -> Option<[u8; 16]> { data.get(n * 16..(n + 1) * 16)?.try_into().ok() };
+        match data.len() {
+            32 => Some(Self { hash_192: value(0), randomizer_192: value(1), ..Default::default() }),
+            64 => Some(Self {
+                hash_192: value(0),
+                randomizer_192: value(1),
+                hash_256: value(2),
+                randomizer_256: value(3),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Encodes the parameters of the management command Add Remote Out Of Band Data
+    /// following the address.
+    ///
+    /// Values that are not present are sent as zeros, which disables
+    /// the use of them.
+    #[cfg(feature = "bluetoothd")]
+    pub(crate) fn to_mgmt(&self, buf: &mut Vec<u8>) {
+        for value in [&self.hash_192, &self.randomizer_192, &self.hash_256, &self.randomizer_256] {
+            buf.extend_from_slice(&value.unwrap_or_default());
+        }
+    }
+}
+
+/// Invalid out-of-band record error.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InvalidOobRecord(pub String);
+
+impl fmt::Display for InvalidOobRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid OOB record: {}", &self.0)
+    }
+}
+
+impl std::error::Error for InvalidOobRecord {}
+
+/// Parses extended inquiry response (EIR) or advertising data (AD) structures.
+fn parse_eir(mut data: &[u8]) -> Result<Vec<(u8, &[u8])>, InvalidOobRecord> {
+    let mut fields = Vec::new();
+    while let Some((&len, rest)) = data.split_first() {
+        let len = len as usize;
+        if len == 0 {
+            // Remaining data is padding.
+            break;
+        }
+        if rest.len() < len {
+            return Err(InvalidOobRecord("truncated data structure".to_string()));
+        }
+        fields.push((rest[0], &rest[1..len]));
+        data = &rest[len..];
+    }
+    Ok(fields)
+}
+
+/// Appends an extended inquiry response (EIR) or advertising data (AD) structure.
+fn push_eir(buf: &mut Vec<u8>, ty: u8, data: &[u8]) -> io::Result<()> {
+    let len = u8::try_from(data.len() + 1)
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, format!("data type 0x{:02x} exceeds 254 bytes", ty)))?;
+    buf.push(len);
+    buf.push(ty);
+    buf.extend_from_slice(data);
+    Ok(())
+}
+
+fn value_16(ty: u8, data: &[u8]) -> Result<[u8; 16], InvalidOobRecord> {
+    data.try_into().map_err(|_| InvalidOobRecord(format!("invalid length of data type 0x{:02x}", ty)))
+}
+
+/// Classic Bluetooth (BR/EDR) out-of-band record.
+///
+/// This is the payload of an NFC record of type [BR_EDR_MIME_TYPE].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct BrEdrOobRecord {
+    /// Device address.
+    pub address: Address,
+    /// Class of device.
+    pub class: Option<u32>,
+    /// Local name of device.
+    pub name: Option<String>,
+    /// Out-of-band pairing data.
+    pub oob_data: OobData,
+    /// Other extended inquiry response data structures as pairs of data type and value.
+    pub other: Vec<(u8, Vec<u8>)>,
+}
+
+impl BrEdrOobRecord {
+    /// Creates a new BR/EDR out-of-band record.
+    pub fn new(address: Address, oob_data: OobData) -> Self {
+        Self { address, oob_data, ..Default::default() }
+    }
+
+    /// Encodes the out-of-band record.
+    ///
+    /// Fails if a data structure exceeds 254 bytes.
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0, 0];
+
+        let mut addr = self.address.0;
+        addr.reverse();
+        buf.extend_from_slice(&addr);
+
+        if let Some(class) = self.class {
+            push_eir(&mut buf, EIR_CLASS_OF_DEV, &class.to_le_bytes()[..3])?;
+        }
+        if let Some(hash) = &self.oob_data.hash_192 {
+            push_eir(&mut buf, EIR_SSP_HASH_C192, hash)?;
+        }
+        if let Some(randomizer) = &self.oob_data.randomizer_192 {
+            push_eir(&mut buf, EIR_SSP_RAND_R192, randomizer)?;
+        }
+        if let Some(hash) = &self.oob_data.hash_256 {
+            push_eir(&mut buf, EIR_SSP_HASH_C256, hash)?;
+        }
+        if let Some(randomizer) = &self.oob_data.randomizer_256 {
+            push_eir(&mut buf, EIR_SSP_RAND_R256, randomizer)?;
+        }
+        if let Some(name) = &self.name {
+            push_eir(&mut buf, EIR_NAME_COMPLETE, name.as_bytes())?;
+        }
+        for (ty, data) in &self.other {
+            push_eir(&mut buf, *ty, data)?;
+        }
+
+        let len = u16::try_from(buf.len())
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "record exceeds 65535 bytes"))?;
+        buf[..2].copy_from_slice(&len.to_le_bytes());
+        Ok(buf)
+    }
+
+    /// Decodes an out-of-band record.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, InvalidOobRecord> {
+        if data.len() < 8 {
+            return Err(InvalidOobRecord("record too short".to_string()));
+        }
+        let len = u16::from_le_bytes([data[0], data[1]]) as usize;
+        if len < 8 || len > data.len() {
+            return Err(InvalidOobRecord("invalid record length".to_string()));
+        }
+
+        let mut addr: [u8; 6] = data[2..8].try_into().unwrap();
+        addr.reverse();
+        let mut record = Self { address: Address::new(addr), ..Default::default() };
+
+        for (ty, value) in parse_eir(&data[8..len])? {
+            match ty {
+                EIR_CLASS_OF_DEV if value.len() == 3 => {
+                    record.class = Some(u32::from_le_bytes([value[0], value[1], value[2], 0]))
+                }
+                EIR_SSP_HASH_C192 => record.oob_data.hash_192 = Some(value_16(ty, value)?),
+                EIR_SSP_RAND_R192 => record.oob_data.randomizer_192 = Some(value_16(ty, value)?),
+                EIR_SSP_HASH_C256 => record.oob_data.hash_256 = Some(value_16(ty, value)?),
+                EIR_SSP_RAND_R256 => record.oob_data.randomizer_256 = Some(value_16(ty, value)?),
+                EIR_NAME_COMPLETE | EIR_NAME_SHORT => {
+                    record.name = Some(String::from_utf8_lossy(value).into_owned())
+                }
+                _ => record.other.push((ty, value.to_vec())),
+            }
+        }
+
+        Ok(record)
+    }
+}
+
+/// Bluetooth LE role supported by a device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LeRole {
+    /// Only peripheral role supported.
+    PeripheralOnly = 0x00,
+    /// Only central role supported.
+    CentralOnly = 0x01,
+    /// Peripheral and central role supported, peripheral role preferred
+    /// for connection establishment.
+    PeripheralPreferred = 0x02,
+    /// Peripheral and central role supported, central role preferred
+    /// for connection establishment.
+    CentralPreferred = 0x03,
+}
+
+impl LeRole {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0x00 => Some(Self::PeripheralOnly),
+            0x01 => Some(Self::CentralOnly),
+            0x02 => Some(Self::PeripheralPreferred),
+            0x03 => Some(Self::CentralPreferred),
+            _ => None,
+        }
+    }
+}
+
+/// Bluetooth LE out-of-band record.
+///
+/// This is the payload of an NFC record of type [LE_MIME_TYPE].
+///
+/// For LE Secure Connections pairing the confirmation and random values
+/// are stored as [hash_256](OobData::hash_256) and [randomizer_256](OobData::randomizer_256).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct LeOobRecord {
+    /// Device address.
+    pub address: Address,
+    /// Device address type.
+    pub address_type: AddressType,
+    /// Supported LE role.
+    pub role: Option<LeRole>,
+    /// Security manager temporary key (TK) for legacy pairing.
+    pub tk: Option<[u8; 16]>,
+    /// External appearance of device.
+    pub appearance: Option<u16>,
+    /// Advertising flags.
+    pub flags: Option<u8>,
+    /// Local name of device.
+    pub name: Option<String>,
+    /// Out-of-band pairing data.
+    pub oob_data: OobData,
+    /// Other advertising data structures as pairs of data type and value.
+    pub other: Vec<(u8, Vec<u8>)>,
+}
+
+impl LeOobRecord {
+    /// Creates a new LE out-of-band record.
+    pub fn new(address: Address, address_type: AddressType, oob_data: OobData) -> Self {
+        Self { address, address_type, oob_data, ..Default::default() }
+    }
+
+    /// Address types parameter of the management command Read Local Out Of Band
+    /// Extended Data selecting LE public and random addresses.
+    #[cfg(feature = "mgmt")]
+    pub(crate) const MGMT_ADDRESS_TYPES: u8 = 0x06;
+
+    /// Parses the return parameters of the management command
+    /// Read Local Out Of Band Extended Data for LE address types.
+    #[cfg(feature = "mgmt")]
+    pub(crate) fn from_mgmt(data: &[u8]) -> Option<Self> {
+        if data.len() < 3 || data[0] != Self::MGMT_ADDRESS_TYPES {
+            return None;
+        }
+        let len = u16::from_le_bytes([data[1], data[2]]) as usize;
+        Self::from_bytes(data.get(3..3 + len)?).ok()
+    }
+
+    /// Encodes the out-of-band record.
+    ///
+    /// Fails if a data structure exceeds 254 bytes.
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+
+        let mut addr = [0; 7];
+        addr[..6].copy_from_slice(&self.address.0);
+        addr[..6].reverse();
+        addr[6] = match self.address_type {
+            AddressType::LeRandom => 0x01,
+            _ => 0x00,
+        };
+        push_eir(&mut buf, EIR_LE_BDADDR, &addr)?;
+
+        if let Some(role) = self.role {
+            push_eir(&mut buf, EIR_LE_ROLE, &[role as u8])?;
+        }
+        if let Some(tk) = &self.tk {
+            push_eir(&mut buf, EIR_SM_TK, tk)?;
+        }
+        if let Some(confirm) = &self.oob_data.hash_256 {
+            push_eir(&mut buf, EIR_LE_SC_CONFIRM, confirm)?;
+        }
+        if let Some(random) = &self.oob_data.randomizer_256 {
+            push_eir(&mut buf, EIR_LE_SC_RANDOM, random)?;
+        }
+        if let Some(appearance) = self.appearance {
+            push_eir(&mut buf, EIR_APPEARANCE, &appearance.to_le_bytes())?;
+        }
+        if let Some(flags) = self.flags {
+            push_eir(&mut buf, EIR_FLAGS, &[flags])?;
+        }
+        if let Some(name) = &self.name {
+            push_eir(&mut buf, EIR_NAME_COMPLETE, name.as_bytes())?;
+        }
+        for (ty, data) in &self.other {
+            push_eir(&mut buf, *ty, data)?;
+        }
+
+        Ok(buf)
+    }
+
+    /// Decodes an out-of-band record.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, InvalidOobRecord> {
+        let mut record = Self::default();
+        let mut has_address = false;
+
+        for (ty, value) in parse_eir(data)? {
+            match ty {
+                EIR_LE_BDADDR => {
+                    if value.len() != 7 {
+                        return Err(InvalidOobRecord("invalid length of LE device address".to_string()));
+                    }
+                    let mut addr: [u8; 6] = value[..6].try_into().unwrap();
+                    addr.reverse();
+                    record.address = Address::new(addr);
+                    record.address_type =
+                        if value[6] & 0x01 != 0 { AddressType::LeRandom } else { AddressType::LePublic };
+                    has_address = true;
+                }
+                EIR_LE_ROLE if value.len() == 1 => record.role = LeRole::from_u8(value[0]),
+                EIR_SM_TK => record.tk = Some(value_16(ty, value)?),
+                EIR_LE_SC_CONFIRM => record.oob_data.hash_256 = Some(value_16(ty, value)?),
+                EIR_LE_SC_RANDOM => record.oob_data.randomizer_256 = Some(value_16(ty, value)?),
+                EIR_APPEARANCE if value.len() == 2 => {
+                    record.appearance = Some(u16::from_le_bytes([value[0], value[1]]))
+                }
+                EIR_FLAGS if value.len() == 1 => record.flags = Some(value[0]),
+                EIR_NAME_COMPLETE | EIR_NAME_SHORT => {
+                    record.name = Some(String::from_utf8_lossy(value).into_owned())
+                }
+                _ => record.other.push((ty, value.to_vec())),
+            }
+        }
+
+        if !has_address {
+            return Err(InvalidOobRecord("LE device address is missing".to_string()));
+        }
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: Address = Address([0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+    const HASH: [u8; 16] = [0x11; 16];
+    const RANDOMIZER: [u8; 16] = [0x22; 16];
+
+    #[rustfmt::skip]
+    const BR_EDR_RECORD: &[u8] = &[
+        // Length and device address.
+        0x38, 0x00, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01,
+        // Class of device.
+        0x04, 0x0d, 0x0c, 0x02, 0x5a,
+        // Simple pairing hash C-192.
+        0x11, 0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+        0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+        // Simple pairing randomizer R-192.
+        0x11, 0x0f, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22,
+        0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22,
+        // Complete local name.
+        0x06, 0x09, b'P', b'h', b'o', b'n', b'e',
+    ];
+
+    #[rustfmt::skip]
+    const LE_RECORD: &[u8] = &[
+        // LE Bluetooth device address.
+        0x08, 0x1b, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, 0x01,
+        // LE role.
+        0x02, 0x1c, 0x02,
+        // LE Secure Connections confirmation value.
+        0x11, 0x22, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+        0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+        // LE Secure Connections random value.
+        0x11, 0x23, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22,
+        0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22,
+        // Appearance.
+        0x03, 0x19, 0xc1, 0x03,
+        // Flags.
+        0x02, 0x01, 0x06,
+    ];
+
+    #[test]
+    fn br_edr_record() {
+        let record = BrEdrOobRecord {
+            class: Some(0x5a020c),
+            name: Some("Phone".to_string()),
+            ..BrEdrOobRecord::new(
+                ADDRESS,
+                OobData { hash_192: Some(HASH), randomizer_192: Some(RANDOMIZER), ..Default::default() },
+            )
+        };
+        assert_eq!(record.to_bytes().unwrap(), BR_EDR_RECORD);
+        assert_eq!(BrEdrOobRecord::from_bytes(BR_EDR_RECORD).unwrap(), record);
+    }
+
+    #[test]
+    fn le_record() {
+        let record = LeOobRecord {
+            role: Some(LeRole::PeripheralPreferred),
+            appearance: Some(0x03c1),
+            flags: Some(0x06),
+            ..LeOobRecord::new(
+                ADDRESS,
+                AddressType::LeRandom,
+                OobData { hash_256: Some(HASH), randomizer_256: Some(RANDOMIZER), ..Default::default() },
+            )
+        };
+        assert_eq!(record.to_bytes().unwrap(), LE_RECORD);
+        assert_eq!(LeOobRecord::from_bytes(LE_RECORD).unwrap(), record);
+    }
+
+    #[cfg(feature = "mgmt")]
+    #[test]
+    fn le_record_from_mgmt() {
+        let mut rsp = vec![LeOobRecord::MGMT_ADDRESS_TYPES];
+        rsp.extend_from_slice(&(LE_RECORD.len() as u16).to_le_bytes());
+        rsp.extend_from_slice(LE_RECORD);
+        assert_eq!(LeOobRecord::from_mgmt(&rsp), LeOobRecord::from_bytes(LE_RECORD).ok());
+        assert_eq!(LeOobRecord::from_mgmt(&rsp[..rsp.len() - 1]), None);
+        assert_eq!(LeOobRecord::from_mgmt(&[0x01, 0x00, 0x00]), None);
+    }
+
+    #[test]
+    fn reject_invalid_records() {
+        assert!(BrEdrOobRecord::from_bytes(&BR_EDR_RECORD[..7]).is_err());
+        assert!(BrEdrOobRecord::from_bytes(&BR_EDR_RECORD[..BR_EDR_RECORD.len() - 1]).is_err());
+        assert!(LeOobRecord::from_bytes(&LE_RECORD[9..]).is_err());
+        assert!(LeOobRecord::from_bytes(&LE_RECORD[..LE_RECORD.len() - 1]).is_err());
+    }
+}
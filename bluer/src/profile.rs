@@ -1,4 +1,11 @@
-//! Bluetooth profiles for RFCOMM connections.
+//! Bluetooth profiles for L2CAP and RFCOMM connections.
+//!
+//! Register a [Profile] using [Session::register_profile](crate::Session::register_profile)
+//! and listen to [connect requests](ConnectRequest) using the [ProfileHandle].
+//! The Bluetooth daemon registers and discovers SDP records and establishes
+//! connections using automatically discovered channel numbers or PSMs.
+//! Accepting a connect request provides the [socket](ProfileSocket) of the
+//! connection, whose type depends on the profile.
 
 use dbus::{
     arg::{OwnedFd, PropMap, RefArg, Variant},
@@ -6,6 +13,7 @@ use dbus::{
 };
 use dbus_crossroads::{Crossroads, IfaceBuilder, IfaceToken};
 use futures::Future;
+use libc::{c_int, AF_BLUETOOTH, SOCK_SEQPACKET, SOCK_STREAM, SOL_SOCKET, SO_DOMAIN, SO_PROTOCOL, SO_TYPE};
 use pin_project::{pin_project, pinned_drop};
 use std::{
     collections::HashMap,
@@ -20,8 +28,15 @@ use tokio::sync::{mpsc, oneshot, Mutex};
 use tokio_stream::wrappers::ReceiverStream;
 use uuid::Uuid;
 
-use super::{Socket, Stream};
-use crate::{method_call, read_dict, Address, Device, Result, SessionInner, ERR_PREFIX, SERVICE_NAME, TIMEOUT};
+#[cfg(feature = "l2cap")]
+use crate::l2cap;
+#[cfg(feature = "rfcomm")]
+use crate::rfcomm;
+use crate::{
+    method_call, read_dict, sock,
+    sys::{BTPROTO_L2CAP, BTPROTO_RFCOMM},
+    Address, Device, Error, ErrorKind, Result, SessionInner, ERR_PREFIX, SERVICE_NAME, TIMEOUT,
+};
 
 pub(crate) const MANAGER_INTERFACE: &str = "org.bluez.ProfileManager1";
 pub(crate) const MANAGER_PATH: &str = "/org/bluez";
@@ -29,7 +44,7 @@ pub(crate) const PROFILE_INTERFACE: &str = "org.bluez.Profile1";
 pub(crate) const PROFILE_PREFIX: &str = publish_path!("profile/");
 
 /// Error response from us to a Bluetooth profile request.
-#[cfg_attr(docsrs, doc(cfg(all(feature = "rfcomm", feature = "bluetoothd"))))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "bluetoothd", any(feature = "l2cap", feature = "rfcomm")))))]
 #[derive(Clone, Copy, Debug, displaydoc::Display, Eq, PartialEq, Ord, PartialOrd, Hash, IntoStaticStr)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
@@ -56,11 +71,11 @@ impl From<ReqError> for dbus::MethodErr {
 }
 
 /// Result of a Bluetooth profile request to us.
-#[cfg_attr(docsrs, doc(cfg(all(feature = "rfcomm", feature = "bluetoothd"))))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "bluetoothd", any(feature = "l2cap", feature = "rfcomm")))))]
 pub type ReqResult<T> = std::result::Result<T, ReqError>;
 
 /// Local profile role.
-#[cfg_attr(docsrs, doc(cfg(all(feature = "rfcomm", feature = "bluetoothd"))))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "bluetoothd", any(feature = "l2cap", feature = "rfcomm")))))]
 #[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord, Hash, Display, EnumString)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Role {
@@ -72,7 +87,10 @@ pub enum Role {
     Server,
 }
 
-/// Bluetooth RFCOMM profile definition.
+/// Bluetooth profile definition.
+///
+/// If [psm](Self::psm) is specified, connections are made over L2CAP;
+/// otherwise RFCOMM is used.
 ///
 /// Use [Session::register_profile](crate::Session::register_profile) to register a profile.
 ///
@@ -99,7 +117,7 @@ pub enum Role {
 ///     is required. Features is one bit value, specify
 ///     capability of Remote Audio Volume Control
 ///     (by default turned off).
-#[cfg_attr(docsrs, doc(cfg(all(feature = "rfcomm", feature = "bluetoothd"))))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "bluetoothd", any(feature = "l2cap", feature = "rfcomm")))))]
 #[derive(Clone, Debug, Default, Eq, PartialEq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Profile {
@@ -129,6 +147,9 @@ pub struct Profile {
     /// channels when a remote device is connected.
     pub auto_connect: Option<bool>,
     /// Provide a manual SDP record.
+    ///
+    /// The record is in the XML format used by BlueZ.
+    /// With the `sdp` feature enabled it can be built using `sdp::ServiceRecord::to_xml`.
     pub service_record: Option<String>,
     /// Profile version (for SDP record).
     pub version: Option<u16>,
@@ -181,7 +202,7 @@ impl Profile {
 /// A request to connect to this profile, either as client or server.
 ///
 /// The new service level connection has been made and authorized.
-#[cfg_attr(docsrs, doc(cfg(all(feature = "rfcomm", feature = "bluetoothd"))))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "bluetoothd", any(feature = "l2cap", feature = "rfcomm")))))]
 pub struct ConnectRequest {
     device: Address,
     fd: OwnedFd,
@@ -226,15 +247,19 @@ impl ConnectRequest {
         async move { closed_tx.closed().await }
     }
 
-    /// Accept the connection request and establish an RFCOMM connection.
-    pub fn accept(self) -> Result<Stream> {
+    /// Accept the connection request and establish the connection.
+    ///
+    /// The type of the returned socket is detected from the file descriptor
+    /// provided by the Bluetooth daemon.
+    /// If the socket type is not supported by the enabled crate features,
+    /// the connection request is rejected and an error is returned.
+    pub fn accept(self) -> Result<ProfileSocket> {
         let Self { fd, tx, .. } = self;
 
-        let socket = unsafe { Socket::from_raw_fd(fd.into_raw_fd()) }?;
-        let stream = Stream::from_socket(socket)?;
+        let socket = ProfileSocket::from_owned_fd(unsafe { sock::OwnedFd::new(fd.into_raw_fd()) })?;
         let _ = tx.send(Ok(()));
 
-        Ok(stream)
+        Ok(socket)
     }
 
     /// Reject the connection request.
@@ -243,6 +268,110 @@ impl ConnectRequest {
     }
 }
 
+/// Socket of a connection established through a Bluetooth profile.
+#[cfg_attr(docsrs, doc(cfg(all(feature = "bluetoothd", any(feature = "l2cap", feature = "rfcomm")))))]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ProfileSocket {
+    /// L2CAP stream socket.
+    #[cfg(feature = "l2cap")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "l2cap")))]
+    L2capStream(l2cap::Stream),
+    /// L2CAP sequential packet socket.
+    #[cfg(feature = "l2cap")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "l2cap")))]
+    L2capSeqPacket(l2cap::SeqPacket),
+    /// RFCOMM stream socket.
+    #[cfg(feature = "rfcomm")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rfcomm")))]
+    Rfcomm(rfcomm::Stream),
+}
+
+impl ProfileSocket {
+    /// Detects the socket type from the address family, protocol and type of the file descriptor.
+    fn from_owned_fd(fd: sock::OwnedFd) -> Result<Self> {
+        let domain: c_int = sock::getsockopt(&fd, SOL_SOCKET, SO_DOMAIN)?;
+        let protocol: c_int = sock::getsockopt(&fd, SOL_SOCKET, SO_PROTOCOL)?;
+        let ty: c_int = sock::getsockopt(&fd, SOL_SOCKET, SO_TYPE)?;
+        log::trace!("Profile connection socket has domain {}, protocol {} and type {}", domain, protocol, ty);
+
+        match (domain, protocol, ty) {
+            #[cfg(feature = "l2cap")]
+            (AF_BLUETOOTH, BTPROTO_L2CAP, SOCK_STREAM) => {
+                Ok(Self::L2capStream(unsafe { l2cap::Stream::from_raw_fd(fd.into_raw_fd()) }?))
+            }
+            #[cfg(feature = "l2cap")]
+            (AF_BLUETOOTH, BTPROTO_L2CAP, SOCK_SEQPACKET) => {
+                Ok(Self::L2capSeqPacket(unsafe { l2cap::SeqPacket::from_raw_fd(fd.into_raw_fd()) }?))
+            }
+            #[cfg(feature = "rfcomm")]
+            (AF_BLUETOOTH, BTPROTO_RFCOMM, SOCK_STREAM) => {
+                Ok(Self::Rfcomm(unsafe { rfcomm::Stream::from_raw_fd(fd.into_raw_fd()) }?))
+            }
+            #[cfg(not(feature = "l2cap"))]
+            (AF_BLUETOOTH, BTPROTO_L2CAP, SOCK_STREAM | SOCK_SEQPACKET) => Err(Error {
+                kind: ErrorKind::NotSupported,
+                message: "L2CAP sockets require the l2cap feature".to_string(),
+            }),
+            #[cfg(not(feature = "rfcomm"))]
+            (AF_BLUETOOTH, BTPROTO_RFCOMM, SOCK_STREAM) => Err(Error {
+                kind: ErrorKind::NotSupported,
+                message: "RFCOMM sockets require the rfcomm feature".to_string(),
+            }),
+            _ => Err(Error {
+                kind: ErrorKind::NotSupported,
+                message: "unsupported profile socket type".to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "l2cap")]
+impl TryFrom<ProfileSocket> for l2cap::Stream {
+    type Error = Error;
+
+    fn try_from(socket: ProfileSocket) -> Result<Self> {
+        match socket {
+            ProfileSocket::L2capStream(stream) => Ok(stream),
+            _ => Err(Error {
+                kind: ErrorKind::InvalidArguments,
+                message: "socket is not an L2CAP stream".to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "l2cap")]
+impl TryFrom<ProfileSocket> for l2cap::SeqPacket {
+    type Error = Error;
+
+    fn try_from(socket: ProfileSocket) -> Result<Self> {
+        match socket {
+            ProfileSocket::L2capSeqPacket(seq_packet) => Ok(seq_packet),
+            _ => Err(Error {
+                kind: ErrorKind::InvalidArguments,
+                message: "socket is not an L2CAP sequential packet socket".to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "rfcomm")]
+impl TryFrom<ProfileSocket> for rfcomm::Stream {
+    type Error = Error;
+
+    fn try_from(socket: ProfileSocket) -> Result<Self> {
+        match socket {
+            ProfileSocket::Rfcomm(stream) => Ok(stream),
+            #[allow(unreachable_patterns)]
+            _ => Err(Error {
+                kind: ErrorKind::InvalidArguments,
+                message: "socket is not an RFCOMM stream".to_string(),
+            }),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct ConnectRequestProps {
     pub version: Option<u16>,
@@ -364,10 +493,10 @@ impl RegisteredProfile {
     }
 }
 
-/// Handle to registered Bluetooth RFCOMM profile receiving its connect requests.
+/// Handle to registered Bluetooth profile receiving its connect requests.
 ///
 /// Drop to unregister profile.
-#[cfg_attr(docsrs, doc(cfg(all(feature = "rfcomm", feature = "bluetoothd"))))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "bluetoothd", any(feature = "l2cap", feature = "rfcomm")))))]
 #[pin_project(PinnedDrop)]
 pub struct ProfileHandle {
     name: dbus::Path<'static>,
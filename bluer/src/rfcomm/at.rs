@@ -0,0 +1,589 @@
+//! AT command codec.
+//!
+//! AT commands are used for signalling by the Hands-Free and Headset profiles.
+//! An [AtStream] frames [commands](Command) and [responses](Response) on any
+//! asynchronous byte stream, usually an RFCOMM [Stream](super::Stream).
+//!
+//! The [hfp](super::hfp) module implements the Hands-Free Profile on top of this codec.
+
+use std::{collections::VecDeque, fmt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Maximum length of a command or response line.
+pub const MAX_LINE_LEN: usize = 2048;
+
+/// Kind of an AT command.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CommandKind {
+    /// Execute command, for example `ATA` or `AT+CHUP`.
+    Execute,
+    /// Read command querying the current value, for example `AT+CIND?`.
+    Read,
+    /// Test command querying the supported values, for example `AT+CIND=?`.
+    Test,
+    /// Set command with arguments, for example `AT+VGS=7` or `ATD1234;`.
+    Set(String),
+}
+
+/// AT command.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Command {
+    /// Command name.
+    ///
+    /// Extended commands include their prefix, for example `+BRSF`.
+    /// Basic commands consist of a single letter, for example `D`.
+    /// The name is always in upper case.
+    pub name: String,
+    /// Command kind and arguments.
+    pub kind: CommandKind,
+}
+
+impl Command {
+    /// Creates an execute command.
+    pub fn execute(name: impl Into<String>) -> Self {
+        Self { name: name.into().to_ascii_uppercase(), kind: CommandKind::Execute }
+    }
+
+    /// Creates a read command.
+    pub fn read(name: impl Into<String>) -> Self {
+        Self { name: name.into().to_ascii_uppercase(), kind: CommandKind::Read }
+    }
+
+    /// Creates a test command.
+    pub fn test(name: impl Into<String>) -> Self {
+        Self { name: name.into().to_ascii_uppercase(), kind: CommandKind::Test }
+    }
+
+    /// Creates a set command with the specified arguments.
+    pub fn set(name: impl Into<String>, args: impl fmt::Display) -> Self {
+        Self { name: name.into().to_ascii_uppercase(), kind: CommandKind::Set(args.to_string()) }
+    }
+
+    /// Returns whether this is a basic command consisting of a single letter.
+    pub fn is_basic(&self) -> bool {
+        self.name.len() == 1 && self.name.chars().all(|c| c.is_ascii_alphabetic())
+    }
+
+    /// Arguments of a set command split using [split_args].
+    ///
+    /// Returns an empty list for other command kinds.
+    pub fn args(&self) -> Vec<String> {
+        match &self.kind {
+            CommandKind::Set(args) => split_args(args),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Parses a command line, not including the line terminator.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if !line.get(..2).map(|at| at.eq_ignore_ascii_case("AT")).unwrap_or_default() {
+            return None;
+        }
+        let rest = &line[2..];
+
+        let (name, suffix) = match rest.chars().next() {
+            None => return Some(Self::execute("")),
+            Some(c) if c.is_ascii_alphabetic() => rest.split_at(1),
+            Some(_) => rest.split_at(rest.find(['=', '?']).unwrap_or(rest.len())),
+        };
+
+        let kind = match suffix {
+            "" => CommandKind::Execute,
+            "?" => CommandKind::Read,
+            "=?" => CommandKind::Test,
+            _ => CommandKind::Set(suffix.strip_prefix('=').unwrap_or(suffix).to_string()),
+        };
+        Some(Self { name: name.to_ascii_uppercase(), kind })
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AT{}", &self.name)?;
+        match &self.kind {
+            CommandKind::Execute => Ok(()),
+            CommandKind::Read => write!(f, "?"),
+            CommandKind::Test => write!(f, "=?"),
+            CommandKind::Set(args) if self.is_basic() => write!(f, "{}", args),
+            CommandKind::Set(args) => write!(f, "={}", args),
+        }
+    }
+}
+
+/// Result code sent in response to a command or unsolicited.
+///
+/// Examples are `+CIEV: 2,1` or `RING`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ResultCode {
+    /// Name, for example `+CIEV`.
+    pub name: String,
+    /// Unparsed arguments, for example `2,1`.
+    pub args: String,
+    /// Separator between name and arguments.
+    ///
+    /// This is `:` for standard result codes.
+    /// Some vendor extensions, such as `+XAPL`, use `=` instead.
+    pub separator: char,
+}
+
+impl ResultCode {
+    /// Creates a result code using the standard separator `:`.
+    pub fn new(name: impl Into<String>, args: impl fmt::Display) -> Self {
+        Self::with_separator(name, ':', args)
+    }
+
+    /// Creates a result code using the specified separator between name and arguments.
+    pub fn with_separator(name: impl Into<String>, separator: char, args: impl fmt::Display) -> Self {
+        Self { name: name.into(), args: args.to_string(), separator }
+    }
+
+    /// Arguments split using [split_args].
+    pub fn args(&self) -> Vec<String> {
+        split_args(&self.args)
+    }
+
+    /// Returns whether this result code has the specified name.
+    ///
+    /// The comparison is case insensitive.
+    pub fn is(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+    }
+}
+
+impl fmt::Display for ResultCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.args.is_empty() {
+            write!(f, "{}", &self.name)
+        } else if self.separator == ':' {
+            write!(f, "{}: {}", &self.name, &self.args)
+        } else {
+            write!(f, "{}{}{}", &self.name, self.separator, &self.args)
+        }
+    }
+}
+
+/// Final result code indicating failure of a command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorResult {
+    /// `ERROR`
+    Error,
+    /// `+CME ERROR` with extended error code.
+    CmeError(u16),
+    /// `NO CARRIER`
+    NoCarrier,
+    /// `BUSY`
+    Busy,
+    /// `NO ANSWER`
+    NoAnswer,
+    /// `DELAYED`
+    Delayed,
+    /// `BLACKLISTED`
+    Blacklisted,
+}
+
+impl fmt::Display for ErrorResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "ERROR"),
+            Self::CmeError(code) => write!(f, "+CME ERROR: {}", code),
+            Self::NoCarrier => write!(f, "NO CARRIER"),
+            Self::Busy => write!(f, "BUSY"),
+            Self::NoAnswer => write!(f, "NO ANSWER"),
+            Self::Delayed => write!(f, "DELAYED"),
+            Self::Blacklisted => write!(f, "BLACKLISTED"),
+        }
+    }
+}
+
+impl std::error::Error for ErrorResult {}
+
+impl From<ErrorResult> for std::io::Error {
+    fn from(err: ErrorResult) -> Self {
+        std::io::Error::new(std::io::ErrorKind::Other, err)
+    }
+}
+
+impl ErrorResult {
+    /// Extracts the error result from an I/O error returned by [AtStream::execute].
+    pub fn from_io_error(err: &std::io::Error) -> Option<Self> {
+        err.get_ref().and_then(|err| err.downcast_ref::<Self>()).copied()
+    }
+}
+
+/// Response line.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Response {
+    /// Final result code `OK`.
+    Ok,
+    /// Final result code indicating failure.
+    Error(ErrorResult),
+    /// Intermediate or unsolicited result code.
+    Result(ResultCode),
+}
+
+impl Response {
+    /// Parses a response line, not including the line terminators.
+    pub fn parse(line: &str) -> Self {
+        let line = line.trim();
+        match line {
+            "OK" => return Self::Ok,
+            "ERROR" => return Self::Error(ErrorResult::Error),
+            "NO CARRIER" => return Self::Error(ErrorResult::NoCarrier),
+            "BUSY" => return Self::Error(ErrorResult::Busy),
+            "NO ANSWER" => return Self::Error(ErrorResult::NoAnswer),
+            "DELAYED" => return Self::Error(ErrorResult::Delayed),
+            "BLACKLISTED" => return Self::Error(ErrorResult::Blacklisted),
+            _ => (),
+        }
+
+        // Some vendor extensions, such as +XAPL, use '=' instead of ':' as separator.
+        let sep = if line.starts_with('+') { line.find([':', '=']) } else { line.find(':') };
+        let (name, separator, args) = match sep {
+            Some(pos) => (line[..pos].trim(), line[pos..].chars().next().unwrap(), line[pos + 1..].trim()),
+            None => (line, ':', ""),
+        };
+
+        if name == "+CME ERROR" {
+            if let Ok(code) = args.parse() {
+                return Self::Error(ErrorResult::CmeError(code));
+            }
+        }
+        Self::Result(ResultCode::with_separator(name, separator, args))
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ok => write!(f, "OK"),
+            Self::Error(err) => write!(f, "{}", err),
+            Self::Result(rc) => write!(f, "{}", rc),
+        }
+    }
+}
+
+impl From<ResultCode> for Response {
+    fn from(rc: ResultCode) -> Self {
+        Self::Result(rc)
+    }
+}
+
+impl From<ErrorResult> for Response {
+    fn from(err: ErrorResult) -> Self {
+        Self::Error(err)
+    }
+}
+
+/// Splits AT command or result code arguments at top-level commas.
+///
+/// Commas within quoted strings and parentheses are not considered.
+/// Whitespace around arguments is removed and
+/// quoted strings are unquoted.
+pub fn split_args(args: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0usize;
+    let mut quoted = false;
+
+    for c in args.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth = depth.saturating_sub(1),
+            ',' if !quoted && depth == 0 => {
+                result.push(unquote(&current));
+                current.clear();
+                continue;
+            }
+            _ => (),
+        }
+        current.push(c);
+    }
+
+    if !current.trim().is_empty() || !result.is_empty() {
+        result.push(unquote(&current));
+    }
+    result
+}
+
+fn unquote(arg: &str) -> String {
+    let arg = arg.trim();
+    match arg.strip_prefix('"').and_then(|arg| arg.strip_suffix('"')) {
+        Some(inner) => inner.to_string(),
+        None => arg.to_string(),
+    }
+}
+
+/// AT command stream.
+///
+/// Frames commands and responses on an asynchronous byte stream.
+/// The device issuing commands, such as a hands-free unit, uses [execute](Self::execute)
+/// and [recv_unsolicited](Self::recv_unsolicited).
+/// The device receiving commands, such as an audio gateway, uses [recv_command](Self::recv_command)
+/// and [send_response](Self::send_response).
+///
+/// All receive methods are cancel safe.
+#[derive(Debug)]
+pub struct AtStream<S> {
+    io: S,
+    buf: Vec<u8>,
+    discard_line: bool,
+    unsolicited: VecDeque<ResultCode>,
+}
+
+impl<S> AtStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Creates an AT command stream over the specified byte stream.
+    pub fn new(io: S) -> Self {
+        Self { io, buf: Vec::new(), discard_line: false, unsolicited: VecDeque::new() }
+    }
+
+    /// Gets a reference to the underlying byte stream.
+    pub fn get_ref(&self) -> &S {
+        &self.io
+    }
+
+    /// Gets a mutable reference to the underlying byte stream.
+    ///
+    /// Reading from or writing to the stream directly will corrupt the framing.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.io
+    }
+
+    /// Consumes the AT command stream, returning the underlying byte stream.
+    ///
+    /// Received data that has not been processed yet is lost.
+    pub fn into_inner(self) -> S {
+        self.io
+    }
+
+    /// Receives the next non-empty line.
+    ///
+    /// Lines are terminated by carriage return or line feed.
+    /// Lines exceeding [MAX_LINE_LEN] are discarded and an error is returned.
+    /// Returns [None] when the end of the stream has been reached.
+    async fn recv_line(&mut self) -> std::io::Result<Option<String>> {
+        let too_long = || std::io::Error::new(std::io::ErrorKind::InvalidData, "AT line too long");
+
+        loop {
+            if let Some(pos) = self.buf.iter().position(|&b| b == b'\r' || b == b'\n') {
+                let line: Vec<u8> = self.buf.drain(..=pos).collect();
+                if self.discard_line {
+                    self.discard_line = false;
+                    continue;
+                }
+                if pos > MAX_LINE_LEN {
+                    return Err(too_long());
+                }
+                let line = String::from_utf8_lossy(&line[..pos]).trim().to_string();
+                if line.is_empty() {
+                    continue;
+                }
+                log::trace!("AT recv: {}", &line);
+                return Ok(Some(line));
+            }
+
+            if self.buf.len() > MAX_LINE_LEN {
+                self.buf.clear();
+                if !self.discard_line {
+                    self.discard_line = true;
+                    return Err(too_long());
+                }
+            }
+
+            let mut chunk = [0; 256];
+            let n = self.io.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(None);
+            }
+            self.buf.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    async fn send_line(&mut self, line: &str) -> std::io::Result<()> {
+        log::trace!("AT send: {}", line.trim());
+        self.io.write_all(line.as_bytes()).await?;
+        self.io.flush().await
+    }
+
+    /// Receives the next command.
+    ///
+    /// Lines that are not AT commands are ignored.
+    /// Returns [None] when the end of the stream has been reached.
+    pub async fn recv_command(&mut self) -> std::io::Result<Option<Command>> {
+        loop {
+            let line = match self.recv_line().await? {
+                Some(line) => line,
+                None => return Ok(None),
+            };
+            match Command::parse(&line) {
+                Some(cmd) => return Ok(Some(cmd)),
+                None => log::debug!("Ignoring invalid AT command: {}", &line),
+            }
+        }
+    }
+
+    /// Sends a command.
+    pub async fn send_command(&mut self, cmd: &Command) -> std::io::Result<()> {
+        self.send_line(&format!("{}\r", cmd)).await
+    }
+
+    /// Receives the next response line.
+    ///
+    /// Returns [None] when the end of the stream has been reached.
+    pub async fn recv_response(&mut self) -> std::io::Result<Option<Response>> {
+        Ok(self.recv_line().await?.map(|line| Response::parse(&line)))
+    }
+
+    /// Sends a response line.
+    pub async fn send_response(&mut self, rsp: &Response) -> std::io::Result<()> {
+        self.send_line(&format!("\r\n{}\r\n", rsp)).await
+    }
+
+    /// Sends a command and waits for its final result code.
+    ///
+    /// Returns the intermediate result codes that have the same name as the command.
+    /// Other result codes received while waiting are queued and returned by
+    /// [recv_unsolicited](Self::recv_unsolicited).
+    ///
+    /// If the command fails, an error containing the [ErrorResult] is returned.
+    /// Use [ErrorResult::from_io_error] to obtain it.
+    pub async fn execute(&mut self, cmd: &Command) -> std::io::Result<Vec<ResultCode>> {
+        self.send_command(cmd).await?;
+
+        let mut results = Vec::new();
+        loop {
+            match self.recv_response().await? {
+                Some(Response::Ok) => return Ok(results),
+                Some(Response::Error(err)) => return Err(err.into()),
+                Some(Response::Result(rc)) if !cmd.name.is_empty() && rc.is(&cmd.name) => results.push(rc),
+                Some(Response::Result(rc)) => self.unsolicited.push_back(rc),
+                None => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            }
+        }
+    }
+
+    /// Receives the next unsolicited result code.
+    ///
+    /// Final result codes received without a pending command are ignored.
+    /// Returns [None] when the end of the stream has been reached.
+    pub async fn recv_unsolicited(&mut self) -> std::io::Result<Option<ResultCode>> {
+        if let Some(rc) = self.unsolicited.pop_front() {
+            return Ok(Some(rc));
+        }
+
+        loop {
+            match self.recv_response().await? {
+                Some(Response::Result(rc)) => return Ok(Some(rc)),
+                Some(rsp) => log::debug!("Ignoring unexpected AT final result code: {}", rsp),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::duplex;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(Command::parse("AT+BRSF=191"), Some(Command::set("+BRSF", "191")));
+        assert_eq!(Command::parse("at+cind?"), Some(Command::read("+CIND")));
+        assert_eq!(Command::parse("AT+CIND=?"), Some(Command::test("+CIND")));
+        assert_eq!(Command::parse("ATA"), Some(Command::execute("A")));
+        assert_eq!(Command::parse("ATD1234;"), Some(Command::set("D", "1234;")));
+        assert_eq!(Command::parse(" AT "), Some(Command::execute("")));
+        assert_eq!(Command::parse("A"), None);
+        assert_eq!(Command::parse("Aé+BRSF"), None);
+        assert_eq!(Command::parse("é"), None);
+        assert_eq!(Command::parse("ATé"), Some(Command::execute("é")));
+    }
+
+    #[test]
+    fn encode_commands() {
+        for line in ["AT+BRSF=191", "AT+CIND?", "AT+CIND=?", "ATA", "ATD1234;", "AT+CMER=3,0,0,1"] {
+            assert_eq!(Command::parse(line).unwrap().to_string(), line);
+        }
+    }
+
+    #[test]
+    fn parse_responses() {
+        assert_eq!(Response::parse("OK"), Response::Ok);
+        assert_eq!(Response::parse("+CME ERROR: 30"), Response::Error(ErrorResult::CmeError(30)));
+        assert_eq!(Response::parse("+CIEV: 2,1"), Response::Result(ResultCode::new("+CIEV", "2,1")));
+        assert_eq!(Response::parse("RING"), Response::Result(ResultCode::new("RING", "")));
+        assert_eq!(
+            Response::parse("+XAPL=iPhone,2"),
+            Response::Result(ResultCode::with_separator("+XAPL", '=', "iPhone,2"))
+        );
+    }
+
+    #[test]
+    fn encode_responses() {
+        for line in ["OK", "+CME ERROR: 30", "+CIEV: 2,1", "RING", "+XAPL=iPhone,2", "NO CARRIER"] {
+            assert_eq!(Response::parse(line).to_string(), line);
+        }
+    }
+
+    #[test]
+    fn split_arguments() {
+        assert_eq!(split_args(r#""1234",129"#), vec!["1234", "129"]);
+        assert_eq!(
+            split_args(r#"("service",(0,1)),("call",(0-1))"#),
+            vec![r#"("service",(0,1))"#, r#"("call",(0-1))"#]
+        );
+        assert_eq!(split_args(""), Vec::<String>::new());
+        assert_eq!(split_args("1,,2"), vec!["1", "", "2"]);
+    }
+
+    #[tokio::test]
+    async fn execute_command() {
+        let (hf, ag) = duplex(256);
+        let mut hf = AtStream::new(hf);
+        let mut ag = AtStream::new(ag);
+
+        let cind = Command::test("+CIND");
+        let (results, ()) = tokio::join!(hf.execute(&cind), async {
+            assert_eq!(ag.recv_command().await.unwrap(), Some(Command::test("+CIND")));
+            ag.send_response(&ResultCode::new("+CIEV", "1,1").into()).await.unwrap();
+            ag.send_response(&ResultCode::new("+CIND", r#"("call",(0,1))"#).into()).await.unwrap();
+            ag.send_response(&Response::Ok).await.unwrap();
+        });
+        assert_eq!(results.unwrap(), vec![ResultCode::new("+CIND", r#"("call",(0,1))"#)]);
+        assert_eq!(hf.recv_unsolicited().await.unwrap(), Some(ResultCode::new("+CIEV", "1,1")));
+
+        let chup = Command::execute("+CHUP");
+        let (result, ()) = tokio::join!(hf.execute(&chup), async {
+            ag.recv_command().await.unwrap();
+            ag.send_response(&ErrorResult::CmeError(3).into()).await.unwrap();
+        });
+        assert_eq!(ErrorResult::from_io_error(&result.unwrap_err()), Some(ErrorResult::CmeError(3)));
+
+        drop(ag);
+        assert_eq!(hf.recv_unsolicited().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn reject_long_line() {
+        let (mut tx, rx) = duplex(8 * MAX_LINE_LEN);
+        let mut at = AtStream::new(rx);
+
+        let mut data = vec![b'A'; MAX_LINE_LEN + 100];
+        data.extend_from_slice(b"\rAT+CHUP\r");
+        data.extend(std::iter::repeat(b'B').take(3 * MAX_LINE_LEN));
+        data.extend_from_slice(b"\rATA\r");
+        tx.write_all(&data).await.unwrap();
+        drop(tx);
+
+        assert_eq!(at.recv_command().await.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(at.recv_command().await.unwrap(), Some(Command::execute("+CHUP")));
+        assert_eq!(at.recv_command().await.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(at.recv_command().await.unwrap(), Some(Command::execute("A")));
+        assert_eq!(at.recv_command().await.unwrap(), None);
+    }
+}
@@ -0,0 +1,1121 @@
+//! Hands-Free Profile (HFP) signalling.
+//!
+//! [HandsFree] implements the hands-free unit (HF) role, taken for example by a car kit,
+//! and [AudioGateway] implements the audio gateway (AG) role, usually taken by a phone.
+//! Both establish the service level connection (SLC) and then exchange AT commands
+//! over an [AtStream], usually running on an RFCOMM [Stream](super::Stream).
+//!
+//! Audio is transported over a separate SCO connection that is not handled by this module.
+//! Apple accessory extensions (`+XAPL` and `+IPHONEACCEV`) are supported for
+//! reporting the battery level of the hands-free unit.
+
+use std::{collections::VecDeque, fmt, io};
+use tokio::io::{AsyncRead, AsyncWrite};
+use uuid::Uuid;
+
+use super::at::{AtStream, Command, CommandKind, ErrorResult, Response, ResultCode};
+
+/// Service class UUID of the hands-free unit.
+pub const HANDSFREE: Uuid = Uuid::from_u128(0x0000111e_0000_1000_8000_00805f9b34fb);
+
+/// Service class UUID of the hands-free audio gateway.
+pub const HANDSFREE_AUDIO_GATEWAY: Uuid = Uuid::from_u128(0x0000111f_0000_1000_8000_00805f9b34fb);
+
+/// Names of the standard indicators of the audio gateway.
+pub mod indicator {
+    /// Service availability.
+    pub const SERVICE: &str = "service";
+    /// Active call.
+    pub const CALL: &str = "call";
+    /// Call setup status.
+    pub const CALL_SETUP: &str = "callsetup";
+    /// Call held status.
+    pub const CALL_HELD: &str = "callheld";
+    /// Signal strength.
+    pub const SIGNAL: &str = "signal";
+    /// Roaming status.
+    pub const ROAM: &str = "roam";
+    /// Battery charge.
+    pub const BATTERY_CHARGE: &str = "battchg";
+}
+
+/// Apple accessory feature bit for battery level reporting.
+const XAPL_BATTERY: u32 = 1 << 1;
+
+macro_rules! define_features {
+    ($name:ident, $doc:tt => {
+        $(
+            $(#[$field_outer:meta])*
+            $field:ident ($bit:expr),
+        )*
+    }) => {
+        #[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        #[doc=$doc]
+        pub struct $name {
+            $(
+                $(#[$field_outer])*
+                pub $field: bool,
+            )*
+        }
+
+        impl $name {
+            /// Decodes the features from the bit mask exchanged using `+BRSF`.
+            pub fn from_bits(bits: u32) -> Self {
+                Self {
+                    $( $field: bits & (1 << $bit) != 0, )*
+                }
+            }
+
+            /// Encodes the features into the bit mask exchanged using `+BRSF`.
+            pub fn to_bits(&self) -> u32 {
+                let mut bits = 0;
+                $(
+                    if self.$field {
+                        bits |= 1 << $bit;
+                    }
+                )*
+                bits
+            }
+        }
+    };
+}
+
+define_features!(HfFeatures, "Supported features of the hands-free unit." => {
+    /// Echo cancellation and/or noise reduction.
+    ec_nr(0),
+    /// Call waiting and three-way calling.
+    three_way_calling(1),
+    /// Calling line identification presentation.
+    cli_presentation(2),
+    /// Voice recognition activation.
+    voice_recognition(3),
+    /// Remote volume control.
+    remote_volume_control(4),
+    /// Enhanced call status.
+    enhanced_call_status(5),
+    /// Enhanced call control.
+    enhanced_call_control(6),
+    /// Codec negotiation.
+    codec_negotiation(7),
+    /// HF indicators.
+    hf_indicators(8),
+    /// eSCO S4 settings.
+    esco_s4(9),
+});
+
+define_features!(AgFeatures, "Supported features of the audio gateway." => {
+    /// Three-way calling.
+    three_way_calling(0),
+    /// Echo cancellation and/or noise reduction.
+    ec_nr(1),
+    /// Voice recognition.
+    voice_recognition(2),
+    /// In-band ring tone.
+    in_band_ring(3),
+    /// Attach a number to a voice tag.
+    voice_tag(4),
+    /// Ability to reject a call.
+    reject_call(5),
+    /// Enhanced call status.
+    enhanced_call_status(6),
+    /// Enhanced call control.
+    enhanced_call_control(7),
+    /// Extended error result codes.
+    extended_error_codes(8),
+    /// Codec negotiation.
+    codec_negotiation(9),
+    /// HF indicators.
+    hf_indicators(10),
+    /// eSCO S4 settings.
+    esco_s4(11),
+});
+
+/// Audio codec negotiated using `+BCS`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Codec {
+    /// CVSD, narrow band speech.
+    Cvsd,
+    /// mSBC, wide band speech.
+    Msbc,
+    /// LC3-SWB, super wide band speech.
+    Lc3Swb,
+    /// Unknown codec.
+    Unknown(u8),
+}
+
+impl From<u8> for Codec {
+    fn from(id: u8) -> Self {
+        match id {
+            1 => Self::Cvsd,
+            2 => Self::Msbc,
+            3 => Self::Lc3Swb,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl From<Codec> for u8 {
+    fn from(codec: Codec) -> Self {
+        match codec {
+            Codec::Cvsd => 1,
+            Codec::Msbc => 2,
+            Codec::Lc3Swb => 3,
+            Codec::Unknown(id) => id,
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cvsd => write!(f, "CVSD"),
+            Self::Msbc => write!(f, "mSBC"),
+            Self::Lc3Swb => write!(f, "LC3-SWB"),
+            Self::Unknown(id) => write!(f, "unknown codec {}", id),
+        }
+    }
+}
+
+/// Indicator of the audio gateway.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Indicator {
+    /// Name, see the [indicator] module for standard names.
+    pub name: String,
+    /// Minimum value.
+    pub min: u8,
+    /// Maximum value.
+    pub max: u8,
+    /// Current value.
+    pub value: u8,
+}
+
+impl Indicator {
+    /// Creates an indicator.
+    pub fn new(name: impl Into<String>, min: u8, max: u8, value: u8) -> Self {
+        Self { name: name.into(), min, max, value }
+    }
+
+    /// The standard indicators of an audio gateway in their usual order.
+    pub fn standard() -> Vec<Self> {
+        vec![
+            Self::new(indicator::SERVICE, 0, 1, 0),
+            Self::new(indicator::CALL, 0, 1, 0),
+            Self::new(indicator::CALL_SETUP, 0, 3, 0),
+            Self::new(indicator::CALL_HELD, 0, 2, 0),
+            Self::new(indicator::SIGNAL, 0, 5, 0),
+            Self::new(indicator::ROAM, 0, 1, 0),
+            Self::new(indicator::BATTERY_CHARGE, 0, 5, 0),
+        ]
+    }
+
+    /// Parses an indicator description as returned by `AT+CIND=?`,
+    /// for example `("callsetup",(0-3))` or `("service",(0,1))`.
+    fn parse_description(desc: &str) -> Option<Self> {
+        let inner = desc.trim().strip_prefix('(')?.strip_suffix(')')?;
+        let args = super::at::split_args(inner);
+        let name = args.first()?.clone();
+        let range = args.get(1)?.trim().strip_prefix('(')?.strip_suffix(')')?;
+        let values: Vec<u8> = range.split([',', '-']).filter_map(|v| v.trim().parse().ok()).collect();
+        Some(Self::new(name, *values.iter().min()?, *values.iter().max()?, 0))
+    }
+
+    fn description(&self) -> String {
+        format!("(\"{}\",({}-{}))", &self.name, self.min, self.max)
+    }
+}
+
+/// Apple accessory identification sent using `AT+XAPL`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct AppleAccessory {
+    /// Vendor id.
+    pub vendor_id: u16,
+    /// Product id.
+    pub product_id: u16,
+    /// Product version.
+    pub version: u16,
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn parse_arg<T: std::str::FromStr>(arg: Option<&String>) -> io::Result<T> {
+    arg.and_then(|arg| arg.trim().parse().ok()).ok_or_else(|| invalid_data("invalid AT argument"))
+}
+
+fn check_volume(volume: u8) -> io::Result<()> {
+    if volume > 15 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "volume must be between 0 and 15"));
+    }
+    Ok(())
+}
+
+/// Converts a battery percentage to the Apple battery level between 0 and 9.
+fn battery_percent_to_level(percent: u8) -> u8 {
+    (percent.min(100).saturating_sub(1) / 10).min(9)
+}
+
+/// Converts an Apple battery level between 0 and 9 to a battery percentage.
+fn battery_level_to_percent(level: u8) -> u8 {
+    (level.min(9) + 1) * 10
+}
+
+/// Hands-free unit configuration.
+#[derive(Clone, Debug)]
+pub struct HfConfig {
+    /// Supported features.
+    pub features: HfFeatures,
+    /// Supported audio codecs.
+    ///
+    /// Only used when codec negotiation is supported by both devices.
+    pub codecs: Vec<Codec>,
+    /// Identify as Apple accessory to enable battery level reporting.
+    pub apple_accessory: Option<AppleAccessory>,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl Default for HfConfig {
+    fn default() -> Self {
+        Self {
+            features: HfFeatures {
+                cli_presentation: true,
+                remote_volume_control: true,
+                codec_negotiation: true,
+                ..Default::default()
+            },
+            codecs: vec![Codec::Cvsd, Codec::Msbc],
+            apple_accessory: None,
+            _non_exhaustive: (),
+        }
+    }
+}
+
+/// Event received by the hands-free unit from the audio gateway.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HfEvent {
+    /// Indicator value changed.
+    Indicator {
+        /// Indicator name.
+        name: String,
+        /// New value.
+        value: u8,
+    },
+    /// Incoming call alert.
+    Ring,
+    /// Calling line identification of incoming call.
+    CallerId {
+        /// Phone number.
+        number: String,
+    },
+    /// Call waiting notification.
+    CallWaiting {
+        /// Phone number.
+        number: String,
+    },
+    /// Speaker volume between 0 and 15 set by audio gateway.
+    SpeakerVolume(u8),
+    /// Microphone volume between 0 and 15 set by audio gateway.
+    MicrophoneVolume(u8),
+    /// In-band ring tone enabled or disabled.
+    InBandRing(bool),
+    /// Audio codec selected and confirmed.
+    CodecSelected(Codec),
+    /// Other unsolicited result code.
+    Other(ResultCode),
+}
+
+/// Hands-free unit (HF).
+#[derive(Debug)]
+pub struct HandsFree<S> {
+    at: AtStream<S>,
+    config: HfConfig,
+    ag_features: AgFeatures,
+    indicators: Vec<Indicator>,
+    call_hold_modes: Vec<String>,
+    codec: Option<Codec>,
+    battery_reporting: bool,
+}
+
+impl<S> HandsFree<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Establishes the service level connection with the audio gateway
+    /// connected through the specified byte stream.
+    pub async fn connect(io: S, config: HfConfig) -> io::Result<Self> {
+        let mut this = Self {
+            at: AtStream::new(io),
+            config,
+            ag_features: AgFeatures::default(),
+            indicators: Vec::new(),
+            call_hold_modes: Vec::new(),
+            codec: None,
+            battery_reporting: false,
+        };
+
+        let brsf = this.at.execute(&Command::set("+BRSF", this.config.features.to_bits())).await?;
+        let rc = brsf.first().ok_or_else(|| invalid_data("missing +BRSF response"))?;
+        this.ag_features = AgFeatures::from_bits(parse_arg(rc.args().first())?);
+
+        if this.codec_negotiation() {
+            this.send_available_codecs().await?;
+        }
+
+        let cind = this.at.execute(&Command::test("+CIND")).await?;
+        let rc = cind.first().ok_or_else(|| invalid_data("missing +CIND response"))?;
+        this.indicators = rc
+            .args()
+            .iter()
+            .map(|desc| Indicator::parse_description(desc))
+            .collect::<Option<_>>()
+            .ok_or_else(|| invalid_data("invalid +CIND indicator description"))?;
+
+        let cind = this.at.execute(&Command::read("+CIND")).await?;
+        let rc = cind.first().ok_or_else(|| invalid_data("missing +CIND response"))?;
+        for (indicator, value) in this.indicators.iter_mut().zip(rc.args()) {
+            indicator.value = parse_arg(Some(&value))?;
+        }
+
+        this.at.execute(&Command::set("+CMER", "3,0,0,1")).await?;
+
+        if this.config.features.three_way_calling && this.ag_features.three_way_calling {
+            let chld = this.at.execute(&Command::test("+CHLD")).await?;
+            if let Some(rc) = chld.first() {
+                let modes = rc.args.trim().trim_start_matches('(').trim_end_matches(')');
+                this.call_hold_modes = modes.split(',').map(|m| m.trim().to_string()).collect();
+            }
+        }
+
+        if let Some(acc) = this.config.apple_accessory.clone() {
+            let cmd = Command::set(
+                "+XAPL",
+                format!("{:04X}-{:04X}-{:04X},{}", acc.vendor_id, acc.product_id, acc.version, XAPL_BATTERY),
+            );
+            match this.at.execute(&cmd).await {
+                Ok(xapl) => {
+                    let features: u32 =
+                        xapl.first().and_then(|rc| rc.args().last()?.parse().ok()).unwrap_or_default();
+                    this.battery_reporting = features & XAPL_BATTERY != 0;
+                }
+                Err(err) if ErrorResult::from_io_error(&err).is_some() => {
+                    log::debug!("Audio gateway does not support Apple accessory extensions: {}", &err)
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(this)
+    }
+
+    fn codec_negotiation(&self) -> bool {
+        self.config.features.codec_negotiation && self.ag_features.codec_negotiation
+    }
+
+    async fn send_available_codecs(&mut self) -> io::Result<()> {
+        let codecs: Vec<String> = self.config.codecs.iter().map(|&c| u8::from(c).to_string()).collect();
+        self.at.execute(&Command::set("+BAC", codecs.join(","))).await?;
+        Ok(())
+    }
+
+    /// Supported features of the audio gateway.
+    pub fn ag_features(&self) -> AgFeatures {
+        self.ag_features
+    }
+
+    /// Indicators of the audio gateway with their current values.
+    pub fn indicators(&self) -> &[Indicator] {
+        &self.indicators
+    }
+
+    /// Current value of the indicator with the specified name.
+    pub fn indicator(&self, name: &str) -> Option<u8> {
+        self.indicators.iter().find(|ind| ind.name == name).map(|ind| ind.value)
+    }
+
+    /// Call hold and multiparty modes supported by the audio gateway.
+    ///
+    /// Only available if three-way calling is supported by both devices.
+    pub fn call_hold_modes(&self) -> &[String] {
+        &self.call_hold_modes
+    }
+
+    /// Currently selected audio codec.
+    ///
+    /// [None] if no codec has been negotiated yet, in which case CVSD is used.
+    pub fn codec(&self) -> Option<Codec> {
+        self.codec
+    }
+
+    /// Whether the audio gateway accepts battery level reports.
+    pub fn battery_reporting(&self) -> bool {
+        self.battery_reporting
+    }
+
+    /// Gets a mutable reference to the underlying AT command stream.
+    pub fn at_stream(&mut self) -> &mut AtStream<S> {
+        &mut self.at
+    }
+
+    /// Consumes the hands-free unit, returning the underlying AT command stream.
+    pub fn into_inner(self) -> AtStream<S> {
+        self.at
+    }
+
+    /// Places a call to the specified phone number.
+    pub async fn dial(&mut self, number: &str) -> io::Result<()> {
+        self.at.execute(&Command::set("D", format!("{};", number))).await?;
+        Ok(())
+    }
+
+    /// Redials the last dialed number.
+    pub async fn redial(&mut self) -> io::Result<()> {
+        self.at.execute(&Command::execute("+BLDN")).await?;
+        Ok(())
+    }
+
+    /// Answers an incoming call.
+    pub async fn answer(&mut self) -> io::Result<()> {
+        self.at.execute(&Command::execute("A")).await?;
+        Ok(())
+    }
+
+    /// Rejects an incoming call or terminates the active call.
+    pub async fn hang_up(&mut self) -> io::Result<()> {
+        self.at.execute(&Command::execute("+CHUP")).await?;
+        Ok(())
+    }
+
+    /// Performs call hold or multiparty handling, for example `"1"` to release
+    /// the active calls and accept the held or waiting call.
+    ///
+    /// See [call_hold_modes](Self::call_hold_modes) for supported modes.
+    pub async fn call_hold(&mut self, mode: &str) -> io::Result<()> {
+        self.at.execute(&Command::set("+CHLD", mode)).await?;
+        Ok(())
+    }
+
+    /// Sends a DTMF tone during an active call.
+    pub async fn send_dtmf(&mut self, tone: char) -> io::Result<()> {
+        self.at.execute(&Command::set("+VTS", tone)).await?;
+        Ok(())
+    }
+
+    /// Enables or disables calling line identification notifications.
+    pub async fn set_caller_id_notification(&mut self, enabled: bool) -> io::Result<()> {
+        self.at.execute(&Command::set("+CLIP", u8::from(enabled))).await?;
+        Ok(())
+    }
+
+    /// Enables or disables call waiting notifications.
+    pub async fn set_call_waiting_notification(&mut self, enabled: bool) -> io::Result<()> {
+        self.at.execute(&Command::set("+CCWA", u8::from(enabled))).await?;
+        Ok(())
+    }
+
+    /// Reports the speaker volume between 0 and 15 to the audio gateway.
+    pub async fn set_speaker_volume(&mut self, volume: u8) -> io::Result<()> {
+        check_volume(volume)?;
+        self.at.execute(&Command::set("+VGS", volume)).await?;
+        Ok(())
+    }
+
+    /// Reports the microphone volume between 0 and 15 to the audio gateway.
+    pub async fn set_microphone_volume(&mut self, volume: u8) -> io::Result<()> {
+        check_volume(volume)?;
+        self.at.execute(&Command::set("+VGM", volume)).await?;
+        Ok(())
+    }
+
+    /// Requests the audio gateway to establish an audio connection.
+    ///
+    /// The audio gateway starts codec negotiation, which is reported
+    /// as [HfEvent::CodecSelected].
+    pub async fn request_audio_connection(&mut self) -> io::Result<()> {
+        self.at.execute(&Command::execute("+BCC")).await?;
+        Ok(())
+    }
+
+    /// Reports the battery level in percent to the audio gateway.
+    ///
+    /// Requires [battery_reporting](Self::battery_reporting).
+    /// The level is transmitted with a resolution of 10 percent.
+    pub async fn report_battery_level(&mut self, percent: u8) -> io::Result<()> {
+        if !self.battery_reporting {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "audio gateway does not accept battery level",
+            ));
+        }
+        let level = battery_percent_to_level(percent);
+        self.at.execute(&Command::set("+IPHONEACCEV", format!("1,1,{}", level))).await?;
+        Ok(())
+    }
+
+    /// Receives the next event from the audio gateway.
+    ///
+    /// Codec selection requests are confirmed automatically if the codec is supported.
+    /// Returns [None] when the connection has been closed.
+    pub async fn next_event(&mut self) -> io::Result<Option<HfEvent>> {
+        loop {
+            let rc = match self.at.recv_unsolicited().await? {
+                Some(rc) => rc,
+                None => return Ok(None),
+            };
+            let args = rc.args();
+
+            let event = if rc.is("+CIEV") {
+                let index: usize = parse_arg(args.first())?;
+                let value: u8 = parse_arg(args.get(1))?;
+                match index.checked_sub(1).and_then(|i| self.indicators.get_mut(i)) {
+                    Some(indicator) => {
+                        indicator.value = value;
+                        HfEvent::Indicator { name: indicator.name.clone(), value }
+                    }
+                    None => {
+                        log::debug!("Ignoring unknown indicator {}", index);
+                        continue;
+                    }
+                }
+            } else if rc.is("RING") {
+                HfEvent::Ring
+            } else if rc.is("+CLIP") {
+                HfEvent::CallerId { number: args.first().cloned().unwrap_or_default() }
+            } else if rc.is("+CCWA") {
+                HfEvent::CallWaiting { number: args.first().cloned().unwrap_or_default() }
+            } else if rc.is("+VGS") {
+                HfEvent::SpeakerVolume(parse_arg(args.first())?)
+            } else if rc.is("+VGM") {
+                HfEvent::MicrophoneVolume(parse_arg(args.first())?)
+            } else if rc.is("+BSIR") {
+                HfEvent::InBandRing(parse_arg::<u8>(args.first())? != 0)
+            } else if rc.is("+BCS") {
+                let codec = Codec::from(parse_arg::<u8>(args.first())?);
+                if !self.config.codecs.contains(&codec) {
+                    log::debug!("Audio gateway selected unsupported {}", codec);
+                    self.send_available_codecs().await?;
+                    continue;
+                }
+                self.at.execute(&Command::set("+BCS", u8::from(codec))).await?;
+                self.codec = Some(codec);
+                HfEvent::CodecSelected(codec)
+            } else {
+                HfEvent::Other(rc)
+            };
+            return Ok(Some(event));
+        }
+    }
+}
+
+/// Audio gateway configuration.
+#[derive(Clone, Debug)]
+pub struct AgConfig {
+    /// Supported features.
+    pub features: AgFeatures,
+    /// Indicators with their initial values.
+    pub indicators: Vec<Indicator>,
+    /// Supported call hold and multiparty modes.
+    pub call_hold_modes: Vec<String>,
+    /// Accept battery level reports from Apple accessories.
+    pub battery_reporting: bool,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl Default for AgConfig {
+    fn default() -> Self {
+        Self {
+            features: AgFeatures { reject_call: true, codec_negotiation: true, ..Default::default() },
+            indicators: Indicator::standard(),
+            call_hold_modes: ["0", "1", "2", "3", "4"].iter().map(|m| m.to_string()).collect(),
+            battery_reporting: true,
+            _non_exhaustive: (),
+        }
+    }
+}
+
+/// Event received by the audio gateway from the hands-free unit.
+///
+/// Events for which [needs_reply](Self::needs_reply) returns true
+/// must be answered using [AudioGateway::ok] or [AudioGateway::error].
+/// All other commands are answered automatically.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AgEvent {
+    /// Place a call to the specified number.
+    ///
+    /// Memory dialing requests start with `>`.
+    Dial {
+        /// Phone number.
+        number: String,
+    },
+    /// Redial the last dialed number.
+    Redial,
+    /// Answer the incoming call.
+    Answer,
+    /// Reject the incoming call or terminate the active call.
+    HangUp,
+    /// Call hold or multiparty handling.
+    CallHold {
+        /// Requested mode.
+        mode: String,
+    },
+    /// Send DTMF tone.
+    Dtmf(char),
+    /// Establish an audio connection.
+    AudioConnectionRequest,
+    /// Speaker volume between 0 and 15 reported by the hands-free unit.
+    SpeakerVolume(u8),
+    /// Microphone volume between 0 and 15 reported by the hands-free unit.
+    MicrophoneVolume(u8),
+    /// Codecs available at the hands-free unit.
+    AvailableCodecs(Vec<Codec>),
+    /// Hands-free unit confirmed the codec selected by [AudioGateway::select_codec].
+    CodecSelected(Codec),
+    /// Battery level in percent reported by the hands-free unit.
+    BatteryLevel(u8),
+    /// Other command.
+    Command(Command),
+}
+
+impl AgEvent {
+    /// Whether the application must reply to the command that caused this event.
+    pub fn needs_reply(&self) -> bool {
+        matches!(
+            self,
+            Self::Dial { .. }
+                | Self::Redial
+                | Self::Answer
+                | Self::HangUp
+                | Self::CallHold { .. }
+                | Self::Dtmf(_)
+                | Self::AudioConnectionRequest
+                | Self::Command(_)
+        )
+    }
+}
+
+/// Audio gateway (AG).
+#[derive(Debug)]
+pub struct AudioGateway<S> {
+    at: AtStream<S>,
+    config: AgConfig,
+    hf_features: HfFeatures,
+    hf_codecs: Vec<Codec>,
+    indicator_reporting: bool,
+    caller_id: bool,
+    call_waiting: bool,
+    apple_accessory: Option<AppleAccessory>,
+    codec: Option<Codec>,
+    pending: VecDeque<AgEvent>,
+}
+
+impl<S> AudioGateway<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Waits for the hands-free unit connected through the specified byte stream
+    /// to establish the service level connection.
+    pub async fn accept(io: S, config: AgConfig) -> io::Result<Self> {
+        let mut this = Self {
+            at: AtStream::new(io),
+            config,
+            hf_features: HfFeatures::default(),
+            hf_codecs: Vec::new(),
+            indicator_reporting: false,
+            caller_id: false,
+            call_waiting: false,
+            apple_accessory: None,
+            codec: None,
+            pending: VecDeque::new(),
+        };
+
+        let mut cmer = false;
+        let mut chld = false;
+        loop {
+            let three_way = this.config.features.three_way_calling && this.hf_features.three_way_calling;
+            if cmer && (chld || !three_way) {
+                break;
+            }
+
+            let cmd = match this.at.recv_command().await? {
+                Some(cmd) => cmd,
+                None => return Err(io::ErrorKind::UnexpectedEof.into()),
+            };
+            cmer |= cmd.name == "+CMER" && matches!(cmd.kind, CommandKind::Set(_));
+            chld |= cmd.name == "+CHLD" && cmd.kind == CommandKind::Test;
+
+            match this.handle_command(cmd).await? {
+                Some(event) if event.needs_reply() => this.error(ErrorResult::Error).await?,
+                Some(event) => this.pending.push_back(event),
+                None => (),
+            }
+        }
+
+        Ok(this)
+    }
+
+    /// Supported features of the hands-free unit.
+    pub fn hf_features(&self) -> HfFeatures {
+        self.hf_features
+    }
+
+    /// Audio codecs available at the hands-free unit.
+    pub fn hf_codecs(&self) -> &[Codec] {
+        &self.hf_codecs
+    }
+
+    /// Currently selected audio codec.
+    ///
+    /// [None] if no codec has been negotiated yet, in which case CVSD is used.
+    pub fn codec(&self) -> Option<Codec> {
+        self.codec
+    }
+
+    /// Apple accessory identification of the hands-free unit, if provided.
+    pub fn apple_accessory(&self) -> Option<&AppleAccessory> {
+        self.apple_accessory.as_ref()
+    }
+
+    /// Indicators with their current values.
+    pub fn indicators(&self) -> &[Indicator] {
+        &self.config.indicators
+    }
+
+    /// Gets a mutable reference to the underlying AT command stream.
+    pub fn at_stream(&mut self) -> &mut AtStream<S> {
+        &mut self.at
+    }
+
+    /// Consumes the audio gateway, returning the underlying AT command stream.
+    pub fn into_inner(self) -> AtStream<S> {
+        self.at
+    }
+
+    async fn send(&mut self, rc: ResultCode) -> io::Result<()> {
+        self.at.send_response(&Response::Result(rc)).await
+    }
+
+    /// Replies with `OK` to a command that [needs a reply](AgEvent::needs_reply).
+    pub async fn ok(&mut self) -> io::Result<()> {
+        self.at.send_response(&Response::Ok).await
+    }
+
+    /// Replies with an error to a command that [needs a reply](AgEvent::needs_reply).
+    pub async fn error(&mut self, err: ErrorResult) -> io::Result<()> {
+        self.at.send_response(&Response::Error(err)).await
+    }
+
+    /// Sets the value of the indicator with the specified name.
+    ///
+    /// The hands-free unit is notified if the value changed and it enabled indicator reporting.
+    pub async fn set_indicator(&mut self, name: &str, value: u8) -> io::Result<()> {
+        let index = self
+            .config
+            .indicators
+            .iter()
+            .position(|ind| ind.name == name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "unknown indicator"))?;
+        let indicator = &mut self.config.indicators[index];
+        if value < indicator.min || value > indicator.max {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "indicator value out of range"));
+        }
+        if indicator.value == value {
+            return Ok(());
+        }
+        indicator.value = value;
+
+        if self.indicator_reporting {
+            self.send(ResultCode::new("+CIEV", format!("{},{}", index + 1, value))).await?;
+        }
+        Ok(())
+    }
+
+    /// Alerts the hands-free unit of an incoming call.
+    ///
+    /// The phone number is sent if the hands-free unit enabled caller identification.
+    pub async fn ring(&mut self, number: Option<&str>) -> io::Result<()> {
+        self.send(ResultCode::new("RING", "")).await?;
+        if let (true, Some(number)) = (self.caller_id, number) {
+            self.send(ResultCode::new("+CLIP", format!("\"{}\",{}", number, number_type(number)))).await?;
+        }
+        Ok(())
+    }
+
+    /// Notifies the hands-free unit of a waiting call, if it enabled call waiting notifications.
+    pub async fn call_waiting(&mut self, number: &str) -> io::Result<()> {
+        if self.call_waiting {
+            self.send(ResultCode::new("+CCWA", format!("\"{}\",{}", number, number_type(number)))).await?;
+        }
+        Ok(())
+    }
+
+    /// Sets the speaker volume between 0 and 15 of the hands-free unit.
+    pub async fn set_speaker_volume(&mut self, volume: u8) -> io::Result<()> {
+        check_volume(volume)?;
+        self.send(ResultCode::new("+VGS", volume)).await
+    }
+
+    /// Sets the microphone volume between 0 and 15 of the hands-free unit.
+    pub async fn set_microphone_volume(&mut self, volume: u8) -> io::Result<()> {
+        check_volume(volume)?;
+        self.send(ResultCode::new("+VGM", volume)).await
+    }
+
+    /// Enables or disables the in-band ring tone.
+    pub async fn set_in_band_ring(&mut self, enabled: bool) -> io::Result<()> {
+        self.send(ResultCode::new("+BSIR", u8::from(enabled))).await
+    }
+
+    /// Starts codec negotiation by selecting the specified codec.
+    ///
+    /// The confirmation of the hands-free unit is reported as [AgEvent::CodecSelected].
+    /// Requires codec negotiation support of both devices and the codec being
+    /// available at the hands-free unit.
+    pub async fn select_codec(&mut self, codec: Codec) -> io::Result<()> {
+        if !(self.config.features.codec_negotiation && self.hf_features.codec_negotiation) {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "codec negotiation not supported"));
+        }
+        if !self.hf_codecs.contains(&codec) {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "codec not available at hands-free unit"));
+        }
+        self.send(ResultCode::new("+BCS", u8::from(codec))).await
+    }
+
+    /// Receives the next event from the hands-free unit.
+    ///
+    /// Returns [None] when the connection has been closed.
+    pub async fn next_event(&mut self) -> io::Result<Option<AgEvent>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Ok(Some(event));
+        }
+
+        loop {
+            let cmd = match self.at.recv_command().await? {
+                Some(cmd) => cmd,
+                None => return Ok(None),
+            };
+            if let Some(event) = self.handle_command(cmd).await? {
+                return Ok(Some(event));
+            }
+        }
+    }
+
+    /// Handles a command, replying to it unless the returned event
+    /// [needs a reply](AgEvent::needs_reply).
+    async fn handle_command(&mut self, cmd: Command) -> io::Result<Option<AgEvent>> {
+        let args = cmd.args();
+
+        let event = match (cmd.name.as_str(), &cmd.kind) {
+            ("+BRSF", CommandKind::Set(_)) => {
+                let hf_features = match parse_arg(args.first()) {
+                    Ok(hf_features) => hf_features,
+                    Err(_) => return self.reject_command(&cmd.name).await,
+                };
+                self.hf_features = HfFeatures::from_bits(hf_features);
+                self.send(ResultCode::new("+BRSF", self.config.features.to_bits())).await?;
+                None
+            }
+            ("+BAC", CommandKind::Set(_)) => {
+                self.hf_codecs = args.iter().filter_map(|id| id.parse::<u8>().ok()).map(Codec::from).collect();
+                Some(AgEvent::AvailableCodecs(self.hf_codecs.clone()))
+            }
+            ("+CIND", CommandKind::Test) => {
+                let descs: Vec<String> = self.config.indicators.iter().map(|ind| ind.description()).collect();
+                self.send(ResultCode::new("+CIND", descs.join(","))).await?;
+                None
+            }
+            ("+CIND", CommandKind::Read) => {
+                let values: Vec<String> =
+                    self.config.indicators.iter().map(|ind| ind.value.to_string()).collect();
+                self.send(ResultCode::new("+CIND", values.join(","))).await?;
+                None
+            }
+            ("+CMER", CommandKind::Set(_)) => {
+                self.indicator_reporting = args.get(3).map(|v| v == "1").unwrap_or_default();
+                None
+            }
+            ("+CHLD", CommandKind::Test) if self.config.features.three_way_calling => {
+                let modes = format!("({})", self.config.call_hold_modes.join(","));
+                self.send(ResultCode::new("+CHLD", modes)).await?;
+                None
+            }
+            ("+CHLD", CommandKind::Set(mode)) => Some(AgEvent::CallHold { mode: mode.trim().to_string() }),
+            ("+CLIP", CommandKind::Set(_)) => {
+                self.caller_id = args.first().map(|v| v == "1").unwrap_or_default();
+                None
+            }
+            ("+CCWA", CommandKind::Set(_)) => {
+                self.call_waiting = args.first().map(|v| v == "1").unwrap_or_default();
+                None
+            }
+            ("+CMEE", CommandKind::Set(_)) | ("+NREC", CommandKind::Set(_)) | ("+BIA", CommandKind::Set(_)) => {
+                None
+            }
+            ("+VGS", CommandKind::Set(_)) => match parse_arg(args.first()) {
+                Ok(volume) => Some(AgEvent::SpeakerVolume(volume)),
+                Err(_) => return self.reject_command(&cmd.name).await,
+            },
+            ("+VGM", CommandKind::Set(_)) => match parse_arg(args.first()) {
+                Ok(volume) => Some(AgEvent::MicrophoneVolume(volume)),
+                Err(_) => return self.reject_command(&cmd.name).await,
+            },
+            ("+BCS", CommandKind::Set(_)) => {
+                let codec = match parse_arg::<u8>(args.first()) {
+                    Ok(id) => Codec::from(id),
+                    Err(_) => return self.reject_command(&cmd.name).await,
+                };
+                self.codec = Some(codec);
+                Some(AgEvent::CodecSelected(codec))
+            }
+            ("+BCC", CommandKind::Execute) => Some(AgEvent::AudioConnectionRequest),
+            ("D", CommandKind::Set(number)) => {
+                Some(AgEvent::Dial { number: number.trim().trim_end_matches(';').to_string() })
+            }
+            ("A", CommandKind::Execute) => Some(AgEvent::Answer),
+            ("+CHUP", CommandKind::Execute) => Some(AgEvent::HangUp),
+            ("+BLDN", CommandKind::Execute) => Some(AgEvent::Redial),
+            ("+VTS", CommandKind::Set(_)) => match args.first().and_then(|tone| tone.chars().next()) {
+                Some(tone) => Some(AgEvent::Dtmf(tone)),
+                None => Some(AgEvent::Command(cmd)),
+            },
+            ("+XAPL", CommandKind::Set(_)) if self.config.battery_reporting => {
+                let ids: Vec<u16> = args
+                    .first()
+                    .map(|id| id.split('-').filter_map(|v| u16::from_str_radix(v, 16).ok()).collect())
+                    .unwrap_or_default();
+                if let [vendor_id, product_id, version] = ids[..] {
+                    self.apple_accessory = Some(AppleAccessory { vendor_id, product_id, version });
+                }
+                let hf_features: u32 = parse_arg(args.get(1)).unwrap_or_default();
+                // Apple specifies '=' as separator for this result code.
+                let features = format!("iPhone,{}", hf_features & XAPL_BATTERY);
+                self.send(ResultCode::with_separator("+XAPL", '=', features)).await?;
+                None
+            }
+            ("+IPHONEACCEV", CommandKind::Set(_)) if self.config.battery_reporting => {
+                let mut level = None;
+                for pair in args.get(1..).unwrap_or_default().chunks_exact(2) {
+                    if pair[0] == "1" {
+                        level = pair[1].parse().ok().map(battery_level_to_percent);
+                    }
+                }
+                level.map(AgEvent::BatteryLevel)
+            }
+            _ => Some(AgEvent::Command(cmd)),
+        };
+
+        match &event {
+            Some(event) if event.needs_reply() => (),
+            _ => self.ok().await?,
+        }
+        Ok(event)
+    }
+
+    /// Replies with `ERROR` to a command having invalid arguments.
+    async fn reject_command(&mut self, name: &str) -> io::Result<Option<AgEvent>> {
+        log::debug!("Rejecting AT command {} with invalid arguments", name);
+        self.error(ErrorResult::Error).await?;
+        Ok(None)
+    }
+}
+
+/// Type of a phone number as used by `+CLIP` and `+CCWA`.
+fn number_type(number: &str) -> u8 {
+    if number.starts_with('+') {
+        145
+    } else {
+        129
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::duplex;
+
+    #[tokio::test]
+    async fn service_level_connection() {
+        let (hf_io, ag_io) = duplex(1024);
+        let accessory = AppleAccessory { vendor_id: 0x004c, product_id: 0x1234, version: 0x0100 };
+        let hf_config = HfConfig { apple_accessory: Some(accessory.clone()), ..Default::default() };
+
+        let (hf, ag) = tokio::join!(
+            async {
+                let mut hf = HandsFree::connect(hf_io, hf_config).await.unwrap();
+                hf.report_battery_level(75).await.unwrap();
+                hf
+            },
+            async {
+                let mut ag = AudioGateway::accept(ag_io, AgConfig::default()).await.unwrap();
+                assert_eq!(
+                    ag.next_event().await.unwrap(),
+                    Some(AgEvent::AvailableCodecs(vec![Codec::Cvsd, Codec::Msbc]))
+                );
+                assert_eq!(ag.next_event().await.unwrap(), Some(AgEvent::BatteryLevel(80)));
+                ag
+            }
+        );
+        let (mut hf, mut ag) = (hf, ag);
+
+        assert!(hf.ag_features().codec_negotiation);
+        assert_eq!(hf.indicators(), &Indicator::standard()[..]);
+        assert!(hf.battery_reporting());
+        assert!(ag.hf_features().codec_negotiation);
+        assert_eq!(ag.hf_codecs(), &[Codec::Cvsd, Codec::Msbc]);
+        assert_eq!(ag.apple_accessory(), Some(&accessory));
+
+        ag.set_indicator(indicator::CALL, 1).await.unwrap();
+        assert_eq!(
+            hf.next_event().await.unwrap(),
+            Some(HfEvent::Indicator { name: indicator::CALL.to_string(), value: 1 })
+        );
+        assert_eq!(hf.indicator(indicator::CALL), Some(1));
+
+        let (res, event) = tokio::join!(hf.dial("1234"), async {
+            let event = ag.next_event().await.unwrap();
+            ag.ok().await.unwrap();
+            event
+        });
+        res.unwrap();
+        assert_eq!(event, Some(AgEvent::Dial { number: "1234".to_string() }));
+
+        let (hf_event, ag_event) = tokio::join!(hf.next_event(), async {
+            ag.select_codec(Codec::Msbc).await.unwrap();
+            ag.next_event().await.unwrap()
+        });
+        assert_eq!(hf_event.unwrap(), Some(HfEvent::CodecSelected(Codec::Msbc)));
+        assert_eq!(ag_event, Some(AgEvent::CodecSelected(Codec::Msbc)));
+        assert_eq!(hf.codec(), Some(Codec::Msbc));
+        assert_eq!(ag.codec(), Some(Codec::Msbc));
+    }
+
+    #[tokio::test]
+    async fn invalid_argument() {
+        let (hf_io, ag_io) = duplex(1024);
+        let (hf, ag) =
+            tokio::join!(async { HandsFree::connect(hf_io, HfConfig::default()).await.unwrap() }, async {
+                let mut ag = AudioGateway::accept(ag_io, AgConfig::default()).await.unwrap();
+                assert_eq!(
+                    ag.next_event().await.unwrap(),
+                    Some(AgEvent::AvailableCodecs(vec![Codec::Cvsd, Codec::Msbc]))
+                );
+                ag
+            });
+        let (mut hf, mut ag) = (hf, ag);
+
+        let ((), event) = tokio::join!(
+            async {
+                let err = hf.at_stream().execute(&Command::set("+VGS", "")).await.unwrap_err();
+                assert_eq!(ErrorResult::from_io_error(&err), Some(ErrorResult::Error));
+                hf.set_speaker_volume(7).await.unwrap();
+            },
+            ag.next_event()
+        );
+        assert_eq!(event.unwrap(), Some(AgEvent::SpeakerVolume(7)));
+    }
+}
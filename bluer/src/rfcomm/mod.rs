@@ -10,6 +10,11 @@
 //!      You will probably need to register an [authorization agent](crate::agent) for this to succeed.
 //!      This requires a running Bluetooth daemon.
 //!
+//! The [spp] module provides a high-level interface to the Serial Port Profile,
+//! including bridging connections to virtual serial ports for legacy applications.
+//! Hands-Free Profile signalling is provided by the [hfp] module on top of the
+//! AT command codec in the [at] module.
+//!
 
 use futures::ready;
 use libc::{
@@ -32,17 +37,15 @@ use std::{
 use tokio::io::{unix::AsyncFd, AsyncRead, AsyncWrite, ReadBuf};
 
 #[cfg(feature = "bluetoothd")]
-pub(crate) mod profile;
-
-#[cfg(feature = "bluetoothd")]
-pub use profile::{ConnectRequest, Profile, ProfileHandle, ReqError, ReqResult, Role};
+pub use crate::profile::{ConnectRequest, Profile, ProfileHandle, ProfileSocket, ReqError, ReqResult, Role};
 
 use crate::{
     sock::{self, OwnedFd},
     sys::{
-        bt_security, rfcomm_dev_req, sockaddr_rc, BTPROTO_RFCOMM, BT_SECURITY, BT_SECURITY_HIGH, BT_SECURITY_LOW,
-        BT_SECURITY_MEDIUM, BT_SECURITY_SDP, RFCOMMCREATEDEV, RFCOMMRELEASEDEV, RFCOMM_CONNINFO, RFCOMM_LM,
-        RFCOMM_LM_MASTER, RFCOMM_RELEASE_ONHUP, RFCOMM_REUSE_DLC, SOL_RFCOMM,
+        bt_security, rfcomm_dev_req, sockaddr_rc, BTPROTO_RFCOMM, BT_DEFER_SETUP, BT_SECURITY, BT_SECURITY_HIGH,
+        BT_SECURITY_LOW, BT_SECURITY_MEDIUM, BT_SECURITY_SDP, RFCOMMCREATEDEV, RFCOMMRELEASEDEV, RFCOMM_CONNINFO,
+        RFCOMM_LM, RFCOMM_LM_AUTH, RFCOMM_LM_ENCRYPT, RFCOMM_LM_FIPS, RFCOMM_LM_MASTER, RFCOMM_LM_RELIABLE,
+        RFCOMM_LM_SECURE, RFCOMM_LM_TRUSTED, RFCOMM_RELEASE_ONHUP, RFCOMM_REUSE_DLC, SOL_RFCOMM,
     },
     Address,
 };
@@ -103,14 +106,47 @@ pub enum SecurityLevel {
     High = BT_SECURITY_HIGH as _,
 }
 
+impl Default for SecurityLevel {
+    fn default() -> Self {
+        Self::Low
+    }
+}
+
 /// RFCOMM socket security.
-#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash)]
+///
+/// Besides the security level, this specifies further options
+/// that affect how connections are established.
+/// Options set to `None` are left unchanged by [Socket::set_security].
+///
+/// Use [Security::new] and the builder methods to specify the options,
+/// for example `Security::new(SecurityLevel::Medium).defer_setup(true)`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Security {
     /// Level.
     pub level: SecurityLevel,
     /// Key size.
+    ///
+    /// This is ignored when setting the security.
     pub key_size: u8,
+    /// Whether connection setup is deferred until the connection is accepted.
+    ///
+    /// This corresponds to the `BT_DEFER_SETUP` socket option.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub defer_setup: Option<bool>,
+}
+
+impl Security {
+    /// Creates socket security with the specified level, leaving all other options unchanged.
+    pub fn new(level: SecurityLevel) -> Self {
+        Self { level, ..Default::default() }
+    }
+
+    /// Sets whether connection setup is deferred until the connection is accepted.
+    pub fn defer_setup(mut self, defer_setup: bool) -> Self {
+        self.defer_setup = Some(defer_setup);
+        self
+    }
 }
 
 impl From<Security> for bt_security {
@@ -126,10 +162,36 @@ impl TryFrom<bt_security> for Security {
             level: SecurityLevel::from_u8(value.level)
                 .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid bt_security::level"))?,
             key_size: value.key_size,
+            ..Default::default()
         })
     }
 }
 
+bitflags::bitflags! {
+    /// RFCOMM link mode.
+    ///
+    /// This is the legacy interface for specifying the security requirements of a socket.
+    /// Setting the [security level](Socket::set_security) is preferred.
+    #[derive(Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct LinkMode: u32 {
+        /// Request the central (master) role of the link.
+        const MASTER = RFCOMM_LM_MASTER;
+        /// Require authentication, i.e. security level low.
+        const AUTH = RFCOMM_LM_AUTH;
+        /// Require encryption, i.e. security level medium.
+        const ENCRYPT = RFCOMM_LM_ENCRYPT;
+        /// Trusted link.
+        const TRUSTED = RFCOMM_LM_TRUSTED;
+        /// Reliable link.
+        const RELIABLE = RFCOMM_LM_RELIABLE;
+        /// Require secure authentication, i.e. security level high.
+        const SECURE = RFCOMM_LM_SECURE;
+        /// FIPS approved algorithms are required.
+        const FIPS = RFCOMM_LM_FIPS;
+    }
+}
+
 /// An RFCOMM socket that has not yet been converted to a [Listener] or [Stream].
 ///
 /// The primary use of this is to configure the socket before connecting or listening.
@@ -189,10 +251,22 @@ impl Socket {
         sock::getpeername(self.fd.get_ref())
     }
 
-    /// Get socket security.
+    /// Get socket security including all further options.
     ///
-    /// This corresponds to the `BT_SECURITY` socket option.
+    /// This corresponds to the `BT_SECURITY` socket option and the socket options
+    /// of the further fields of [Security].
+    /// The other options of [Security] are `None` if they are not available for this socket.
+    /// Use [Self::bt_security] to obtain only the security level and key size.
     pub fn security(&self) -> Result<Security> {
+        Ok(Security { defer_setup: self.is_defer_setup().ok(), ..self.bt_security()? })
+    }
+
+    /// Get socket security level and key size as reported by the kernel.
+    ///
+    /// This corresponds to the `BT_SECURITY` socket option.
+    /// All other options of [Security] are `None` and thus passing the result
+    /// to [Self::set_security] leaves them unchanged.
+    pub fn bt_security(&self) -> Result<Security> {
         let bts: bt_security = sock::getsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_SECURITY)?;
         Security::try_from(bts)
     }
@@ -200,9 +274,33 @@ impl Socket {
     /// Set socket security.
     ///
     /// This corresponds to the `BT_SECURITY` socket option.
+    /// The other options of [Security] are set when specified.
     pub fn set_security(&self, security: Security) -> Result<()> {
         let bts: bt_security = security.into();
-        sock::setsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_SECURITY, &bts)
+        sock::setsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_SECURITY, &bts)?;
+        if let Some(defer_setup) = security.defer_setup {
+            self.set_defer_setup(defer_setup)?;
+        }
+        Ok(())
+    }
+
+    /// Gets whether connection setup is deferred until the connection is accepted.
+    ///
+    /// This corresponds to the `BT_DEFER_SETUP` socket option.
+    pub fn is_defer_setup(&self) -> Result<bool> {
+        let value: u32 = sock::getsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_DEFER_SETUP)?;
+        Ok(value != 0)
+    }
+
+    /// Sets whether connection setup is deferred until the connection is accepted.
+    ///
+    /// Must be set on the socket before listening.
+    /// Incoming connections are then obtained using [Listener::accept_deferred].
+    ///
+    /// This corresponds to the `BT_DEFER_SETUP` socket option.
+    pub fn set_defer_setup(&self, defer_setup: bool) -> Result<()> {
+        let value: u32 = defer_setup.into();
+        sock::setsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_DEFER_SETUP, &value)
     }
 
     /// Gets the maximum socket receive buffer in bytes.
@@ -226,25 +324,38 @@ impl Socket {
         sock::getsockopt(self.fd.get_ref(), SOL_RFCOMM, RFCOMM_CONNINFO)
     }
 
+    /// Gets the RFCOMM link mode.
+    ///
+    /// This corresponds to the `RFCOMM_LM` socket option.
+    pub fn link_mode(&self) -> Result<LinkMode> {
+        let value: u32 = sock::getsockopt(self.fd.get_ref(), SOL_RFCOMM, RFCOMM_LM)?;
+        Ok(LinkMode::from_bits_truncate(value))
+    }
+
+    /// Sets the RFCOMM link mode.
+    ///
+    /// This also sets the security level corresponding to the highest required security.
+    /// [LinkMode::FIPS] cannot be set.
+    ///
+    /// This corresponds to the `RFCOMM_LM` socket option.
+    pub fn set_link_mode(&self, link_mode: LinkMode) -> Result<()> {
+        sock::setsockopt(self.fd.get_ref(), SOL_RFCOMM, RFCOMM_LM, &link_mode.bits())
+    }
+
     /// Gets whether the RFCOMM socket is the master.
     ///
-    /// This corresponds to the `RFCOMM_LM` socket option and option bit `RFCOMM_LM_MASTER`.
+    /// This corresponds to the `RFCOMM_LM` socket option and [LinkMode::MASTER].
     pub fn is_master(&self) -> Result<bool> {
-        let opt: u32 = sock::getsockopt(self.fd.get_ref(), SOL_RFCOMM, RFCOMM_LM)?;
-        Ok((opt & RFCOMM_LM_MASTER) != 0)
+        Ok(self.link_mode()?.contains(LinkMode::MASTER))
     }
 
     /// sets whether the RFCOMM socket is the master.
     ///
-    /// This corresponds to the `RFCOMM_LM` socket option and option bit `RFCOMM_LM_MASTER`.
+    /// This corresponds to the `RFCOMM_LM` socket option and [LinkMode::MASTER].
     pub fn set_master(&self, master: bool) -> Result<()> {
-        let mut opt: u32 = sock::getsockopt(self.fd.get_ref(), SOL_RFCOMM, RFCOMM_LM)?;
-        if master {
-            opt |= RFCOMM_LM_MASTER;
-        } else {
-            opt &= !RFCOMM_LM_MASTER;
-        }
-        sock::setsockopt(self.fd.get_ref(), SOL_RFCOMM, RFCOMM_LM, &opt)
+        let mut link_mode = self.link_mode()?;
+        link_mode.set(LinkMode::MASTER, master);
+        self.set_link_mode(link_mode)
     }
 
     /// Get the number of bytes in the input buffer.
@@ -311,7 +422,7 @@ impl Socket {
         Ok(Self { fd: AsyncFd::new(fd)? })
     }
 
-    sock_priv!();
+    sock_priv!(accept_deferred, peek, poll_peek);
 }
 
 impl AsRawFd for Socket {
@@ -371,6 +482,20 @@ impl Listener {
         Poll::Ready(Ok((Stream::from_socket(socket)?, sa)))
     }
 
+    /// Accepts a new incoming connection whose setup has been deferred.
+    ///
+    /// This allows to inspect the connection, for example its peer address and security,
+    /// before deciding whether to [accept](PendingStream::accept) or [reject](PendingStream::reject) it.
+    /// Deferred setup must have been enabled using [Socket::set_defer_setup]
+    /// before listening, otherwise an error is returned.
+    pub async fn accept_deferred(&self) -> Result<PendingStream> {
+        if !self.socket.is_defer_setup()? {
+            return Err(Error::new(ErrorKind::InvalidInput, "deferred setup is not enabled"));
+        }
+        let (socket, _sa) = self.socket.accept_priv().await?;
+        Ok(PendingStream { socket })
+    }
+
     /// Constructs a new [Listener] from the given raw file descriptor.
     ///
     /// The file descriptor must have been set to non-blocking mode.
@@ -411,6 +536,46 @@ impl FromRawFd for Listener {
     }
 }
 
+/// An incoming RFCOMM stream connection whose setup has been deferred.
+///
+/// Obtained from [Listener::accept_deferred].
+/// The connection is established by [accept](Self::accept) and
+/// dropping it rejects the connection.
+#[derive(Debug)]
+pub struct PendingStream {
+    socket: Socket,
+}
+
+impl PendingStream {
+    /// Gets the peer address of this connection.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.socket.peer_addr_priv()
+    }
+
+    /// Accepts the connection and waits until it is established.
+    pub async fn accept(self) -> Result<Stream> {
+        self.socket.accept_deferred_priv().await?;
+        Stream::from_socket(self.socket)
+    }
+
+    /// Rejects the connection.
+    pub fn reject(self) {
+        drop(self);
+    }
+}
+
+impl AsRef<Socket> for PendingStream {
+    fn as_ref(&self) -> &Socket {
+        &self.socket
+    }
+}
+
+impl AsRawFd for PendingStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
 /// An RFCOMM stream between a local and remote socket (sequenced, reliable, two-way, connection-based).
 #[derive(Debug)]
 pub struct Stream {
@@ -474,6 +639,24 @@ impl Stream {
         (r, w)
     }
 
+    /// Frames this stream using the specified codec.
+    #[cfg(feature = "codec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "codec")))]
+    pub fn framed<C>(self, codec: C) -> crate::codec::Framed<Self, C> {
+        crate::codec::Framed::new(self, codec)
+    }
+
+    /// Frames this stream by prefixing each message with its length.
+    ///
+    /// The length is encoded as a 32-bit big-endian integer and the maximum message length is 8 MiB.
+    /// Use [LengthDelimitedCodec::builder](crate::codec::LengthDelimitedCodec::builder) and
+    /// [Self::framed] for other configurations.
+    #[cfg(feature = "codec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "codec")))]
+    pub fn length_delimited(self) -> crate::codec::Framed<Self, crate::codec::LengthDelimitedCodec> {
+        self.framed(crate::codec::LengthDelimitedCodec::new())
+    }
+
     fn poll_write_priv(&self, cx: &mut Context, buf: &[u8]) -> Poll<Result<usize>> {
         self.socket.poll_send_priv(cx, buf)
     }
@@ -540,3 +723,10 @@ impl AsyncWrite for Stream {
 
 #[path = "../stream_util.rs"]
 pub mod stream;
+
+#[cfg(feature = "bluetoothd")]
+#[cfg_attr(docsrs, doc(cfg(feature = "bluetoothd")))]
+pub mod spp;
+
+pub mod at;
+pub mod hfp;
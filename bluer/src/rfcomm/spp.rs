@@ -0,0 +1,408 @@
+//! Serial Port Profile (SPP).
+//!
+//! Register a [Server] to accept incoming serial port connections or a [Client]
+//! to connect to the serial port of a remote device.
+//! The Bluetooth daemon publishes and discovers the SDP records of the serial port.
+//!
+//! An established [Connection] can be used directly as an RFCOMM [Stream] or
+//! exposed as a virtual serial port for legacy applications, either as a
+//! [pseudo-terminal](Connection::into_pty) or as an
+//! [RFCOMM TTY device](Connection::into_rfcomm_tty).
+
+use futures::{pin_mut, ready, FutureExt, StreamExt};
+use libc::{c_int, O_CLOEXEC, O_NOCTTY, O_NONBLOCK, O_RDWR};
+use std::{
+    ffi::{CString, OsStr},
+    io,
+    os::unix::{
+        ffi::OsStrExt,
+        io::{AsRawFd, RawFd},
+    },
+    path::{Path, PathBuf},
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{
+    io::{unix::AsyncFd, AsyncRead, AsyncWrite, ReadBuf},
+    sync::oneshot,
+    task::JoinHandle,
+    time::sleep,
+};
+use uuid::Uuid;
+
+use super::{ConnectRequest, Profile, ProfileHandle, ReqError, Role, Socket, Stream};
+use crate::{sock::OwnedFd, Address, Device, Error, ErrorKind, Result, Session};
+
+/// Service class UUID of the Serial Port Profile.
+pub const SERIAL_PORT: Uuid = Uuid::from_u128(0x00001101_0000_1000_8000_00805f9b34fb);
+
+/// Time to wait for the Bluetooth daemon to hand over a connection after connecting the profile.
+const CONNECTION_HANDOVER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Serial port profile options.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Human readable service name published in the SDP record.
+    pub name: Option<String>,
+    /// RFCOMM channel number.
+    ///
+    /// If unspecified, the Bluetooth daemon allocates a channel.
+    pub channel: Option<u16>,
+    /// Pairing is required before connections will be established.
+    pub require_authentication: Option<bool>,
+    /// Request authorization before any connection will be established.
+    pub require_authorization: Option<bool>,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl Options {
+    fn to_profile(&self, role: Role) -> Profile {
+        Profile {
+            uuid: SERIAL_PORT,
+            name: self.name.clone(),
+            role: Some(role),
+            channel: self.channel,
+            require_authentication: self.require_authentication,
+            require_authorization: self.require_authorization,
+            ..Default::default()
+        }
+    }
+}
+
+fn profile_unregistered() -> Error {
+    Error { kind: ErrorKind::Failed, message: "serial port profile was unregistered".to_string() }
+}
+
+/// Serial port server accepting connections from remote devices.
+///
+/// Drop to unregister the serial port profile.
+#[derive(Debug)]
+pub struct Server {
+    handle: ProfileHandle,
+}
+
+impl Server {
+    /// Registers the serial port profile in the server role.
+    pub async fn register(session: &Session, options: Options) -> Result<Self> {
+        let handle = session.register_profile(options.to_profile(Role::Server)).await?;
+        Ok(Self { handle })
+    }
+
+    /// Accepts the next incoming connection.
+    pub async fn accept(&mut self) -> Result<Connection> {
+        let req = self.handle.next().await.ok_or_else(profile_unregistered)?;
+        Connection::from_request(req)
+    }
+}
+
+/// Serial port client connecting to remote devices.
+///
+/// Drop to unregister the serial port profile.
+#[derive(Debug)]
+pub struct Client {
+    handle: ProfileHandle,
+}
+
+impl Client {
+    /// Registers the serial port profile in the client role.
+    pub async fn register(session: &Session, options: Options) -> Result<Self> {
+        let handle = session.register_profile(options.to_profile(Role::Client)).await?;
+        Ok(Self { handle })
+    }
+
+    /// Connects to the serial port of the specified device.
+    ///
+    /// The RFCOMM channel is discovered by the Bluetooth daemon.
+    /// Connection requests from other devices received in the meantime are rejected.
+    ///
+    /// If the Bluetooth daemon does not hand over the connection in time, for example because
+    /// the serial port profile of the device is already connected, an error of kind
+    /// [Io](crate::InternalErrorKind::Io)([TimedOut](io::ErrorKind::TimedOut)) is returned.
+    pub async fn connect(&mut self, device: &Device) -> Result<Connection> {
+        let connect = device.connect_profile(&SERIAL_PORT).fuse();
+        pin_mut!(connect);
+        let handover_timeout = sleep(CONNECTION_HANDOVER_TIMEOUT).fuse();
+        pin_mut!(handover_timeout);
+        let mut connecting = true;
+
+        loop {
+            tokio::select! {
+                res = &mut connect, if connecting => {
+                    res?;
+                    connecting = false;
+                    handover_timeout.set(sleep(CONNECTION_HANDOVER_TIMEOUT).fuse());
+                }
+                () = &mut handover_timeout, if !connecting => {
+                    return Err(Error::from(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "serial port connection was not handed over",
+                    )));
+                }
+                req = self.handle.next() => {
+                    let req = req.ok_or_else(profile_unregistered)?;
+                    if req.device() == device.address() {
+                        return Connection::from_request(req);
+                    }
+                    log::debug!("Rejecting serial port connection from unexpected device {}", req.device());
+                    req.reject(ReqError::Rejected);
+                }
+            }
+        }
+    }
+}
+
+/// Established serial port connection.
+#[derive(Debug)]
+pub struct Connection {
+    device: Address,
+    stream: Stream,
+}
+
+impl Connection {
+    fn from_request(req: ConnectRequest) -> Result<Self> {
+        let device = req.device();
+        let stream = Stream::try_from(req.accept()?)?;
+        Ok(Self { device, stream })
+    }
+
+    /// Address of the remote device.
+    pub fn device(&self) -> Address {
+        self.device
+    }
+
+    /// Consumes the connection, returning the RFCOMM stream.
+    pub fn into_stream(self) -> Stream {
+        self.stream
+    }
+
+    /// Exposes the connection as an RFCOMM TTY device (`/dev/rfcommN`).
+    ///
+    /// The kernel takes over the connection and releases the device
+    /// when it is hung up by the last process that opened it.
+    pub fn into_rfcomm_tty(self) -> Result<RfcommTty> {
+        let dev_id = self.stream.as_ref().create_tty(-1)?;
+        Ok(RfcommTty { dev_id, _stream: self.stream })
+    }
+
+    /// Exposes the connection as a pseudo-terminal.
+    ///
+    /// Data is forwarded between the connection and the pseudo-terminal
+    /// by a background task until the connection is closed by the remote device
+    /// or the returned [Pty] is dropped.
+    /// Unlike an RFCOMM TTY device, the pseudo-terminal can be opened and closed
+    /// repeatedly by applications without terminating the connection.
+    pub fn into_pty(self, settings: TtySettings) -> Result<Pty> {
+        let (master, slave, path) = open_pty(&settings)?;
+        let mut master = PtyMaster(AsyncFd::new(master)?);
+
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let device = self.device;
+        let task = tokio::spawn(async move {
+            let _slave = slave;
+            let (mut stream_rh, mut stream_wh) = self.stream.into_split();
+            let (mut master_rh, mut master_wh) = tokio::io::split(&mut master);
+            let res = tokio::select! {
+                res = tokio::io::copy(&mut stream_rh, &mut master_wh) => res.map(|_| ()),
+                res = tokio::io::copy(&mut master_rh, &mut stream_wh) => res.map(|_| ()),
+                _ = stop_rx => Ok(()),
+            };
+            log::trace!("Serial port bridge for {} terminated: {:?}", device, &res);
+            res
+        });
+
+        Ok(Pty { path, task, _stop_tx: stop_tx })
+    }
+}
+
+impl AsRef<Stream> for Connection {
+    fn as_ref(&self) -> &Stream {
+        &self.stream
+    }
+}
+
+/// RFCOMM TTY device (`/dev/rfcommN`) of a serial port connection.
+///
+/// The device is not released when this is dropped.
+/// Use [Socket::release_tty] to release it explicitly.
+#[derive(Debug)]
+pub struct RfcommTty {
+    dev_id: i16,
+    _stream: Stream,
+}
+
+impl RfcommTty {
+    /// Device id.
+    pub fn dev_id(&self) -> i16 {
+        self.dev_id
+    }
+
+    /// Path of the device node.
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from(format!("/dev/rfcomm{}", self.dev_id))
+    }
+
+    /// Releases the device.
+    pub fn release(self) -> Result<()> {
+        Ok(Socket::release_tty(self.dev_id)?)
+    }
+}
+
+/// Terminal settings of a pseudo-terminal.
+#[derive(Clone, Debug)]
+pub struct TtySettings {
+    /// Put the terminal into raw mode, so that data is passed through unmodified.
+    ///
+    /// Enabled by default.
+    pub raw: bool,
+    /// Baud rate reported to applications.
+    ///
+    /// This has no influence on the actual transmission speed.
+    /// Only standard baud rates are supported.
+    pub baud_rate: Option<u32>,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl Default for TtySettings {
+    fn default() -> Self {
+        Self { raw: true, baud_rate: None, _non_exhaustive: () }
+    }
+}
+
+/// Pseudo-terminal bridged to a serial port connection.
+///
+/// Drop to stop forwarding data and close the connection.
+#[derive(Debug)]
+pub struct Pty {
+    path: PathBuf,
+    task: JoinHandle<io::Result<()>>,
+    _stop_tx: oneshot::Sender<()>,
+}
+
+impl Pty {
+    /// Path of the terminal device that applications should open.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Waits until the connection has been closed.
+    pub async fn closed(self) -> Result<()> {
+        let Self { task, _stop_tx, .. } = self;
+        Ok(task.await??)
+    }
+}
+
+fn cvt(res: c_int) -> io::Result<c_int> {
+    if res == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(res)
+    }
+}
+
+fn baud_rate_to_speed(baud_rate: u32) -> io::Result<libc::speed_t> {
+    let speed = match baud_rate {
+        1200 => libc::B1200,
+        2400 => libc::B2400,
+        4800 => libc::B4800,
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        230400 => libc::B230400,
+        460800 => libc::B460800,
+        921600 => libc::B921600,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "unsupported baud rate")),
+    };
+    Ok(speed)
+}
+
+/// Opens a pseudo-terminal pair and applies the terminal settings.
+///
+/// Returns the non-blocking master, the slave and the path of the slave.
+/// The slave is kept open to prevent the master from reporting hangups
+/// while no application has the terminal open.
+fn open_pty(settings: &TtySettings) -> io::Result<(OwnedFd, OwnedFd, PathBuf)> {
+    let master = unsafe { OwnedFd::new(cvt(libc::posix_openpt(O_RDWR | O_NOCTTY | O_CLOEXEC))?) };
+    cvt(unsafe { libc::grantpt(master.as_raw_fd()) })?;
+    cvt(unsafe { libc::unlockpt(master.as_raw_fd()) })?;
+
+    let mut buf = [0u8; 128];
+    let res = unsafe { libc::ptsname_r(master.as_raw_fd(), buf.as_mut_ptr() as *mut _, buf.len()) };
+    if res != 0 {
+        return Err(io::Error::from_raw_os_error(res));
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    let path = PathBuf::from(OsStr::from_bytes(&buf[..len]));
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    let slave = unsafe { OwnedFd::new(cvt(libc::open(c_path.as_ptr(), O_RDWR | O_NOCTTY | O_CLOEXEC))?) };
+
+    let mut termios = unsafe {
+        let mut termios = std::mem::MaybeUninit::uninit();
+        cvt(libc::tcgetattr(slave.as_raw_fd(), termios.as_mut_ptr()))?;
+        termios.assume_init()
+    };
+    if settings.raw {
+        unsafe { libc::cfmakeraw(&mut termios) };
+    }
+    if let Some(baud_rate) = settings.baud_rate {
+        cvt(unsafe { libc::cfsetspeed(&mut termios, baud_rate_to_speed(baud_rate)?) })?;
+    }
+    cvt(unsafe { libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &termios) })?;
+
+    let flags = cvt(unsafe { libc::fcntl(master.as_raw_fd(), libc::F_GETFL) })?;
+    cvt(unsafe { libc::fcntl(master.as_raw_fd(), libc::F_SETFL, flags | O_NONBLOCK) })?;
+
+    Ok((master, slave, path))
+}
+
+/// Non-blocking master side of a pseudo-terminal.
+struct PtyMaster(AsyncFd<OwnedFd>);
+
+impl PtyMaster {
+    fn io<R>(&self, cx: &mut Context, read: bool, mut f: impl FnMut(RawFd) -> isize) -> Poll<io::Result<R>>
+    where
+        R: TryFrom<isize>,
+    {
+        loop {
+            let mut guard =
+                if read { ready!(self.0.poll_read_ready(cx))? } else { ready!(self.0.poll_write_ready(cx))? };
+            match guard.try_io(|fd| match f(fd.as_raw_fd()) {
+                -1 => Err(io::Error::last_os_error()),
+                n => Ok(R::try_from(n).unwrap_or_else(|_| unreachable!())),
+            }) {
+                Ok(res) => return Poll::Ready(res),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+impl AsyncRead for PtyMaster {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context, buf: &mut ReadBuf) -> Poll<io::Result<()>> {
+        let unfilled = buf.initialize_unfilled();
+        let n: usize =
+            ready!(self
+                .io(cx, true, |fd| unsafe { libc::read(fd, unfilled.as_mut_ptr() as *mut _, unfilled.len()) }))?;
+        buf.advance(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for PtyMaster {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.io(cx, false, |fd| unsafe { libc::write(fd, buf.as_ptr() as *const _, buf.len()) })
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
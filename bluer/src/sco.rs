@@ -0,0 +1,520 @@
+//! Synchronous connection-oriented (SCO) sockets.
+//!
+//! SCO links carry voice data between an audio gateway and a headset or
+//! hands-free unit over classic Bluetooth (BR/EDR).
+//! They are usually established after the profile signalling connection,
+//! for example the [Hands-Free Profile](crate::rfcomm::hfp) over RFCOMM,
+//! has selected a codec.
+//!
+//! Use a [Listener] to accept incoming SCO connections or [SeqPacket::connect]
+//! to establish an outgoing connection.
+//! The [voice setting](Socket::set_voice) must be configured before connecting or listening.
+//! Use [Voice::Transparent] for wideband speech codecs, such as mSBC and LC3-SWB,
+//! that are encoded by the host.
+//!
+
+use crate::{
+    sock::{self, OwnedFd},
+    sys::{
+        bt_voice, sco_options, sockaddr_sco, BTPROTO_SCO, BT_PHY, BT_PKT_STATUS, BT_VOICE, BT_VOICE_CVSD_16BIT,
+        BT_VOICE_TRANSPARENT, SCO_CONNINFO, SCO_OPTIONS, SOL_SCO,
+    },
+    Address, Phy,
+};
+use futures::ready;
+use libc::{
+    c_int, AF_BLUETOOTH, EAGAIN, EINPROGRESS, SHUT_RD, SHUT_RDWR, SHUT_WR, SOCK_SEQPACKET, SOL_BLUETOOTH,
+    SOL_SOCKET, SO_ERROR, SO_RCVBUF, TIOCINQ, TIOCOUTQ,
+};
+use std::{
+    convert::TryInto,
+    fmt,
+    io::{Error, ErrorKind, Result},
+    net::Shutdown,
+    os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{unix::AsyncFd, AsyncRead, AsyncWrite, ReadBuf};
+
+pub use crate::{sock::PacketStatus, sys::sco_conninfo as ConnInfo};
+
+/// An SCO socket address.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SocketAddr {
+    /// Device address.
+    ///
+    /// When listening or binding, specify [Address::any] for any local adapter address.
+    pub addr: Address,
+}
+
+impl SocketAddr {
+    /// Creates a new SCO socket address.
+    pub const fn new(addr: Address) -> Self {
+        Self { addr }
+    }
+
+    /// When specified to [Socket::bind] binds to any local adapter address.
+    pub const fn any() -> Self {
+        Self { addr: Address::any() }
+    }
+}
+
+impl sock::SysSockAddr for SocketAddr {
+    type SysSockAddr = sockaddr_sco;
+
+    fn into_sys_sock_addr(self) -> Self::SysSockAddr {
+        sockaddr_sco { sco_family: AF_BLUETOOTH as _, sco_bdaddr: self.addr.into() }
+    }
+
+    fn try_from_sys_sock_addr(saddr: Self::SysSockAddr) -> Result<Self> {
+        if saddr.sco_family != AF_BLUETOOTH as _ {
+            return Err(Error::new(ErrorKind::InvalidInput, "sockaddr_sco::sco_family is not AF_BLUETOOTH"));
+        }
+        Ok(Self { addr: Address::from(saddr.sco_bdaddr) })
+    }
+}
+
+/// SCO voice setting.
+///
+/// This determines how voice data is coded on the air interface.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Voice {
+    /// 16-bit linear PCM samples are coded using CVSD by the controller.
+    ///
+    /// This is the narrowband default.
+    Cvsd16Bit,
+    /// Data is transmitted as is.
+    ///
+    /// Used for codecs encoded by the host, such as mSBC and LC3-SWB.
+    Transparent,
+    /// Other voice setting.
+    Other(u16),
+}
+
+impl Default for Voice {
+    fn default() -> Self {
+        Self::Cvsd16Bit
+    }
+}
+
+impl From<u16> for Voice {
+    fn from(setting: u16) -> Self {
+        match setting {
+            BT_VOICE_CVSD_16BIT => Self::Cvsd16Bit,
+            BT_VOICE_TRANSPARENT => Self::Transparent,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl From<Voice> for u16 {
+    fn from(voice: Voice) -> Self {
+        match voice {
+            Voice::Cvsd16Bit => BT_VOICE_CVSD_16BIT,
+            Voice::Transparent => BT_VOICE_TRANSPARENT,
+            Voice::Other(other) => other,
+        }
+    }
+}
+
+/// An SCO socket that has not yet been converted to a [Listener] or [SeqPacket].
+///
+/// The primary use of this is to configure the socket before connecting or listening.
+pub struct Socket {
+    fd: AsyncFd<OwnedFd>,
+}
+
+impl fmt::Debug for Socket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Socket").field("fd", &self.fd.as_raw_fd()).finish()
+    }
+}
+
+impl Socket {
+    /// Creates a new socket of sequential packet type.
+    pub fn new() -> Result<Self> {
+        Ok(Self { fd: AsyncFd::new(sock::socket(AF_BLUETOOTH, SOCK_SEQPACKET, BTPROTO_SCO)?)? })
+    }
+
+    /// Convert the socket into a [Listener].
+    ///
+    /// `backlog` defines the maximum number of pending connections are queued by the operating system
+    /// at any given time.
+    pub fn listen(self, backlog: u32) -> Result<Listener> {
+        sock::listen(
+            self.fd.get_ref(),
+            backlog.try_into().map_err(|_| Error::new(ErrorKind::InvalidInput, "invalid backlog"))?,
+        )?;
+        Ok(Listener { socket: self })
+    }
+
+    /// Establish an SCO connection with a peer at the specified socket address.
+    ///
+    /// An ACL connection to the peer must already exist.
+    pub async fn connect(self, sa: SocketAddr) -> Result<SeqPacket> {
+        self.connect_priv(sa).await?;
+        Ok(SeqPacket { socket: self })
+    }
+
+    /// Bind the socket to the given address.
+    pub fn bind(&self, sa: SocketAddr) -> Result<()> {
+        sock::bind(self.fd.get_ref(), sa)
+    }
+
+    /// Get the local address of this socket.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        sock::getsockname(self.fd.get_ref())
+    }
+
+    /// Get the peer address of this socket.
+    fn peer_addr_priv(&self) -> Result<SocketAddr> {
+        sock::getpeername(self.fd.get_ref())
+    }
+
+    /// Get voice setting.
+    ///
+    /// This corresponds to the `BT_VOICE` socket option.
+    pub fn voice(&self) -> Result<Voice> {
+        let value: bt_voice = sock::getsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_VOICE)?;
+        Ok(value.setting.into())
+    }
+
+    /// Set voice setting.
+    ///
+    /// Must be set before connecting or, for incoming connections, on the listener
+    /// socket before accepting.
+    ///
+    /// This corresponds to the `BT_VOICE` socket option.
+    pub fn set_voice(&self, voice: Voice) -> Result<()> {
+        let value = bt_voice { setting: voice.into() };
+        sock::setsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_VOICE, &value)
+    }
+
+    /// Gets whether the status of received packets is reported.
+    ///
+    /// This corresponds to the `BT_PKT_STATUS` socket option.
+    pub fn is_packet_status_enabled(&self) -> Result<bool> {
+        let value: c_int = sock::getsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_PKT_STATUS)?;
+        Ok(value != 0)
+    }
+
+    /// Sets whether the status of received packets is reported.
+    ///
+    /// When enabled, the status is returned by [SeqPacket::recv_with_status].
+    ///
+    /// This corresponds to the `BT_PKT_STATUS` socket option.
+    pub fn set_packet_status(&self, packet_status: bool) -> Result<()> {
+        let value: c_int = packet_status.into();
+        sock::setsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_PKT_STATUS, &value)
+    }
+
+    /// Gets the maximum transmission unit (MTU) of the connection.
+    ///
+    /// This is only available once the socket is connected.
+    ///
+    /// This corresponds to the `SCO_OPTIONS` socket option.
+    pub fn mtu(&self) -> Result<u16> {
+        let opts: sco_options = sock::getsockopt(self.fd.get_ref(), SOL_SCO, SCO_OPTIONS)?;
+        Ok(opts.mtu)
+    }
+
+    /// Gets the SCO socket connection information.
+    ///
+    /// This corresponds to the `SCO_CONNINFO` socket option.
+    pub fn conn_info(&self) -> Result<ConnInfo> {
+        sock::getsockopt(self.fd.get_ref(), SOL_SCO, SCO_CONNINFO)
+    }
+
+    /// Get PHYs used by the connection.
+    ///
+    /// This corresponds to the `BT_PHY` socket option.
+    pub fn phy(&self) -> Result<Phy> {
+        let phy: u32 = sock::getsockopt(self.fd.get_ref(), SOL_BLUETOOTH, BT_PHY)?;
+        Ok(Phy::from_bits_truncate(phy))
+    }
+
+    /// Gets the maximum socket receive buffer in bytes.
+    ///
+    /// This corresponds to the `SO_RCVBUF` socket option.
+    pub fn recv_buffer(&self) -> Result<i32> {
+        sock::getsockopt(self.fd.get_ref(), SOL_SOCKET, SO_RCVBUF)
+    }
+
+    /// Sets the maximum socket receive buffer in bytes.
+    ///
+    /// This corresponds to the `SO_RCVBUF` socket option.
+    pub fn set_recv_buffer(&self, recv_buffer: i32) -> Result<()> {
+        sock::setsockopt(self.fd.get_ref(), SOL_SOCKET, SO_RCVBUF, &recv_buffer)
+    }
+
+    /// Get the number of bytes in the input buffer.
+    ///
+    /// This corresponds to the `TIOCINQ` IOCTL.
+    pub fn input_buffer(&self) -> Result<u32> {
+        let value: c_int = sock::ioctl_read(self.fd.get_ref(), TIOCINQ)?;
+        Ok(value as _)
+    }
+
+    /// Get the number of bytes in the output buffer.
+    ///
+    /// This corresponds to the `TIOCOUTQ` IOCTL.
+    pub fn output_buffer(&self) -> Result<u32> {
+        let value: c_int = sock::ioctl_read(self.fd.get_ref(), TIOCOUTQ)?;
+        Ok(value as _)
+    }
+
+    /// Constructs a new [Socket] from the given raw file descriptor.
+    ///
+    /// The file descriptor must have been set to non-blocking mode.
+    ///
+    /// This function *consumes ownership* of the specified file descriptor.
+    /// The returned object will take responsibility for closing it when the object goes out of scope.
+    ///
+    /// # Safety
+    /// If the passed file descriptor is invalid, undefined behavior may occur.
+    pub unsafe fn from_raw_fd(fd: RawFd) -> Result<Self> {
+        Ok(Self { fd: AsyncFd::new(OwnedFd::new(fd))? })
+    }
+
+    fn from_owned_fd(fd: OwnedFd) -> Result<Self> {
+        Ok(Self { fd: AsyncFd::new(fd)? })
+    }
+
+    sock_priv!(recvmsg, poll_recvmsg);
+}
+
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl IntoRawFd for Socket {
+    fn into_raw_fd(self) -> RawFd {
+        self.fd.into_inner().into_raw_fd()
+    }
+}
+
+impl FromRawFd for Socket {
+    /// Constructs a new instance of `Self` from the given raw file
+    /// descriptor.
+    ///
+    /// The file descriptor must have been set to non-blocking mode.
+    ///
+    /// # Panics
+    /// Panics when the conversion fails.
+    /// Use [Socket::from_raw_fd] for a non-panicking variant.
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        Self::from_raw_fd(fd).expect("from_raw_fd failed")
+    }
+}
+
+/// An SCO socket server, listening for [SeqPacket] connections.
+#[derive(Debug)]
+pub struct Listener {
+    socket: Socket,
+}
+
+impl Listener {
+    /// Creates a new Listener, which will be bound to the specified socket address.
+    ///
+    /// Incoming connections use the default [CVSD voice setting](Voice::Cvsd16Bit).
+    /// Use [Socket::set_voice] and [Socket::listen] to accept connections
+    /// with another voice setting.
+    pub async fn bind(sa: SocketAddr) -> Result<Self> {
+        let socket = Socket::new()?;
+        socket.bind(sa)?;
+        socket.listen(1)
+    }
+
+    /// Accepts a new incoming connection from this listener.
+    pub async fn accept(&self) -> Result<(SeqPacket, SocketAddr)> {
+        let (socket, sa) = self.socket.accept_priv().await?;
+        Ok((SeqPacket { socket }, sa))
+    }
+
+    /// Polls to accept a new incoming connection to this listener.
+    pub fn poll_accept(&self, cx: &mut Context) -> Poll<Result<(SeqPacket, SocketAddr)>> {
+        let (socket, sa) = ready!(self.socket.poll_accept_priv(cx))?;
+        Poll::Ready(Ok((SeqPacket { socket }, sa)))
+    }
+
+    /// Constructs a new [Listener] from the given raw file descriptor.
+    ///
+    /// The file descriptor must have been set to non-blocking mode.
+    ///
+    /// This function *consumes ownership* of the specified file descriptor.
+    /// The returned object will take responsibility for closing it when the object goes out of scope.
+    ///
+    /// # Safety
+    /// If the passed file descriptor is invalid, undefined behavior may occur.
+    pub unsafe fn from_raw_fd(fd: RawFd) -> Result<Self> {
+        Ok(Self { socket: Socket::from_raw_fd(fd)? })
+    }
+}
+
+impl AsRef<Socket> for Listener {
+    fn as_ref(&self) -> &Socket {
+        &self.socket
+    }
+}
+
+impl AsRawFd for Listener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+impl FromRawFd for Listener {
+    /// Constructs a new instance of `Self` from the given raw file
+    /// descriptor.
+    ///
+    /// The file descriptor must have been set to non-blocking mode.
+    ///
+    /// # Panics
+    /// Panics when the conversion fails.
+    /// Use [Listener::from_raw_fd] for a non-panicking variant.
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        Self::from_raw_fd(fd).expect("from_raw_fd failed")
+    }
+}
+
+/// An SCO sequential packet socket carrying voice data.
+///
+/// Each packet should not exceed the [MTU](Self::mtu) of the connection.
+/// [AsyncRead] and [AsyncWrite] are implemented for convenience;
+/// each read returns one packet and each write sends one packet.
+#[derive(Debug)]
+pub struct SeqPacket {
+    socket: Socket,
+}
+
+impl SeqPacket {
+    /// Establish an SCO connection with a peer at the specified socket address
+    /// using the default [CVSD voice setting](Voice::Cvsd16Bit).
+    ///
+    /// Uses any local Bluetooth adapter.
+    /// Use [Socket::set_voice] and [Socket::connect] to connect with another voice setting.
+    pub async fn connect(addr: SocketAddr) -> Result<Self> {
+        let socket = Socket::new()?;
+        socket.bind(SocketAddr::any())?;
+        socket.connect(addr).await
+    }
+
+    /// Gets the peer address of this connection.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.socket.peer_addr_priv()
+    }
+
+    /// Sends a packet.
+    pub async fn send(&self, buf: &[u8]) -> Result<usize> {
+        self.socket.send_priv(buf).await
+    }
+
+    /// Attempts to send a packet.
+    pub fn poll_send(&self, cx: &mut Context, buf: &[u8]) -> Poll<Result<usize>> {
+        self.socket.poll_send_priv(cx, buf)
+    }
+
+    /// Receives a packet.
+    pub async fn recv(&self, buf: &mut [u8]) -> Result<usize> {
+        self.socket.recv_priv(buf).await
+    }
+
+    /// Attempts to receive a packet.
+    pub fn poll_recv(&self, cx: &mut Context, buf: &mut ReadBuf) -> Poll<Result<()>> {
+        self.socket.poll_recv_priv(cx, buf)
+    }
+
+    /// Receives a packet together with its status.
+    ///
+    /// The status is only available if [packet status reporting](Socket::set_packet_status)
+    /// is enabled.
+    pub async fn recv_with_status(&self, buf: &mut [u8]) -> Result<(usize, Option<PacketStatus>)> {
+        let (n, cmsgs) = self.socket.recvmsg_priv(buf).await?;
+        Ok((n, PacketStatus::from_cmsgs(&cmsgs)))
+    }
+
+    /// Attempts to receive a packet together with its status.
+    ///
+    /// The status is only available if [packet status reporting](Socket::set_packet_status)
+    /// is enabled.
+    pub fn poll_recv_with_status(
+        &self, cx: &mut Context, buf: &mut ReadBuf,
+    ) -> Poll<Result<Option<PacketStatus>>> {
+        let cmsgs = ready!(self.socket.poll_recvmsg_priv(cx, buf))?;
+        Poll::Ready(Ok(PacketStatus::from_cmsgs(&cmsgs)))
+    }
+
+    /// Shuts down the read, write, or both halves of this connection.
+    pub fn shutdown(&self, how: Shutdown) -> Result<()> {
+        self.socket.shutdown_priv(how)
+    }
+
+    /// Maximum transmission unit (MTU) of the connection.
+    pub fn mtu(&self) -> Result<usize> {
+        self.socket.mtu().map(|v| v.into())
+    }
+
+    /// Constructs a new [SeqPacket] from the given raw file descriptor.
+    ///
+    /// The file descriptor must have been set to non-blocking mode.
+    ///
+    /// This function *consumes ownership* of the specified file descriptor.
+    /// The returned object will take responsibility for closing it when the object goes out of scope.
+    ///
+    /// # Safety
+    /// If the passed file descriptor is invalid, undefined behavior may occur.
+    pub unsafe fn from_raw_fd(fd: RawFd) -> Result<Self> {
+        Ok(Self { socket: Socket::from_raw_fd(fd)? })
+    }
+}
+
+impl AsRef<Socket> for SeqPacket {
+    fn as_ref(&self) -> &Socket {
+        &self.socket
+    }
+}
+
+impl AsRawFd for SeqPacket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+impl FromRawFd for SeqPacket {
+    /// Constructs a new instance of `Self` from the given raw file
+    /// descriptor.
+    ///
+    /// The file descriptor must have been set to non-blocking mode.
+    ///
+    /// # Panics
+    /// Panics when the conversion fails.
+    /// Use [SeqPacket::from_raw_fd] for a non-panicking variant.
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        Self::from_raw_fd(fd).expect("from_raw_fd failed")
+    }
+}
+
+impl AsyncRead for SeqPacket {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context, buf: &mut ReadBuf) -> Poll<Result<()>> {
+        self.socket.poll_recv_priv(cx, buf)
+    }
+}
+
+impl AsyncWrite for SeqPacket {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<Result<usize>> {
+        self.socket.poll_send_priv(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        self.socket.poll_flush_priv(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        self.socket.poll_shutdown_priv(cx, Shutdown::Write)
+    }
+}
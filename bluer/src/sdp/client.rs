@@ -0,0 +1,356 @@
+//! SDP client.
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    ops::RangeInclusive,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use uuid::Uuid;
+
+use super::{attr, pdu, DataElement, ErrorCode, ParseError, ServiceRecord, PSM};
+use crate::{
+    l2cap::{SocketAddr, Stream},
+    Address, AddressType,
+};
+
+/// Maximum number of service record handles or attribute bytes requested per response.
+const MAX_RESPONSE: u16 = 0xffff;
+
+/// Maximum number of requests sent to follow continuation state of one transaction.
+const MAX_CONT_ROUNDS: usize = 256;
+
+/// Maximum number of bytes of service record handles or attribute lists accumulated
+/// over all responses of one transaction.
+const MAX_RESPONSE_LEN: usize = 1 << 20;
+
+/// SDP client for querying service records of a remote device.
+///
+/// The client sends one request at a time and transparently follows
+/// continuation state until the complete response has been received.
+/// A response that requires more than 256 continuation requests or
+/// exceeds 1 MiB is rejected.
+#[derive(Debug)]
+pub struct Client<S = Stream> {
+    stream: S,
+    tid: u16,
+}
+
+impl Client<Stream> {
+    /// Connects to the SDP server of the specified classic Bluetooth (BR/EDR) device.
+    ///
+    /// Uses any local Bluetooth adapter.
+    pub async fn connect(addr: Address) -> Result<Self> {
+        let stream = Stream::connect(SocketAddr::new(addr, AddressType::BrEdr, PSM)).await?;
+        Ok(Self::new(stream))
+    }
+}
+
+impl<S> Client<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Creates an SDP client using a stream, usually an L2CAP stream, connected to the SDP server.
+    pub fn new(stream: S) -> Self {
+        Self { stream, tid: 0 }
+    }
+
+    /// Consumes the client, returning the underlying stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    /// Sends a request and receives the matching response.
+    ///
+    /// Returns the parameters of the response.
+    async fn transact(&mut self, req_id: u8, rsp_id: u8, params: &[u8]) -> Result<Vec<u8>> {
+        self.tid = self.tid.wrapping_add(1);
+        self.stream.write_all(&pdu::encode(req_id, self.tid, params)).await?;
+
+        loop {
+            let mut hdr = [0; pdu::HDR_SIZE];
+            self.stream.read_exact(&mut hdr).await?;
+            let (pdu_id, tid, len) = pdu::decode_header(&hdr);
+            let mut params = vec![0; len];
+            self.stream.read_exact(&mut params).await?;
+
+            if tid != self.tid {
+                log::debug!("Ignoring SDP response with unexpected transaction id {}", tid);
+                continue;
+            }
+
+            return match pdu_id {
+                pdu::ERROR_RSP if params.len() >= 2 => {
+                    Err(ErrorCode::from(u16::from_be_bytes([params[0], params[1]])).into())
+                }
+                id if id == rsp_id => Ok(params),
+                id => {
+                    Err(Error::new(ErrorKind::InvalidData, format!("unexpected SDP response PDU 0x{:02x}", id)))
+                }
+            };
+        }
+    }
+
+    /// Searches for service records containing all UUIDs of the specified pattern.
+    ///
+    /// At most 12 UUIDs may be specified.
+    /// Returns the handles of the matching service records.
+    pub async fn service_search(&mut self, pattern: &[Uuid]) -> Result<Vec<u32>> {
+        check_pattern(pattern)?;
+
+        let mut handles = Vec::new();
+        let mut cont = Vec::new();
+        for _ in 0..MAX_CONT_ROUNDS {
+            let mut params = Vec::new();
+            pdu::encode_search_pattern(&mut params, pattern);
+            params.extend_from_slice(&MAX_RESPONSE.to_be_bytes());
+            pdu::encode_cont_state(&mut params, &cont);
+
+            let rsp = self.transact(pdu::SERVICE_SEARCH_REQ, pdu::SERVICE_SEARCH_RSP, &params).await?;
+            if rsp.len() < 4 {
+                return Err(invalid_response());
+            }
+            let count = u16::from_be_bytes([rsp[2], rsp[3]]) as usize;
+            let end = 4 + count * 4;
+            if rsp.len() < end {
+                return Err(invalid_response());
+            }
+            handles.extend(rsp[4..end].chunks_exact(4).map(|h| u32::from_be_bytes(h.try_into().unwrap())));
+            if handles.len() * 4 > MAX_RESPONSE_LEN {
+                return Err(response_too_long());
+            }
+
+            cont = pdu::decode_cont_state(&rsp[end..])?;
+            if cont.is_empty() {
+                return Ok(handles);
+            }
+        }
+        Err(too_many_continuations())
+    }
+
+    /// Retrieves attributes of the service record with the specified handle.
+    ///
+    /// Only attributes with ids within the specified ranges are returned.
+    /// Use [ALL_ATTRIBUTES](super::ALL_ATTRIBUTES) to retrieve all attributes.
+    pub async fn service_attributes(
+        &mut self, handle: u32, attrs: &[RangeInclusive<u16>],
+    ) -> Result<ServiceRecord> {
+        let mut head = Vec::new();
+        head.extend_from_slice(&handle.to_be_bytes());
+        head.extend_from_slice(&MAX_RESPONSE.to_be_bytes());
+        pdu::encode_attr_ranges(&mut head, attrs);
+
+        let data = self.attribute_transaction(pdu::SERVICE_ATTR_REQ, pdu::SERVICE_ATTR_RSP, &head).await?;
+        Ok(ServiceRecord::from_bytes(&data)?)
+    }
+
+    /// Searches for service records containing all UUIDs of the specified pattern and
+    /// retrieves their attributes.
+    ///
+    /// At most 12 UUIDs may be specified.
+    /// Only attributes with ids within the specified ranges are returned.
+    /// Use [ALL_ATTRIBUTES](super::ALL_ATTRIBUTES) to retrieve all attributes.
+    pub async fn service_search_attributes(
+        &mut self, pattern: &[Uuid], attrs: &[RangeInclusive<u16>],
+    ) -> Result<Vec<ServiceRecord>> {
+        check_pattern(pattern)?;
+
+        let mut head = Vec::new();
+        pdu::encode_search_pattern(&mut head, pattern);
+        head.extend_from_slice(&MAX_RESPONSE.to_be_bytes());
+        pdu::encode_attr_ranges(&mut head, attrs);
+
+        let data =
+            self.attribute_transaction(pdu::SERVICE_SEARCH_ATTR_REQ, pdu::SERVICE_SEARCH_ATTR_RSP, &head).await?;
+        match DataElement::from_bytes(&data)? {
+            DataElement::Sequence(lists) => {
+                Ok(lists.iter().map(ServiceRecord::from_data_element).collect::<std::result::Result<_, _>>()?)
+            }
+            _ => Err(ParseError("attribute lists must be a sequence".to_string()).into()),
+        }
+    }
+
+    /// Performs an attribute transaction, following continuation state.
+    ///
+    /// Returns the concatenated attribute list bytes.
+    async fn attribute_transaction(&mut self, req_id: u8, rsp_id: u8, head: &[u8]) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut cont = Vec::new();
+        for _ in 0..MAX_CONT_ROUNDS {
+            let mut params = head.to_vec();
+            pdu::encode_cont_state(&mut params, &cont);
+
+            let rsp = self.transact(req_id, rsp_id, &params).await?;
+            if rsp.len() < 2 {
+                return Err(invalid_response());
+            }
+            let count = u16::from_be_bytes([rsp[0], rsp[1]]) as usize;
+            let end = 2 + count;
+            if rsp.len() < end {
+                return Err(invalid_response());
+            }
+            data.extend_from_slice(&rsp[2..end]);
+            if data.len() > MAX_RESPONSE_LEN {
+                return Err(response_too_long());
+            }
+
+            cont = pdu::decode_cont_state(&rsp[end..])?;
+            if cont.is_empty() {
+                return Ok(data);
+            }
+        }
+        Err(too_many_continuations())
+    }
+
+    /// Finds the RFCOMM channel of the first service record of the specified service class.
+    ///
+    /// Returns [None] if no such service record exists or if it does not use RFCOMM.
+    pub async fn find_rfcomm_channel(&mut self, service_class: Uuid) -> Result<Option<u8>> {
+        let records = self
+            .service_search_attributes(
+                &[service_class],
+                &[attr::PROTOCOL_DESCRIPTOR_LIST..=attr::PROTOCOL_DESCRIPTOR_LIST],
+            )
+            .await?;
+        Ok(records.iter().find_map(|record| record.rfcomm_channel()))
+    }
+}
+
+fn check_pattern(pattern: &[Uuid]) -> Result<()> {
+    if pattern.is_empty() || pattern.len() > pdu::MAX_SEARCH_PATTERN_LEN {
+        return Err(Error::new(ErrorKind::InvalidInput, "service search pattern must contain 1 to 12 UUIDs"));
+    }
+    Ok(())
+}
+
+fn invalid_response() -> Error {
+    Error::new(ErrorKind::InvalidData, "invalid SDP response")
+}
+
+fn response_too_long() -> Error {
+    Error::new(ErrorKind::InvalidData, "SDP response too long")
+}
+
+fn too_many_continuations() -> Error {
+    Error::new(ErrorKind::InvalidData, "too many SDP continuation requests")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdp::ALL_ATTRIBUTES;
+    use tokio::net::UnixStream;
+
+    const PATTERN: [Uuid; 1] = [Uuid::from_u128(1)];
+
+    /// Receives a request, checks its PDU id and continuation state and returns its transaction id.
+    async fn recv_request(server: &mut UnixStream, req_id: u8, cont: &[u8]) -> u16 {
+        let mut hdr = [0; pdu::HDR_SIZE];
+        server.read_exact(&mut hdr).await.unwrap();
+        let (pdu_id, tid, len) = pdu::decode_header(&hdr);
+        let mut params = vec![0; len];
+        server.read_exact(&mut params).await.unwrap();
+        assert_eq!(pdu_id, req_id);
+        let mut expected = Vec::new();
+        pdu::encode_cont_state(&mut expected, cont);
+        assert!(params.ends_with(&expected));
+        tid
+    }
+
+    async fn send_response(server: &mut UnixStream, rsp_id: u8, tid: u16, params: &[u8]) {
+        server.write_all(&pdu::encode(rsp_id, tid, params)).await.unwrap();
+    }
+
+    fn search_rsp(total: u16, handles: &[u32], cont: &[u8]) -> Vec<u8> {
+        let mut params = Vec::new();
+        params.extend_from_slice(&total.to_be_bytes());
+        params.extend_from_slice(&(handles.len() as u16).to_be_bytes());
+        for handle in handles {
+            params.extend_from_slice(&handle.to_be_bytes());
+        }
+        pdu::encode_cont_state(&mut params, cont);
+        params
+    }
+
+    fn attr_rsp(data: &[u8], cont: &[u8]) -> Vec<u8> {
+        let mut params = Vec::new();
+        params.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        params.extend_from_slice(data);
+        pdu::encode_cont_state(&mut params, cont);
+        params
+    }
+
+    #[tokio::test]
+    async fn continuation() {
+        let (stream, mut server) = UnixStream::pair().unwrap();
+        let mut client = Client::new(stream);
+        let mut record = ServiceRecord::new();
+        record.attributes.insert(attr::SERVICE_RECORD_HANDLE, DataElement::U32(0x10000));
+        record.attributes.insert(attr::SERVICE_CLASS_ID_LIST, DataElement::Sequence(Vec::new()));
+        let data = record.to_bytes();
+        let (first, second) = data.split_at(data.len() / 2);
+
+        let (handles, ()) = tokio::join!(client.service_search(&PATTERN), async {
+            let tid = recv_request(&mut server, pdu::SERVICE_SEARCH_REQ, &[]).await;
+            send_response(&mut server, pdu::SERVICE_SEARCH_RSP, tid, &search_rsp(2, &[0x10000], &[1, 2])).await;
+            let tid = recv_request(&mut server, pdu::SERVICE_SEARCH_REQ, &[1, 2]).await;
+            send_response(&mut server, pdu::SERVICE_SEARCH_RSP, tid, &search_rsp(2, &[0x10001], &[])).await;
+        });
+        assert_eq!(handles.unwrap(), vec![0x10000, 0x10001]);
+
+        let (res, ()) = tokio::join!(client.service_attributes(0x10000, &[ALL_ATTRIBUTES]), async {
+            let tid = recv_request(&mut server, pdu::SERVICE_ATTR_REQ, &[]).await;
+            send_response(&mut server, pdu::SERVICE_ATTR_RSP, tid, &attr_rsp(first, &[3])).await;
+            let tid = recv_request(&mut server, pdu::SERVICE_ATTR_REQ, &[3]).await;
+            send_response(&mut server, pdu::SERVICE_ATTR_RSP, tid, &attr_rsp(second, &[])).await;
+        });
+        assert_eq!(res.unwrap(), record);
+    }
+
+    #[tokio::test]
+    async fn repeated_continuation() {
+        let (stream, mut server) = UnixStream::pair().unwrap();
+        let mut client = Client::new(stream);
+
+        let responder = tokio::spawn(async move {
+            let mut rounds = 0;
+            let mut hdr = [0; pdu::HDR_SIZE];
+            while server.read_exact(&mut hdr).await.is_ok() {
+                let (_, tid, len) = pdu::decode_header(&hdr);
+                let mut params = vec![0; len];
+                server.read_exact(&mut params).await.unwrap();
+                send_response(&mut server, pdu::SERVICE_SEARCH_ATTR_RSP, tid, &attr_rsp(&[], &[7])).await;
+                rounds += 1;
+            }
+            rounds
+        });
+
+        let err = client.service_search_attributes(&PATTERN, &[ALL_ATTRIBUTES]).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        drop(client);
+        assert_eq!(responder.await.unwrap(), MAX_CONT_ROUNDS);
+    }
+
+    #[tokio::test]
+    async fn truncated_pdu() {
+        let (stream, mut server) = UnixStream::pair().unwrap();
+        let mut client = Client::new(stream);
+
+        // Response announces two handles but contains only one.
+        let (res, ()) = tokio::join!(client.service_search(&PATTERN), async {
+            let tid = recv_request(&mut server, pdu::SERVICE_SEARCH_REQ, &[]).await;
+            let mut params = search_rsp(2, &[0x10000, 0x10001], &[]);
+            params.truncate(4 + 4);
+            send_response(&mut server, pdu::SERVICE_SEARCH_RSP, tid, &params).await;
+        });
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::InvalidData);
+
+        // Connection closes before the announced parameters have been received.
+        let (res, ()) = tokio::join!(client.service_search(&PATTERN), async {
+            let tid = recv_request(&mut server, pdu::SERVICE_SEARCH_REQ, &[]).await;
+            let pdu = pdu::encode(pdu::SERVICE_SEARCH_RSP, tid, &search_rsp(1, &[0x10000], &[]));
+            server.write_all(&pdu[..pdu.len() - 2]).await.unwrap();
+            server.shutdown().await.unwrap();
+        });
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,939 @@
+//! Service discovery protocol (SDP) records.
+//!
+//! A [ServiceRecord] describes a service offered by a classic Bluetooth (BR/EDR) device.
+//! It consists of attributes identified by [attribute ids](attr), whose values are
+//! [data elements](DataElement).
+//!
+//! Service records can be built using the provided builder methods,
+//! serialized to and parsed from the XML format used by BlueZ
+//! and encoded to and decoded from the binary format used by the SDP protocol.
+//!
+//! With the `l2cap` feature enabled, the service records of remote devices can be queried
+//! using the SDP [Client] and local service records can be published without the
+//! Bluetooth daemon using the SDP [Server].
+//!
+//! Use [ServiceRecord::to_xml] to obtain a service record for the `service_record`
+//! field of an RFCOMM profile.
+
+use std::{collections::BTreeMap, fmt, ops::RangeInclusive};
+use uuid::Uuid;
+
+use crate::UuidExt;
+
+#[cfg(feature = "l2cap")]
+mod client;
+#[cfg(feature = "l2cap")]
+mod pdu;
+#[cfg(feature = "l2cap")]
+mod server;
+mod xml;
+
+#[cfg(feature = "l2cap")]
+#[cfg_attr(docsrs, doc(cfg(feature = "l2cap")))]
+pub use client::Client;
+#[cfg(feature = "l2cap")]
+#[cfg_attr(docsrs, doc(cfg(feature = "l2cap")))]
+pub use server::{RecordHandle, Server};
+
+/// L2CAP protocol service multiplexor (PSM) of the SDP server.
+pub const PSM: u16 = 0x0001;
+
+/// Range covering all attribute ids.
+pub const ALL_ATTRIBUTES: RangeInclusive<u16> = 0x0000..=0xffff;
+
+/// Service attribute identifiers.
+pub mod attr {
+    /// Service record handle.
+    pub const SERVICE_RECORD_HANDLE: u16 = 0x0000;
+    /// Service class id list.
+    pub const SERVICE_CLASS_ID_LIST: u16 = 0x0001;
+    /// Service record state.
+    pub const SERVICE_RECORD_STATE: u16 = 0x0002;
+    /// Service id.
+    pub const SERVICE_ID: u16 = 0x0003;
+    /// Protocol descriptor list.
+    pub const PROTOCOL_DESCRIPTOR_LIST: u16 = 0x0004;
+    /// Browse group list.
+    pub const BROWSE_GROUP_LIST: u16 = 0x0005;
+    /// Language base attribute id list.
+    pub const LANGUAGE_BASE_ATTRIBUTE_ID_LIST: u16 = 0x0006;
+    /// Service info time to live.
+    pub const SERVICE_INFO_TIME_TO_LIVE: u16 = 0x0007;
+    /// Service availability.
+    pub const SERVICE_AVAILABILITY: u16 = 0x0008;
+    /// Bluetooth profile descriptor list.
+    pub const BLUETOOTH_PROFILE_DESCRIPTOR_LIST: u16 = 0x0009;
+    /// Documentation URL.
+    pub const DOCUMENTATION_URL: u16 = 0x000a;
+    /// Client executable URL.
+    pub const CLIENT_EXECUTABLE_URL: u16 = 0x000b;
+    /// Icon URL.
+    pub const ICON_URL: u16 = 0x000c;
+    /// Additional protocol descriptor lists.
+    pub const ADDITIONAL_PROTOCOL_DESCRIPTOR_LISTS: u16 = 0x000d;
+    /// Primary language base for human readable attributes.
+    pub const PRIMARY_LANGUAGE_BASE: u16 = 0x0100;
+    /// Offset of service name from language base.
+    pub const SERVICE_NAME_OFFSET: u16 = 0x0000;
+    /// Offset of service description from language base.
+    pub const SERVICE_DESCRIPTION_OFFSET: u16 = 0x0001;
+    /// Offset of provider name from language base.
+    pub const PROVIDER_NAME_OFFSET: u16 = 0x0002;
+    /// Supported features of many profiles.
+    pub const SUPPORTED_FEATURES: u16 = 0x0311;
+}
+
+/// Protocol UUIDs.
+pub mod protocol {
+    use uuid::Uuid;
+
+    /// Service discovery protocol.
+    pub const SDP: Uuid = Uuid::from_u128(0x00000001_0000_1000_8000_00805f9b34fb);
+    /// RFCOMM.
+    pub const RFCOMM: Uuid = Uuid::from_u128(0x00000003_0000_1000_8000_00805f9b34fb);
+    /// OBEX.
+    pub const OBEX: Uuid = Uuid::from_u128(0x00000008_0000_1000_8000_00805f9b34fb);
+    /// Bluetooth network encapsulation protocol.
+    pub const BNEP: Uuid = Uuid::from_u128(0x0000000f_0000_1000_8000_00805f9b34fb);
+    /// Human interface device protocol.
+    pub const HIDP: Uuid = Uuid::from_u128(0x00000011_0000_1000_8000_00805f9b34fb);
+    /// Audio/video control transport protocol.
+    pub const AVCTP: Uuid = Uuid::from_u128(0x00000017_0000_1000_8000_00805f9b34fb);
+    /// Audio/video distribution transport protocol.
+    pub const AVDTP: Uuid = Uuid::from_u128(0x00000019_0000_1000_8000_00805f9b34fb);
+    /// L2CAP.
+    pub const L2CAP: Uuid = Uuid::from_u128(0x00000100_0000_1000_8000_00805f9b34fb);
+}
+
+/// UUID of the public browse group.
+///
+/// Services that are part of this group are found by clients browsing the SDP server.
+pub const PUBLIC_BROWSE_GROUP: Uuid = Uuid::from_u128(0x00001002_0000_1000_8000_00805f9b34fb);
+
+/// Invalid SDP data error.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParseError(pub String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid SDP data: {}", &self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for std::io::Error {
+    fn from(err: ParseError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// Error code of an SDP error response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ErrorCode {
+    /// Invalid or unsupported SDP version.
+    InvalidVersion,
+    /// Invalid service record handle.
+    InvalidRecordHandle,
+    /// Invalid request syntax.
+    InvalidSyntax,
+    /// Invalid PDU size.
+    InvalidPduSize,
+    /// Invalid continuation state.
+    InvalidContinuationState,
+    /// Insufficient resources to satisfy request.
+    InsufficientResources,
+    /// Unknown error code.
+    Unknown(u16),
+}
+
+impl From<u16> for ErrorCode {
+    fn from(code: u16) -> Self {
+        match code {
+            0x0001 => Self::InvalidVersion,
+            0x0002 => Self::InvalidRecordHandle,
+            0x0003 => Self::InvalidSyntax,
+            0x0004 => Self::InvalidPduSize,
+            0x0005 => Self::InvalidContinuationState,
+            0x0006 => Self::InsufficientResources,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl From<ErrorCode> for u16 {
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::InvalidVersion => 0x0001,
+            ErrorCode::InvalidRecordHandle => 0x0002,
+            ErrorCode::InvalidSyntax => 0x0003,
+            ErrorCode::InvalidPduSize => 0x0004,
+            ErrorCode::InvalidContinuationState => 0x0005,
+            ErrorCode::InsufficientResources => 0x0006,
+            ErrorCode::Unknown(other) => other,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidVersion => write!(f, "invalid SDP version"),
+            Self::InvalidRecordHandle => write!(f, "invalid service record handle"),
+            Self::InvalidSyntax => write!(f, "invalid request syntax"),
+            Self::InvalidPduSize => write!(f, "invalid PDU size"),
+            Self::InvalidContinuationState => write!(f, "invalid continuation state"),
+            Self::InsufficientResources => write!(f, "insufficient resources"),
+            Self::Unknown(code) => write!(f, "unknown SDP error 0x{:04x}", code),
+        }
+    }
+}
+
+impl std::error::Error for ErrorCode {}
+
+impl From<ErrorCode> for std::io::Error {
+    fn from(code: ErrorCode) -> Self {
+        let kind = match code {
+            ErrorCode::InvalidRecordHandle => std::io::ErrorKind::NotFound,
+            ErrorCode::InsufficientResources => std::io::ErrorKind::OutOfMemory,
+            _ => std::io::ErrorKind::InvalidInput,
+        };
+        std::io::Error::new(kind, code)
+    }
+}
+
+fn parse_err<T>(msg: impl ToString) -> Result<T, ParseError> {
+    Err(ParseError(msg.to_string()))
+}
+
+const TYPE_NIL: u8 = 0;
+const TYPE_UINT: u8 = 1;
+const TYPE_SINT: u8 = 2;
+const TYPE_UUID: u8 = 3;
+const TYPE_TEXT: u8 = 4;
+const TYPE_BOOL: u8 = 5;
+const TYPE_SEQUENCE: u8 = 6;
+const TYPE_ALTERNATIVE: u8 = 7;
+const TYPE_URL: u8 = 8;
+
+/// Maximum nesting depth of sequences and alternatives accepted when parsing.
+const MAX_DEPTH: usize = 32;
+
+/// SDP data element.
+///
+/// UUIDs are encoded using their shortest form.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DataElement {
+    /// Nil, the null type.
+    Nil,
+    /// Unsigned 8-bit integer.
+    U8(u8),
+    /// Unsigned 16-bit integer.
+    U16(u16),
+    /// Unsigned 32-bit integer.
+    U32(u32),
+    /// Unsigned 64-bit integer.
+    U64(u64),
+    /// Unsigned 128-bit integer.
+    U128(u128),
+    /// Signed 8-bit integer.
+    I8(i8),
+    /// Signed 16-bit integer.
+    I16(i16),
+    /// Signed 32-bit integer.
+    I32(i32),
+    /// Signed 64-bit integer.
+    I64(i64),
+    /// Signed 128-bit integer.
+    I128(i128),
+    /// UUID.
+    Uuid(Uuid),
+    /// Text string.
+    Text(String),
+    /// Boolean.
+    Bool(bool),
+    /// Sequence of data elements.
+    Sequence(Vec<DataElement>),
+    /// Data element alternative, of which one element is to be selected.
+    Alternative(Vec<DataElement>),
+    /// Uniform resource locator (URL).
+    Url(String),
+}
+
+impl DataElement {
+    /// Encodes the data element into its binary representation.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode(&mut buf);
+        buf
+    }
+
+    /// Decodes a data element from its binary representation.
+    ///
+    /// All data must be consumed by the data element.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        let (de, rest) = Self::decode(data)?;
+        if !rest.is_empty() {
+            return parse_err("trailing data after data element");
+        }
+        Ok(de)
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        fn fixed(buf: &mut Vec<u8>, ty: u8, data: &[u8]) {
+            let size_idx = match data.len() {
+                1 => 0,
+                2 => 1,
+                4 => 2,
+                8 => 3,
+                16 => 4,
+                _ => unreachable!(),
+            };
+            buf.push(ty << 3 | size_idx);
+            buf.extend_from_slice(data);
+        }
+
+        fn var(buf: &mut Vec<u8>, ty: u8, data: &[u8]) {
+            if let Ok(len) = u8::try_from(data.len()) {
+                buf.push(ty << 3 | 5);
+                buf.push(len);
+            } else if let Ok(len) = u16::try_from(data.len()) {
+                buf.push(ty << 3 | 6);
+                buf.extend_from_slice(&len.to_be_bytes());
+            } else {
+                buf.push(ty << 3 | 7);
+                buf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            }
+            buf.extend_from_slice(data);
+        }
+
+        match self {
+            Self::Nil => buf.push(TYPE_NIL << 3),
+            Self::U8(v) => fixed(buf, TYPE_UINT, &v.to_be_bytes()),
+            Self::U16(v) => fixed(buf, TYPE_UINT, &v.to_be_bytes()),
+            Self::U32(v) => fixed(buf, TYPE_UINT, &v.to_be_bytes()),
+            Self::U64(v) => fixed(buf, TYPE_UINT, &v.to_be_bytes()),
+            Self::U128(v) => fixed(buf, TYPE_UINT, &v.to_be_bytes()),
+            Self::I8(v) => fixed(buf, TYPE_SINT, &v.to_be_bytes()),
+            Self::I16(v) => fixed(buf, TYPE_SINT, &v.to_be_bytes()),
+            Self::I32(v) => fixed(buf, TYPE_SINT, &v.to_be_bytes()),
+            Self::I64(v) => fixed(buf, TYPE_SINT, &v.to_be_bytes()),
+            Self::I128(v) => fixed(buf, TYPE_SINT, &v.to_be_bytes()),
+            Self::Uuid(uuid) => match (uuid.as_u16(), uuid.as_u32()) {
+                (Some(v), _) => fixed(buf, TYPE_UUID, &v.to_be_bytes()),
+                (None, Some(v)) => fixed(buf, TYPE_UUID, &v.to_be_bytes()),
+                (None, None) => fixed(buf, TYPE_UUID, uuid.as_bytes()),
+            },
+            Self::Text(s) => var(buf, TYPE_TEXT, s.as_bytes()),
+            Self::Bool(v) => fixed(buf, TYPE_BOOL, &[*v as u8]),
+            Self::Sequence(elems) | Self::Alternative(elems) => {
+                let mut inner = Vec::new();
+                for elem in elems {
+                    elem.encode(&mut inner);
+                }
+                let ty = if matches!(self, Self::Sequence(_)) { TYPE_SEQUENCE } else { TYPE_ALTERNATIVE };
+                var(buf, ty, &inner);
+            }
+            Self::Url(s) => var(buf, TYPE_URL, s.as_bytes()),
+        }
+    }
+
+    /// Decodes a data element and returns the remaining data.
+    pub(crate) fn decode(data: &[u8]) -> Result<(Self, &[u8]), ParseError> {
+        Self::decode_nested(data, 0)
+    }
+
+    fn decode_nested(data: &[u8], depth: usize) -> Result<(Self, &[u8]), ParseError> {
+        let (&desc, data) = match data.split_first() {
+            Some(v) => v,
+            None => return parse_err("missing data element"),
+        };
+        let ty = desc >> 3;
+        let size_idx = desc & 0x07;
+
+        let (len, data) = match size_idx {
+            0 if ty == TYPE_NIL => (0, data),
+            0 => (1, data),
+            1 => (2, data),
+            2 => (4, data),
+            3 => (8, data),
+            4 => (16, data),
+            5 if !data.is_empty() => (data[0] as usize, &data[1..]),
+            6 if data.len() >= 2 => (u16::from_be_bytes([data[0], data[1]]) as usize, &data[2..]),
+            7 if data.len() >= 4 => {
+                (u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize, &data[4..])
+            }
+            _ => return parse_err("truncated data element size"),
+        };
+        if data.len() < len {
+            return parse_err("truncated data element");
+        }
+        let (value, rest) = data.split_at(len);
+
+        let text = |value: &[u8]| String::from_utf8_lossy(value).into_owned();
+        let de = match (ty, size_idx) {
+            (TYPE_NIL, 0) => Self::Nil,
+            (TYPE_UINT, 0) => Self::U8(value[0]),
+            (TYPE_UINT, 1) => Self::U16(u16::from_be_bytes(value.try_into().unwrap())),
+            (TYPE_UINT, 2) => Self::U32(u32::from_be_bytes(value.try_into().unwrap())),
+            (TYPE_UINT, 3) => Self::U64(u64::from_be_bytes(value.try_into().unwrap())),
+            (TYPE_UINT, 4) => Self::U128(u128::from_be_bytes(value.try_into().unwrap())),
+            (TYPE_SINT, 0) => Self::I8(value[0] as i8),
+            (TYPE_SINT, 1) => Self::I16(i16::from_be_bytes(value.try_into().unwrap())),
+            (TYPE_SINT, 2) => Self::I32(i32::from_be_bytes(value.try_into().unwrap())),
+            (TYPE_SINT, 3) => Self::I64(i64::from_be_bytes(value.try_into().unwrap())),
+            (TYPE_SINT, 4) => Self::I128(i128::from_be_bytes(value.try_into().unwrap())),
+            (TYPE_UUID, 1) => Self::Uuid(Uuid::from_u16(u16::from_be_bytes(value.try_into().unwrap()))),
+            (TYPE_UUID, 2) => Self::Uuid(Uuid::from_u32(u32::from_be_bytes(value.try_into().unwrap()))),
+            (TYPE_UUID, 4) => Self::Uuid(Uuid::from_slice(value).unwrap()),
+            (TYPE_TEXT, 5..=7) => Self::Text(text(value)),
+            (TYPE_BOOL, 0) => Self::Bool(value[0] != 0),
+            (TYPE_SEQUENCE, 5..=7) | (TYPE_ALTERNATIVE, 5..=7) => {
+                if depth >= MAX_DEPTH {
+                    return parse_err("data elements nested too deeply");
+                }
+                let mut elems = Vec::new();
+                let mut inner = value;
+                while !inner.is_empty() {
+                    let (elem, rest) = Self::decode_nested(inner, depth + 1)?;
+                    elems.push(elem);
+                    inner = rest;
+                }
+                if ty == TYPE_SEQUENCE {
+                    Self::Sequence(elems)
+                } else {
+                    Self::Alternative(elems)
+                }
+            }
+            (TYPE_URL, 5..=7) => Self::Url(text(value)),
+            _ => return parse_err(format!("invalid data element descriptor 0x{:02x}", desc)),
+        };
+
+        Ok((de, rest))
+    }
+
+    /// Returns the value as an unsigned integer, if it is one.
+    pub fn as_uint(&self) -> Option<u128> {
+        match self {
+            Self::U8(v) => Some(*v as _),
+            Self::U16(v) => Some(*v as _),
+            Self::U32(v) => Some(*v as _),
+            Self::U64(v) => Some(*v as _),
+            Self::U128(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns the UUID, if the value is one.
+    pub fn as_uuid(&self) -> Option<Uuid> {
+        match self {
+            Self::Uuid(uuid) => Some(*uuid),
+            _ => None,
+        }
+    }
+
+    /// Returns the text or URL, if the value is one.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Text(s) | Self::Url(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the elements of a sequence or alternative, if the value is one.
+    pub fn as_slice(&self) -> Option<&[DataElement]> {
+        match self {
+            Self::Sequence(elems) | Self::Alternative(elems) => Some(elems),
+            _ => None,
+        }
+    }
+
+    /// Whether the data element contains the specified UUID,
+    /// either directly or within a sequence or alternative.
+    pub fn contains_uuid(&self, uuid: Uuid) -> bool {
+        match self {
+            Self::Uuid(u) => *u == uuid,
+            Self::Sequence(elems) | Self::Alternative(elems) => elems.iter().any(|e| e.contains_uuid(uuid)),
+            _ => false,
+        }
+    }
+}
+
+impl From<Uuid> for DataElement {
+    fn from(uuid: Uuid) -> Self {
+        Self::Uuid(uuid)
+    }
+}
+
+impl From<&str> for DataElement {
+    fn from(s: &str) -> Self {
+        Self::Text(s.to_string())
+    }
+}
+
+impl From<String> for DataElement {
+    fn from(s: String) -> Self {
+        Self::Text(s)
+    }
+}
+
+impl From<bool> for DataElement {
+    fn from(v: bool) -> Self {
+        Self::Bool(v)
+    }
+}
+
+impl From<Vec<DataElement>> for DataElement {
+    fn from(elems: Vec<DataElement>) -> Self {
+        Self::Sequence(elems)
+    }
+}
+
+macro_rules! impl_from_int {
+    ($($ty:ty => $variant:ident),*) => {
+        $(
+            impl From<$ty> for DataElement {
+                fn from(v: $ty) -> Self {
+                    Self::$variant(v)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_int!(u8 => U8, u16 => U16, u32 => U32, u64 => U64, u128 => U128,
+               i8 => I8, i16 => I16, i32 => I32, i64 => I64, i128 => I128);
+
+/// Protocol descriptor of a service record.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProtocolDescriptor {
+    /// Protocol UUID.
+    pub protocol: Uuid,
+    /// Protocol-specific parameters.
+    pub params: Vec<DataElement>,
+}
+
+impl ProtocolDescriptor {
+    /// L2CAP protocol descriptor with optional PSM.
+    pub fn l2cap(psm: Option<u16>) -> Self {
+        Self { protocol: protocol::L2CAP, params: psm.into_iter().map(DataElement::U16).collect() }
+    }
+
+    /// RFCOMM protocol descriptor with channel number.
+    pub fn rfcomm(channel: u8) -> Self {
+        Self { protocol: protocol::RFCOMM, params: vec![DataElement::U8(channel)] }
+    }
+
+    /// OBEX protocol descriptor.
+    pub fn obex() -> Self {
+        Self { protocol: protocol::OBEX, params: Vec::new() }
+    }
+
+    fn to_data_element(&self) -> DataElement {
+        let mut elems = vec![DataElement::Uuid(self.protocol)];
+        elems.extend(self.params.iter().cloned());
+        DataElement::Sequence(elems)
+    }
+
+    fn from_data_element(de: &DataElement) -> Option<Self> {
+        let elems = de.as_slice()?;
+        let (protocol, params) = elems.split_first()?;
+        Some(Self { protocol: protocol.as_uuid()?, params: params.to_vec() })
+    }
+}
+
+/// Language base of human readable attributes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LanguageBase {
+    /// Language code as defined by ISO 639, for example `0x656e` for `en`.
+    pub language: u16,
+    /// Character encoding as IANA MIBenum, for example 106 for UTF-8.
+    pub encoding: u16,
+    /// Base attribute id of human readable attributes in this language.
+    pub base: u16,
+}
+
+impl Default for LanguageBase {
+    /// English in UTF-8 encoding using the primary language base.
+    fn default() -> Self {
+        Self { language: 0x656e, encoding: 106, base: attr::PRIMARY_LANGUAGE_BASE }
+    }
+}
+
+/// Bluetooth profile descriptor of a service record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProfileDescriptor {
+    /// Profile UUID.
+    pub profile: Uuid,
+    /// Profile version with the major version in the upper byte
+    /// and the minor version in the lower byte.
+    pub version: u16,
+}
+
+/// SDP service record.
+///
+/// Attributes are ordered by their id.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ServiceRecord {
+    /// Attributes by attribute id.
+    pub attributes: BTreeMap<u16, DataElement>,
+}
+
+impl ServiceRecord {
+    /// Creates an empty service record.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the value of an attribute.
+    pub fn get(&self, id: u16) -> Option<&DataElement> {
+        self.attributes.get(&id)
+    }
+
+    /// Sets the value of an attribute.
+    pub fn set(&mut self, id: u16, value: impl Into<DataElement>) {
+        self.attributes.insert(id, value.into());
+    }
+
+    /// Removes an attribute.
+    pub fn remove(&mut self, id: u16) -> Option<DataElement> {
+        self.attributes.remove(&id)
+    }
+
+    /// Sets the value of an attribute.
+    pub fn with(mut self, id: u16, value: impl Into<DataElement>) -> Self {
+        self.set(id, value);
+        self
+    }
+
+    /// Sets the service record handle.
+    pub fn with_handle(self, handle: u32) -> Self {
+        self.with(attr::SERVICE_RECORD_HANDLE, handle)
+    }
+
+    /// Sets the service classes this service is an instance of.
+    ///
+    /// The most specific class should be listed first.
+    pub fn with_service_class_ids(self, classes: impl IntoIterator<Item = Uuid>) -> Self {
+        let classes = classes.into_iter().map(DataElement::Uuid).collect::<Vec<_>>();
+        self.with(attr::SERVICE_CLASS_ID_LIST, classes)
+    }
+
+    /// Sets the protocol stack used to access the service.
+    ///
+    /// The lowest layer protocol should be listed first.
+    pub fn with_protocol_descriptors(self, protocols: impl IntoIterator<Item = ProtocolDescriptor>) -> Self {
+        let protocols = protocols.into_iter().map(|p| p.to_data_element()).collect::<Vec<_>>();
+        self.with(attr::PROTOCOL_DESCRIPTOR_LIST, protocols)
+    }
+
+    /// Sets the protocol stack to RFCOMM over L2CAP using the specified channel.
+    pub fn with_rfcomm_channel(self, channel: u8) -> Self {
+        self.with_protocol_descriptors([ProtocolDescriptor::l2cap(None), ProtocolDescriptor::rfcomm(channel)])
+    }
+
+    /// Sets the protocol stack to L2CAP using the specified PSM.
+    pub fn with_l2cap_psm(self, psm: u16) -> Self {
+        self.with_protocol_descriptors([ProtocolDescriptor::l2cap(Some(psm))])
+    }
+
+    /// Sets the browse groups this service belongs to.
+    pub fn with_browse_groups(self, groups: impl IntoIterator<Item = Uuid>) -> Self {
+        let groups = groups.into_iter().map(DataElement::Uuid).collect::<Vec<_>>();
+        self.with(attr::BROWSE_GROUP_LIST, groups)
+    }
+
+    /// Makes the service part of the [public browse group](PUBLIC_BROWSE_GROUP).
+    pub fn with_public_browse_group(self) -> Self {
+        self.with_browse_groups([PUBLIC_BROWSE_GROUP])
+    }
+
+    /// Sets the language bases of human readable attributes.
+    pub fn with_language_bases(self, bases: impl IntoIterator<Item = LanguageBase>) -> Self {
+        let mut elems = Vec::new();
+        for base in bases {
+            elems.push(DataElement::U16(base.language));
+            elems.push(DataElement::U16(base.encoding));
+            elems.push(DataElement::U16(base.base));
+        }
+        self.with(attr::LANGUAGE_BASE_ATTRIBUTE_ID_LIST, elems)
+    }
+
+    /// Sets the Bluetooth profiles this service conforms to.
+    pub fn with_profile_descriptors(self, profiles: impl IntoIterator<Item = ProfileDescriptor>) -> Self {
+        let profiles = profiles
+            .into_iter()
+            .map(|p| DataElement::Sequence(vec![DataElement::Uuid(p.profile), DataElement::U16(p.version)]))
+            .collect::<Vec<_>>();
+        self.with(attr::BLUETOOTH_PROFILE_DESCRIPTOR_LIST, profiles)
+    }
+
+    /// Sets the service name in the primary language.
+    pub fn with_service_name(self, name: impl Into<String>) -> Self {
+        self.with(attr::PRIMARY_LANGUAGE_BASE + attr::SERVICE_NAME_OFFSET, name.into())
+    }
+
+    /// Sets the service description in the primary language.
+    pub fn with_service_description(self, description: impl Into<String>) -> Self {
+        self.with(attr::PRIMARY_LANGUAGE_BASE + attr::SERVICE_DESCRIPTION_OFFSET, description.into())
+    }
+
+    /// Sets the provider name in the primary language.
+    pub fn with_provider_name(self, name: impl Into<String>) -> Self {
+        self.with(attr::PRIMARY_LANGUAGE_BASE + attr::PROVIDER_NAME_OFFSET, name.into())
+    }
+
+    /// Service record handle.
+    pub fn handle(&self) -> Option<u32> {
+        match self.get(attr::SERVICE_RECORD_HANDLE)? {
+            DataElement::U32(handle) => Some(*handle),
+            _ => None,
+        }
+    }
+
+    /// Service classes this service is an instance of.
+    pub fn service_class_ids(&self) -> Vec<Uuid> {
+        self.uuid_list(attr::SERVICE_CLASS_ID_LIST)
+    }
+
+    /// Browse groups this service belongs to.
+    pub fn browse_groups(&self) -> Vec<Uuid> {
+        self.uuid_list(attr::BROWSE_GROUP_LIST)
+    }
+
+    fn uuid_list(&self, id: u16) -> Vec<Uuid> {
+        self.get(id)
+            .and_then(|de| de.as_slice())
+            .map(|elems| elems.iter().filter_map(|e| e.as_uuid()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Protocol stack used to access the service.
+    pub fn protocol_descriptors(&self) -> Vec<ProtocolDescriptor> {
+        self.get(attr::PROTOCOL_DESCRIPTOR_LIST)
+            .and_then(|de| match de {
+                // Alternative protocol stacks; use the first.
+                DataElement::Alternative(alts) => alts.first(),
+                de => Some(de),
+            })
+            .and_then(|de| de.as_slice())
+            .map(|elems| elems.iter().filter_map(ProtocolDescriptor::from_data_element).collect())
+            .unwrap_or_default()
+    }
+
+    /// RFCOMM channel used by the service.
+    pub fn rfcomm_channel(&self) -> Option<u8> {
+        let rfcomm = self.protocol_descriptors().into_iter().find(|p| p.protocol == protocol::RFCOMM)?;
+        match rfcomm.params.first()? {
+            DataElement::U8(channel) => Some(*channel),
+            _ => None,
+        }
+    }
+
+    /// L2CAP PSM used by the service.
+    pub fn l2cap_psm(&self) -> Option<u16> {
+        let l2cap = self.protocol_descriptors().into_iter().find(|p| p.protocol == protocol::L2CAP)?;
+        match l2cap.params.first()? {
+            DataElement::U16(psm) => Some(*psm),
+            _ => None,
+        }
+    }
+
+    /// Language bases of human readable attributes.
+    pub fn language_bases(&self) -> Vec<LanguageBase> {
+        let elems = match self.get(attr::LANGUAGE_BASE_ATTRIBUTE_ID_LIST).and_then(|de| de.as_slice()) {
+            Some(elems) => elems,
+            None => return Vec::new(),
+        };
+        elems
+            .chunks_exact(3)
+            .filter_map(|c| match c {
+                [DataElement::U16(language), DataElement::U16(encoding), DataElement::U16(base)] => {
+                    Some(LanguageBase { language: *language, encoding: *encoding, base: *base })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Bluetooth profiles this service conforms to.
+    pub fn profile_descriptors(&self) -> Vec<ProfileDescriptor> {
+        let elems = match self.get(attr::BLUETOOTH_PROFILE_DESCRIPTOR_LIST).and_then(|de| de.as_slice()) {
+            Some(elems) => elems,
+            None => return Vec::new(),
+        };
+        elems
+            .iter()
+            .filter_map(|de| match de.as_slice()? {
+                [DataElement::Uuid(profile), DataElement::U16(version)] => {
+                    Some(ProfileDescriptor { profile: *profile, version: *version })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn primary_text(&self, offset: u16) -> Option<&str> {
+        let base = self.language_bases().first().map(|b| b.base).unwrap_or(attr::PRIMARY_LANGUAGE_BASE);
+        self.get(base.checked_add(offset)?)?.as_str()
+    }
+
+    /// Service name in the primary language.
+    pub fn service_name(&self) -> Option<&str> {
+        self.primary_text(attr::SERVICE_NAME_OFFSET)
+    }
+
+    /// Service description in the primary language.
+    pub fn service_description(&self) -> Option<&str> {
+        self.primary_text(attr::SERVICE_DESCRIPTION_OFFSET)
+    }
+
+    /// Provider name in the primary language.
+    pub fn provider_name(&self) -> Option<&str> {
+        self.primary_text(attr::PROVIDER_NAME_OFFSET)
+    }
+
+    /// Converts the service record into an attribute list data element,
+    /// which is a sequence of attribute id and value pairs.
+    pub fn to_data_element(&self) -> DataElement {
+        let mut elems = Vec::with_capacity(self.attributes.len() * 2);
+        for (id, value) in &self.attributes {
+            elems.push(DataElement::U16(*id));
+            elems.push(value.clone());
+        }
+        DataElement::Sequence(elems)
+    }
+
+    /// Converts an attribute list data element into a service record.
+    pub fn from_data_element(de: &DataElement) -> Result<Self, ParseError> {
+        let elems = match de {
+            DataElement::Sequence(elems) if elems.len() % 2 == 0 => elems,
+            _ => return parse_err("attribute list must be a sequence of attribute id and value pairs"),
+        };
+        let mut attributes = BTreeMap::new();
+        for pair in elems.chunks_exact(2) {
+            match &pair[0] {
+                DataElement::U16(id) => {
+                    attributes.insert(*id, pair[1].clone());
+                }
+                _ => return parse_err("attribute id must be an unsigned 16-bit integer"),
+            }
+        }
+        Ok(Self { attributes })
+    }
+
+    /// Encodes the service record into the binary attribute list format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_data_element().to_bytes()
+    }
+
+    /// Decodes a service record from the binary attribute list format.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        Self::from_data_element(&DataElement::from_bytes(data)?)
+    }
+
+    /// Serializes the service record into the XML format used by BlueZ.
+    pub fn to_xml(&self) -> String {
+        xml::to_xml(self)
+    }
+
+    /// Parses a service record from the XML format used by BlueZ.
+    pub fn from_xml(xml: &str) -> Result<Self, ParseError> {
+        xml::from_xml(xml)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> String {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sdp/spp.xml");
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    fn nested(depth: usize) -> DataElement {
+        (0..depth).fold(DataElement::Nil, |de, _| DataElement::Sequence(vec![de]))
+    }
+
+    #[test]
+    fn parse_bluez_record() {
+        let record = ServiceRecord::from_xml(&fixture()).unwrap();
+        assert_eq!(record.handle(), Some(0x00010005));
+        assert_eq!(record.service_class_ids(), vec![Uuid::from_u16(0x1101)]);
+        assert_eq!(record.rfcomm_channel(), Some(3));
+        assert_eq!(record.browse_groups(), vec![PUBLIC_BROWSE_GROUP]);
+        assert_eq!(record.language_bases(), vec![LanguageBase::default()]);
+        assert_eq!(
+            record.profile_descriptors(),
+            vec![ProfileDescriptor { profile: Uuid::from_u16(0x1101), version: 0x0102 }]
+        );
+        assert_eq!(record.service_name(), Some("Serial Port"));
+        assert_eq!(
+            record.get(attr::SERVICE_ID),
+            Some(&DataElement::Uuid("a5e3a3c4-5c9b-4b6e-9d3f-1c2b3a4d5e6f".parse().unwrap()))
+        );
+        assert_eq!(record.get(0x0200), Some(&DataElement::U64(0x0123456789abcdef)));
+        assert_eq!(record.get(0x0201), Some(&DataElement::U128(0x00112233445566778899aabbccddeeff)));
+        assert_eq!(record.get(0x0202), Some(&DataElement::I128(-2)));
+        assert_eq!(record.get(0x0203), Some(&DataElement::I8(-5)));
+        assert_eq!(record.get(0x0204), Some(&DataElement::Text("ABC\n\0".to_string())));
+        assert_eq!(record.get(0x0205), Some(&DataElement::Bool(true)));
+    }
+
+    #[test]
+    fn xml_round_trip() {
+        let record = ServiceRecord::from_xml(&fixture()).unwrap();
+        let xml = record.to_xml();
+        assert!(xml.contains(r#"<uint128 value="00112233445566778899aabbccddeeff" />"#));
+        assert!(xml.contains(r#"<int128 value="fffffffffffffffffffffffffffffffe" />"#));
+        assert_eq!(ServiceRecord::from_xml(&xml).unwrap(), record);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let record = ServiceRecord::from_xml(&fixture()).unwrap();
+        let data = record.to_bytes();
+        assert_eq!(ServiceRecord::from_bytes(&data).unwrap(), record);
+    }
+
+    #[test]
+    fn binary_encoding() {
+        let de = DataElement::Sequence(vec![
+            DataElement::Uuid(Uuid::from_u16(0x1101)),
+            DataElement::U16(0x0102),
+            DataElement::Text("SP".to_string()),
+        ]);
+        let data = [0x35, 0x0a, 0x19, 0x11, 0x01, 0x09, 0x01, 0x02, 0x25, 0x02, b'S', b'P'];
+        assert_eq!(de.to_bytes(), data);
+        assert_eq!(DataElement::from_bytes(&data).unwrap(), de);
+    }
+
+    #[test]
+    fn reject_invalid_128_bit_xml() {
+        for value in ["0x00112233445566778899aabbccddeeff", "112233", "-1"] {
+            let xml =
+                format!(r#"<record><attribute id="0x0201"><uint128 value="{}" /></attribute></record>"#, value);
+            assert!(ServiceRecord::from_xml(&xml).is_err(), "accepted {}", value);
+        }
+    }
+
+    #[test]
+    fn reject_deep_nesting() {
+        let ok = nested(MAX_DEPTH);
+        assert_eq!(DataElement::from_bytes(&ok.to_bytes()).unwrap(), ok);
+        assert!(DataElement::from_bytes(&nested(MAX_DEPTH + 1).to_bytes()).is_err());
+
+        let record = ServiceRecord::new().with(0x0200, nested(MAX_DEPTH + 1));
+        assert!(ServiceRecord::from_xml(&record.to_xml()).is_err());
+        let record = ServiceRecord::new().with(0x0200, ok);
+        assert_eq!(ServiceRecord::from_xml(&record.to_xml()).unwrap(), record);
+    }
+}
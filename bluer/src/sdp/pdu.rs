@@ -0,0 +1,105 @@
+//! SDP protocol data units.
+
+use std::ops::RangeInclusive;
+use uuid::Uuid;
+
+use super::{parse_err, DataElement, ParseError};
+
+pub(crate) const ERROR_RSP: u8 = 0x01;
+pub(crate) const SERVICE_SEARCH_REQ: u8 = 0x02;
+pub(crate) const SERVICE_SEARCH_RSP: u8 = 0x03;
+pub(crate) const SERVICE_ATTR_REQ: u8 = 0x04;
+pub(crate) const SERVICE_ATTR_RSP: u8 = 0x05;
+pub(crate) const SERVICE_SEARCH_ATTR_REQ: u8 = 0x06;
+pub(crate) const SERVICE_SEARCH_ATTR_RSP: u8 = 0x07;
+
+/// Size of PDU header consisting of PDU id, transaction id and parameter length.
+pub(crate) const HDR_SIZE: usize = 5;
+
+/// Maximum number of UUIDs in a service search pattern.
+pub(crate) const MAX_SEARCH_PATTERN_LEN: usize = 12;
+
+/// Maximum length of continuation state information.
+pub(crate) const MAX_CONT_STATE_LEN: usize = 16;
+
+/// Encodes a PDU with the specified id, transaction id and parameters.
+pub(crate) fn encode(pdu_id: u8, tid: u16, params: &[u8]) -> Vec<u8> {
+    let mut pdu = Vec::with_capacity(HDR_SIZE + params.len());
+    pdu.push(pdu_id);
+    pdu.extend_from_slice(&tid.to_be_bytes());
+    pdu.extend_from_slice(&(params.len() as u16).to_be_bytes());
+    pdu.extend_from_slice(params);
+    pdu
+}
+
+/// Decodes a PDU header into PDU id, transaction id and parameter length.
+pub(crate) fn decode_header(hdr: &[u8; HDR_SIZE]) -> (u8, u16, usize) {
+    (hdr[0], u16::from_be_bytes([hdr[1], hdr[2]]), u16::from_be_bytes([hdr[3], hdr[4]]) as usize)
+}
+
+/// Encodes continuation state.
+pub(crate) fn encode_cont_state(buf: &mut Vec<u8>, cont: &[u8]) {
+    buf.push(cont.len() as u8);
+    buf.extend_from_slice(cont);
+}
+
+/// Decodes continuation state, which must be located at the end of the parameters.
+pub(crate) fn decode_cont_state(data: &[u8]) -> Result<Vec<u8>, ParseError> {
+    match data.split_first() {
+        Some((&len, cont)) if len as usize == cont.len() && cont.len() <= MAX_CONT_STATE_LEN => Ok(cont.to_vec()),
+        _ => parse_err("invalid continuation state"),
+    }
+}
+
+/// Encodes a service search pattern.
+pub(crate) fn encode_search_pattern(buf: &mut Vec<u8>, pattern: &[Uuid]) {
+    DataElement::Sequence(pattern.iter().map(|uuid| DataElement::Uuid(*uuid)).collect()).encode(buf);
+}
+
+/// Decodes a service search pattern and returns the remaining data.
+pub(crate) fn decode_search_pattern(data: &[u8]) -> Result<(Vec<Uuid>, &[u8]), ParseError> {
+    let (de, rest) = DataElement::decode(data)?;
+    let pattern = match &de {
+        DataElement::Sequence(elems) => elems.iter().map(|e| e.as_uuid()).collect::<Option<Vec<_>>>(),
+        _ => None,
+    };
+    match pattern {
+        Some(pattern) if !pattern.is_empty() && pattern.len() <= MAX_SEARCH_PATTERN_LEN => Ok((pattern, rest)),
+        _ => parse_err("invalid service search pattern"),
+    }
+}
+
+/// Encodes an attribute id list.
+pub(crate) fn encode_attr_ranges(buf: &mut Vec<u8>, ranges: &[RangeInclusive<u16>]) {
+    let elems = ranges
+        .iter()
+        .map(|range| {
+            if range.start() == range.end() {
+                DataElement::U16(*range.start())
+            } else {
+                DataElement::U32((*range.start() as u32) << 16 | *range.end() as u32)
+            }
+        })
+        .collect();
+    DataElement::Sequence(elems).encode(buf);
+}
+
+/// Decodes an attribute id list and returns the remaining data.
+pub(crate) fn decode_attr_ranges(data: &[u8]) -> Result<(Vec<RangeInclusive<u16>>, &[u8]), ParseError> {
+    let (de, rest) = DataElement::decode(data)?;
+    let ranges = match &de {
+        DataElement::Sequence(elems) if !elems.is_empty() => elems
+            .iter()
+            .map(|e| match e {
+                DataElement::U16(id) => Some(*id..=*id),
+                DataElement::U32(range) => Some((*range >> 16) as u16..=*range as u16),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>(),
+        _ => None,
+    };
+    match ranges {
+        Some(ranges) => Ok((ranges, rest)),
+        None => parse_err("invalid attribute id list"),
+    }
+}
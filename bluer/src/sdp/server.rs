@@ -0,0 +1,542 @@
+//! In-process SDP server.
+
+use futures::{pin_mut, select, stream::FuturesUnordered, FutureExt, StreamExt};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::Result,
+    ops::RangeInclusive,
+    sync::{Arc, Mutex, Weak},
+};
+use uuid::Uuid;
+
+use super::{attr, pdu, DataElement, ErrorCode, ServiceRecord, PSM};
+use crate::{
+    l2cap::{SeqPacket, SeqPacketListener, SocketAddr},
+    Address, AddressType, UuidExt,
+};
+
+/// Service record handle of the SDP server itself.
+const SERVER_RECORD_HANDLE: u32 = 0x0000_0000;
+
+/// First service record handle assigned to registered records.
+const FIRST_RECORD_HANDLE: u32 = 0x0001_0000;
+
+/// Version number list attribute of the SDP server service record.
+const VERSION_NUMBER_LIST: u16 = 0x0200;
+
+/// Maximum length of continuation state used by this server.
+const CONT_STATE_LEN: usize = 4;
+
+/// Size of the parameters of a response that are not payload.
+const RSP_OVERHEAD: usize = pdu::HDR_SIZE + 4 + 1 + CONT_STATE_LEN;
+
+#[derive(Debug)]
+struct Records {
+    next_handle: u32,
+    records: BTreeMap<u32, ServiceRecord>,
+}
+
+impl Records {
+    fn new() -> Self {
+        let server_record = ServiceRecord::new()
+            .with_handle(SERVER_RECORD_HANDLE)
+            .with_service_class_ids([Uuid::from_u16(0x1000)])
+            .with_public_browse_group()
+            .with(VERSION_NUMBER_LIST, vec![DataElement::U16(0x0100)]);
+        let mut records = BTreeMap::new();
+        records.insert(SERVER_RECORD_HANDLE, server_record);
+        Self { next_handle: FIRST_RECORD_HANDLE, records }
+    }
+
+    /// Handles of records containing all UUIDs of the pattern.
+    fn search(&self, pattern: &[Uuid]) -> Vec<u32> {
+        self.records
+            .iter()
+            .filter(|(_, record)| {
+                pattern.iter().all(|uuid| record.attributes.values().any(|value| value.contains_uuid(*uuid)))
+            })
+            .map(|(handle, _)| *handle)
+            .collect()
+    }
+
+    /// Attribute list of the specified record, containing only attributes within the specified ranges.
+    fn attributes(&self, handle: u32, ranges: &[RangeInclusive<u16>]) -> Option<DataElement> {
+        let record = self.records.get(&handle)?;
+        let mut elems = Vec::new();
+        for (id, value) in &record.attributes {
+            if ranges.iter().any(|range| range.contains(id)) {
+                elems.push(DataElement::U16(*id));
+                elems.push(value.clone());
+            }
+        }
+        Some(DataElement::Sequence(elems))
+    }
+}
+
+/// In-process SDP server.
+///
+/// This allows classic Bluetooth (BR/EDR) devices to discover services provided
+/// over [L2CAP](crate::l2cap) and [RFCOMM](crate::rfcomm) sockets when
+/// the Bluetooth daemon is not running.
+/// The Bluetooth daemon provides its own SDP server on the same PSM, thus
+/// both cannot be run at the same time.
+///
+/// Register service records using [register](Self::register) and
+/// call [serve](Self::serve) to answer requests.
+/// Listening on the SDP PSM requires the `CAP_NET_BIND_SERVICE` capability.
+pub struct Server {
+    listener: SeqPacketListener,
+    records: Arc<Mutex<Records>>,
+}
+
+impl fmt::Debug for Server {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Server").field("listener", &self.listener).finish()
+    }
+}
+
+impl Server {
+    /// Creates an SDP server listening on the specified local adapter address.
+    ///
+    /// Specify [Address::any] to listen on all local adapters.
+    pub async fn bind(addr: Address) -> Result<Self> {
+        let listener = SeqPacketListener::bind(SocketAddr::new(addr, AddressType::BrEdr, PSM)).await?;
+        Ok(Self::from_listener(listener))
+    }
+
+    /// Creates an SDP server using a listener bound to the SDP PSM.
+    pub fn from_listener(listener: SeqPacketListener) -> Self {
+        Self { listener, records: Arc::new(Mutex::new(Records::new())) }
+    }
+
+    /// Registers a service record.
+    ///
+    /// A service record handle is assigned and stored in the record.
+    /// The record is unregistered when the returned handle is dropped.
+    pub fn register(&self, mut record: ServiceRecord) -> RecordHandle {
+        let mut records = self.records.lock().unwrap();
+        let handle = records.next_handle;
+        records.next_handle = records.next_handle.wrapping_add(1).max(FIRST_RECORD_HANDLE);
+        record.set(attr::SERVICE_RECORD_HANDLE, handle);
+        records.records.insert(handle, record);
+        log::trace!("Registered SDP record 0x{:08x}", handle);
+        RecordHandle { handle, records: Arc::downgrade(&self.records) }
+    }
+
+    /// Currently registered service records, including the record of the SDP server itself.
+    pub fn records(&self) -> Vec<ServiceRecord> {
+        self.records.lock().unwrap().records.values().cloned().collect()
+    }
+
+    /// Accepts connections and answers requests.
+    ///
+    /// This runs until accepting a connection fails.
+    /// Errors on individual connections are logged and do not stop the server.
+    pub async fn serve(&self) -> Result<()> {
+        let mut conns = FuturesUnordered::new();
+        loop {
+            let accept = self.listener.accept().fuse();
+            pin_mut!(accept);
+            select! {
+                res = accept => {
+                    let (conn, peer) = res?;
+                    log::trace!("SDP connection from {}", peer.addr);
+                    let records = self.records.clone();
+                    conns.push(async move {
+                        if let Err(err) = serve_connection(&records, conn).await {
+                            log::debug!("SDP connection from {} failed: {}", peer.addr, err);
+                        }
+                    });
+                },
+                () = conns.select_next_some() => (),
+            }
+        }
+    }
+}
+
+/// Handle of a service record registered with an SDP [Server].
+///
+/// Drop to unregister the service record.
+pub struct RecordHandle {
+    handle: u32,
+    records: Weak<Mutex<Records>>,
+}
+
+impl RecordHandle {
+    /// Assigned service record handle.
+    pub fn handle(&self) -> u32 {
+        self.handle
+    }
+}
+
+impl fmt::Debug for RecordHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RecordHandle {{ handle: 0x{:08x} }}", self.handle)
+    }
+}
+
+impl Drop for RecordHandle {
+    fn drop(&mut self) {
+        if let Some(records) = self.records.upgrade() {
+            records.lock().unwrap().records.remove(&self.handle);
+            log::trace!("Unregistered SDP record 0x{:08x}", self.handle);
+        }
+    }
+}
+
+async fn serve_connection(records: &Mutex<Records>, conn: SeqPacket) -> Result<()> {
+    let send_mtu = conn.send_mtu()?;
+    let mut buf = vec![0; conn.recv_mtu()?];
+    loop {
+        let n = conn.recv(&mut buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        let rsp = handle_request(&records.lock().unwrap(), &buf[..n], send_mtu);
+        conn.send(&rsp).await?;
+    }
+}
+
+/// Handles a request PDU and returns the response PDU.
+fn handle_request(records: &Records, req: &[u8], send_mtu: usize) -> Vec<u8> {
+    let (tid, res) = match req.get(..pdu::HDR_SIZE) {
+        Some(hdr) => {
+            let (pdu_id, tid, len) = pdu::decode_header(hdr.try_into().unwrap());
+            let params = &req[pdu::HDR_SIZE..];
+            let res = if params.len() != len {
+                Err(ErrorCode::InvalidPduSize)
+            } else {
+                let max_payload = send_mtu.saturating_sub(RSP_OVERHEAD).max(1);
+                match pdu_id {
+                    pdu::SERVICE_SEARCH_REQ => service_search(records, params, max_payload),
+                    pdu::SERVICE_ATTR_REQ => service_attributes(records, params, max_payload),
+                    pdu::SERVICE_SEARCH_ATTR_REQ => service_search_attributes(records, params, max_payload),
+                    _ => Err(ErrorCode::InvalidSyntax),
+                }
+            };
+            (tid, res)
+        }
+        None => (0, Err(ErrorCode::InvalidPduSize)),
+    };
+
+    match res {
+        Ok((pdu_id, params)) => pdu::encode(pdu_id, tid, &params),
+        Err(code) => {
+            log::debug!("SDP request failed: {}", code);
+            pdu::encode(pdu::ERROR_RSP, tid, &u16::from(code).to_be_bytes())
+        }
+    }
+}
+
+type PduResult = std::result::Result<(u8, Vec<u8>), ErrorCode>;
+
+fn read_u16(data: &[u8]) -> std::result::Result<(u16, &[u8]), ErrorCode> {
+    match data {
+        [a, b, rest @ ..] => Ok((u16::from_be_bytes([*a, *b]), rest)),
+        _ => Err(ErrorCode::InvalidSyntax),
+    }
+}
+
+/// Decodes a service search pattern.
+///
+/// An empty pattern would match every record and is rejected as invalid syntax.
+fn read_search_pattern(data: &[u8]) -> std::result::Result<(Vec<Uuid>, &[u8]), ErrorCode> {
+    match pdu::decode_search_pattern(data) {
+        Ok((pattern, rest)) if !pattern.is_empty() => Ok((pattern, rest)),
+        _ => Err(ErrorCode::InvalidSyntax),
+    }
+}
+
+/// Decodes the continuation state sent by a client into an offset.
+fn read_offset(data: &[u8]) -> std::result::Result<usize, ErrorCode> {
+    let cont = pdu::decode_cont_state(data).map_err(|_| ErrorCode::InvalidSyntax)?;
+    match cont.len() {
+        0 => Ok(0),
+        CONT_STATE_LEN => Ok(u32::from_be_bytes(cont.try_into().unwrap()) as usize),
+        _ => Err(ErrorCode::InvalidContinuationState),
+    }
+}
+
+/// Encodes the continuation state for the specified offset, if more data is available.
+fn write_offset(buf: &mut Vec<u8>, offset: usize, total: usize) {
+    if offset < total {
+        pdu::encode_cont_state(buf, &(offset as u32).to_be_bytes());
+    } else {
+        pdu::encode_cont_state(buf, &[]);
+    }
+}
+
+fn service_search(records: &Records, params: &[u8], max_payload: usize) -> PduResult {
+    let (pattern, rest) = read_search_pattern(params)?;
+    let (max_count, rest) = read_u16(rest)?;
+    let offset = read_offset(rest)?;
+
+    let mut handles = records.search(&pattern);
+    handles.truncate(max_count as usize);
+    if offset > handles.len() {
+        return Err(ErrorCode::InvalidContinuationState);
+    }
+    let end = handles.len().min(offset + max_payload / 4);
+
+    let mut rsp = Vec::new();
+    rsp.extend_from_slice(&(handles.len() as u16).to_be_bytes());
+    rsp.extend_from_slice(&((end - offset) as u16).to_be_bytes());
+    for handle in &handles[offset..end] {
+        rsp.extend_from_slice(&handle.to_be_bytes());
+    }
+    write_offset(&mut rsp, end, handles.len());
+    Ok((pdu::SERVICE_SEARCH_RSP, rsp))
+}
+
+/// Builds a response containing a fragment of the attribute list bytes.
+fn attribute_response(pdu_id: u8, data: &[u8], max_bytes: u16, offset: usize, max_payload: usize) -> PduResult {
+    if offset > data.len() {
+        return Err(ErrorCode::InvalidContinuationState);
+    }
+    let end = data.len().min(offset + max_payload.min(max_bytes.max(1) as usize));
+
+    let mut rsp = Vec::new();
+    rsp.extend_from_slice(&((end - offset) as u16).to_be_bytes());
+    rsp.extend_from_slice(&data[offset..end]);
+    write_offset(&mut rsp, end, data.len());
+    Ok((pdu_id, rsp))
+}
+
+fn service_attributes(records: &Records, params: &[u8], max_payload: usize) -> PduResult {
+    if params.len() < 4 {
+        return Err(ErrorCode::InvalidSyntax);
+    }
+    let handle = u32::from_be_bytes(params[..4].try_into().unwrap());
+    let (max_bytes, rest) = read_u16(&params[4..])?;
+    let (ranges, rest) = pdu::decode_attr_ranges(rest).map_err(|_| ErrorCode::InvalidSyntax)?;
+    let offset = read_offset(rest)?;
+
+    let attrs = records.attributes(handle, &ranges).ok_or(ErrorCode::InvalidRecordHandle)?;
+    attribute_response(pdu::SERVICE_ATTR_RSP, &attrs.to_bytes(), max_bytes, offset, max_payload)
+}
+
+fn service_search_attributes(records: &Records, params: &[u8], max_payload: usize) -> PduResult {
+    let (pattern, rest) = read_search_pattern(params)?;
+    let (max_bytes, rest) = read_u16(rest)?;
+    let (ranges, rest) = pdu::decode_attr_ranges(rest).map_err(|_| ErrorCode::InvalidSyntax)?;
+    let offset = read_offset(rest)?;
+
+    let lists = records
+        .search(&pattern)
+        .into_iter()
+        .filter_map(|handle| records.attributes(handle, &ranges))
+        .filter(|attrs| !attrs.as_slice().unwrap().is_empty())
+        .collect();
+    let data = DataElement::Sequence(lists).to_bytes();
+    attribute_response(pdu::SERVICE_SEARCH_ATTR_RSP, &data, max_bytes, offset, max_payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdp::ALL_ATTRIBUTES;
+
+    const SERVICE: Uuid = Uuid::from_u128(1);
+    const MTU: usize = 672;
+
+    /// Records of the SDP server and three services of the same class.
+    fn records() -> Records {
+        let mut records = Records::new();
+        for handle in FIRST_RECORD_HANDLE..FIRST_RECORD_HANDLE + 3 {
+            let record = ServiceRecord::new()
+                .with_handle(handle)
+                .with_service_class_ids([SERVICE])
+                .with(0x0100, format!("Service {}", handle));
+            records.records.insert(handle, record);
+        }
+        records
+    }
+
+    fn search_req(pattern: &[Uuid], max_count: u16, cont: &[u8]) -> Vec<u8> {
+        let mut params = Vec::new();
+        pdu::encode_search_pattern(&mut params, pattern);
+        params.extend_from_slice(&max_count.to_be_bytes());
+        pdu::encode_cont_state(&mut params, cont);
+        pdu::encode(pdu::SERVICE_SEARCH_REQ, 1, &params)
+    }
+
+    fn attr_req(handle: u32, ranges: &[RangeInclusive<u16>], cont: &[u8]) -> Vec<u8> {
+        let mut params = handle.to_be_bytes().to_vec();
+        params.extend_from_slice(&u16::MAX.to_be_bytes());
+        pdu::encode_attr_ranges(&mut params, ranges);
+        pdu::encode_cont_state(&mut params, cont);
+        pdu::encode(pdu::SERVICE_ATTR_REQ, 1, &params)
+    }
+
+    fn search_attr_req(pattern: &[Uuid], ranges: &[RangeInclusive<u16>], cont: &[u8]) -> Vec<u8> {
+        let mut params = Vec::new();
+        pdu::encode_search_pattern(&mut params, pattern);
+        params.extend_from_slice(&u16::MAX.to_be_bytes());
+        pdu::encode_attr_ranges(&mut params, ranges);
+        pdu::encode_cont_state(&mut params, cont);
+        pdu::encode(pdu::SERVICE_SEARCH_ATTR_REQ, 1, &params)
+    }
+
+    /// Checks the header of a response and returns its parameters.
+    fn response(rsp: &[u8], rsp_id: u8) -> &[u8] {
+        let (pdu_id, tid, len) = pdu::decode_header(rsp[..pdu::HDR_SIZE].try_into().unwrap());
+        assert_eq!(pdu_id, rsp_id);
+        assert_eq!(tid, 1);
+        assert_eq!(len, rsp.len() - pdu::HDR_SIZE);
+        &rsp[pdu::HDR_SIZE..]
+    }
+
+    fn error_code(rsp: &[u8]) -> ErrorCode {
+        let params = response(rsp, pdu::ERROR_RSP);
+        ErrorCode::from(u16::from_be_bytes(params.try_into().unwrap()))
+    }
+
+    /// Splits the parameters of an attribute response into data and continuation state.
+    fn attr_rsp(params: &[u8]) -> (&[u8], Vec<u8>) {
+        let (len, rest) = read_u16(params).unwrap();
+        let (data, rest) = rest.split_at(len as usize);
+        (data, pdu::decode_cont_state(rest).unwrap())
+    }
+
+    #[test]
+    fn search() {
+        let records = records();
+        let rsp = handle_request(&records, &search_req(&[SERVICE], 0xffff, &[]), MTU);
+        let params = response(&rsp, pdu::SERVICE_SEARCH_RSP);
+        let mut expected = vec![0, 3, 0, 3];
+        for handle in FIRST_RECORD_HANDLE..FIRST_RECORD_HANDLE + 3 {
+            expected.extend_from_slice(&handle.to_be_bytes());
+        }
+        expected.push(0);
+        assert_eq!(params, expected);
+
+        let rsp = handle_request(&records, &search_req(&[Uuid::from_u16(0x1000)], 0xffff, &[]), MTU);
+        assert_eq!(response(&rsp, pdu::SERVICE_SEARCH_RSP), [0, 1, 0, 1, 0, 0, 0, 0, 0]);
+
+        let rsp = handle_request(&records, &search_req(&[SERVICE], 2, &[]), MTU);
+        assert_eq!(&response(&rsp, pdu::SERVICE_SEARCH_RSP)[..4], [0, 2, 0, 2]);
+    }
+
+    #[test]
+    fn search_continuation() {
+        let records = records();
+        let mtu = RSP_OVERHEAD + 8;
+        let mut handles = Vec::new();
+        let mut cont = Vec::new();
+        loop {
+            let rsp = handle_request(&records, &search_req(&[SERVICE], 0xffff, &cont), mtu);
+            assert!(rsp.len() <= mtu);
+            let params = response(&rsp, pdu::SERVICE_SEARCH_RSP);
+            let (total, rest) = read_u16(params).unwrap();
+            let (count, rest) = read_u16(rest).unwrap();
+            assert_eq!(total, 3);
+            let (data, rest) = rest.split_at(count as usize * 4);
+            handles.extend(data.chunks(4).map(|h| u32::from_be_bytes(h.try_into().unwrap())));
+            cont = pdu::decode_cont_state(rest).unwrap();
+            if cont.is_empty() {
+                break;
+            }
+        }
+        assert_eq!(handles, records.search(&[SERVICE]));
+    }
+
+    #[test]
+    fn attribute_ranges() {
+        let records = records();
+        let handle = FIRST_RECORD_HANDLE;
+
+        let rsp = handle_request(&records, &attr_req(handle, &[0x0100..=0x0100], &[]), MTU);
+        let (data, cont) = attr_rsp(response(&rsp, pdu::SERVICE_ATTR_RSP));
+        assert!(cont.is_empty());
+        let expected = DataElement::Sequence(vec![
+            DataElement::U16(0x0100),
+            DataElement::from(format!("Service {}", handle)),
+        ]);
+        assert_eq!(data, expected.to_bytes());
+
+        let rsp = handle_request(&records, &attr_req(handle, &[0x0000..=0x0001], &[]), MTU);
+        let (data, _) = attr_rsp(response(&rsp, pdu::SERVICE_ATTR_RSP));
+        let (attrs, _) = DataElement::decode(data).unwrap();
+        let ids: Vec<_> = attrs.as_slice().unwrap().iter().step_by(2).cloned().collect();
+        assert_eq!(
+            ids,
+            [DataElement::U16(attr::SERVICE_RECORD_HANDLE), DataElement::U16(attr::SERVICE_CLASS_ID_LIST)]
+        );
+
+        let rsp = handle_request(&records, &attr_req(handle, &[0x0200..=0x0300], &[]), MTU);
+        let (data, _) = attr_rsp(response(&rsp, pdu::SERVICE_ATTR_RSP));
+        assert_eq!(data, DataElement::Sequence(Vec::new()).to_bytes());
+    }
+
+    #[test]
+    fn search_attributes_continuation() {
+        let records = records();
+        let ranges = [ALL_ATTRIBUTES];
+        let expected = {
+            let lists = records
+                .search(&[SERVICE])
+                .into_iter()
+                .map(|handle| records.attributes(handle, &ranges).unwrap())
+                .collect();
+            DataElement::Sequence(lists).to_bytes()
+        };
+
+        let mtu = RSP_OVERHEAD + 16;
+        let mut data = Vec::new();
+        let mut cont = Vec::new();
+        let mut rounds = 0;
+        loop {
+            let rsp = handle_request(&records, &search_attr_req(&[SERVICE], &ranges, &cont), mtu);
+            assert!(rsp.len() <= mtu);
+            let (fragment, next) = attr_rsp(response(&rsp, pdu::SERVICE_SEARCH_ATTR_RSP));
+            data.extend_from_slice(fragment);
+            cont = next;
+            rounds += 1;
+            if cont.is_empty() {
+                break;
+            }
+        }
+        assert!(rounds > 1);
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn errors() {
+        let records = records();
+
+        let mut empty = Vec::new();
+        DataElement::Sequence(Vec::new()).encode(&mut empty);
+        empty.extend_from_slice(&0xffffu16.to_be_bytes());
+        pdu::encode_cont_state(&mut empty, &[]);
+        let req = pdu::encode(pdu::SERVICE_SEARCH_REQ, 1, &empty);
+        assert_eq!(error_code(&handle_request(&records, &req, MTU)), ErrorCode::InvalidSyntax);
+
+        let mut empty = Vec::new();
+        DataElement::Sequence(Vec::new()).encode(&mut empty);
+        empty.extend_from_slice(&0xffffu16.to_be_bytes());
+        pdu::encode_attr_ranges(&mut empty, &[ALL_ATTRIBUTES]);
+        pdu::encode_cont_state(&mut empty, &[]);
+        let req = pdu::encode(pdu::SERVICE_SEARCH_ATTR_REQ, 1, &empty);
+        assert_eq!(error_code(&handle_request(&records, &req, MTU)), ErrorCode::InvalidSyntax);
+
+        let req = attr_req(0x1234_5678, &[ALL_ATTRIBUTES], &[]);
+        assert_eq!(error_code(&handle_request(&records, &req, MTU)), ErrorCode::InvalidRecordHandle);
+
+        let mut req = search_req(&[SERVICE], 0xffff, &[]);
+        req.push(0);
+        assert_eq!(error_code(&handle_request(&records, &req, MTU)), ErrorCode::InvalidPduSize);
+        let rsp = handle_request(&records, &[pdu::SERVICE_SEARCH_REQ], MTU);
+        assert_eq!(rsp, pdu::encode(pdu::ERROR_RSP, 0, &u16::from(ErrorCode::InvalidPduSize).to_be_bytes()));
+
+        let req = search_req(&[SERVICE], 0xffff, &[0, 0]);
+        assert_eq!(error_code(&handle_request(&records, &req, MTU)), ErrorCode::InvalidContinuationState);
+        let req = search_req(&[SERVICE], 0xffff, &100u32.to_be_bytes());
+        assert_eq!(error_code(&handle_request(&records, &req, MTU)), ErrorCode::InvalidContinuationState);
+        let req = attr_req(FIRST_RECORD_HANDLE, &[ALL_ATTRIBUTES], &0xffffu32.to_be_bytes());
+        assert_eq!(error_code(&handle_request(&records, &req, MTU)), ErrorCode::InvalidContinuationState);
+
+        let req = pdu::encode(0x7f, 1, &[]);
+        assert_eq!(error_code(&handle_request(&records, &req, MTU)), ErrorCode::InvalidSyntax);
+    }
+}
@@ -0,0 +1,375 @@
+//! XML representation of service records as used by BlueZ.
+
+use std::fmt::Write;
+use uuid::Uuid;
+
+use super::{parse_err, DataElement, ParseError, ServiceRecord, MAX_DEPTH};
+use crate::UuidExt;
+
+pub(crate) fn to_xml(record: &ServiceRecord) -> String {
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8" ?>"#).unwrap();
+    writeln!(xml, "<record>").unwrap();
+    for (id, value) in &record.attributes {
+        writeln!(xml, r#"  <attribute id="0x{:04x}">"#, id).unwrap();
+        write_element(&mut xml, value, 2);
+        writeln!(xml, "  </attribute>").unwrap();
+    }
+    writeln!(xml, "</record>").unwrap();
+    xml
+}
+
+fn write_element(xml: &mut String, de: &DataElement, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let (name, value) = match de {
+        DataElement::Nil => ("nil", None),
+        DataElement::U8(v) => ("uint8", Some(format!("0x{:02x}", v))),
+        DataElement::U16(v) => ("uint16", Some(format!("0x{:04x}", v))),
+        DataElement::U32(v) => ("uint32", Some(format!("0x{:08x}", v))),
+        DataElement::U64(v) => ("uint64", Some(format!("0x{:016x}", v))),
+        DataElement::U128(v) => ("uint128", Some(format!("{:032x}", v))),
+        DataElement::I8(v) => ("int8", Some(v.to_string())),
+        DataElement::I16(v) => ("int16", Some(v.to_string())),
+        DataElement::I32(v) => ("int32", Some(v.to_string())),
+        DataElement::I64(v) => ("int64", Some(v.to_string())),
+        DataElement::I128(v) => ("int128", Some(format!("{:032x}", v))),
+        DataElement::Uuid(uuid) => match (uuid.as_u16(), uuid.as_u32()) {
+            (Some(v), _) => ("uuid", Some(format!("0x{:04x}", v))),
+            (None, Some(v)) => ("uuid", Some(format!("0x{:08x}", v))),
+            (None, None) => ("uuid", Some(uuid.to_string())),
+        },
+        DataElement::Text(s) if s.chars().any(|c| c.is_control() && c != '\n' && c != '\t') => {
+            let hex = s.bytes().map(|b| format!("{:02x}", b)).collect::<String>();
+            writeln!(xml, r#"{}<text encoding="hex" value="{}" />"#, indent, hex).unwrap();
+            return;
+        }
+        DataElement::Text(s) => ("text", Some(escape(s))),
+        DataElement::Bool(v) => ("boolean", Some(v.to_string())),
+        DataElement::Url(s) => ("url", Some(escape(s))),
+        DataElement::Sequence(elems) | DataElement::Alternative(elems) => {
+            let name = if matches!(de, DataElement::Sequence(_)) { "sequence" } else { "alternate" };
+            writeln!(xml, "{}<{}>", indent, name).unwrap();
+            for elem in elems {
+                write_element(xml, elem, depth + 1);
+            }
+            writeln!(xml, "{}</{}>", indent, name).unwrap();
+            return;
+        }
+    };
+    match value {
+        Some(value) => writeln!(xml, r#"{}<{} value="{}" />"#, indent, name, value).unwrap(),
+        None => writeln!(xml, "{}<{} />", indent, name).unwrap(),
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\n' => out.push_str("&#10;"),
+            '\t' => out.push_str("&#9;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(s: &str) -> Result<String, ParseError> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(pos) = rest.find('&') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => return parse_err("unterminated XML entity"),
+        };
+        let entity = &rest[..end];
+        rest = &rest[end + 1..];
+        let c = match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = if let Some(hex) = entity.strip_prefix("#x") {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(dec) = entity.strip_prefix('#') {
+                    dec.parse().ok()
+                } else {
+                    None
+                };
+                match code.and_then(char::from_u32) {
+                    Some(c) => c,
+                    None => return parse_err(format!("unknown XML entity &{};", entity)),
+                }
+            }
+        };
+        out.push(c);
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// XML tag.
+#[derive(Debug)]
+enum Tag {
+    Start { name: String, attrs: Vec<(String, String)>, empty: bool },
+    End { name: String },
+}
+
+impl Tag {
+    fn attr(&self, attr: &str) -> Option<&str> {
+        match self {
+            Self::Start { attrs, .. } => attrs.iter().find(|(n, _)| n == attr).map(|(_, v)| v.as_str()),
+            Self::End { .. } => None,
+        }
+    }
+}
+
+/// Minimal XML tokenizer sufficient for service records.
+///
+/// Character data between tags is ignored, since service records store all values in attributes.
+struct Tokenizer<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn next_tag(&mut self) -> Result<Option<Tag>, ParseError> {
+        loop {
+            let start = match self.rest.find('<') {
+                Some(start) => start,
+                None => {
+                    if !self.rest.trim().is_empty() {
+                        return parse_err("unexpected character data");
+                    }
+                    return Ok(None);
+                }
+            };
+            self.rest = &self.rest[start..];
+
+            if let Some(rest) = self.rest.strip_prefix("<?") {
+                self.rest = Self::skip_past(rest, "?>")?;
+            } else if let Some(rest) = self.rest.strip_prefix("<!--") {
+                self.rest = Self::skip_past(rest, "-->")?;
+            } else if let Some(rest) = self.rest.strip_prefix("<!") {
+                self.rest = Self::skip_past(rest, ">")?;
+            } else if let Some(rest) = self.rest.strip_prefix("</") {
+                let end = rest.find('>').ok_or_else(|| ParseError("unterminated end tag".to_string()))?;
+                let name = rest[..end].trim().to_string();
+                self.rest = &rest[end + 1..];
+                return Ok(Some(Tag::End { name }));
+            } else {
+                return self.start_tag().map(Some);
+            }
+        }
+    }
+
+    fn skip_past(s: &'a str, pat: &str) -> Result<&'a str, ParseError> {
+        match s.find(pat) {
+            Some(pos) => Ok(&s[pos + pat.len()..]),
+            None => parse_err(format!("missing {}", pat)),
+        }
+    }
+
+    fn start_tag(&mut self) -> Result<Tag, ParseError> {
+        let mut s = &self.rest[1..];
+        let name_end = s.find(|c: char| c.is_whitespace() || c == '/' || c == '>').unwrap_or(s.len());
+        let name = s[..name_end].to_string();
+        if name.is_empty() {
+            return parse_err("missing tag name");
+        }
+        s = &s[name_end..];
+
+        let mut attrs = Vec::new();
+        loop {
+            s = s.trim_start();
+            if let Some(rest) = s.strip_prefix("/>") {
+                self.rest = rest;
+                return Ok(Tag::Start { name, attrs, empty: true });
+            } else if let Some(rest) = s.strip_prefix('>') {
+                self.rest = rest;
+                return Ok(Tag::Start { name, attrs, empty: false });
+            }
+
+            let eq = match s.find('=') {
+                Some(eq) => eq,
+                None => return parse_err(format!("malformed attribute in tag <{}>", name)),
+            };
+            let attr_name = s[..eq].trim().to_string();
+            s = s[eq + 1..].trim_start();
+            let quote = match s.chars().next() {
+                Some(q @ ('"' | '\'')) => q,
+                _ => return parse_err(format!("unquoted attribute value in tag <{}>", name)),
+            };
+            s = &s[1..];
+            let end = match s.find(quote) {
+                Some(end) => end,
+                None => return parse_err(format!("unterminated attribute value in tag <{}>", name)),
+            };
+            attrs.push((attr_name, unescape(&s[..end])?));
+            s = &s[end + 1..];
+        }
+    }
+}
+
+pub(crate) fn from_xml(xml: &str) -> Result<ServiceRecord, ParseError> {
+    let mut tokens = Tokenizer { rest: xml };
+
+    match tokens.next_tag()? {
+        Some(Tag::Start { name, empty, .. }) if name == "record" => {
+            if empty {
+                return Ok(ServiceRecord::new());
+            }
+        }
+        _ => return parse_err("missing <record> element"),
+    }
+
+    let mut record = ServiceRecord::new();
+    loop {
+        let tag = tokens.next_tag()?.ok_or_else(|| ParseError("missing </record>".to_string()))?;
+        match &tag {
+            Tag::End { name } if name == "record" => break,
+            Tag::Start { name, empty, .. } if name == "attribute" => {
+                let id = tag.attr("id").ok_or_else(|| ParseError("attribute without id".to_string()))?;
+                let id = parse_uint(id)
+                    .and_then(|id| u16::try_from(id).ok())
+                    .ok_or_else(|| ParseError(format!("invalid attribute id {}", id)))?;
+                if *empty {
+                    return parse_err(format!("attribute 0x{:04x} has no value", id));
+                }
+                let value = match tokens.next_tag()? {
+                    Some(tag) => parse_element(&mut tokens, tag, 0)?,
+                    None => return parse_err("unexpected end of document"),
+                };
+                match tokens.next_tag()? {
+                    Some(Tag::End { name }) if name == "attribute" => (),
+                    _ => return parse_err(format!("attribute 0x{:04x} must contain exactly one value", id)),
+                }
+                record.attributes.insert(id, value);
+            }
+            _ => return parse_err(format!("unexpected tag {:?} in record", tag)),
+        }
+    }
+
+    if tokens.next_tag()?.is_some() {
+        return parse_err("unexpected content after </record>");
+    }
+
+    Ok(record)
+}
+
+fn parse_element(tokens: &mut Tokenizer, tag: Tag, depth: usize) -> Result<DataElement, ParseError> {
+    let (name, empty) = match &tag {
+        Tag::Start { name, empty, .. } => (name.as_str(), *empty),
+        Tag::End { name } => return parse_err(format!("unexpected end tag </{}>", name)),
+    };
+
+    if name == "sequence" || name == "alternate" {
+        if depth >= MAX_DEPTH {
+            return parse_err("data elements nested too deeply");
+        }
+        let mut elems = Vec::new();
+        if !empty {
+            loop {
+                match tokens.next_tag()? {
+                    Some(Tag::End { name: end }) if end == name => break,
+                    Some(tag) => elems.push(parse_element(tokens, tag, depth + 1)?),
+                    None => return parse_err(format!("missing </{}>", name)),
+                }
+            }
+        }
+        return Ok(if name == "sequence" {
+            DataElement::Sequence(elems)
+        } else {
+            DataElement::Alternative(elems)
+        });
+    }
+
+    if !empty {
+        match tokens.next_tag()? {
+            Some(Tag::End { name: end }) if end == name => (),
+            _ => return parse_err(format!("element <{}> must be empty", name)),
+        }
+    }
+
+    if name == "nil" {
+        return Ok(DataElement::Nil);
+    }
+
+    let value = tag.attr("value").ok_or_else(|| ParseError(format!("element <{}> without value", name)))?;
+    let invalid = || ParseError(format!("invalid value {:?} for element <{}>", value, name));
+
+    let de = match name {
+        "uint8" => DataElement::U8(parse_uint(value).and_then(|v| v.try_into().ok()).ok_or_else(invalid)?),
+        "uint16" => DataElement::U16(parse_uint(value).and_then(|v| v.try_into().ok()).ok_or_else(invalid)?),
+        "uint32" => DataElement::U32(parse_uint(value).and_then(|v| v.try_into().ok()).ok_or_else(invalid)?),
+        "uint64" => DataElement::U64(parse_uint(value).and_then(|v| v.try_into().ok()).ok_or_else(invalid)?),
+        "uint128" => DataElement::U128(parse_uint128(value).ok_or_else(invalid)?),
+        "int8" => DataElement::I8(parse_int(value).and_then(|v| v.try_into().ok()).ok_or_else(invalid)?),
+        "int16" => DataElement::I16(parse_int(value).and_then(|v| v.try_into().ok()).ok_or_else(invalid)?),
+        "int32" => DataElement::I32(parse_int(value).and_then(|v| v.try_into().ok()).ok_or_else(invalid)?),
+        "int64" => DataElement::I64(parse_int(value).and_then(|v| v.try_into().ok()).ok_or_else(invalid)?),
+        "int128" => DataElement::I128(parse_uint128(value).ok_or_else(invalid)? as i128),
+        "uuid" => DataElement::Uuid(parse_uuid(value).ok_or_else(invalid)?),
+        "boolean" => match value {
+            "true" | "1" => DataElement::Bool(true),
+            "false" | "0" => DataElement::Bool(false),
+            _ => return Err(invalid()),
+        },
+        "text" if tag.attr("encoding") == Some("hex") => {
+            DataElement::Text(String::from_utf8_lossy(&parse_hex(value).ok_or_else(invalid)?).into_owned())
+        }
+        "text" => DataElement::Text(value.to_string()),
+        "url" => DataElement::Url(value.to_string()),
+        _ => return parse_err(format!("unknown element <{}>", name)),
+    };
+    Ok(de)
+}
+
+fn parse_uint(s: &str) -> Option<u128> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u128::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+fn parse_int(s: &str) -> Option<i128> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u128::from_str_radix(hex, 16).ok().map(|v| v as i128),
+        None => s.parse().ok(),
+    }
+}
+
+/// Parses a 128-bit value, which BlueZ writes as 32 hex digits without prefix.
+fn parse_uint128(s: &str) -> Option<u128> {
+    if s.len() != 32 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u128::from_str_radix(s, 16).ok()
+}
+
+fn parse_uuid(s: &str) -> Option<Uuid> {
+    if let Some(hex) = s.strip_prefix("0x") {
+        match hex.len() {
+            4 => u16::from_str_radix(hex, 16).ok().map(Uuid::from_u16),
+            8 => u32::from_str_radix(hex, 16).ok().map(Uuid::from_u32),
+            _ => None,
+        }
+    } else {
+        s.parse().ok()
+    }
+}
+
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok()).collect()
+}
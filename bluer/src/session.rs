@@ -19,7 +19,6 @@ use futures::{
     lock::Mutex,
     Future, SinkExt, Stream, StreamExt,
 };
-use lazy_static::lazy_static;
 use std::{
     collections::{HashMap, HashSet},
     fmt::{Debug, Formatter},
@@ -37,8 +36,8 @@ use crate::{
     all_dbus_objects, gatt, parent_path, Adapter, Error, ErrorKind, InternalErrorKind, Result, SERVICE_NAME,
 };
 
-#[cfg(feature = "rfcomm")]
-use crate::rfcomm::{profile::RegisteredProfile, Profile, ProfileHandle};
+#[cfg(any(feature = "l2cap", feature = "rfcomm"))]
+use crate::profile::{Profile, ProfileHandle, RegisteredProfile};
 
 /// Terminate TX and terminated RX for single session.
 type SingleSessionTerm = (Weak<oneshot::Sender<()>>, oneshot::Receiver<()>);
@@ -53,7 +52,7 @@ pub(crate) struct SessionInner {
     pub gatt_reg_characteristic_descriptor_token: IfaceToken<Arc<gatt::local::RegisteredDescriptor>>,
     pub gatt_profile_token: IfaceToken<gatt::local::Profile>,
     pub agent_token: IfaceToken<Arc<RegisteredAgent>>,
-    #[cfg(feature = "rfcomm")]
+    #[cfg(any(feature = "l2cap", feature = "rfcomm"))]
     pub profile_token: IfaceToken<Arc<RegisteredProfile>>,
     pub single_sessions: Mutex<HashMap<dbus::Path<'static>, SingleSessionTerm>>,
     pub event_sub_tx: mpsc::Sender<SubscriptionReq>,
@@ -169,11 +168,11 @@ impl Session {
             gatt::local::RegisteredDescriptor::register_interface(&mut crossroads);
         let gatt_profile_token = gatt::local::Profile::register_interface(&mut crossroads);
         let agent_token = RegisteredAgent::register_interface(&mut crossroads);
-        #[cfg(feature = "rfcomm")]
+        #[cfg(any(feature = "l2cap", feature = "rfcomm"))]
         let profile_token = RegisteredProfile::register_interface(&mut crossroads);
 
         let (event_sub_tx, event_sub_rx) = mpsc::channel(1);
-        Event::handle_connection(connection.clone(), event_sub_rx).await?;
+        Event::handle_connection(connection.clone(), SERVICE_NAME, event_sub_rx).await?;
 
         let inner = Arc::new(SessionInner {
             connection: connection.clone(),
@@ -184,7 +183,7 @@ impl Session {
             gatt_reg_characteristic_descriptor_token,
             gatt_profile_token,
             agent_token,
-            #[cfg(feature = "rfcomm")]
+            #[cfg(any(feature = "l2cap", feature = "rfcomm"))]
             profile_token,
             single_sessions: Mutex::new(HashMap::new()),
             event_sub_tx,
@@ -264,19 +263,21 @@ impl Session {
     /// agents per application is not supported.
     ///
     /// Drop the returned [AgentHandle] to unregister the agent.
-    pub async fn register_agent(&self, agent: Agent) -> Result<AgentHandle> {
-        let reg_agent = RegisteredAgent::new(agent);
-        reg_agent.register(self.inner.clone()).await
+    ///
+    /// A [PolicyAgent](crate::agent::PolicyAgent) can be registered directly.
+    pub async fn register_agent(&self, agent: impl Into<Agent>) -> Result<AgentHandle> {
+        let (reg_agent, event_rx) = RegisteredAgent::new(agent.into());
+        reg_agent.register(event_rx, self.inner.clone()).await
     }
 
-    /// This registers a [Bluetooth profile implementation](Profile) for RFCOMM connections.
+    /// This registers a [Bluetooth profile implementation](Profile) for L2CAP or RFCOMM connections.
     ///
     /// The returned [ProfileHandle] provides a stream of
-    /// [connection requests](crate::rfcomm::ConnectRequest).
+    /// [connection requests](crate::profile::ConnectRequest).
     ///
     /// Drop the handle to unregister the profile.
-    #[cfg(feature = "rfcomm")]
-    #[cfg_attr(docsrs, doc(cfg(feature = "rfcomm")))]
+    #[cfg(any(feature = "l2cap", feature = "rfcomm"))]
+    #[cfg_attr(docsrs, doc(cfg(any(feature = "l2cap", feature = "rfcomm"))))]
     pub async fn register_profile(&self, profile: Profile) -> Result<ProfileHandle> {
         let (req_tx, req_rx) = tokio::sync::mpsc::channel(1);
         let reg_profile = RegisteredProfile::new(req_tx);
@@ -343,15 +344,13 @@ pub(crate) struct SubscriptionReq {
 }
 
 impl Event {
-    /// Spawns a task that handles events for the specified connection.
+    /// Spawns a task that handles events sent by the specified service over the specified connection.
     pub(crate) async fn handle_connection(
-        connection: Arc<SyncConnection>, mut sub_rx: mpsc::Receiver<SubscriptionReq>,
+        connection: Arc<SyncConnection>, service_name: &'static str, mut sub_rx: mpsc::Receiver<SubscriptionReq>,
     ) -> Result<()> {
         use dbus::message::SignalArgs;
-        lazy_static! {
-            static ref SERVICE_NAME_BUS: BusName<'static> = BusName::new(SERVICE_NAME).unwrap();
-            static ref SERVICE_NAME_REF: Option<&'static BusName<'static>> = Some(&SERVICE_NAME_BUS);
-        }
+        let service_name_bus = BusName::new(service_name).unwrap();
+        let service_name_ref = Some(&service_name_bus);
 
         let (msg_tx, mut msg_rx) = mpsc::unbounded();
         let handle_msg = move |msg: Message| {
@@ -359,13 +358,13 @@ impl Event {
             true
         };
 
-        let rule_add = ObjectManagerInterfacesAdded::match_rule(*SERVICE_NAME_REF, None);
+        let rule_add = ObjectManagerInterfacesAdded::match_rule(service_name_ref, None).static_clone();
         let msg_match_add = connection.add_match(rule_add).await?.msg_cb(handle_msg.clone());
 
-        let rule_removed = ObjectManagerInterfacesRemoved::match_rule(*SERVICE_NAME_REF, None);
+        let rule_removed = ObjectManagerInterfacesRemoved::match_rule(service_name_ref, None).static_clone();
         let msg_match_removed = connection.add_match(rule_removed).await?.msg_cb(handle_msg.clone());
 
-        let rule_prop = PropertiesPropertiesChanged::match_rule(*SERVICE_NAME_REF, None);
+        let rule_prop = PropertiesPropertiesChanged::match_rule(service_name_ref, None).static_clone();
         let msg_match_prop = connection.add_match(rule_prop).await?.msg_cb(handle_msg.clone());
 
         tokio::spawn(async move {
@@ -1,13 +1,36 @@
 //! System socket base.
 
+#[cfg(feature = "sco")]
+use libc::SOL_BLUETOOTH;
 use libc::{c_int, c_ulong, sockaddr, socklen_t, SOCK_CLOEXEC, SOCK_NONBLOCK};
+#[cfg(any(feature = "iso", feature = "l2cap"))]
+use libc::{
+    c_uint, timespec, SCM_TIMESTAMPING, SOF_TIMESTAMPING_RAW_HARDWARE, SOF_TIMESTAMPING_RX_HARDWARE,
+    SOF_TIMESTAMPING_RX_SOFTWARE, SOF_TIMESTAMPING_SOFTWARE, SOL_SOCKET, SO_TIMESTAMPING,
+};
+#[cfg(feature = "sco")]
+use num_derive::{FromPrimitive, ToPrimitive};
+#[cfg(feature = "sco")]
+use num_traits::FromPrimitive;
+#[cfg(any(feature = "hci", feature = "iso", feature = "l2cap", feature = "sco"))]
+use std::mem::size_of_val;
+#[cfg(any(feature = "iso", feature = "l2cap"))]
+use std::time::Duration;
 use std::{
     io::{Error, ErrorKind, Result},
     mem::{size_of, MaybeUninit},
     os::unix::io::{AsRawFd, IntoRawFd, RawFd},
 };
+#[cfg(feature = "l2cap")]
+use std::{
+    io::{IoSlice, IoSliceMut},
+    ptr,
+};
 use tokio::io::ReadBuf;
 
+#[cfg(feature = "sco")]
+use crate::sys::BT_SCM_PKT_STATUS;
+
 /// File descriptor that is closed on drop.
 #[derive(Debug)]
 pub struct OwnedFd {
@@ -53,6 +76,38 @@ pub trait SysSockAddr: Sized {
 
     /// Convert from system socket address.
     fn try_from_sys_sock_addr(addr: Self::SysSockAddr) -> Result<Self>;
+
+    /// Length of the system socket address passed to the kernel.
+    ///
+    /// Defaults to the size of the system socket address type.
+    fn sys_sock_addr_len(&self) -> usize {
+        size_of::<Self::SysSockAddr>()
+    }
+
+    /// Convert from system socket address of the specified length returned by the kernel.
+    ///
+    /// Bytes of `addr` beyond `len` are zero.
+    /// Defaults to requiring the full size of the system socket address type.
+    fn try_from_sys_sock_addr_len(addr: Self::SysSockAddr, len: usize) -> Result<Self> {
+        if len != size_of::<Self::SysSockAddr>() {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid sockaddr length"));
+        }
+        Self::try_from_sys_sock_addr(addr)
+    }
+}
+
+/// Converts a system socket address filled in by the kernel.
+///
+/// The system socket address must have been zero initialized.
+fn sys_sock_addr_from_kernel<SA>(saddr: MaybeUninit<SA::SysSockAddr>, length: socklen_t) -> Result<SA>
+where
+    SA: SysSockAddr,
+{
+    if length as usize > size_of::<SA::SysSockAddr>() {
+        return Err(Error::new(ErrorKind::InvalidInput, "sockaddr too long"));
+    }
+    let saddr = unsafe { saddr.assume_init() };
+    SA::try_from_sys_sock_addr_len(saddr, length as usize)
 }
 
 /// Creates a socket of the specified type and returns its file descriptor.
@@ -71,15 +126,9 @@ pub fn bind<SA>(socket: &OwnedFd, sa: SA) -> Result<()>
 where
     SA: SysSockAddr,
 {
+    let len = sa.sys_sock_addr_len();
     let addr: SA::SysSockAddr = sa.into_sys_sock_addr();
-    if unsafe {
-        libc::bind(
-            socket.as_raw_fd(),
-            &addr as *const _ as *const sockaddr,
-            size_of::<SA::SysSockAddr>() as socklen_t,
-        )
-    } == 0
-    {
+    if unsafe { libc::bind(socket.as_raw_fd(), &addr as *const _ as *const sockaddr, len as socklen_t) } == 0 {
         Ok(())
     } else {
         Err(Error::last_os_error())
@@ -91,18 +140,14 @@ pub fn getsockname<SA>(socket: &OwnedFd) -> Result<SA>
 where
     SA: SysSockAddr,
 {
-    let mut saddr: MaybeUninit<SA::SysSockAddr> = MaybeUninit::uninit();
+    let mut saddr: MaybeUninit<SA::SysSockAddr> = MaybeUninit::zeroed();
     let mut length = size_of::<SA::SysSockAddr>() as socklen_t;
 
     if unsafe { libc::getsockname(socket.as_raw_fd(), saddr.as_mut_ptr() as *mut _, &mut length) } == -1 {
         return Err(Error::last_os_error());
     };
 
-    if length != size_of::<SA::SysSockAddr>() as socklen_t {
-        return Err(Error::new(ErrorKind::InvalidInput, "invalid sockaddr length from getsockname"));
-    }
-    let saddr = unsafe { saddr.assume_init() };
-    SA::try_from_sys_sock_addr(saddr)
+    sys_sock_addr_from_kernel(saddr, length)
 }
 
 /// Gets the address the socket is connected to.
@@ -110,18 +155,14 @@ pub fn getpeername<SA>(socket: &OwnedFd) -> Result<SA>
 where
     SA: SysSockAddr,
 {
-    let mut saddr: MaybeUninit<SA::SysSockAddr> = MaybeUninit::uninit();
+    let mut saddr: MaybeUninit<SA::SysSockAddr> = MaybeUninit::zeroed();
     let mut length = size_of::<SA::SysSockAddr>() as socklen_t;
 
     if unsafe { libc::getpeername(socket.as_raw_fd(), saddr.as_mut_ptr() as *mut _, &mut length) } == -1 {
         return Err(Error::last_os_error());
     };
 
-    if length != size_of::<SA::SysSockAddr>() as socklen_t {
-        return Err(Error::new(ErrorKind::InvalidInput, "invalid sockaddr length from getpeername"));
-    }
-    let saddr = unsafe { saddr.assume_init() };
-    SA::try_from_sys_sock_addr(saddr)
+    sys_sock_addr_from_kernel(saddr, length)
 }
 
 /// Puts socket in listen mode.
@@ -140,7 +181,7 @@ pub fn accept<SA>(socket: &OwnedFd) -> Result<(OwnedFd, SA)>
 where
     SA: SysSockAddr,
 {
-    let mut saddr: MaybeUninit<SA::SysSockAddr> = MaybeUninit::uninit();
+    let mut saddr: MaybeUninit<SA::SysSockAddr> = MaybeUninit::zeroed();
     let mut length = size_of::<SA::SysSockAddr>() as socklen_t;
 
     let fd = match unsafe {
@@ -150,11 +191,7 @@ where
         fd => unsafe { OwnedFd::new(fd) },
     };
 
-    if length != size_of::<SA::SysSockAddr>() as socklen_t {
-        return Err(Error::new(ErrorKind::InvalidInput, "invalid sockaddr length"));
-    }
-    let saddr = unsafe { saddr.assume_init() };
-    let sa = SA::try_from_sys_sock_addr(saddr)?;
+    let sa = sys_sock_addr_from_kernel(saddr, length)?;
 
     Ok((fd, sa))
 }
@@ -164,15 +201,9 @@ pub fn connect<SA>(socket: &OwnedFd, sa: SA) -> Result<()>
 where
     SA: SysSockAddr,
 {
+    let len = sa.sys_sock_addr_len();
     let addr: SA::SysSockAddr = sa.into_sys_sock_addr();
-    if unsafe {
-        libc::connect(
-            socket.as_raw_fd(),
-            &addr as *const _ as *const sockaddr,
-            size_of::<SA::SysSockAddr>() as socklen_t,
-        )
-    } == 0
-    {
+    if unsafe { libc::connect(socket.as_raw_fd(), &addr as *const _ as *const sockaddr, len as socklen_t) } == 0 {
         Ok(())
     } else {
         Err(Error::last_os_error())
@@ -192,6 +223,7 @@ pub fn sendto<SA>(socket: &OwnedFd, buf: &[u8], flags: c_int, sa: SA) -> Result<
 where
     SA: SysSockAddr,
 {
+    let len = sa.sys_sock_addr_len();
     let addr: SA::SysSockAddr = sa.into_sys_sock_addr();
     match unsafe {
         libc::sendto(
@@ -200,7 +232,7 @@ where
             buf.len(),
             flags,
             &addr as *const _ as *const sockaddr,
-            size_of::<SA::SysSockAddr>() as socklen_t,
+            len as socklen_t,
         )
     } {
         -1 => Err(Error::last_os_error()),
@@ -230,7 +262,7 @@ where
     SA: SysSockAddr,
 {
     let unfilled = unsafe { buf.unfilled_mut() };
-    let mut saddr: MaybeUninit<SA::SysSockAddr> = MaybeUninit::uninit();
+    let mut saddr: MaybeUninit<SA::SysSockAddr> = MaybeUninit::zeroed();
     let mut length = size_of::<SA::SysSockAddr>() as socklen_t;
     match unsafe {
         libc::recvfrom(
@@ -250,17 +282,294 @@ where
             }
             buf.advance(n);
 
-            if length != size_of::<SA::SysSockAddr>() as socklen_t {
-                return Err(Error::new(ErrorKind::InvalidInput, "invalid sockaddr length"));
-            }
-            let saddr = unsafe { saddr.assume_init() };
-            let sa = SA::try_from_sys_sock_addr(saddr)?;
+            let sa = sys_sock_addr_from_kernel(saddr, length)?;
 
             Ok((n, sa))
         }
     }
 }
 
+/// Control message received as ancillary data.
+#[cfg(any(feature = "hci", feature = "iso", feature = "l2cap", feature = "sco"))]
+#[derive(Debug, Clone)]
+pub struct CMsg {
+    /// Originating protocol.
+    pub level: c_int,
+    /// Protocol-specific type.
+    pub ty: c_int,
+    /// Data.
+    pub data: Vec<u8>,
+}
+
+/// Control message buffer using u64 elements to ensure alignment suitable for cmsghdr.
+#[cfg(any(feature = "hci", feature = "iso", feature = "l2cap", feature = "sco"))]
+type ControlBuf = [u64; 16];
+
+/// Parses the control messages of a received message.
+#[cfg(any(feature = "hci", feature = "iso", feature = "l2cap", feature = "sco"))]
+fn parse_cmsgs(msg: &libc::msghdr) -> Vec<CMsg> {
+    let mut cmsgs = Vec::new();
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(msg) };
+    while !cmsg.is_null() {
+        let hdr = unsafe { &*cmsg };
+        let data_len = hdr.cmsg_len as usize - unsafe { libc::CMSG_LEN(0) } as usize;
+        let data = unsafe { std::slice::from_raw_parts(libc::CMSG_DATA(cmsg), data_len) };
+        cmsgs.push(CMsg { level: hdr.cmsg_level, ty: hdr.cmsg_type, data: data.to_vec() });
+        cmsg = unsafe { libc::CMSG_NXTHDR(msg, cmsg) };
+    }
+    cmsgs
+}
+
+/// Receive from socket into buffer together with ancillary data.
+#[cfg(any(feature = "hci", feature = "iso", feature = "l2cap", feature = "sco"))]
+pub fn recvmsg(socket: &OwnedFd, buf: &mut ReadBuf, flags: c_int) -> Result<(usize, Vec<CMsg>)> {
+    let unfilled = unsafe { buf.unfilled_mut() };
+    let mut iov = libc::iovec { iov_base: unfilled.as_mut_ptr() as *mut _, iov_len: unfilled.len() };
+    let mut control: ControlBuf = Default::default();
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut _;
+    msg.msg_controllen = size_of_val(&control) as _;
+
+    match unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, flags) } {
+        -1 => Err(Error::last_os_error()),
+        n => {
+            let n = n as usize;
+            unsafe {
+                buf.assume_init(n);
+            }
+            buf.advance(n);
+            Ok((n, parse_cmsgs(&msg)))
+        }
+    }
+}
+
+/// Receive one packet from socket into multiple buffers together with ancillary data.
+#[cfg(feature = "l2cap")]
+pub fn recvmsg_vectored(socket: &OwnedFd, bufs: &mut [IoSliceMut], flags: c_int) -> Result<(usize, Vec<CMsg>)> {
+    let mut control: ControlBuf = Default::default();
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    // IoSliceMut is ABI compatible with iovec.
+    msg.msg_iov = bufs.as_mut_ptr() as *mut libc::iovec;
+    msg.msg_iovlen = bufs.len() as _;
+    msg.msg_control = control.as_mut_ptr() as *mut _;
+    msg.msg_controllen = size_of_val(&control) as _;
+
+    match unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, flags) } {
+        -1 => Err(Error::last_os_error()),
+        n => Ok((n as usize, parse_cmsgs(&msg))),
+    }
+}
+
+/// Sends multiple buffers as one packet into socket, optionally using destination address.
+#[cfg(feature = "l2cap")]
+pub fn sendmsg<SA>(socket: &OwnedFd, bufs: &[IoSlice], flags: c_int, sa: Option<SA>) -> Result<usize>
+where
+    SA: SysSockAddr,
+{
+    let len = sa.as_ref().map(|sa| sa.sys_sock_addr_len()).unwrap_or_default();
+    let mut addr: Option<SA::SysSockAddr> = sa.map(|sa| sa.into_sys_sock_addr());
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    if let Some(addr) = &mut addr {
+        msg.msg_name = addr as *mut _ as *mut _;
+        msg.msg_namelen = len as _;
+    }
+    // IoSlice is ABI compatible with iovec.
+    msg.msg_iov = bufs.as_ptr() as *mut libc::iovec;
+    msg.msg_iovlen = bufs.len() as _;
+
+    match unsafe { libc::sendmsg(socket.as_raw_fd(), &msg, flags) } {
+        -1 => Err(Error::last_os_error()),
+        n => Ok(n as _),
+    }
+}
+
+/// Packet received by [recvmmsg].
+#[cfg(feature = "l2cap")]
+#[derive(Debug)]
+pub struct MMsg<SA> {
+    /// Length of packet.
+    pub len: usize,
+    /// Source address, if provided by the kernel.
+    pub addr: Option<SA>,
+    /// Ancillary data.
+    pub cmsgs: Vec<CMsg>,
+    /// Whether the packet was truncated because the buffer was too small.
+    pub truncated: bool,
+}
+
+/// Receives multiple packets from socket, one into each buffer.
+///
+/// Returns the packets received, which may be less than the number of buffers.
+#[cfg(feature = "l2cap")]
+pub fn recvmmsg<SA>(socket: &OwnedFd, bufs: &mut [&mut [u8]], flags: c_int) -> Result<Vec<MMsg<SA>>>
+where
+    SA: SysSockAddr,
+{
+    let mut iovs: Vec<_> = bufs
+        .iter_mut()
+        .map(|buf| libc::iovec { iov_base: buf.as_mut_ptr() as *mut _, iov_len: buf.len() })
+        .collect();
+    let mut saddrs: Vec<MaybeUninit<SA::SysSockAddr>> = bufs.iter().map(|_| MaybeUninit::zeroed()).collect();
+    let mut controls: Vec<ControlBuf> = bufs.iter().map(|_| Default::default()).collect();
+    let mut msgs: Vec<libc::mmsghdr> = iovs
+        .iter_mut()
+        .zip(&mut saddrs)
+        .zip(&mut controls)
+        .map(|((iov, saddr), control)| {
+            let mut hdr: libc::mmsghdr = unsafe { std::mem::zeroed() };
+            hdr.msg_hdr.msg_name = saddr.as_mut_ptr() as *mut _;
+            hdr.msg_hdr.msg_namelen = size_of::<SA::SysSockAddr>() as _;
+            hdr.msg_hdr.msg_iov = iov;
+            hdr.msg_hdr.msg_iovlen = 1;
+            hdr.msg_hdr.msg_control = control.as_mut_ptr() as *mut _;
+            hdr.msg_hdr.msg_controllen = size_of::<ControlBuf>() as _;
+            hdr
+        })
+        .collect();
+
+    let n = match unsafe {
+        libc::recvmmsg(socket.as_raw_fd(), msgs.as_mut_ptr(), msgs.len() as _, flags as _, ptr::null_mut())
+    } {
+        -1 => return Err(Error::last_os_error()),
+        n => n as usize,
+    };
+
+    let mut received = Vec::with_capacity(n);
+    for (hdr, saddr) in msgs.iter().zip(saddrs).take(n) {
+        let addr = match hdr.msg_hdr.msg_namelen {
+            0 => None,
+            length => Some(sys_sock_addr_from_kernel(saddr, length)?),
+        };
+        received.push(MMsg {
+            len: hdr.msg_len as usize,
+            addr,
+            cmsgs: parse_cmsgs(&hdr.msg_hdr),
+            truncated: hdr.msg_hdr.msg_flags & libc::MSG_TRUNC != 0,
+        });
+    }
+    Ok(received)
+}
+
+/// Sends multiple packets into socket, optionally using destination address.
+///
+/// Returns the number of packets sent, which may be less than the number of packets.
+#[cfg(feature = "l2cap")]
+pub fn sendmmsg<SA>(socket: &OwnedFd, packets: &[&[u8]], flags: c_int, sa: Option<SA>) -> Result<usize>
+where
+    SA: SysSockAddr,
+{
+    let len = sa.as_ref().map(|sa| sa.sys_sock_addr_len()).unwrap_or_default();
+    let mut addr: Option<SA::SysSockAddr> = sa.map(|sa| sa.into_sys_sock_addr());
+    let mut iovs: Vec<_> = packets
+        .iter()
+        .map(|packet| libc::iovec { iov_base: packet.as_ptr() as *mut _, iov_len: packet.len() })
+        .collect();
+    let mut msgs: Vec<libc::mmsghdr> = iovs
+        .iter_mut()
+        .map(|iov| {
+            let mut hdr: libc::mmsghdr = unsafe { std::mem::zeroed() };
+            if let Some(addr) = &mut addr {
+                hdr.msg_hdr.msg_name = addr as *mut _ as *mut _;
+                hdr.msg_hdr.msg_namelen = len as _;
+            }
+            hdr.msg_hdr.msg_iov = iov;
+            hdr.msg_hdr.msg_iovlen = 1;
+            hdr
+        })
+        .collect();
+
+    match unsafe { libc::sendmmsg(socket.as_raw_fd(), msgs.as_mut_ptr(), msgs.len() as _, flags as _) } {
+        -1 => Err(Error::last_os_error()),
+        n => Ok(n as _),
+    }
+}
+
+/// Receive timestamps of a packet.
+///
+/// Timestamps are only available if timestamping is enabled on the socket.
+#[cfg(any(feature = "iso", feature = "l2cap"))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Timestamp {
+    /// Time the packet was received by the kernel since the Unix epoch.
+    pub software: Option<Duration>,
+    /// Time the packet was received by the controller, as reported by the controller.
+    ///
+    /// The time base is controller-specific.
+    pub hardware: Option<Duration>,
+}
+
+#[cfg(any(feature = "iso", feature = "l2cap"))]
+impl Timestamp {
+    /// Extracts the timestamps from received control messages.
+    pub(crate) fn from_cmsgs(cmsgs: &[CMsg]) -> Option<Self> {
+        let cmsg = cmsgs.iter().find(|cmsg| cmsg.level == SOL_SOCKET && cmsg.ty == SCM_TIMESTAMPING)?;
+        if cmsg.data.len() < 3 * size_of::<timespec>() {
+            return None;
+        }
+        let ts: [timespec; 3] = unsafe { (cmsg.data.as_ptr() as *const [timespec; 3]).read_unaligned() };
+        let to_duration = |ts: &timespec| {
+            if ts.tv_sec == 0 && ts.tv_nsec == 0 {
+                None
+            } else {
+                Some(Duration::new(ts.tv_sec as _, ts.tv_nsec as _))
+            }
+        };
+        Some(Self { software: to_duration(&ts[0]), hardware: to_duration(&ts[2]) })
+    }
+}
+
+/// Timestamping flags used for reporting software and hardware receive timestamps.
+#[cfg(any(feature = "iso", feature = "l2cap"))]
+const TIMESTAMPING_FLAGS: c_uint = SOF_TIMESTAMPING_RX_SOFTWARE
+    | SOF_TIMESTAMPING_SOFTWARE
+    | SOF_TIMESTAMPING_RX_HARDWARE
+    | SOF_TIMESTAMPING_RAW_HARDWARE;
+
+/// Gets whether receive timestamps are reported.
+#[cfg(any(feature = "iso", feature = "l2cap"))]
+pub fn is_timestamping(socket: &OwnedFd) -> Result<bool> {
+    let value: c_int = getsockopt(socket, SOL_SOCKET, SO_TIMESTAMPING)?;
+    Ok(value as c_uint & TIMESTAMPING_FLAGS != 0)
+}
+
+/// Sets whether software and hardware receive timestamps are reported.
+#[cfg(any(feature = "iso", feature = "l2cap"))]
+pub fn set_timestamping(socket: &OwnedFd, timestamping: bool) -> Result<()> {
+    let value: c_int = if timestamping { TIMESTAMPING_FLAGS as _ } else { 0 };
+    setsockopt(socket, SOL_SOCKET, SO_TIMESTAMPING, &value)
+}
+
+/// Status of a received packet as reported by the controller.
+#[cfg(feature = "sco")]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, FromPrimitive, ToPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PacketStatus {
+    /// Correctly received data.
+    Correct = 0x00,
+    /// Possibly invalid data.
+    PossiblyInvalid = 0x01,
+    /// No data received.
+    NoData = 0x02,
+    /// Data partially lost.
+    PartiallyLost = 0x03,
+}
+
+#[cfg(feature = "sco")]
+impl PacketStatus {
+    /// Extracts the packet status from received control messages.
+    pub(crate) fn from_cmsgs(cmsgs: &[CMsg]) -> Option<Self> {
+        cmsgs
+            .iter()
+            .find(|cmsg| cmsg.level == SOL_BLUETOOTH && cmsg.ty == BT_SCM_PKT_STATUS)
+            .and_then(|cmsg| cmsg.data.first())
+            .and_then(|&status| Self::from_u8(status))
+    }
+}
+
 /// Shut down part of a socket.
 pub fn shutdown(socket: &OwnedFd, how: c_int) -> Result<()> {
     if unsafe { libc::shutdown(socket.as_raw_fd(), how) } == 0 {
@@ -297,6 +606,32 @@ pub fn setsockopt<T>(socket: &OwnedFd, level: c_int, optname: i32, optval: &T) -
     Ok(())
 }
 
+/// Get socket option of variable length.
+#[cfg(feature = "iso")]
+pub fn getsockopt_bytes(socket: &OwnedFd, level: c_int, optname: c_int, max_len: usize) -> Result<Vec<u8>> {
+    let mut optval = vec![0u8; max_len];
+    let mut optlen: socklen_t = max_len as _;
+    if unsafe { libc::getsockopt(socket.as_raw_fd(), level, optname, optval.as_mut_ptr() as *mut _, &mut optlen) }
+        == -1
+    {
+        return Err(Error::last_os_error());
+    }
+    optval.truncate(optlen as _);
+    Ok(optval)
+}
+
+/// Set socket option of variable length.
+#[cfg(feature = "iso")]
+pub fn setsockopt_bytes(socket: &OwnedFd, level: c_int, optname: i32, optval: &[u8]) -> Result<()> {
+    if unsafe {
+        libc::setsockopt(socket.as_raw_fd(), level, optname, optval.as_ptr() as *const _, optval.len() as _)
+    } == -1
+    {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
 /// Perform an IOCTL that reads a single value.
 pub fn ioctl_read<T>(socket: &OwnedFd, request: c_ulong) -> Result<T> {
     let mut value: MaybeUninit<T> = MaybeUninit::uninit();
@@ -308,6 +643,16 @@ pub fn ioctl_read<T>(socket: &OwnedFd, request: c_ulong) -> Result<T> {
     Ok(value)
 }
 
+/// Perform an IOCTL that passes a single value to the kernel and receives it back modified.
+#[cfg(feature = "hci")]
+pub fn ioctl_read_write<T>(socket: &OwnedFd, request: c_ulong, value: &mut T) -> Result<c_int> {
+    let ret = unsafe { libc::ioctl(socket.as_raw_fd(), request, value as *mut _) };
+    if ret == -1 {
+        return Err(Error::last_os_error());
+    }
+    Ok(ret)
+}
+
 /// Perform an IOCTL that writes a single value.
 #[allow(dead_code)]
 pub fn ioctl_write<T>(socket: &OwnedFd, request: c_ulong, value: &T) -> Result<c_int> {
@@ -319,8 +664,16 @@ pub fn ioctl_write<T>(socket: &OwnedFd, request: c_ulong, value: &T) -> Result<c
 }
 
 /// Private socket implementation functions.
+///
+/// The base functions are always implemented.
+/// Additional functions are implemented when their name without the `_priv`
+/// suffix is passed, for example `sock_priv!(peek, poll_peek)`.
 macro_rules! sock_priv {
-    () => {
+    ($($ext:ident),* $(,)?) => {
+        sock_priv!(@base);
+        $( sock_priv!(@$ext); )*
+    };
+    (@base) => {
         async fn accept_priv(&self) -> Result<(Self, SocketAddr)> {
             let (fd, sa) = loop {
                 let mut guard = self.fd.readable().await?;
@@ -459,27 +812,6 @@ macro_rules! sock_priv {
             }
         }
 
-        async fn peek_priv(&self, buf: &mut [u8]) -> Result<usize> {
-            let mut buf = ReadBuf::new(buf);
-            loop {
-                let mut guard = self.fd.readable().await?;
-                match guard.try_io(|inner| sock::recv(inner.get_ref(), &mut buf, MSG_PEEK)) {
-                    Ok(result) => return result,
-                    Err(_would_block) => continue,
-                }
-            }
-        }
-
-        fn poll_peek_priv(&self, cx: &mut Context, buf: &mut ReadBuf) -> Poll<Result<usize>> {
-            loop {
-                let mut guard = ready!(self.fd.poll_read_ready(cx))?;
-                match guard.try_io(|inner| sock::recv(inner.get_ref(), buf, MSG_PEEK)) {
-                    Ok(result) => return Poll::Ready(result),
-                    Err(_would_block) => continue,
-                }
-            }
-        }
-
         fn poll_flush_priv(&self, _cx: &mut Context) -> Poll<Result<()>> {
             // Flush is a no-op.
             Poll::Ready(Ok(()))
@@ -500,4 +832,120 @@ macro_rules! sock_priv {
             Poll::Ready(Ok(()))
         }
     };
+    (@accept_deferred) => {
+        async fn accept_deferred_priv(&self) -> Result<()> {
+            sock::recv(self.fd.get_ref(), &mut ReadBuf::new(&mut []), 0)?;
+            loop {
+                let mut guard = self.fd.writable().await?;
+                match guard.try_io(|inner| {
+                    let err: c_int = sock::getsockopt(inner.get_ref(), SOL_SOCKET, SO_ERROR)?;
+                    match err {
+                        0 => Ok(()),
+                        _ => Err(Error::from_raw_os_error(err)),
+                    }
+                }) {
+                    Ok(result) => break result,
+                    Err(_would_block) => continue,
+                }
+            }
+        }
+    };
+    (@recvmsg) => {
+        async fn recvmsg_priv(&self, buf: &mut [u8]) -> Result<(usize, Vec<sock::CMsg>)> {
+            let mut buf = ReadBuf::new(buf);
+            loop {
+                let mut guard = self.fd.readable().await?;
+                match guard.try_io(|inner| sock::recvmsg(inner.get_ref(), &mut buf, 0)) {
+                    Ok(result) => return result,
+                    Err(_would_block) => continue,
+                }
+            }
+        }
+    };
+    (@poll_recvmsg) => {
+        fn poll_recvmsg_priv(&self, cx: &mut Context, buf: &mut ReadBuf) -> Poll<Result<Vec<sock::CMsg>>> {
+            loop {
+                let mut guard = ready!(self.fd.poll_read_ready(cx))?;
+                match guard.try_io(|inner| sock::recvmsg(inner.get_ref(), buf, 0)) {
+                    Ok(result) => return Poll::Ready(result.map(|(_n, cmsgs)| cmsgs)),
+                    Err(_would_block) => continue,
+                }
+            }
+        }
+    };
+    (@send_vectored) => {
+        async fn send_vectored_priv(&self, bufs: &[std::io::IoSlice<'_>]) -> Result<usize> {
+            loop {
+                let mut guard = self.fd.writable().await?;
+                match guard.try_io(|inner| sock::sendmsg::<SocketAddr>(inner.get_ref(), bufs, 0, None)) {
+                    Ok(result) => return result,
+                    Err(_would_block) => continue,
+                }
+            }
+        }
+    };
+    (@recv_vectored) => {
+        async fn recv_vectored_priv(
+            &self, bufs: &mut [std::io::IoSliceMut<'_>],
+        ) -> Result<(usize, Vec<sock::CMsg>)> {
+            loop {
+                let mut guard = self.fd.readable().await?;
+                match guard.try_io(|inner| sock::recvmsg_vectored(inner.get_ref(), bufs, 0)) {
+                    Ok(result) => return result,
+                    Err(_would_block) => continue,
+                }
+            }
+        }
+    };
+    (@send_batch) => {
+        async fn send_batch_priv(&self, packets: &[&[u8]], target: Option<SocketAddr>) -> Result<usize> {
+            if packets.is_empty() {
+                return Ok(0);
+            }
+            loop {
+                let mut guard = self.fd.writable().await?;
+                match guard.try_io(|inner| sock::sendmmsg(inner.get_ref(), packets, 0, target)) {
+                    Ok(result) => return result,
+                    Err(_would_block) => continue,
+                }
+            }
+        }
+    };
+    (@recv_batch) => {
+        async fn recv_batch_priv(&self, bufs: &mut [&mut [u8]]) -> Result<Vec<sock::MMsg<SocketAddr>>> {
+            if bufs.is_empty() {
+                return Ok(Vec::new());
+            }
+            loop {
+                let mut guard = self.fd.readable().await?;
+                match guard.try_io(|inner| sock::recvmmsg(inner.get_ref(), bufs, 0)) {
+                    Ok(result) => return result,
+                    Err(_would_block) => continue,
+                }
+            }
+        }
+    };
+    (@peek) => {
+        async fn peek_priv(&self, buf: &mut [u8]) -> Result<usize> {
+            let mut buf = ReadBuf::new(buf);
+            loop {
+                let mut guard = self.fd.readable().await?;
+                match guard.try_io(|inner| sock::recv(inner.get_ref(), &mut buf, MSG_PEEK)) {
+                    Ok(result) => return result,
+                    Err(_would_block) => continue,
+                }
+            }
+        }
+    };
+    (@poll_peek) => {
+        fn poll_peek_priv(&self, cx: &mut Context, buf: &mut ReadBuf) -> Poll<Result<usize>> {
+            loop {
+                let mut guard = ready!(self.fd.poll_read_ready(cx))?;
+                match guard.try_io(|inner| sock::recv(inner.get_ref(), buf, MSG_PEEK)) {
+                    Ok(result) => return Poll::Ready(result),
+                    Err(_would_block) => continue,
+                }
+            }
+        }
+    };
 }
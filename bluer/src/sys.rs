@@ -2,10 +2,11 @@
 #![allow(dead_code)]
 
 use libc::{c_int, c_ulong, c_ushort, sa_family_t};
-use nix::request_code_write;
+use nix::{request_code_read, request_code_write};
 use std::mem::size_of;
 
 pub const SOL_L2CAP: i32 = 6;
+pub const SOL_SCO: i32 = 17;
 pub const SOL_RFCOMM: i32 = 18;
 
 /// Bluetooth security.
@@ -35,10 +36,42 @@ pub const BT_POWER: i32 = 9;
 pub const BT_POWER_FORCE_ACTIVE_OFF: i32 = 0;
 pub const BT_POWER_FORCE_ACTIVE_ON: i32 = 1;
 
+#[repr(C)]
+#[derive(Clone)]
+pub struct bt_voice {
+    pub setting: u16,
+}
+
+pub const BT_VOICE: i32 = 11;
+pub const BT_VOICE_TRANSPARENT: u16 = 0x0003;
+pub const BT_VOICE_CVSD_16BIT: u16 = 0x0060;
+
 pub const BT_SNDMTU: i32 = 12;
 pub const BT_RCVMTU: i32 = 13;
 pub const BT_PHY: i32 = 14;
 pub const BT_MODE: i32 = 15;
+pub const BT_MODE_BASIC: u8 = 0x00;
+pub const BT_MODE_ERTM: u8 = 0x01;
+pub const BT_MODE_STREAMING: u8 = 0x02;
+pub const BT_MODE_LE_FLOWCTL: u8 = 0x03;
+pub const BT_MODE_EXT_FLOWCTL: u8 = 0x04;
+pub const BT_PKT_STATUS: i32 = 16;
+
+pub const BT_SCM_PKT_STATUS: i32 = 0x03;
+
+pub const BT_DEFER_SETUP: i32 = 7;
+
+pub const BT_FLUSHABLE: i32 = 8;
+pub const BT_FLUSHABLE_OFF: u32 = 0;
+pub const BT_FLUSHABLE_ON: u32 = 1;
+
+pub const BT_CHANNEL_POLICY: i32 = 10;
+pub const BT_CHANNEL_POLICY_BREDR_ONLY: u32 = 0;
+pub const BT_CHANNEL_POLICY_BREDR_PREFERRED: u32 = 1;
+pub const BT_CHANNEL_POLICY_AMP_PREFERRED: u32 = 2;
+
+pub const BT_ISO_QOS: i32 = 17;
+pub const BT_ISO_BASE: i32 = 20;
 
 /// BR1M1SLOT PHY.
 pub const BR1M1SLOT: i32 = 1 << 0;
@@ -72,12 +105,15 @@ pub const LECODEDTX: i32 = 1 << 13;
 pub const LECODEDRX: i32 = 1 << 14;
 
 pub const BTPROTO_L2CAP: i32 = 0;
+pub const BTPROTO_HCI: i32 = 1;
+pub const BTPROTO_SCO: i32 = 2;
 pub const BTPROTO_RFCOMM: i32 = 3;
+pub const BTPROTO_ISO: i32 = 8;
 
 /// Bluetooth address.
 #[repr(packed)]
 #[repr(C)]
-#[derive(Clone, Default)]
+#[derive(Clone, Copy, Default)]
 pub struct bdaddr_t {
     pub b: [u8; 6],
 }
@@ -102,19 +138,27 @@ pub const L2CAP_CONNINFO: i32 = 0x02;
 pub const L2CAP_LM: i32 = 0x03;
 
 /// Master.
-pub const L2CAP_LM_MASTER: i32 = 0x0001;
+pub const L2CAP_LM_MASTER: u32 = 0x0001;
 /// Auth.
-pub const L2CAP_LM_AUTH: i32 = 0x0002;
+pub const L2CAP_LM_AUTH: u32 = 0x0002;
 /// Encrypt.
-pub const L2CAP_LM_ENCRYPT: i32 = 0x0004;
+pub const L2CAP_LM_ENCRYPT: u32 = 0x0004;
 /// Trusted.
-pub const L2CAP_LM_TRUSTED: i32 = 0x0008;
+pub const L2CAP_LM_TRUSTED: u32 = 0x0008;
 /// Reliable.
-pub const L2CAP_LM_RELIABLE: i32 = 0x0010;
+pub const L2CAP_LM_RELIABLE: u32 = 0x0010;
 /// Secure.
-pub const L2CAP_LM_SECURE: i32 = 0x0020;
+pub const L2CAP_LM_SECURE: u32 = 0x0020;
 /// FIPS.
-pub const L2CAP_LM_FIPS: i32 = 0x0040;
+pub const L2CAP_LM_FIPS: u32 = 0x0040;
+
+pub const L2CAP_MODE_BASIC: u8 = 0x00;
+pub const L2CAP_MODE_ERTM: u8 = 0x03;
+pub const L2CAP_MODE_STREAMING: u8 = 0x04;
+pub const L2CAP_MODE_LE_FLOWCTL: u8 = 0x80;
+pub const L2CAP_MODE_EXT_FLOWCTL: u8 = 0x81;
+
+pub const L2CAP_ECRED_MAX_CID: usize = 5;
 
 /// Raw socket options for classic Bluetooth (BR/EDR) L2CAP sockets.
 #[repr(C)]
@@ -169,6 +213,12 @@ pub const RFCOMM_CONNINFO: i32 = 0x02;
 pub const RFCOMM_LM: i32 = 0x03;
 
 pub const RFCOMM_LM_MASTER: u32 = 0x0001;
+pub const RFCOMM_LM_AUTH: u32 = 0x0002;
+pub const RFCOMM_LM_ENCRYPT: u32 = 0x0004;
+pub const RFCOMM_LM_TRUSTED: u32 = 0x0008;
+pub const RFCOMM_LM_RELIABLE: u32 = 0x0010;
+pub const RFCOMM_LM_SECURE: u32 = 0x0020;
+pub const RFCOMM_LM_FIPS: u32 = 0x0040;
 
 /// RFCOMM socket connection information.
 #[repr(C)]
@@ -198,3 +248,343 @@ pub struct rfcomm_dev_req {
     pub dst: bdaddr_t,
     pub channel: u8,
 }
+
+/// SCO socket address.
+#[repr(C)]
+#[derive(Clone)]
+pub struct sockaddr_sco {
+    pub sco_family: sa_family_t,
+    pub sco_bdaddr: bdaddr_t,
+}
+
+pub const SCO_OPTIONS: i32 = 0x01;
+pub const SCO_CONNINFO: i32 = 0x02;
+
+/// SCO socket options.
+#[repr(C)]
+#[derive(Clone)]
+pub struct sco_options {
+    pub mtu: u16,
+}
+
+/// SCO socket connection information.
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct sco_conninfo {
+    /// Host controller interface (HCI) handle for the connection.
+    pub hci_handle: u16,
+    /// Device class.
+    pub dev_class: [u8; 3],
+}
+
+pub const ISO_MAX_NUM_BIS: usize = 0x1f;
+pub const BASE_MAX_LENGTH: usize = 248;
+
+/// ISO broadcast socket address.
+#[repr(C)]
+#[derive(Clone, Default)]
+pub struct sockaddr_iso_bc {
+    pub bc_bdaddr: bdaddr_t,
+    pub bc_bdaddr_type: u8,
+    pub bc_sid: u8,
+    pub bc_num_bis: u8,
+    pub bc_bis: [u8; ISO_MAX_NUM_BIS],
+}
+
+/// ISO socket address.
+///
+/// The broadcast part is only present if the address length includes it.
+#[repr(C)]
+#[derive(Clone, Default)]
+pub struct sockaddr_iso {
+    pub iso_family: sa_family_t,
+    pub iso_bdaddr: bdaddr_t,
+    pub iso_bdaddr_type: u8,
+    pub iso_bc: sockaddr_iso_bc,
+}
+
+/// Length of an ISO socket address without broadcast part.
+pub const SOCKADDR_ISO_UCAST_LEN: usize = 10;
+
+/// Let the kernel choose the connected isochronous group (CIG).
+pub const BT_ISO_QOS_CIG_UNSET: u8 = 0xff;
+/// Let the kernel choose the connected isochronous stream (CIS).
+pub const BT_ISO_QOS_CIS_UNSET: u8 = 0xff;
+/// Let the kernel choose the broadcast isochronous group (BIG).
+pub const BT_ISO_QOS_BIG_UNSET: u8 = 0xff;
+/// Let the kernel choose the broadcast isochronous stream (BIS).
+pub const BT_ISO_QOS_BIS_UNSET: u8 = 0xff;
+
+/// LE 1M PHY.
+pub const BT_ISO_PHY_1M: u8 = 0x01;
+/// LE 2M PHY.
+pub const BT_ISO_PHY_2M: u8 = 0x02;
+/// LE Coded PHY.
+pub const BT_ISO_PHY_CODED: u8 = 0x04;
+/// Any LE PHY.
+pub const BT_ISO_PHY_ANY: u8 = BT_ISO_PHY_1M | BT_ISO_PHY_2M | BT_ISO_PHY_CODED;
+
+pub const BT_ISO_SYNC_TIMEOUT: u16 = 0x07d0;
+
+/// ISO quality of service parameters for one direction.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct bt_iso_io_qos {
+    /// SDU interval in microseconds.
+    pub interval: u32,
+    /// Maximum transport latency in milliseconds.
+    pub latency: u16,
+    /// Maximum SDU size in bytes.
+    ///
+    /// Zero if this direction is unused.
+    pub sdu: u16,
+    /// PHY.
+    pub phy: u8,
+    /// Retransmission number.
+    pub rtn: u8,
+}
+
+impl Default for bt_iso_io_qos {
+    fn default() -> Self {
+        Self { interval: 10000, latency: 10, sdu: 40, phy: BT_ISO_PHY_2M, rtn: 2 }
+    }
+}
+
+/// ISO quality of service parameters of a connected isochronous stream (CIS).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct bt_iso_ucast_qos {
+    /// Connected isochronous group (CIG) identifier or `0xff` to let the kernel choose.
+    pub cig: u8,
+    /// Connected isochronous stream (CIS) identifier or `0xff` to let the kernel choose.
+    pub cis: u8,
+    /// Sleep clock accuracy.
+    pub sca: u8,
+    /// Packing.
+    pub packing: u8,
+    /// Framing.
+    pub framing: u8,
+    /// Input parameters.
+    pub input: bt_iso_io_qos,
+    /// Output parameters.
+    pub output: bt_iso_io_qos,
+}
+
+impl Default for bt_iso_ucast_qos {
+    fn default() -> Self {
+        Self {
+            cig: BT_ISO_QOS_CIG_UNSET,
+            cis: BT_ISO_QOS_CIS_UNSET,
+            sca: 0,
+            packing: 0,
+            framing: 0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+}
+
+/// ISO quality of service parameters of a broadcast isochronous stream (BIS).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct bt_iso_bcast_qos {
+    /// Broadcast isochronous group (BIG) handle or `0xff` to let the kernel choose.
+    pub big: u8,
+    /// Broadcast isochronous stream (BIS) index or `0xff` to let the kernel choose.
+    pub bis: u8,
+    /// Periodic advertising sync factor.
+    pub sync_factor: u8,
+    /// Packing.
+    pub packing: u8,
+    /// Framing.
+    pub framing: u8,
+    /// Input parameters.
+    pub input: bt_iso_io_qos,
+    /// Output parameters.
+    pub output: bt_iso_io_qos,
+    /// Whether the broadcast is encrypted.
+    pub encryption: u8,
+    /// Broadcast code used for encryption.
+    pub bcode: [u8; 16],
+    /// Periodic advertising create sync options.
+    pub options: u8,
+    /// Maximum number of periodic advertising events that can be skipped.
+    pub skip: u16,
+    /// Periodic advertising synchronization timeout in units of 10 ms.
+    pub sync_timeout: u16,
+    /// Constant tone extension types that are not synchronized to.
+    pub sync_cte_type: u8,
+    /// Maximum number of subevents for BIG synchronization.
+    pub mse: u8,
+    /// BIG synchronization timeout in units of 10 ms.
+    pub timeout: u16,
+}
+
+impl Default for bt_iso_bcast_qos {
+    fn default() -> Self {
+        Self {
+            big: BT_ISO_QOS_BIG_UNSET,
+            bis: BT_ISO_QOS_BIS_UNSET,
+            sync_factor: 0x01,
+            packing: 0,
+            framing: 0,
+            input: Default::default(),
+            output: Default::default(),
+            encryption: 0,
+            bcode: [0; 16],
+            options: 0,
+            skip: 0,
+            sync_timeout: BT_ISO_SYNC_TIMEOUT,
+            sync_cte_type: 0,
+            mse: 0,
+            timeout: BT_ISO_SYNC_TIMEOUT,
+        }
+    }
+}
+
+/// ISO quality of service parameters as passed to the kernel.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union bt_iso_qos {
+    pub ucast: bt_iso_ucast_qos,
+    pub bcast: bt_iso_bcast_qos,
+}
+
+pub const HCI_DEV_NONE: u16 = 0xffff;
+
+pub const HCI_CHANNEL_RAW: u16 = 0;
+pub const HCI_CHANNEL_USER: u16 = 1;
+pub const HCI_CHANNEL_MONITOR: u16 = 2;
+pub const HCI_CHANNEL_CONTROL: u16 = 3;
+
+/// HCI socket address.
+#[repr(C)]
+#[derive(Clone)]
+pub struct sockaddr_hci {
+    pub hci_family: sa_family_t,
+    pub hci_dev: c_ushort,
+    pub hci_channel: c_ushort,
+}
+
+pub const SOL_HCI: i32 = 0;
+pub const HCI_DATA_DIR: i32 = 1;
+pub const HCI_FILTER: i32 = 2;
+pub const HCI_TIME_STAMP: i32 = 3;
+
+pub const HCI_COMMAND_PKT: u8 = 0x01;
+pub const HCI_ACLDATA_PKT: u8 = 0x02;
+pub const HCI_SCODATA_PKT: u8 = 0x03;
+pub const HCI_EVENT_PKT: u8 = 0x04;
+pub const HCI_ISODATA_PKT: u8 = 0x05;
+pub const HCI_VENDOR_PKT: u8 = 0xff;
+
+pub const HCI_FLT_TYPE_BITS: u8 = 31;
+pub const HCI_FLT_EVENT_BITS: u8 = 63;
+
+/// HCI socket filter.
+#[repr(C)]
+#[derive(Clone, Default)]
+pub struct hci_filter {
+    pub type_mask: u32,
+    pub event_mask: [u32; 2],
+    pub opcode: u16,
+}
+
+pub const HCI_MAX_DEV: usize = 16;
+
+pub const HCIGETDEVLIST: c_ulong = request_code_read!('H', 210, size_of::<c_int>());
+pub const HCIGETDEVINFO: c_ulong = request_code_read!('H', 211, size_of::<c_int>());
+pub const HCIGETCONNLIST: c_ulong = request_code_read!('H', 212, size_of::<c_int>());
+
+/// HCI device request.
+#[repr(C)]
+#[derive(Clone, Default)]
+pub struct hci_dev_req {
+    pub dev_id: u16,
+    pub dev_opt: u32,
+}
+
+/// HCI device list request with room for [HCI_MAX_DEV] devices.
+#[repr(C)]
+#[derive(Clone, Default)]
+pub struct hci_dev_list_req {
+    pub dev_num: u16,
+    pub dev_req: [hci_dev_req; HCI_MAX_DEV],
+}
+
+/// HCI device statistics.
+#[repr(C)]
+#[derive(Clone, Default)]
+pub struct hci_dev_stats {
+    pub err_rx: u32,
+    pub err_tx: u32,
+    pub cmd_tx: u32,
+    pub evt_rx: u32,
+    pub acl_tx: u32,
+    pub acl_rx: u32,
+    pub sco_tx: u32,
+    pub sco_rx: u32,
+    pub byte_rx: u32,
+    pub byte_tx: u32,
+}
+
+/// HCI device information.
+#[repr(C)]
+#[derive(Clone, Default)]
+pub struct hci_dev_info {
+    pub dev_id: u16,
+    pub name: [u8; 8],
+    pub bdaddr: bdaddr_t,
+    pub flags: u32,
+    pub type_: u8,
+    pub features: [u8; 8],
+    pub pkt_type: u32,
+    pub link_policy: u32,
+    pub link_mode: u32,
+    pub acl_mtu: u16,
+    pub acl_pkts: u16,
+    pub sco_mtu: u16,
+    pub sco_pkts: u16,
+    pub stat: hci_dev_stats,
+}
+
+/// Maximum number of connections returned by [HCIGETCONNLIST].
+pub const HCI_MAX_CONN: usize = 64;
+
+/// HCI connection information.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct hci_conn_info {
+    pub handle: u16,
+    pub bdaddr: bdaddr_t,
+    pub type_: u8,
+    pub out: u8,
+    pub state: u16,
+    pub link_mode: u32,
+}
+
+/// HCI connection list request with room for [HCI_MAX_CONN] connections.
+#[repr(C)]
+#[derive(Clone)]
+pub struct hci_conn_list_req {
+    pub dev_id: u16,
+    pub conn_num: u16,
+    pub conn_info: [hci_conn_info; HCI_MAX_CONN],
+}
+
+impl Default for hci_conn_list_req {
+    fn default() -> Self {
+        Self { dev_id: 0, conn_num: 0, conn_info: [Default::default(); HCI_MAX_CONN] }
+    }
+}
+
+/// Size of management packet header consisting of opcode, index and parameter length.
+pub const MGMT_HDR_SIZE: usize = 6;